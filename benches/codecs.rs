@@ -0,0 +1,133 @@
+//! Benchmarks of this crate against the JPEG codecs from the `image` crate and from `zune-jpeg`.
+//!
+//! Run with `cargo bench --bench codecs`. Besides comparing the codecs, the `turbojpeg_overhead`
+//! group measures the cost of the conveniences in this crate (allocating the output, parsing the
+//! header twice) relative to decompressing into a reused buffer.
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use turbojpeg::{Decompressor, Image, PixelFormat, Subsamp};
+
+const SIZES: [usize; 3] = [256, 1024, 2048];
+const QUALITIES: [i32; 3] = [50, 75, 95];
+
+fn test_jpeg(size: usize, quality: i32) -> Vec<u8> {
+    let image = Image::mandelbrot(size, size, PixelFormat::RGB);
+    turbojpeg::compress(image.as_deref(), quality, Subsamp::Sub2x2).unwrap().to_vec()
+}
+
+fn decompress(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decompress");
+    for size in SIZES {
+        let jpeg_data = test_jpeg(size, 90);
+        group.throughput(Throughput::Elements((size * size) as u64));
+
+        group.bench_with_input(BenchmarkId::new("turbojpeg", size), &jpeg_data, |b, jpeg_data| {
+            let mut decompressor = Decompressor::new().unwrap();
+            let mut image = Image {
+                pixels: vec![0; 3 * size * size],
+                width: size,
+                pitch: 3 * size,
+                height: size,
+                format: PixelFormat::RGB,
+            };
+            b.iter(|| decompressor.decompress(jpeg_data, image.as_deref_mut()).unwrap())
+        });
+
+        group.bench_with_input(BenchmarkId::new("image", size), &jpeg_data, |b, jpeg_data| {
+            b.iter(|| image::load_from_memory_with_format(jpeg_data, image::ImageFormat::Jpeg).unwrap())
+        });
+
+        group.bench_with_input(BenchmarkId::new("zune-jpeg", size), &jpeg_data, |b, jpeg_data| {
+            b.iter(|| zune_jpeg::JpegDecoder::new(jpeg_data).decode().unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn decompress_formats(c: &mut Criterion) {
+    let size = 1024;
+    let jpeg_data = test_jpeg(size, 90);
+    let mut decompressor = Decompressor::new().unwrap();
+
+    let mut group = c.benchmark_group("decompress_format");
+    group.throughput(Throughput::Elements((size * size) as u64));
+    for format in [PixelFormat::RGB, PixelFormat::RGBA, PixelFormat::BGRX, PixelFormat::GRAY] {
+        let mut image = Image {
+            pixels: vec![0; format.size() * size * size],
+            width: size,
+            pitch: format.size() * size,
+            height: size,
+            format,
+        };
+        group.bench_function(BenchmarkId::from_parameter(format!("{:?}", format)), |b| {
+            b.iter(|| decompressor.decompress(&jpeg_data, image.as_deref_mut()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn compress(c: &mut Criterion) {
+    let size = 1024;
+    let image = Image::mandelbrot(size, size, PixelFormat::RGB);
+
+    let mut group = c.benchmark_group("compress");
+    group.throughput(Throughput::Elements((size * size) as u64));
+    for quality in QUALITIES {
+        group.bench_with_input(BenchmarkId::new("turbojpeg", quality), &quality, |b, &quality| {
+            let mut compressor = turbojpeg::Compressor::new().unwrap();
+            compressor.set_quality(quality).unwrap();
+            compressor.set_subsamp(Subsamp::Sub2x2).unwrap();
+            let mut output = turbojpeg::OutputBuf::new_owned();
+            b.iter(|| compressor.compress(image.as_deref(), &mut output).unwrap())
+        });
+
+        group.bench_with_input(BenchmarkId::new("image", quality), &quality, |b, &quality| {
+            b.iter(|| {
+                let mut output = Vec::new();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output, quality as u8)
+                    .encode(&image.pixels, size as u32, size as u32, image::ColorType::Rgb8)
+                    .unwrap();
+                output
+            })
+        });
+    }
+    group.finish();
+}
+
+fn turbojpeg_overhead(c: &mut Criterion) {
+    let size = 256;
+    let jpeg_data = test_jpeg(size, 90);
+
+    let mut group = c.benchmark_group("turbojpeg_overhead");
+    group.bench_function("read_header", |b| {
+        let mut decompressor = Decompressor::new().unwrap();
+        b.iter(|| decompressor.read_header(&jpeg_data).unwrap())
+    });
+    group.bench_function("decompress_reused", |b| {
+        let mut decompressor = Decompressor::new().unwrap();
+        let mut image = Image {
+            pixels: vec![0; 3 * size * size],
+            width: size,
+            pitch: 3 * size,
+            height: size,
+            format: PixelFormat::RGB,
+        };
+        b.iter(|| decompressor.decompress(&jpeg_data, image.as_deref_mut()).unwrap())
+    });
+    group.bench_function("decompress_free_fn", |b| {
+        b.iter(|| turbojpeg::decompress(&jpeg_data, PixelFormat::RGB).unwrap())
+    });
+    group.bench_function("compress_to_owned", |b| {
+        let image = Image::mandelbrot(size, size, PixelFormat::RGB);
+        let mut compressor = turbojpeg::Compressor::new().unwrap();
+        b.iter(|| compressor.compress_to_owned(image.as_deref()).unwrap())
+    });
+    group.bench_function("compress_to_vec", |b| {
+        let image = Image::mandelbrot(size, size, PixelFormat::RGB);
+        let mut compressor = turbojpeg::Compressor::new().unwrap();
+        b.iter(|| compressor.compress_to_vec(image.as_deref()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decompress, decompress_formats, compress, turbojpeg_overhead);
+criterion_main!(benches);
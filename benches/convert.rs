@@ -0,0 +1,78 @@
+//! Benchmarks of the conversions in `turbojpeg::convert` against straightforward scalar loops.
+//!
+//! Run with `cargo bench --bench convert`.
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use turbojpeg::convert;
+
+// one 1080p frame
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+
+fn uv(c: &mut Criterion) {
+    let len = WIDTH / 2 * HEIGHT / 2;
+    let uv: Vec<u8> = (0..2 * len).map(|i| i as u8).collect();
+    let mut u = vec![0; len];
+    let mut v = vec![0; len];
+
+    let mut group = c.benchmark_group("uv");
+    group.throughput(Throughput::Bytes(2 * len as u64));
+    group.bench_function("deinterleave", |b| {
+        b.iter(|| convert::deinterleave_uv(&uv, &mut u, &mut v))
+    });
+    group.bench_function("deinterleave_scalar", |b| {
+        b.iter(|| {
+            for ((uv, u), v) in uv.chunks_exact(2).zip(u.iter_mut()).zip(v.iter_mut()) {
+                *u = uv[0];
+                *v = uv[1];
+            }
+        })
+    });
+
+    let mut uv = uv.clone();
+    group.bench_function("interleave", |b| {
+        b.iter(|| convert::interleave_uv(&u, &v, &mut uv))
+    });
+    group.bench_function("interleave_scalar", |b| {
+        b.iter(|| {
+            for ((uv, u), v) in uv.chunks_exact_mut(2).zip(u.iter()).zip(v.iter()) {
+                uv[0] = *u;
+                uv[1] = *v;
+            }
+        })
+    });
+    group.finish();
+}
+
+fn rgba(c: &mut Criterion) {
+    let pixels = WIDTH * HEIGHT;
+    let mut rgba: Vec<u8> = (0..4 * pixels).map(|i| i as u8).collect();
+    let mut rgb = vec![0; 3 * pixels];
+
+    let mut group = c.benchmark_group("rgba");
+    group.throughput(Throughput::Elements(pixels as u64));
+    group.bench_function("rgba_to_rgb", |b| {
+        b.iter(|| convert::rgba_to_rgb(&rgba, &mut rgb))
+    });
+    group.bench_function("rgba_to_rgb_scalar", |b| {
+        b.iter(|| {
+            for (rgba, rgb) in rgba.chunks_exact(4).zip(rgb.chunks_exact_mut(3)) {
+                rgb.copy_from_slice(&rgba[..3]);
+            }
+        })
+    });
+    group.bench_function("rgb_to_rgba", |b| {
+        b.iter(|| convert::rgb_to_rgba(&rgb, &mut rgba))
+    });
+    group.bench_function("rgb_to_rgba_scalar", |b| {
+        b.iter(|| {
+            for (rgb, rgba) in rgb.chunks_exact(3).zip(rgba.chunks_exact_mut(4)) {
+                rgba[..3].copy_from_slice(rgb);
+                rgba[3] = 255;
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, uv, rgba);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+//! Measures the time to decompress an image into each pixel format, and compares decompressing
+//! directly into the final layout with decompressing into RGBA and swizzling the pixels afterwards.
+//!
+//! Run with `cargo run --release --example pixel_formats [image.jpg] [iterations]`.
+use std::time::{Duration, Instant};
+use anyhow::Result;
+
+use turbojpeg::{Decompressor, Image, PixelFormat, PixelLayout};
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| "examples/parrots.jpg".into());
+    let iterations: u32 = args.next().map(|arg| arg.parse()).transpose()?.unwrap_or(100);
+
+    let jpeg_data = std::fs::read(path)?;
+    let mut decompressor = Decompressor::new()?;
+    let header = decompressor.read_header(&jpeg_data)?;
+    println!("image {}x{}, {} iterations", header.width, header.height, iterations);
+
+    let mut decompress = |format: PixelFormat| -> Result<Image<Vec<u8>>> {
+        let pitch = header.width * format.size();
+        let mut image = Image {
+            pixels: vec![0; pitch * header.height],
+            width: header.width,
+            pitch,
+            height: header.height,
+            format,
+        };
+        decompressor.decompress(&jpeg_data, image.as_deref_mut())?;
+        Ok(image)
+    };
+
+    println!("\ndecompression into each pixel format:");
+    for format in PixelFormat::iter() {
+        if format == PixelFormat::CMYK {
+            continue
+        }
+        let time = measure(iterations, || decompress(format).map(drop))?;
+        println!("  {:>5?}: {:>8.3} ms", format, ms(time));
+    }
+
+    println!("\ndecompression into the final layout vs. RGBA followed by swizzling:");
+    for layout in [PixelLayout::Bgra8, PixelLayout::Argb32, PixelLayout::Rgba32] {
+        let format = layout.pixel_format();
+        let direct = measure(iterations, || decompress(format).map(drop))?;
+        let swizzled = measure(iterations, || {
+            let mut image = decompress(PixelFormat::RGBA)?;
+            swizzle_from_rgba(&mut image.pixels, format);
+            Ok(())
+        })?;
+        println!("  {:>7?} ({:?}): direct {:>8.3} ms, swizzled {:>8.3} ms",
+            layout, format, ms(direct), ms(swizzled));
+    }
+
+    Ok(())
+}
+
+fn measure(iterations: u32, mut f: impl FnMut() -> Result<()>) -> Result<Duration> {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f()?;
+    }
+    Ok(start.elapsed() / iterations)
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn swizzle_from_rgba(pixels: &mut [u8], format: PixelFormat) {
+    let r = format.red_offset().unwrap();
+    let g = format.green_offset().unwrap();
+    let b = format.blue_offset().unwrap();
+    let a = format.alpha_offset().unwrap();
+    for pixel in pixels.chunks_exact_mut(4) {
+        let rgba = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        pixel[r] = rgba[0];
+        pixel[g] = rgba[1];
+        pixel[b] = rgba[2];
+        pixel[a] = rgba[3];
+    }
+}
@@ -0,0 +1,28 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use turbojpeg::{Decompressor, Image, PixelFormat};
+
+// limit the number of pixels, so that the fuzzer does not run out of memory on huge headers
+const MAX_PIXELS: usize = 4096 * 4096;
+
+fuzz_target!(|input: (PixelFormat, &[u8])| {
+    let (format, jpeg_data) = input;
+    let mut decompressor = Decompressor::new().unwrap();
+    let header = match decompressor.read_header(jpeg_data) {
+        Ok(header) => header,
+        Err(_) => return,
+    };
+    if header.width.saturating_mul(header.height) > MAX_PIXELS {
+        return
+    }
+
+    let pitch = header.width * format.size();
+    let mut image = Image {
+        pixels: vec![0; pitch * header.height],
+        width: header.width,
+        pitch,
+        height: header.height,
+        format,
+    };
+    let _ = decompressor.decompress(jpeg_data, image.as_deref_mut());
+});
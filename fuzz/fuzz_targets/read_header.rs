@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|jpeg_data: &[u8]| {
+    let _ = turbojpeg::read_header(jpeg_data);
+    let _ = turbojpeg::read_header_partial(jpeg_data);
+});
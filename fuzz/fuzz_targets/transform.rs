@@ -0,0 +1,20 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use turbojpeg::{Transform, Transformer};
+
+// limit the number of pixels, so that the fuzzer does not run out of memory on huge headers
+const MAX_PIXELS: usize = 4096 * 4096;
+
+fuzz_target!(|input: (Transform, &[u8])| {
+    let (transform, jpeg_data) = input;
+    let header = match turbojpeg::read_header(jpeg_data) {
+        Ok(header) => header,
+        Err(_) => return,
+    };
+    if header.width.saturating_mul(header.height) > MAX_PIXELS {
+        return
+    }
+
+    let mut transformer = Transformer::new().unwrap();
+    let _ = transformer.transform_to_owned(&transform, jpeg_data);
+});
@@ -0,0 +1,493 @@
+//! Reading and writing Motion JPEG (MJPEG) videos in AVI files.
+//!
+//! Motion JPEG is a video format in which every frame is an independent JPEG image. It is produced
+//! by many cameras and it is supported by virtually all video players, so it is an easy way to
+//! package a sequence of JPEG images into a video.
+//!
+//! - [`Reader`] parses an AVI file in memory and gives you the JPEG data of the frames, which you
+//!   can decompress with [`Decompressor`][crate::Decompressor].
+//! - [`Writer`] appends JPEG frames (for example, produced by [`Compressor`][crate::Compressor])
+//!   into an AVI file.
+//!
+//! Only the video stream is supported; audio and other streams are ignored by the reader.
+//!
+//! # Example
+//!
+//! ```
+//! use turbojpeg::{avi, Image, PixelFormat, Subsamp};
+//!
+//! // write a video with 10 frames
+//! let mut writer = avi::Writer::new(std::io::Cursor::new(Vec::new()), 320, 240, 25.)?;
+//! for i in 0..10 {
+//!     let frame = Image::mandelbrot(320 + 10 * i, 240, PixelFormat::RGB);
+//!     let jpeg_data = turbojpeg::compress(frame.as_deref(), 80, Subsamp::Sub2x2)?;
+//!     writer.write_frame(&jpeg_data)?;
+//! }
+//! let avi_data = writer.finish()?.into_inner();
+//!
+//! // read the video back
+//! let reader = avi::Reader::new(&avi_data)?;
+//! assert_eq!((reader.width(), reader.height()), (320, 240));
+//! assert_eq!(reader.frame_count(), 10);
+//! for jpeg_data in reader.frames() {
+//!     let frame = turbojpeg::decompress(jpeg_data, PixelFormat::RGB)?;
+//!     assert_eq!(frame.height, 240);
+//! }
+//!
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::convert::TryInto as _;
+use std::io::{Seek, SeekFrom, Write};
+use crate::common::{Error, Result};
+
+const AVIF_HASINDEX: u32 = 0x10;
+const AVIIF_KEYFRAME: u32 = 0x10;
+
+/// Parses Motion JPEG videos in AVI files.
+///
+/// The reader finds the first video stream in the file and collects its frames, using the index
+/// (`idx1` chunk) if the file has one, or by scanning the `movi` lists otherwise. Large files in
+/// the OpenDML (AVI 2.0) format, which are split into multiple RIFF chunks, are also supported.
+///
+/// Some cameras omit the Huffman tables from the frames; TurboJPEG uses the standard tables for
+/// such frames, so they can be decompressed as usual. Some AVI files also contain empty frames,
+/// which mean that the previous frame should be repeated.
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    width: usize,
+    height: usize,
+    codec: [u8; 4],
+    micros_per_frame: u32,
+    rate: u32,
+    scale: u32,
+    frames: Vec<&'a [u8]>,
+}
+
+impl<'a> Reader<'a> {
+    /// Parses an AVI file.
+    ///
+    /// Returns an error if the data is not an AVI file or if it does not contain a video stream.
+    pub fn new(data: &'a [u8]) -> Result<Reader<'a>> {
+        if data.get(0..4) != Some(b"RIFF") || data.get(8..12) != Some(b"AVI ") {
+            return Err(Error::BadAvi("the data does not start with an AVI header"))
+        }
+
+        let mut header = None;
+        let mut movi_lists = Vec::new();
+        let mut index = None;
+        for riff in Chunks::new(data, 0) {
+            if &riff.id != b"RIFF" || riff.data.len() < 4 {
+                continue
+            }
+            for chunk in riff.children() {
+                match (&chunk.id, chunk.list_type()) {
+                    (b"LIST", Some(b"hdrl")) if header.is_none() =>
+                        header = Some(parse_header(&chunk)?),
+                    (b"LIST", Some(b"movi")) =>
+                        movi_lists.push(chunk),
+                    (b"idx1", _) if index.is_none() =>
+                        index = Some(chunk),
+                    _ => {},
+                }
+            }
+        }
+
+        let header = header.ok_or(Error::BadAvi("the file does not contain a header"))?;
+        let stream = header.video_stream.ok_or(Error::BadAvi("the file does not contain a video stream"))?;
+        let stream_id = [b'0' + (stream / 10) as u8, b'0' + (stream % 10) as u8];
+        let is_frame = |id: &[u8; 4]| id[0..2] == stream_id && matches!(&id[2..4], b"dc" | b"db");
+
+        // the index covers only the first RIFF chunk, so we must scan the files with multiple RIFF
+        // chunks
+        let mut frames = None;
+        if let (Some(index), [movi]) = (&index, movi_lists.as_slice()) {
+            frames = parse_index(data, index.data, movi.offset + 8, is_frame);
+        }
+        let frames = frames.unwrap_or_else(|| {
+            let mut frames = Vec::new();
+            for movi in movi_lists.iter() {
+                scan_movi(movi, &is_frame, &mut frames);
+            }
+            frames
+        });
+
+        Ok(Reader {
+            width: header.width,
+            height: header.height,
+            codec: header.codec,
+            micros_per_frame: header.micros_per_frame,
+            rate: header.rate,
+            scale: header.scale,
+            frames,
+        })
+    }
+
+    /// Returns the width of the video frames in pixels, as declared in the header.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the video frames in pixels, as declared in the header.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the FourCC code of the video codec, which is typically `b"MJPG"`.
+    ///
+    /// The reader does not check the codec, so you can use this to reject videos that are not in
+    /// Motion JPEG.
+    pub fn codec(&self) -> [u8; 4] {
+        self.codec
+    }
+
+    /// Returns the number of frames per second, or `None` if the header does not declare it.
+    pub fn frame_rate(&self) -> Option<f64> {
+        if self.rate != 0 && self.scale != 0 {
+            Some(self.rate as f64 / self.scale as f64)
+        } else if self.micros_per_frame != 0 {
+            Some(1e6 / self.micros_per_frame as f64)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of frames in the video.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the JPEG data of the frame with the given index.
+    pub fn frame(&self, index: usize) -> Option<&'a [u8]> {
+        self.frames.get(index).copied()
+    }
+
+    /// Returns an iterator over the JPEG data of all frames.
+    pub fn frames(&self) -> impl ExactSizeIterator<Item = &'a [u8]> + '_ {
+        self.frames.iter().copied()
+    }
+}
+
+/// Writes Motion JPEG videos into AVI files.
+///
+/// The writer writes the headers when it is created, appends the frames in
+/// [`write_frame()`][Self::write_frame] and writes the index in [`finish()`][Self::finish]. You
+/// must call `finish()` after writing all frames, otherwise the file will be incomplete.
+///
+/// The writer produces AVI 1.0 files, so the size of the file is limited to 4 GB.
+#[derive(Debug)]
+pub struct Writer<W> {
+    writer: W,
+    /// Position of the start of the file in `writer`.
+    start: u64,
+    /// Number of bytes written so far.
+    len: u64,
+    /// Offsets (relative to the `movi` list type) and sizes of the frames.
+    index: Vec<(u32, u32)>,
+    max_frame_size: u32,
+}
+
+// offsets of the fields in the header that are patched in `Writer::finish()`
+const RIFF_SIZE_OFFSET: u64 = 4;
+const AVIH_TOTAL_FRAMES_OFFSET: u64 = 48;
+const AVIH_BUFFER_SIZE_OFFSET: u64 = 60;
+const STRH_LENGTH_OFFSET: u64 = 140;
+const STRH_BUFFER_SIZE_OFFSET: u64 = 144;
+const MOVI_SIZE_OFFSET: u64 = 216;
+const MOVI_TYPE_OFFSET: u64 = 220;
+const HEADER_LEN: u64 = 224;
+
+impl<W: Write + Seek> Writer<W> {
+    /// Creates a writer and writes the headers of the AVI file into `writer`.
+    ///
+    /// The `width` and `height` should be the size of the frames that you will write and
+    /// `frame_rate` is the number of frames per second. Returns [`Error::BadFrameRate`] if the
+    /// frame rate is not positive and finite.
+    pub fn new(mut writer: W, width: usize, height: usize, frame_rate: f64) -> Result<Writer<W>> {
+        let width: u32 = width.try_into().map_err(|_| Error::IntegerOverflow("width"))?;
+        let height: u32 = height.try_into().map_err(|_| Error::IntegerOverflow("height"))?;
+        let rect_width: u16 = width.try_into().map_err(|_| Error::IntegerOverflow("width"))?;
+        let rect_height: u16 = height.try_into().map_err(|_| Error::IntegerOverflow("height"))?;
+        if !(frame_rate.is_finite() && frame_rate > 0.) {
+            return Err(Error::BadFrameRate(frame_rate))
+        }
+        let scale = 1000;
+        let rate = (frame_rate * scale as f64).round() as u32;
+        let micros_per_frame = (1e6 / frame_rate).round() as u32;
+
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        let mut put = |bytes: &[u8]| header.extend_from_slice(bytes);
+        put(b"RIFF"); put(&0u32.to_le_bytes()); put(b"AVI ");
+        put(b"LIST"); put(&192u32.to_le_bytes()); put(b"hdrl");
+
+        put(b"avih"); put(&56u32.to_le_bytes());
+        for value in [
+            micros_per_frame, 0, 0, AVIF_HASINDEX,
+            0, // total frames
+            0, 1,
+            0, // suggested buffer size
+            width, height, 0, 0, 0, 0,
+        ] {
+            put(&value.to_le_bytes());
+        }
+
+        put(b"LIST"); put(&116u32.to_le_bytes()); put(b"strl");
+        put(b"strh"); put(&56u32.to_le_bytes());
+        put(b"vids"); put(b"MJPG");
+        for value in [
+            0, 0, 0, scale, rate, 0,
+            0, // length
+            0, // suggested buffer size
+            u32::MAX, 0,
+        ] {
+            put(&value.to_le_bytes());
+        }
+        for value in [0, 0, rect_width, rect_height] {
+            put(&value.to_le_bytes());
+        }
+
+        put(b"strf"); put(&40u32.to_le_bytes());
+        put(&40u32.to_le_bytes()); put(&width.to_le_bytes()); put(&height.to_le_bytes());
+        put(&1u16.to_le_bytes()); put(&24u16.to_le_bytes()); put(b"MJPG");
+        put(&width.wrapping_mul(height).wrapping_mul(3).to_le_bytes());
+        for value in [0u32, 0, 0, 0] {
+            put(&value.to_le_bytes());
+        }
+
+        put(b"LIST"); put(&0u32.to_le_bytes()); put(b"movi");
+        debug_assert_eq!(header.len() as u64, HEADER_LEN);
+
+        let start = writer.stream_position()?;
+        writer.write_all(&header)?;
+        Ok(Writer { writer, start, len: HEADER_LEN, index: Vec::new(), max_frame_size: 0 })
+    }
+
+    /// Appends a frame with the given JPEG data to the video.
+    pub fn write_frame(&mut self, jpeg_data: &[u8]) -> Result<()> {
+        let size: u32 = jpeg_data.len().try_into().map_err(|_| Error::IntegerOverflow("frame size"))?;
+        let padded_size = size as u64 + (size as u64 & 1);
+        let offset: u32 = (self.len - MOVI_TYPE_OFFSET).try_into()
+            .map_err(|_| Error::IntegerOverflow("AVI file size"))?;
+        // make sure that the file size (including the index) still fits into 32 bits
+        let index_len = 8 + 16 * (self.index.len() as u64 + 1);
+        if self.len + 8 + padded_size + index_len > u32::MAX as u64 {
+            return Err(Error::IntegerOverflow("AVI file size"))
+        }
+
+        self.writer.write_all(b"00dc")?;
+        self.writer.write_all(&size.to_le_bytes())?;
+        self.writer.write_all(jpeg_data)?;
+        if size & 1 != 0 {
+            self.writer.write_all(&[0])?;
+        }
+
+        self.len += 8 + padded_size;
+        self.index.push((offset, size));
+        self.max_frame_size = self.max_frame_size.max(size);
+        Ok(())
+    }
+
+    /// Returns the number of frames written so far.
+    pub fn frame_count(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Writes the index, updates the headers and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let movi_end = self.len;
+        let mut index = Vec::with_capacity(8 + 16 * self.index.len());
+        index.extend_from_slice(b"idx1");
+        index.extend_from_slice(&(16 * self.index.len() as u32).to_le_bytes());
+        for &(offset, size) in self.index.iter() {
+            index.extend_from_slice(b"00dc");
+            index.extend_from_slice(&AVIIF_KEYFRAME.to_le_bytes());
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&size.to_le_bytes());
+        }
+        self.writer.write_all(&index)?;
+        self.len += index.len() as u64;
+
+        // the sizes were checked in write_frame(), so they fit into 32 bits
+        let frame_count = self.index.len() as u32;
+        self.patch(RIFF_SIZE_OFFSET, (self.len - 8) as u32)?;
+        self.patch(AVIH_TOTAL_FRAMES_OFFSET, frame_count)?;
+        self.patch(AVIH_BUFFER_SIZE_OFFSET, self.max_frame_size)?;
+        self.patch(STRH_LENGTH_OFFSET, frame_count)?;
+        self.patch(STRH_BUFFER_SIZE_OFFSET, self.max_frame_size)?;
+        self.patch(MOVI_SIZE_OFFSET, (movi_end - MOVI_TYPE_OFFSET) as u32)?;
+        self.writer.seek(SeekFrom::Start(self.start + self.len))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn patch(&mut self, offset: u64, value: u32) -> Result<()> {
+        self.writer.seek(SeekFrom::Start(self.start + offset))?;
+        self.writer.write_all(&value.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+/// A RIFF chunk.
+#[derive(Debug, Clone, Copy)]
+struct Chunk<'a> {
+    id: [u8; 4],
+    /// Offset of the chunk id in the file.
+    offset: usize,
+    data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    /// Returns the type of a `LIST` or `RIFF` chunk.
+    fn list_type(&self) -> Option<&'a [u8; 4]> {
+        self.data.get(0..4)?.try_into().ok()
+    }
+
+    /// Returns the chunks in the `LIST` or `RIFF` chunk.
+    fn children(&self) -> Chunks<'a> {
+        Chunks::new(self.data.get(4..).unwrap_or(&[]), self.offset + 12)
+    }
+}
+
+/// Iterator over RIFF chunks that are stored one after another.
+///
+/// The last chunk is truncated if the data ends prematurely (which is common for recordings that
+/// were interrupted).
+struct Chunks<'a> {
+    data: &'a [u8],
+    /// Offset of `data` in the file.
+    offset: usize,
+    pos: usize,
+}
+
+impl<'a> Chunks<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self {
+        Chunks { data, offset, pos: 0 }
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Chunk<'a>;
+
+    fn next(&mut self) -> Option<Chunk<'a>> {
+        let id = self.data.get(self.pos..self.pos + 4)?.try_into().ok()?;
+        let size = read_u32(self.data, self.pos + 4)? as usize;
+        let start = self.pos + 8;
+        let end = start.saturating_add(size).min(self.data.len());
+        let chunk = Chunk { id, offset: self.offset + self.pos, data: &self.data[start..end] };
+        self.pos = end + (size & 1);
+        Some(chunk)
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    micros_per_frame: u32,
+    video_stream: Option<usize>,
+    codec: [u8; 4],
+    rate: u32,
+    scale: u32,
+}
+
+fn parse_header(hdrl: &Chunk) -> Result<Header> {
+    let bad = || Error::BadAvi("the main header is too short");
+    let mut header = Header {
+        width: 0, height: 0, micros_per_frame: 0,
+        video_stream: None, codec: [0; 4], rate: 0, scale: 0,
+    };
+
+    let mut stream = 0;
+    for chunk in hdrl.children() {
+        match (&chunk.id, chunk.list_type()) {
+            (b"avih", _) => {
+                header.micros_per_frame = read_u32(chunk.data, 0).ok_or_else(bad)?;
+                header.width = read_u32(chunk.data, 32).ok_or_else(bad)? as usize;
+                header.height = read_u32(chunk.data, 36).ok_or_else(bad)? as usize;
+            },
+            (b"LIST", Some(b"strl")) => {
+                if header.video_stream.is_none() {
+                    parse_stream(&chunk, stream, &mut header)?;
+                }
+                stream += 1;
+            },
+            _ => {},
+        }
+    }
+    Ok(header)
+}
+
+fn parse_stream(strl: &Chunk, stream: usize, header: &mut Header) -> Result<()> {
+    let bad = || Error::BadAvi("the stream header is too short");
+    let strh = match strl.children().find(|chunk| &chunk.id == b"strh") {
+        Some(strh) => strh,
+        None => return Ok(()),
+    };
+    if strh.data.get(0..4) != Some(b"vids") || stream >= 100 {
+        return Ok(())
+    }
+
+    header.video_stream = Some(stream);
+    header.codec = strh.data.get(4..8).ok_or_else(bad)?.try_into().unwrap();
+    header.scale = read_u32(strh.data, 20).ok_or_else(bad)?;
+    header.rate = read_u32(strh.data, 24).ok_or_else(bad)?;
+    if let Some(strf) = strl.children().find(|chunk| &chunk.id == b"strf") {
+        // prefer the codec from BITMAPINFOHEADER, because the codec in the stream header is
+        // sometimes missing
+        if let Some(codec) = strf.data.get(16..20) {
+            header.codec = codec.try_into().unwrap();
+        }
+    }
+    Ok(())
+}
+
+/// Collects the frames using the `idx1` chunk.
+///
+/// Returns `None` if the index does not seem to match the data, so that we can fall back to
+/// scanning the `movi` list.
+fn parse_index<'a, F>(data: &'a [u8], index: &[u8], movi_type_offset: usize, is_frame: F) -> Option<Vec<&'a [u8]>>
+    where F: Fn(&[u8; 4]) -> bool
+{
+    let entries = index.chunks_exact(16)
+        .map(|entry| (entry[0..4].try_into().unwrap(), read_u32(entry, 8).unwrap() as usize))
+        .filter(|(id, _)| is_frame(id))
+        .collect::<Vec<([u8; 4], usize)>>();
+
+    // the offsets should be relative to the `movi` list type, but some writers use absolute
+    // offsets
+    let chunk_at = |pos: usize| -> Option<Chunk<'a>> {
+        Chunks::new(data.get(pos..)?, pos).next()
+    };
+    let &(first_id, first_offset) = entries.first()?;
+    let base = if chunk_at(movi_type_offset + first_offset).map(|c| c.id) == Some(first_id) {
+        movi_type_offset
+    } else if chunk_at(first_offset).map(|c| c.id) == Some(first_id) {
+        0
+    } else {
+        return None
+    };
+
+    entries.iter()
+        .map(|&(id, offset)| chunk_at(base + offset).filter(|c| c.id == id).map(|c| c.data))
+        .collect()
+}
+
+/// Collects the frames by scanning a `movi` list (including the nested `rec ` lists).
+fn scan_movi<'a, F>(movi: &Chunk<'a>, is_frame: &F, frames: &mut Vec<&'a [u8]>)
+    where F: Fn(&[u8; 4]) -> bool
+{
+    // the nested lists are walked using an explicit stack, so that deeply nested lists in a
+    // crafted file cannot overflow the call stack
+    let mut lists = vec![movi.children()];
+    while let Some(chunks) = lists.last_mut() {
+        match chunks.next() {
+            Some(chunk) if &chunk.id == b"LIST" => lists.push(chunk.children()),
+            Some(chunk) => if is_frame(&chunk.id) {
+                frames.push(chunk.data);
+            },
+            None => { lists.pop(); },
+        }
+    }
+}
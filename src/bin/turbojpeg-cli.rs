@@ -0,0 +1,237 @@
+use std::fs;
+use anyhow::{Result, Context as _, bail};
+use clap::{clap_app, ArgMatches};
+
+use turbojpeg::{Compressor, Image, PixelFormat, Settings, Subsamp};
+use turbojpeg::{Transform, TransformCrop, TransformOp, Transformer};
+
+fn main() -> Result<()> {
+    let args = clap_app!(("turbojpeg-cli") =>
+        (about: "Compresses, decompresses, transforms and inspects JPEG images with TurboJPEG")
+        (@setting SubcommandRequiredElseHelp)
+        (@subcommand compress =>
+            (about: "Compresses an image to JPEG")
+            (@arg INPUT: <input> "Input image file (PNG, BMP, PNM, ...)")
+            (@arg OUTPUT: <output> "Output JPEG file")
+            (@arg QUALITY: -q --quality [quality]
+                "Quality of the output JPEG file (1 is worst, 100 is best)")
+            (@arg SUBSAMP: -s --subsamp [subsamp]
+                "Chrominance subsampling (444, 422, 420, 440, 411, 441 or gray)")
+            (@arg PROGRESSIVE: --progressive ...
+                "Use progressive entropy coding")
+            (@arg OPTIMIZE: --optimize ...
+                "Use optimized baseline entropy coding")
+            (@arg ARITHMETIC: --arithmetic ...
+                "Use arithmetic entropy coding")
+            (@arg RESTART: --restart [rows]
+                "Insert a restart marker every <rows> MCU rows")
+        )
+        (@subcommand decompress =>
+            (about: "Decompresses a JPEG image")
+            (@arg INPUT: <input> "Input JPEG file")
+            (@arg OUTPUT: <output> "Output image file (the format is given by the extension)")
+        )
+        (@subcommand transform =>
+            (about: "Losslessly transforms a JPEG image")
+            (@arg INPUT: <input> "Input JPEG file")
+            (@arg OUTPUT: <output> "Output JPEG file")
+            (@arg FLIP: --flip [direction]
+                "Performs a flip ('horizontal' or 'vertical')")
+            (@arg ROTATE: --rotate +allow_hyphen_values [angle]
+                "Rotates the image clockwise (angle is a multiple of 90, negative is counter-clockwise)")
+            (@arg TRANSPOSE: --transpose ...
+                "Transpose image (flip along upper left to lower right axis)")
+            (@arg TRANSVERSE: --transverse ...
+                "Transverse transpose image (flip along upper right to lower left axis)")
+            (@arg CROP: --crop [region]
+                "Crops the image to the region 'WxH+X+Y' (X and Y must be aligned to MCUs)")
+            (@arg PERFECT: --perfect ...
+                "Return an error if the transformation is not perfecly lossless")
+            (@arg TRIM: --trim ...
+                "Discard partial blocks that cannot be transformed")
+            (@arg PROGRESSIVE: --progressive ...
+                "Use progressive entropy coding")
+            (@arg OPTIMIZE: --optimize ...
+                "Use optimized baseline entropy coding")
+            (@arg GRAYSCALE: --grayscale ...
+                "Convert the image into grayscale")
+            (@arg COPY_NONE: --("copy-none") ...
+                "Do not copy any extra markers (such as EXIF data)")
+        )
+        (@subcommand inspect =>
+            (about: "Prints information about a JPEG image")
+            (@arg INPUT: <input> "Input JPEG file")
+        )
+    ).get_matches();
+
+    match args.subcommand() {
+        ("compress", Some(args)) => compress(args),
+        ("decompress", Some(args)) => decompress(args),
+        ("transform", Some(args)) => transform(args),
+        ("inspect", Some(args)) => inspect(args),
+        _ => unreachable!(),
+    }
+}
+
+fn compress(args: &ArgMatches) -> Result<()> {
+    let mut settings = Settings {
+        progressive: args.is_present("PROGRESSIVE"),
+        optimize: args.is_present("OPTIMIZE"),
+        arithmetic: args.is_present("ARITHMETIC"),
+        ..Settings::default()
+    };
+    if let Some(quality) = args.value_of("QUALITY") {
+        settings.quality = quality.parse().context("could not parse value of --quality")?;
+    }
+    if let Some(subsamp) = args.value_of("SUBSAMP") {
+        settings.subsamp = parse_subsamp(subsamp)?;
+    }
+    if let Some(rows) = args.value_of("RESTART") {
+        settings.restart_rows = rows.parse().context("could not parse value of --restart")?;
+    }
+
+    let image = turbojpeg::ImageReader::open(args.value_of("INPUT").unwrap())
+        .context("could not open input image")?
+        .with_guessed_format()?
+        .decode()
+        .context("could not decode input image")?;
+    let (pixels, format) = if image.color().has_color() {
+        (image.to_rgb8().into_raw(), PixelFormat::RGB)
+    } else {
+        settings.subsamp = Subsamp::Gray;
+        (image.to_luma8().into_raw(), PixelFormat::GRAY)
+    };
+
+    let mut compressor = Compressor::new()?;
+    compressor.apply(&settings)?;
+    let jpeg_data = compressor.compress_to_owned(Image {
+        pixels: &pixels[..],
+        width: image.width() as usize,
+        pitch: 0,
+        height: image.height() as usize,
+        format,
+    }).context("could not compress image")?;
+
+    fs::write(args.value_of("OUTPUT").unwrap(), &jpeg_data)
+        .context("could not write output image")?;
+    Ok(())
+}
+
+fn decompress(args: &ArgMatches) -> Result<()> {
+    let jpeg_data = fs::read(args.value_of("INPUT").unwrap())
+        .context("could not read input image")?;
+    let header = turbojpeg::read_header(&jpeg_data)
+        .context("could not read JPEG header")?;
+    let output = args.value_of("OUTPUT").unwrap();
+    if header.colorspace == turbojpeg::Colorspace::Gray {
+        let image: image::GrayImage = turbojpeg::decompress_image(&jpeg_data)
+            .context("could not decompress image")?;
+        image.save(output).context("could not write output image")?;
+    } else {
+        let image: image::RgbImage = turbojpeg::decompress_image(&jpeg_data)
+            .context("could not decompress image")?;
+        image.save(output).context("could not write output image")?;
+    }
+    Ok(())
+}
+
+fn transform(args: &ArgMatches) -> Result<()> {
+    let mut transform = Transform::default();
+    if let Some(direction) = args.value_of("FLIP") {
+        transform.op = match direction {
+            "horizontal" => TransformOp::Hflip,
+            "vertical" => TransformOp::Vflip,
+            _ => bail!("unknown value of --flip"),
+        };
+    } else if let Some(angle) = args.value_of("ROTATE") {
+        let angle = angle.parse().context("could not parse value of --rotate")?;
+        transform.op = TransformOp::from_degrees(angle).context("unsupported value of --rotate")?;
+    } else if args.is_present("TRANSPOSE") {
+        transform.op = TransformOp::Transpose;
+    } else if args.is_present("TRANSVERSE") {
+        transform.op = TransformOp::Transverse;
+    }
+
+    if let Some(region) = args.value_of("CROP") {
+        transform.crop = Some(parse_crop(region)?);
+    }
+    transform.perfect = args.is_present("PERFECT");
+    transform.trim = args.is_present("TRIM");
+    transform.progressive = args.is_present("PROGRESSIVE");
+    transform.optimize = args.is_present("OPTIMIZE");
+    transform.gray = args.is_present("GRAYSCALE");
+    transform.copy_none = args.is_present("COPY_NONE");
+
+    let jpeg_data = fs::read(args.value_of("INPUT").unwrap())
+        .context("could not read input image")?;
+    let mut transformer = Transformer::new()
+        .context("could not create transformer")?;
+    let transformed_data = transformer.transform_to_owned(&transform, &jpeg_data)
+        .context("could not transform JPEG data")?;
+    fs::write(args.value_of("OUTPUT").unwrap(), &transformed_data)
+        .context("could not write output image")?;
+    Ok(())
+}
+
+fn inspect(args: &ArgMatches) -> Result<()> {
+    let jpeg_data = fs::read(args.value_of("INPUT").unwrap())
+        .context("could not read input image")?;
+    let header = turbojpeg::read_header(&jpeg_data)
+        .context("could not read JPEG header")?;
+    println!("size:         {}x{}", header.width, header.height);
+    println!("colorspace:   {:?}", header.colorspace);
+    println!("subsampling:  {:?}", header.subsamp);
+    println!("restart:      {} MCUs", header.restart_interval);
+    match turbojpeg::estimate_quality(&jpeg_data) {
+        Some(quality) => println!("quality:      {} (estimated)", quality),
+        None => println!("quality:      unknown"),
+    }
+
+    let report = turbojpeg::validate(&jpeg_data);
+    println!("scans:        {}", report.scans);
+    match report.problem {
+        Some(problem) => println!("problem:      {}", problem),
+        None => println!("problem:      none"),
+    }
+
+    let quant_tables = turbojpeg::read_quant_tables(&jpeg_data).unwrap_or_default();
+    let huffman_tables = turbojpeg::read_huffman_tables(&jpeg_data).unwrap_or_default();
+    println!("quant tables: {}", quant_tables.len());
+    println!("huff tables:  {}", huffman_tables.len());
+    if let Some(icc_profile) = turbojpeg::read_icc_profile(&jpeg_data) {
+        println!("icc profile:  {} bytes", icc_profile.len());
+    }
+    if let Some(exif) = turbojpeg::read_exif(&jpeg_data) {
+        println!("exif:         {} bytes", exif.len());
+    }
+    Ok(())
+}
+
+fn parse_subsamp(subsamp: &str) -> Result<Subsamp> {
+    Ok(match subsamp {
+        "444" => Subsamp::None,
+        "422" => Subsamp::Sub2x1,
+        "420" => Subsamp::Sub2x2,
+        "440" => Subsamp::Sub1x2,
+        "411" => Subsamp::Sub4x1,
+        "441" => Subsamp::Sub1x4,
+        "gray" => Subsamp::Gray,
+        _ => bail!("unknown value of --subsamp"),
+    })
+}
+
+/// Parses the crop region in the format `WxH+X+Y` (as in jpegtran).
+fn parse_crop(region: &str) -> Result<TransformCrop> {
+    let parse = || -> Option<TransformCrop> {
+        let (size, offset) = region.split_once('+')?;
+        let (width, height) = size.split_once('x')?;
+        let (x, y) = offset.split_once('+')?;
+        Some(TransformCrop {
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+            width: Some(width.parse().ok()?),
+            height: Some(height.parse().ok()?),
+        })
+    };
+    parse().context("could not parse value of --crop (expected 'WxH+X+Y')")
+}
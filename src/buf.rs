@@ -1,7 +1,10 @@
-use std::{ptr, slice};
+use std::{fmt, ptr, slice};
+use std::alloc::{self, Layout};
 use std::convert::{AsRef, AsMut};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::Arc;
 
 /// Owned buffer with JPEG data.
 ///
@@ -14,6 +17,10 @@ pub struct OwnedBuf {
     len: usize,
 }
 
+// `OwnedBuf` uniquely owns its memory and `tj3Free()` can be called from any thread.
+unsafe impl Send for OwnedBuf {}
+unsafe impl Sync for OwnedBuf {}
+
 impl Deref for OwnedBuf {
     type Target = [u8];
     fn deref(&self) -> &[u8] { unsafe { deref(self.ptr, self.len) } }
@@ -54,6 +61,54 @@ impl OwnedBuf {
     pub fn len(&self) -> usize {
         self.len
     }
+
+    /// Converts this buffer into a [`SharedBuf`] without copying the data.
+    pub fn into_shared(self) -> SharedBuf {
+        SharedBuf::new(self)
+    }
+
+    /// Decomposes the buffer into a raw pointer and length, without freeing the memory.
+    ///
+    /// The memory was allocated by TurboJPEG, so you can pass it to C code, which must free it
+    /// using `tj3Free()` (or `tjFree()`), or you can convert it back into a buffer using
+    /// [`from_raw_parts()`][Self::from_raw_parts]. The pointer is null if the buffer is empty and
+    /// has never been allocated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image::mandelbrot(100, 100, turbojpeg::PixelFormat::RGB);
+    /// let jpeg_data = turbojpeg::compress(image.as_deref(), 90, turbojpeg::Subsamp::Sub2x2)?;
+    ///
+    /// // hand the buffer over to C code...
+    /// let (ptr, len) = jpeg_data.into_raw_parts();
+    ///
+    /// // ...and adopt it back
+    /// let jpeg_data = unsafe { turbojpeg::OwnedBuf::from_raw_parts(ptr, len) };
+    /// assert_eq!(turbojpeg::read_header(&jpeg_data)?.width, 100);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn into_raw_parts(self) -> (*mut u8, usize) {
+        let this = std::mem::ManuallyDrop::new(self);
+        (this.ptr, this.len)
+    }
+
+    /// Creates a buffer from a raw pointer and length.
+    ///
+    /// The buffer takes ownership of the memory, which is freed using `tj3Free()` when the buffer
+    /// is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null (with `len` equal to zero) or it must point to memory that was allocated
+    /// by TurboJPEG (using `tj3Alloc()` or by a compression or transformation function of
+    /// TurboJPEG, for example, a pointer obtained from [`into_raw_parts()`][Self::into_raw_parts]),
+    /// which is valid for reads and writes of `len` bytes. The memory must not be freed or
+    /// accessed through any other pointer afterwards.
+    pub unsafe fn from_raw_parts(ptr: *mut u8, len: usize) -> OwnedBuf {
+        OwnedBuf { ptr, len }
+    }
 }
 
 impl Drop for OwnedBuf {
@@ -63,6 +118,193 @@ impl Drop for OwnedBuf {
 }
 
 
+/// Shared, reference-counted buffer with JPEG data.
+///
+/// This is an [`OwnedBuf`] wrapped in an [`Arc`], so cloning a `SharedBuf` is cheap and does not
+/// copy the data. The memory is freed by TurboJPEG when the last clone is dropped. This is useful
+/// when one compressed image needs to be handed to multiple consumers (for example, written to
+/// disk, sent over the network and stored in a cache), possibly on different threads.
+///
+/// # Example
+///
+/// ```
+/// let image = turbojpeg::Image::mandelbrot(100, 100, turbojpeg::PixelFormat::RGB);
+/// let jpeg_data = turbojpeg::compress(image.as_deref(), 90, turbojpeg::Subsamp::Sub2x2)?;
+///
+/// // share the JPEG data without copying
+/// let shared = jpeg_data.into_shared();
+/// let for_thread = shared.clone();
+/// let len = std::thread::spawn(move || for_thread.len()).join().unwrap();
+/// assert_eq!(len, shared.len());
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedBuf {
+    buf: Arc<OwnedBuf>,
+}
+
+impl Deref for SharedBuf {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] { self.buf.deref() }
+}
+impl AsRef<[u8]> for SharedBuf {
+    fn as_ref(&self) -> &[u8] { self.deref() }
+}
+
+impl SharedBuf {
+    /// Wraps an `OwnedBuf` into a shared buffer.
+    pub fn new(buf: OwnedBuf) -> SharedBuf {
+        SharedBuf { buf: Arc::new(buf) }
+    }
+
+    /// Returns the length of the buffer.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns true if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Returns the number of `SharedBuf`s that point to the same data.
+    pub fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.buf)
+    }
+
+    /// Converts this buffer back into an [`OwnedBuf`].
+    ///
+    /// If this is the only reference to the data, the buffer is returned without copying.
+    /// Otherwise, the data is copied into a new `OwnedBuf`.
+    pub fn into_owned(self) -> OwnedBuf {
+        Arc::try_unwrap(self.buf)
+            .unwrap_or_else(|buf| OwnedBuf::copy_from_slice(&buf))
+    }
+
+    /// Returns true if both buffers point to the same data.
+    pub fn ptr_eq(this: &SharedBuf, other: &SharedBuf) -> bool {
+        Arc::ptr_eq(&this.buf, &other.buf)
+    }
+}
+
+impl From<OwnedBuf> for SharedBuf {
+    fn from(buf: OwnedBuf) -> SharedBuf {
+        SharedBuf::new(buf)
+    }
+}
+
+impl<'a> From<OutputBuf<'a>> for SharedBuf {
+    fn from(buf: OutputBuf<'a>) -> SharedBuf {
+        SharedBuf::new(buf.into_owned())
+    }
+}
+
+
+/// Zero-initialized buffer for pixels with aligned start.
+///
+/// The SIMD color conversion in TurboJPEG is measurably slower if the rows of the image are not
+/// aligned, and a `Vec<u8>` is only guaranteed to be aligned to a single byte. This buffer is
+/// allocated with the given alignment (such as [`DEFAULT_ALIGN`][Self::DEFAULT_ALIGN], which is
+/// enough for the widest SIMD registers and matches the size of a cache line), so if you also
+/// align the pitch (see [`Image::with_aligned_pitch()`][crate::Image::with_aligned_pitch]), every
+/// row is aligned. The easiest way to get such an image is
+/// [`Image::allocate_aligned()`][crate::Image::allocate_aligned].
+///
+/// # Example
+///
+/// ```
+/// let buf = turbojpeg::AlignedBuf::zeroed(1000, 64);
+/// assert_eq!(buf.len(), 1000);
+/// assert_eq!(buf.as_ptr() as usize % 64, 0);
+/// assert!(buf.iter().all(|&byte| byte == 0));
+/// ```
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    align: usize,
+}
+
+// `AlignedBuf` uniquely owns its memory, like a `Box<[u8]>`.
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    /// The default alignment in bytes (the size of a cache line and of an AVX-512 register).
+    pub const DEFAULT_ALIGN: usize = 64;
+
+    /// Allocates a buffer of `len` zero bytes that starts at a multiple of `align`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two or if `len` rounded up to `align` overflows
+    /// `isize`. Aborts if the memory cannot be allocated (like `Vec`).
+    pub fn zeroed(len: usize, align: usize) -> AlignedBuf {
+        let layout = Layout::from_size_align(len, align)
+            .expect("invalid size or alignment of AlignedBuf");
+        let ptr = if len == 0 {
+            // zero-sized allocations are not allowed, but an aligned dangling pointer is fine
+            NonNull::new(align as *mut u8).unwrap()
+        } else {
+            let ptr = unsafe { alloc::alloc_zeroed(layout) };
+            NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout))
+        };
+        AlignedBuf { ptr, len, align }
+    }
+
+    /// Returns the length of the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the alignment of the buffer.
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            let layout = Layout::from_size_align(self.len, self.align).unwrap();
+            unsafe { alloc::dealloc(self.ptr.as_ptr(), layout) };
+        }
+    }
+}
+
+impl Clone for AlignedBuf {
+    fn clone(&self) -> AlignedBuf {
+        let mut buf = AlignedBuf::zeroed(self.len, self.align);
+        buf.copy_from_slice(self);
+        buf
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlignedBuf").field("len", &self.len).field("align", &self.align).finish()
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] { unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) } }
+}
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] { unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) } }
+}
+impl AsRef<[u8]> for AlignedBuf {
+    fn as_ref(&self) -> &[u8] { self.deref() }
+}
+impl AsMut<[u8]> for AlignedBuf {
+    fn as_mut(&mut self) -> &mut [u8] { self.deref_mut() }
+}
+
 
 /// Output buffer for JPEG data (borrowed or owned).
 ///
@@ -71,11 +313,12 @@ impl Drop for OwnedBuf {
 /// from the standard library:
 ///
 /// - Borrowed buffer wraps a `&mut [u8]`, preallocated slice of fixed size provided by you. When
-/// using a borrowed buffer, TurboJPEG cannot resize the buffer, so the operation will fail if the
-/// output does not fit into the buffer.
+///   using a borrowed buffer, TurboJPEG cannot resize the buffer, so the operation will fail if the
+///   output does not fit into the buffer.
 ///
 /// - Owned buffer wraps an [`OwnedBuf`], memory buffer owned by TurboJPEG. This buffer can be
-/// automatically resized to contain the compressed data, so you don't have to worry about its size.
+///   automatically resized to contain the compressed data, so you don't have to worry about its
+///   size.
 ///
 /// The lifetime parameter `'a` tracks the lifetime of the borrowed slice. In the case of owned
 /// buffer, the lifetime can be `'static`.
@@ -0,0 +1,70 @@
+use bumpalo::Bump;
+use crate::Image;
+#[cfg(feature = "yuv")]
+use crate::YuvImage;
+use crate::common::{PixelFormat, Result};
+#[cfg(feature = "yuv")]
+use crate::decompress::yuv_pixels_len;
+use crate::decompress::Decompressor;
+
+impl Decompressor {
+    /// Decompress a JPEG image into pixels allocated in the arena `bump`.
+    ///
+    /// This is like [`decompress()`][crate::decompress()], but the pixels of the returned image
+    /// are allocated in a [`bumpalo::Bump`] instead of the heap. In a server or a video player that
+    /// decompresses many images, you can reset the arena once per request or frame, which frees all
+    /// images at once and reuses the memory without calling the global allocator for every image.
+    /// The returned image has no padding between rows and it borrows the arena, so the arena cannot
+    /// be reset while the image is alive. If an error is returned, the pixels may have already been
+    /// allocated, and they are freed when the arena is reset.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// let mut bump = bumpalo::Bump::new();
+    ///
+    /// for _ in 0..3 {
+    ///     let image = decompressor.decompress_in(&jpeg_data, turbojpeg::PixelFormat::RGB, &bump)?;
+    ///     assert_eq!((image.width, image.height), (384, 256));
+    ///     // ... use the image ...
+    ///
+    ///     // free the image and reuse the memory for the next one
+    ///     bump.reset();
+    /// }
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "bumpalo")))]
+    pub fn decompress_in<'b>(
+        &mut self,
+        jpeg_data: &[u8],
+        format: PixelFormat,
+        bump: &'b Bump,
+    ) -> Result<Image<&'b mut [u8]>> {
+        let header = self.read_header(jpeg_data)?;
+        let pitch = header.width * format.size();
+        let pixels = bump.alloc_slice_fill_copy(pitch * header.height, 0u8);
+        let mut image = Image { pixels, width: header.width, pitch, height: header.height, format };
+        self.decompress(jpeg_data, image.as_deref_mut())?;
+        Ok(image)
+    }
+
+    /// Decompress a JPEG image into a YUV image allocated in the arena `bump`.
+    ///
+    /// This is like [`decompress_to_yuv()`][crate::decompress_to_yuv()] (the rows of the planes are
+    /// aligned to 4 bytes), but the pixels are allocated in a [`bumpalo::Bump`]. See
+    /// [`decompress_in()`][Self::decompress_in] for details.
+    #[cfg(feature = "yuv")]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "bumpalo", feature = "yuv"))))]
+    pub fn decompress_to_yuv_in<'b>(&mut self, jpeg_data: &[u8], bump: &'b Bump) -> Result<YuvImage<&'b mut [u8]>> {
+        let header = self.read_header(jpeg_data)?;
+        let align = 4;
+        let len = yuv_pixels_len(header.width, align, header.height, header.subsamp)?;
+        let pixels = bump.alloc_slice_fill_copy(len, 0u8);
+        let mut image = YuvImage { pixels, width: header.width, align, height: header.height, subsamp: header.subsamp };
+        self.decompress_to_yuv(jpeg_data, image.as_deref_mut())?;
+        Ok(image)
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::common::{Error, Result};
+
+/// Token for cancelling long-running operations.
+///
+/// The token can be cloned and shared between threads; all clones refer to the same flag. When
+/// you call [`cancel()`][Self::cancel] on any clone, the operations that received the token stop
+/// at the next opportunity (between images or between strips) and return [`Error::Cancelled`].
+/// An operation that is already running inside TurboJPEG cannot be interrupted, so the
+/// cancellation takes effect after the current image (or strip) is finished.
+///
+/// The token is accepted by [`Compressor::compress_batch_cancellable()`][batch] and by the
+/// multi-threaded pipeline (with the `pipeline` feature). In your own callbacks (for example, in
+/// [`Decompressor::decompress_strips()`][strips]), you can use [`check()`][Self::check] to stop
+/// the operation.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{CancelToken, Error, Image, PixelFormat};
+///
+/// let pages = (0..100)
+///     .map(|_| Image::mandelbrot(64, 64, PixelFormat::RGB))
+///     .collect::<Vec<_>>();
+///
+/// let cancel = CancelToken::new();
+/// // ... pass a clone of `cancel` to the user interface, which calls `cancel.cancel()` ...
+/// cancel.cancel();
+///
+/// let mut compressor = turbojpeg::Compressor::new()?;
+/// let result = compressor.compress_batch_cancellable(pages.iter().map(|page| page.as_deref()), &cancel);
+/// assert!(matches!(result, Err(Error::Cancelled)));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [batch]: crate::Compressor::compress_batch_cancellable
+/// [strips]: crate::Decompressor::decompress_strips
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Creates a new token that is not cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Cancels the operations that use this token (or any of its clones).
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns [`Error::Cancelled`] if the token has been cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled)
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::OnceLock;
+use crate::handle::Handle;
+
+/// Capabilities of the linked TurboJPEG library, returned by [`capabilities()`].
+///
+/// TurboJPEG 3 supports all of these features, but TurboJPEG 2 (with the `compat-2x` feature)
+/// supports only 8-bit lossy JPEG. You can use the capabilities to hide the features that are not
+/// available, instead of discovering the limitations through errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Data precisions (bits per sample) that the library can compress and decompress. TurboJPEG 3
+    /// supports 8 and 12 bits in lossy JPEG, and also 16 bits in lossless JPEG.
+    pub precisions: &'static [usize],
+    /// Whether the library supports lossless JPEG (`TJPARAM_LOSSLESS`).
+    pub lossless: bool,
+    /// Whether the library can compute optimal Huffman tables (see
+    /// [`Compressor::set_optimize()`][crate::Compressor::set_optimize]).
+    pub optimize: bool,
+    /// Whether the library supports any limit of the number of scans in
+    /// [`Limits::max_scans`][crate::Limits::max_scans]. TurboJPEG 2 can only limit the number of
+    /// scans to 500, so other limits are rejected.
+    pub scan_limit: bool,
+    /// Maximal width of a JPEG image in pixels.
+    pub max_width: usize,
+    /// Maximal height of a JPEG image in pixels.
+    pub max_height: usize,
+}
+
+impl Capabilities {
+    /// Returns true if the library supports the data precision (bits per sample).
+    pub fn supports_precision(&self, precision: usize) -> bool {
+        self.precisions.contains(&precision)
+    }
+}
+
+/// Maximal width and height of a JPEG image in libjpeg (`JPEG_MAX_DIMENSION`).
+const MAX_DIMENSION: usize = 65500;
+
+/// Returns the capabilities of the linked TurboJPEG library.
+///
+/// The capabilities are determined by setting the parameters of a TurboJPEG instance when this
+/// function is called for the first time, so the result is correct even if the library is linked
+/// dynamically.
+///
+/// # Example
+///
+/// ```
+/// let capabilities = turbojpeg::capabilities();
+/// assert!(capabilities.supports_precision(8));
+/// if capabilities.lossless {
+///     println!("lossless JPEG is supported");
+/// }
+///
+/// // a panorama that is too wide must be split into multiple JPEG images
+/// let panorama_width = 100_000;
+/// assert!(panorama_width > capabilities.max_width);
+/// ```
+pub fn capabilities() -> Capabilities {
+    static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+    *CAPABILITIES.get_or_init(|| {
+        let probe = |param| match Handle::new(raw::TJINIT_TJINIT_COMPRESS) {
+            Ok(mut handle) => handle.set(param, 1).is_ok(),
+            Err(_) => false,
+        };
+        let lossless = probe(raw::TJPARAM_TJPARAM_LOSSLESS);
+        let optimize = probe(raw::TJPARAM_TJPARAM_OPTIMIZE);
+        let scan_limit = match Handle::new(raw::TJINIT_TJINIT_DECOMPRESS) {
+            Ok(mut handle) => handle.set(raw::TJPARAM_TJPARAM_SCANLIMIT, 1).is_ok(),
+            Err(_) => false,
+        };
+        Capabilities {
+            // 12-bit and 16-bit JPEG were added in TurboJPEG 3, together with lossless JPEG
+            precisions: if lossless { &[8, 12, 16] } else { &[8] },
+            lossless,
+            optimize,
+            scan_limit,
+            max_width: MAX_DIMENSION,
+            max_height: MAX_DIMENSION,
+        }
+    })
+}
@@ -0,0 +1,254 @@
+use crate::Image;
+use crate::common::{Result, Subsamp};
+use crate::compress::Compressor;
+use crate::decompress::{Decompressor, DecompressHeader};
+#[cfg(feature = "transform")]
+use crate::transform::{Transform, Transformer};
+
+/// Encoder of images into JPEG.
+///
+/// This trait is implemented by [`Compressor`]. You can write your code against this trait
+/// instead of using `Compressor` directly, so that you can replace the encoder with a mock in
+/// tests, or with another implementation on platforms where you cannot link to TurboJPEG.
+///
+/// The trait is object safe, so you can also use `Box<dyn JpegEncode>` or `&mut dyn JpegEncode`.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{Image, JpegEncode, PixelFormat};
+///
+/// fn make_thumbnail<E: JpegEncode>(encoder: &mut E, image: Image<&[u8]>) -> turbojpeg::Result<Vec<u8>> {
+///     encoder.set_quality(70)?;
+///     encoder.compress_to_vec(image)
+/// }
+///
+/// let image = Image::mandelbrot(64, 64, PixelFormat::RGB);
+/// let jpeg_data = make_thumbnail(&mut turbojpeg::Compressor::new()?, image.as_deref())?;
+/// assert_eq!(turbojpeg::read_header(&jpeg_data)?.width, 64);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// A fake encoder can simulate failures of the codec, without invoking TurboJPEG at all:
+///
+/// ```
+/// use turbojpeg::{Error, Image, JpegEncode, PixelFormat, Subsamp};
+///
+/// struct FailingEncoder;
+///
+/// impl JpegEncode for FailingEncoder {
+///     fn set_quality(&mut self, _: i32) -> turbojpeg::Result<()> { Ok(()) }
+///     fn set_subsamp(&mut self, _: Subsamp) -> turbojpeg::Result<()> { Ok(()) }
+///     fn compress_to_vec(&mut self, _: Image<&[u8]>) -> turbojpeg::Result<Vec<u8>> {
+///         Err(Error::TurboJpegError("simulated failure".into()))
+///     }
+/// }
+///
+/// let mut encoder: Box<dyn JpegEncode> = Box::new(FailingEncoder);
+/// let image = Image::mandelbrot(8, 8, PixelFormat::RGB);
+/// assert!(encoder.compress_to_vec(image.as_deref()).is_err());
+/// ```
+pub trait JpegEncode {
+    /// Set the quality of the compressed JPEG images, from 1 (worst) to 100 (best).
+    ///
+    /// See [`Compressor::set_quality()`].
+    fn set_quality(&mut self, quality: i32) -> Result<()>;
+
+    /// Set the level of chrominance subsampling of the compressed JPEG images.
+    ///
+    /// See [`Compressor::set_subsamp()`].
+    fn set_subsamp(&mut self, subsamp: Subsamp) -> Result<()>;
+
+    /// Compress the `image` into a new `Vec<u8>`.
+    ///
+    /// See [`Compressor::compress_to_vec()`].
+    fn compress_to_vec(&mut self, image: Image<&[u8]>) -> Result<Vec<u8>>;
+}
+
+/// Decoder of JPEG images.
+///
+/// This trait is implemented by [`Decompressor`]. You can write your code against this trait
+/// instead of using `Decompressor` directly, so that you can replace the decoder with a mock in
+/// tests, or with another implementation on platforms where you cannot link to TurboJPEG.
+///
+/// The trait is object safe, so you can also use `Box<dyn JpegDecode>` or `&mut dyn JpegDecode`.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{Colorspace, DecompressHeader, Image, JpegDecode, Subsamp};
+///
+/// // a mock decoder that produces a white image of fixed size
+/// struct WhiteDecoder;
+///
+/// impl JpegDecode for WhiteDecoder {
+///     fn read_header(&mut self, _: &[u8]) -> turbojpeg::Result<DecompressHeader> {
+///         Ok(DecompressHeader::new(8, 8, Subsamp::None, Colorspace::YCbCr))
+///     }
+///
+///     fn decompress(&mut self, _: &[u8], output: Image<&mut [u8]>) -> turbojpeg::Result<()> {
+///         output.pixels.fill(255);
+///         Ok(())
+///     }
+/// }
+///
+/// fn image_width<D: JpegDecode>(decoder: &mut D, jpeg_data: &[u8]) -> turbojpeg::Result<usize> {
+///     Ok(decoder.read_header(jpeg_data)?.width)
+/// }
+///
+/// assert_eq!(image_width(&mut WhiteDecoder, &[])?, 8);
+///
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// assert_eq!(image_width(&mut turbojpeg::Decompressor::new()?, &jpeg_data)?, 384);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait JpegDecode {
+    /// Read the JPEG header without decompressing the image.
+    ///
+    /// See [`Decompressor::read_header()`].
+    fn read_header(&mut self, jpeg_data: &[u8]) -> Result<DecompressHeader>;
+
+    /// Decompress a JPEG image in `jpeg_data` into `output`.
+    ///
+    /// See [`Decompressor::decompress()`].
+    fn decompress(&mut self, jpeg_data: &[u8], output: Image<&mut [u8]>) -> Result<()>;
+}
+
+/// Lossless transformer of JPEG images.
+///
+/// This trait is implemented by [`Transformer`]. Like [`JpegEncode`] and [`JpegDecode`], it allows
+/// you to replace the transformer with a mock in tests. The trait is object safe.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{JpegTransform, Transform, TransformOp};
+///
+/// // a mock transformer that returns the data unchanged
+/// struct IdentityTransformer;
+///
+/// impl JpegTransform for IdentityTransformer {
+///     fn transform_to_vec(&mut self, _: &Transform, jpeg_data: &[u8]) -> turbojpeg::Result<Vec<u8>> {
+///         Ok(jpeg_data.to_vec())
+///     }
+/// }
+///
+/// fn rotate(transformer: &mut dyn JpegTransform, jpeg_data: &[u8]) -> turbojpeg::Result<Vec<u8>> {
+///     transformer.transform_to_vec(&Transform::op(TransformOp::Rot90), jpeg_data)
+/// }
+///
+/// assert_eq!(rotate(&mut IdentityTransformer, &[1, 2, 3])?, [1, 2, 3]);
+///
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let rotated = rotate(&mut turbojpeg::Transformer::new()?, &jpeg_data)?;
+/// assert_eq!(turbojpeg::read_header(&rotated)?.width, 256);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg(feature = "transform")]
+#[cfg_attr(docsrs, doc(cfg(feature = "transform")))]
+pub trait JpegTransform {
+    /// Apply a lossless transformation to the JPEG image in `jpeg_data` and return the result in a
+    /// new `Vec<u8>`.
+    ///
+    /// See [`Transformer::transform_to_vec()`].
+    fn transform_to_vec(&mut self, transform: &Transform, jpeg_data: &[u8]) -> Result<Vec<u8>>;
+}
+
+impl JpegEncode for Compressor {
+    fn set_quality(&mut self, quality: i32) -> Result<()> {
+        Compressor::set_quality(self, quality)
+    }
+
+    fn set_subsamp(&mut self, subsamp: Subsamp) -> Result<()> {
+        Compressor::set_subsamp(self, subsamp)
+    }
+
+    fn compress_to_vec(&mut self, image: Image<&[u8]>) -> Result<Vec<u8>> {
+        Compressor::compress_to_vec(self, image)
+    }
+}
+
+impl JpegDecode for Decompressor {
+    fn read_header(&mut self, jpeg_data: &[u8]) -> Result<DecompressHeader> {
+        Decompressor::read_header(self, jpeg_data)
+    }
+
+    fn decompress(&mut self, jpeg_data: &[u8], output: Image<&mut [u8]>) -> Result<()> {
+        Decompressor::decompress(self, jpeg_data, output)
+    }
+}
+
+#[cfg(feature = "transform")]
+#[cfg_attr(docsrs, doc(cfg(feature = "transform")))]
+impl JpegTransform for Transformer {
+    fn transform_to_vec(&mut self, transform: &Transform, jpeg_data: &[u8]) -> Result<Vec<u8>> {
+        Transformer::transform_to_vec(self, transform, jpeg_data)
+    }
+}
+
+impl<T: JpegEncode + ?Sized> JpegEncode for &mut T {
+    fn set_quality(&mut self, quality: i32) -> Result<()> {
+        (**self).set_quality(quality)
+    }
+
+    fn set_subsamp(&mut self, subsamp: Subsamp) -> Result<()> {
+        (**self).set_subsamp(subsamp)
+    }
+
+    fn compress_to_vec(&mut self, image: Image<&[u8]>) -> Result<Vec<u8>> {
+        (**self).compress_to_vec(image)
+    }
+}
+
+impl<T: JpegDecode + ?Sized> JpegDecode for &mut T {
+    fn read_header(&mut self, jpeg_data: &[u8]) -> Result<DecompressHeader> {
+        (**self).read_header(jpeg_data)
+    }
+
+    fn decompress(&mut self, jpeg_data: &[u8], output: Image<&mut [u8]>) -> Result<()> {
+        (**self).decompress(jpeg_data, output)
+    }
+}
+
+#[cfg(feature = "transform")]
+#[cfg_attr(docsrs, doc(cfg(feature = "transform")))]
+impl<T: JpegTransform + ?Sized> JpegTransform for &mut T {
+    fn transform_to_vec(&mut self, transform: &Transform, jpeg_data: &[u8]) -> Result<Vec<u8>> {
+        (**self).transform_to_vec(transform, jpeg_data)
+    }
+}
+
+impl<T: JpegEncode + ?Sized> JpegEncode for Box<T> {
+    fn set_quality(&mut self, quality: i32) -> Result<()> {
+        (**self).set_quality(quality)
+    }
+
+    fn set_subsamp(&mut self, subsamp: Subsamp) -> Result<()> {
+        (**self).set_subsamp(subsamp)
+    }
+
+    fn compress_to_vec(&mut self, image: Image<&[u8]>) -> Result<Vec<u8>> {
+        (**self).compress_to_vec(image)
+    }
+}
+
+impl<T: JpegDecode + ?Sized> JpegDecode for Box<T> {
+    fn read_header(&mut self, jpeg_data: &[u8]) -> Result<DecompressHeader> {
+        (**self).read_header(jpeg_data)
+    }
+
+    fn decompress(&mut self, jpeg_data: &[u8], output: Image<&mut [u8]>) -> Result<()> {
+        (**self).decompress(jpeg_data, output)
+    }
+}
+
+#[cfg(feature = "transform")]
+#[cfg_attr(docsrs, doc(cfg(feature = "transform")))]
+impl<T: JpegTransform + ?Sized> JpegTransform for Box<T> {
+    fn transform_to_vec(&mut self, transform: &Transform, jpeg_data: &[u8]) -> Result<Vec<u8>> {
+        (**self).transform_to_vec(transform, jpeg_data)
+    }
+}
@@ -0,0 +1,268 @@
+use crate::raw;
+use crate::common::{Error, Result};
+use crate::handle::Handle;
+use crate::join::{self, Capture};
+use crate::markers;
+use crate::tables::read_quant_tables;
+
+/// Quantized DCT coefficients of one component of a JPEG image.
+///
+/// Returned by [`read_coefficients()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ComponentCoefficients {
+    /// Width of the component in 8×8 blocks.
+    pub width_in_blocks: usize,
+    /// Height of the component in 8×8 blocks.
+    pub height_in_blocks: usize,
+    /// Quantization table of the component in the natural (row-major) order. Multiply a
+    /// coefficient by the corresponding value to obtain the dequantized coefficient.
+    pub quant_table: [u16; 64],
+    /// The coefficients, 64 coefficients per block in the natural (row-major) order, with blocks
+    /// in row-major order.
+    pub coeffs: Vec<i16>,
+}
+
+impl ComponentCoefficients {
+    /// Returns the coefficients of the block in column `x` and row `y`.
+    ///
+    /// `block[8*v + u]` is the coefficient with horizontal frequency `u` and vertical frequency
+    /// `v`, so `block[0]` is the DC coefficient.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block is outside of the component.
+    pub fn block(&self, x: usize, y: usize) -> &[i16] {
+        assert!(x < self.width_in_blocks && y < self.height_in_blocks, "block is outside of the component");
+        let offset = (y * self.width_in_blocks + x) * 64;
+        &self.coeffs[offset..offset + 64]
+    }
+}
+
+/// Reads the quantized DCT coefficients of all components of a JPEG image.
+///
+/// The coefficients are read using a lossless transform, without decompressing the image into
+/// pixels. The components are returned in the order of the frame header (for YCbCr images, this is
+/// Y, Cb and Cr). Note that the size of the components in blocks is rounded up, so the blocks at
+/// the right and bottom edges may extend beyond the image.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let components = turbojpeg::read_coefficients(&jpeg_data)?;
+/// let luma = &components[0];
+/// println!("DC coefficient of the first block: {}", luma.block(0, 0)[0]);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[doc(alias = "tj3Transform")]
+pub fn read_coefficients(jpeg_data: &[u8]) -> Result<Vec<ComponentCoefficients>> {
+    let mut handle = Handle::new(raw::TJINIT_TJINIT_TRANSFORM)?;
+    let mut capture = Capture { planes: Vec::new(), failed: false };
+    join::run_filter(&mut handle, jpeg_data, join::capture_filter, &mut capture as *mut Capture as *mut _, 0)?;
+    if capture.failed {
+        return Err(Error::BadCoefficients("invalid region passed to the filter"))
+    }
+
+    let components = markers::read_header_segments(jpeg_data)
+        .and_then(|segments| segments.into_iter().find(|segment| markers::is_sof(segment.marker)))
+        .and_then(|sof| markers::parse_frame_components(sof.data))
+        .ok_or(Error::BadCoefficients("invalid frame header"))?;
+    let tables = read_quant_tables(jpeg_data).unwrap_or_default();
+    if capture.planes.len() != components.len() {
+        return Err(Error::BadCoefficients("number of components does not match the frame header"))
+    }
+
+    capture.planes.into_iter().zip(components.iter()).map(|(plane, component)| {
+        let table = tables.iter().find(|table| table.id == component.tq)
+            .ok_or(Error::BadCoefficients("missing quantization table"))?;
+        Ok(ComponentCoefficients {
+            width_in_blocks: plane.width_in_blocks,
+            height_in_blocks: plane.height_in_blocks,
+            quant_table: table.values,
+            coeffs: plane.coeffs,
+        })
+    }).collect()
+}
+
+/// Statistics of the DCT coefficients of one component of a JPEG image.
+///
+/// Returned by [`coefficient_stats()`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ComponentStats {
+    /// Histograms of the quantized coefficients for every frequency.
+    pub histogram: CoefficientHistogram,
+    /// Blockiness of the component: the mean absolute difference between neighboring samples
+    /// across the boundaries of 8×8 blocks, divided by the mean absolute difference between
+    /// neighboring samples inside the blocks.
+    ///
+    /// Values close to 1 mean that the block boundaries are not visible, larger values indicate
+    /// blocking artifacts. A completely flat component has blockiness 1.
+    pub blockiness: f64,
+}
+
+/// Histograms of quantized DCT coefficients, one histogram for each of the 64 frequencies.
+///
+/// Values outside of the range `-MAX_VALUE..=MAX_VALUE` are counted as `-MAX_VALUE` or
+/// `MAX_VALUE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoefficientHistogram {
+    counts: Vec<u64>,
+}
+
+impl CoefficientHistogram {
+    /// The largest absolute value that is counted separately.
+    pub const MAX_VALUE: i16 = 255;
+
+    const BINS: usize = 2 * Self::MAX_VALUE as usize + 1;
+
+    fn new() -> Self {
+        CoefficientHistogram { counts: vec![0; 64 * Self::BINS] }
+    }
+
+    fn add(&mut self, frequency: usize, value: i16) {
+        let bin = (value.clamp(-Self::MAX_VALUE, Self::MAX_VALUE) + Self::MAX_VALUE) as usize;
+        self.counts[frequency * Self::BINS + bin] += 1;
+    }
+
+    /// Returns the number of coefficients at the given frequency with the given quantized value.
+    ///
+    /// The frequency is the index in the natural (row-major) order, so `8*v + u` is the
+    /// frequency with horizontal frequency `u` and vertical frequency `v`, and 0 is the DC
+    /// coefficient.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frequency` is not less than 64.
+    pub fn count(&self, frequency: usize, value: i16) -> u64 {
+        if value.unsigned_abs() > Self::MAX_VALUE as u16 {
+            return 0
+        }
+        self.counts(frequency)[(value + Self::MAX_VALUE) as usize]
+    }
+
+    /// Returns the counts of all values at the given frequency, from `-MAX_VALUE` to `MAX_VALUE`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frequency` is not less than 64.
+    pub fn counts(&self, frequency: usize) -> &[u64] {
+        assert!(frequency < 64, "frequency must be less than 64");
+        &self.counts[frequency * Self::BINS..(frequency + 1) * Self::BINS]
+    }
+}
+
+/// Computes statistics of the DCT coefficients of all components of a JPEG image.
+///
+/// For every component, this computes the histograms of the quantized coefficients (see
+/// [`CoefficientHistogram`]) and a blockiness metric (see [`ComponentStats::blockiness`]). The
+/// histograms are useful for forensic analysis: for example, an image that was compressed twice
+/// with different quality has periodic gaps or peaks in the histograms. The blockiness is computed
+/// from the samples of the component (which are reconstructed from the coefficients), so it also
+/// works for components with subsampled chrominance.
+///
+/// The components are returned in the same order as in [`read_coefficients()`].
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let stats = turbojpeg::coefficient_stats(&jpeg_data)?;
+/// let luma = &stats[0];
+/// println!("blockiness: {:.3}", luma.blockiness);
+/// println!("zero DC coefficients: {}", luma.histogram.count(0, 0));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn coefficient_stats(jpeg_data: &[u8]) -> Result<Vec<ComponentStats>> {
+    let components = read_coefficients(jpeg_data)?;
+    Ok(components.iter().map(|component| {
+        let mut histogram = CoefficientHistogram::new();
+        for block in component.coeffs.chunks_exact(64) {
+            for (frequency, &value) in block.iter().enumerate() {
+                histogram.add(frequency, value);
+            }
+        }
+        ComponentStats { histogram, blockiness: blockiness(component) }
+    }).collect())
+}
+
+fn blockiness(component: &ComponentCoefficients) -> f64 {
+    let width = component.width_in_blocks * 8;
+    let height = component.height_in_blocks * 8;
+    let samples = reconstruct_samples(component);
+
+    // sums and counts of absolute differences across the block boundaries and inside the blocks
+    let mut boundary = (0.0, 0usize);
+    let mut inner = (0.0, 0usize);
+    let mut add = |diff: f32, at_boundary: bool| {
+        let sum = if at_boundary { &mut boundary } else { &mut inner };
+        sum.0 += diff.abs() as f64;
+        sum.1 += 1;
+    };
+    for y in 0..height {
+        for x in 0..width {
+            let sample = samples[y * width + x];
+            if x + 1 < width {
+                add(samples[y * width + x + 1] - sample, (x + 1) / 8 * 8 == x + 1);
+            }
+            if y + 1 < height {
+                add(samples[(y + 1) * width + x] - sample, (y + 1) / 8 * 8 == y + 1);
+            }
+        }
+    }
+
+    let boundary_mean = if boundary.1 > 0 { boundary.0 / boundary.1 as f64 } else { 0.0 };
+    let inner_mean = if inner.1 > 0 { inner.0 / inner.1 as f64 } else { 0.0 };
+    if inner_mean > 0.0 {
+        boundary_mean / inner_mean
+    } else if boundary_mean > 0.0 {
+        f64::INFINITY
+    } else {
+        1.0
+    }
+}
+
+/// Computes the samples of the component using the inverse DCT of the dequantized coefficients.
+///
+/// The samples are not level-shifted or clamped, because we only need their differences.
+fn reconstruct_samples(component: &ComponentCoefficients) -> Vec<f32> {
+    // basis[8*x + u] = C(u)/2 * cos((2x + 1)uπ/16)
+    let mut basis = [0f32; 64];
+    for x in 0..8 {
+        for u in 0..8 {
+            let c = if u == 0 { std::f32::consts::FRAC_1_SQRT_2 } else { 1.0 };
+            let angle = (2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0;
+            basis[8 * x + u] = c / 2.0 * angle.cos();
+        }
+    }
+
+    let width = component.width_in_blocks * 8;
+    let mut samples = vec![0f32; width * component.height_in_blocks * 8];
+    for block_y in 0..component.height_in_blocks {
+        for block_x in 0..component.width_in_blocks {
+            let block = component.block(block_x, block_y);
+            let mut dequantized = [0f32; 64];
+            for ((dequantized, &coeff), &step) in dequantized.iter_mut().zip(block).zip(component.quant_table.iter()) {
+                *dequantized = coeff as f32 * step as f32;
+            }
+
+            // separable inverse DCT: first the rows, then the columns
+            let mut rows = [0f32; 64];
+            for v in 0..8 {
+                for x in 0..8 {
+                    rows[8 * v + x] = (0..8).map(|u| basis[8 * x + u] * dequantized[8 * v + u]).sum();
+                }
+            }
+            for y in 0..8 {
+                for x in 0..8 {
+                    let sample = (0..8).map(|v| basis[8 * y + v] * rows[8 * v + x]).sum();
+                    samples[(block_y * 8 + y) * width + block_x * 8 + x] = sample;
+                }
+            }
+        }
+    }
+    samples
+}
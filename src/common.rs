@@ -100,6 +100,45 @@ pub enum PixelFormat {
 }
 
 impl PixelFormat {
+    /// All pixel formats supported by TurboJPEG.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// for format in turbojpeg::PixelFormat::ALL {
+    ///     let image = turbojpeg::Image::mandelbrot(16, 16, format);
+    ///     assert!(image.pitch >= image.width * format.size());
+    /// }
+    /// ```
+    pub const ALL: [PixelFormat; 12] = [
+        PixelFormat::RGB,
+        PixelFormat::BGR,
+        PixelFormat::RGBX,
+        PixelFormat::BGRX,
+        PixelFormat::XBGR,
+        PixelFormat::XRGB,
+        PixelFormat::GRAY,
+        PixelFormat::RGBA,
+        PixelFormat::BGRA,
+        PixelFormat::ABGR,
+        PixelFormat::ARGB,
+        PixelFormat::CMYK,
+    ];
+
+    /// Returns an iterator over [all pixel formats][Self::ALL].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let alpha_formats = turbojpeg::PixelFormat::iter()
+    ///     .filter(|format| format.has_alpha())
+    ///     .count();
+    /// assert_eq!(alpha_formats, 4);
+    /// ```
+    pub fn iter() -> impl Iterator<Item = PixelFormat> {
+        PixelFormat::ALL.into_iter()
+    }
+
     /// The size of a pixel in bytes.
     pub fn size(&self) -> usize {
         match self {
@@ -117,6 +156,185 @@ impl PixelFormat {
             PixelFormat::CMYK => 4,
         }
     }
+
+    /// Returns true if the pixel format has an alpha channel.
+    ///
+    /// When decompressing, the alpha channel is set to 0xFF (opaque).
+    pub fn has_alpha(self) -> bool {
+        matches!(self, PixelFormat::RGBA | PixelFormat::BGRA | PixelFormat::ABGR | PixelFormat::ARGB)
+    }
+
+    /// The offset of the red component in bytes within a pixel, or `None` for grayscale and CMYK.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// assert_eq!(turbojpeg::PixelFormat::XRGB.red_offset(), Some(1));
+    /// assert_eq!(turbojpeg::PixelFormat::GRAY.red_offset(), None);
+    /// ```
+    #[doc(alias = "tjRedOffset")]
+    pub fn red_offset(self) -> Option<usize> {
+        self.rgba_offsets().map(|(r, _, _, _)| r)
+    }
+
+    /// The offset of the green component in bytes within a pixel, or `None` for grayscale and
+    /// CMYK.
+    #[doc(alias = "tjGreenOffset")]
+    pub fn green_offset(self) -> Option<usize> {
+        self.rgba_offsets().map(|(_, g, _, _)| g)
+    }
+
+    /// The offset of the blue component in bytes within a pixel, or `None` for grayscale and
+    /// CMYK.
+    #[doc(alias = "tjBlueOffset")]
+    pub fn blue_offset(self) -> Option<usize> {
+        self.rgba_offsets().map(|(_, _, b, _)| b)
+    }
+
+    /// The offset of the alpha (or unused X) component in bytes within a pixel, or `None` if the
+    /// pixel format has no such component.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// assert_eq!(turbojpeg::PixelFormat::ABGR.alpha_offset(), Some(0));
+    /// assert_eq!(turbojpeg::PixelFormat::RGB.alpha_offset(), None);
+    /// ```
+    #[doc(alias = "tjAlphaOffset")]
+    pub fn alpha_offset(self) -> Option<usize> {
+        self.rgba_offsets().and_then(|(_, _, _, a)| a)
+    }
+
+    /// Returns the pixel format for `u32` pixels with components in the given logical order.
+    ///
+    /// GUI toolkits usually describe pixels as `u32` values with components ordered from the most
+    /// significant byte, so for example `ARGB` means `0xAARRGGBB` (this is `CAIRO_FORMAT_ARGB32` in
+    /// Cairo and GDK, and `SkColor` in Skia). The order of the bytes in memory depends on the
+    /// endianness of the platform, so `0xAARRGGBB` is stored as [`BGRA`][Self::BGRA] on
+    /// little-endian platforms and as [`ARGB`][Self::ARGB] on big-endian platforms. This function
+    /// interprets `self` as the logical order and returns the pixel format with the matching
+    /// layout in memory, which you can use to decompress directly into such pixels (see also
+    /// [`Image::pixels_u32()`][crate::Image::pixels_u32]). The mapping is its own inverse, so you
+    /// can also use it to get the logical order of a pixel format.
+    ///
+    /// Returns `None` if the pixel format does not have 4-byte RGB pixels (grayscale, RGB, BGR
+    /// and CMYK).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::PixelFormat;
+    /// let format = PixelFormat::ARGB.from_u32_order().unwrap();
+    /// if cfg!(target_endian = "little") {
+    ///     assert_eq!(format, PixelFormat::BGRA);
+    /// } else {
+    ///     assert_eq!(format, PixelFormat::ARGB);
+    /// }
+    ///
+    /// // red is always in the third byte from the bottom of 0xAARRGGBB
+    /// let pixel = u32::from_ne_bytes([10, 20, 30, 40]);
+    /// let red = format.red_offset().unwrap();
+    /// assert_eq!((pixel >> 16) as u8, [10, 20, 30, 40][red]);
+    /// ```
+    pub fn from_u32_order(self) -> Option<PixelFormat> {
+        if self.size() != 4 || self.rgba_offsets().is_none() {
+            return None
+        }
+        if cfg!(target_endian = "big") {
+            return Some(self)
+        }
+        Some(match self {
+            PixelFormat::RGBX => PixelFormat::XBGR,
+            PixelFormat::XBGR => PixelFormat::RGBX,
+            PixelFormat::BGRX => PixelFormat::XRGB,
+            PixelFormat::XRGB => PixelFormat::BGRX,
+            PixelFormat::RGBA => PixelFormat::ABGR,
+            PixelFormat::ABGR => PixelFormat::RGBA,
+            PixelFormat::BGRA => PixelFormat::ARGB,
+            PixelFormat::ARGB => PixelFormat::BGRA,
+            _ => unreachable!(),
+        })
+    }
+
+    fn rgba_offsets(self) -> Option<(usize, usize, usize, Option<usize>)> {
+        match self {
+            PixelFormat::RGB => Some((0, 1, 2, None)),
+            PixelFormat::BGR => Some((2, 1, 0, None)),
+            PixelFormat::RGBX | PixelFormat::RGBA => Some((0, 1, 2, Some(3))),
+            PixelFormat::BGRX | PixelFormat::BGRA => Some((2, 1, 0, Some(3))),
+            PixelFormat::XBGR | PixelFormat::ABGR => Some((3, 2, 1, Some(0))),
+            PixelFormat::XRGB | PixelFormat::ARGB => Some((1, 2, 3, Some(0))),
+            PixelFormat::GRAY | PixelFormat::CMYK => None,
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> arbitrary::Arbitrary<'a> for PixelFormat {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&PixelFormat::ALL)?)
+    }
+}
+
+/// Logical pixel layout expected by a consumer of decompressed images.
+///
+/// Graphics APIs describe their pixel layouts in two different ways: GPU texture formats specify
+/// the order of bytes in memory, while many UI toolkits specify the order of channels in a 32-bit
+/// word, so the order of bytes depends on the endianness of the platform. Use
+/// [`pixel_format()`][Self::pixel_format] to get the [`PixelFormat`] that produces the expected
+/// layout on the current platform, so that TurboJPEG writes the pixels in their final order and
+/// you don't need to swizzle them after decompression.
+///
+/// TurboJPEG decompresses into all RGB pixel formats at the same speed, whereas swizzling the
+/// pixels afterwards requires another pass over the whole image (run `cargo run --release
+/// --example pixel_formats` to measure the difference on your machine).
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{PixelFormat, PixelLayout};
+///
+/// assert_eq!(PixelLayout::Rgba8.pixel_format(), PixelFormat::RGBA);
+/// if cfg!(target_endian = "little") {
+///     // Windows GDI, Cairo and Qt store ARGB words as bytes B, G, R, A on little-endian platforms
+///     assert_eq!(PixelLayout::Argb32.pixel_format(), PixelFormat::BGRA);
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PixelLayout {
+    /// Bytes R, G, B, A in memory, as expected by GPU texture uploads (such as `Rgba8Unorm` in
+    /// wgpu or `GL_RGBA` with `GL_UNSIGNED_BYTE` in OpenGL) and by most image libraries.
+    Rgba8,
+    /// Bytes B, G, R, A in memory, as expected by `Bgra8Unorm` textures, which are often the
+    /// preferred swapchain format.
+    Bgra8,
+    /// Native-endian 32-bit words `0xAARRGGBB`, as used by Windows GDI and Direct2D, Cairo
+    /// `ARGB32` and Qt `Format_ARGB32`.
+    Argb32,
+    /// Native-endian 32-bit words `0xffRRGGBB`, as used by Cairo `RGB24`, Qt `Format_RGB32` and
+    /// X11 TrueColor visuals.
+    Xrgb32,
+    /// Native-endian 32-bit words `0xRRGGBBAA`, as used by SDL `PIXELFORMAT_RGBA8888`.
+    Rgba32,
+}
+
+impl PixelLayout {
+    /// Returns the pixel format that produces this layout on the current platform.
+    pub fn pixel_format(self) -> PixelFormat {
+        let little_endian = cfg!(target_endian = "little");
+        match self {
+            PixelLayout::Rgba8 => PixelFormat::RGBA,
+            PixelLayout::Bgra8 => PixelFormat::BGRA,
+            PixelLayout::Argb32 if little_endian => PixelFormat::BGRA,
+            PixelLayout::Argb32 => PixelFormat::ARGB,
+            PixelLayout::Xrgb32 if little_endian => PixelFormat::BGRX,
+            PixelLayout::Xrgb32 => PixelFormat::XRGB,
+            PixelLayout::Rgba32 if little_endian => PixelFormat::ABGR,
+            PixelLayout::Rgba32 => PixelFormat::RGBA,
+        }
+    }
 }
 
 
@@ -129,6 +347,7 @@ impl PixelFormat {
 /// This is called "chrominance subsampling".
 #[doc(alias = "TJSAMP")]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(i32)]
 #[non_exhaustive]
 pub enum Subsamp {
@@ -205,7 +424,7 @@ pub enum Subsamp {
     ///
     /// - decompressed into planar YUV images,
     /// - losslessly transformed if [`Transform::crop`][crate::Transform::crop] is specified and
-    /// [`Transform::gray`][crate::Transform::gray] is not specified, or
+    ///   [`Transform::gray`][crate::Transform::gray] is not specified, or
     /// - partially decompressed using a cropping region.
     #[doc(alias = "TJSAMP_UNKNOWN")]
     Unknown = raw::TJSAMP_TJSAMP_UNKNOWN,
@@ -417,5 +636,133 @@ pub enum Error {
     /// When decompressing, the output image is too small for the input JPEG image.
     #[error("output image is too small for image of size {0}x{1}")]
     OutputTooSmall(i32, i32),
+
+    /// The pitch of an [`Image`][crate::Image] is too small for its width and pixel format.
+    #[error("pitch {0} is too small for width {1} and pixel format {2:?}")]
+    PitchTooSmall(usize, usize, PixelFormat),
+
+    /// The pixel data of an image are too short, the first number is the actual length and the
+    /// second number is the required length.
+    #[error("pixels length {0} is too small, the image requires {1} bytes")]
+    PixelsTooSmall(usize, usize),
+
+    /// The scaling factor is not supported by TurboJPEG (see
+    /// [`ScalingFactor::supported()`][crate::ScalingFactor::supported]).
+    #[error("scaling factor {0}/{1} is not supported")]
+    BadScalingFactor(usize, usize),
+
+    /// The left boundary of a cropping region (the first number) is not divisible by the scaled
+    /// MCU width (the second number).
+    #[error("left boundary {0} of the cropping region is not divisible by the scaled MCU width {1}")]
+    CropNotAligned(usize, usize),
+
+    /// The cropping region does not fit into the scaled image of the given width and height.
+    #[error("cropping region does not fit into the scaled image of size {0}x{1}")]
+    CropOutOfBounds(usize, usize),
+
+    /// The image cannot be cropped, because it uses [unknown subsampling][Subsamp::Unknown].
+    #[error("image with unknown chrominance subsampling cannot be cropped")]
+    UnsupportedSubsampForCrop,
+
+    /// The image cannot be decompressed into a planar YUV image, because it uses [unknown
+    /// subsampling][Subsamp::Unknown]. Such images can only be decompressed into packed-pixel
+    /// images (see [`Decompressor::decompress()`][crate::Decompressor::decompress]).
+    #[error("image with unknown chrominance subsampling cannot be decompressed into YUV, \
+        decompress it into packed pixels instead")]
+    UnsupportedSubsampForYuv,
+
+    /// The image cannot be losslessly cropped, because it uses [unknown
+    /// subsampling][Subsamp::Unknown]. Such images can only be cropped if they are also converted
+    /// to grayscale (see [`Transform::gray`][crate::Transform::gray]), or if they are decompressed
+    /// into packed pixels and compressed again.
+    #[error("image with unknown chrominance subsampling cannot be losslessly cropped, \
+        convert it to grayscale or decompress it into packed pixels instead")]
+    UnsupportedSubsampForTransformCrop,
+
+    /// The rotation angle in degrees is not a multiple of 90, so it cannot be performed by a
+    /// lossless transform (see [`TransformOp::from_degrees()`][crate::TransformOp::from_degrees]).
+    #[error("rotation by {0} degrees is not a multiple of 90 degrees")]
+    BadRotation(i32),
+
+    /// The JPEG image in colorspace (the first value) cannot be decompressed into the requested
+    /// output colorspace (the second value) using the given pixel format (see
+    /// [`Decompressor::set_output_colorspace()`][crate::Decompressor::set_output_colorspace]).
+    #[error("image in colorspace {0:?} cannot be decompressed into colorspace {1:?} with pixel format {2:?}")]
+    UnsupportedOutputColorspace(Colorspace, Colorspace, PixelFormat),
+
+    /// The environment variable with the given name is set, so the compressed data might differ
+    /// from the data compressed in another environment (see
+    /// [`Compressor::set_deterministic()`][crate::Compressor::set_deterministic]).
+    #[error("environment variable {0} changes the compressed data, which is not allowed in deterministic mode")]
+    NondeterministicEnv(&'static str),
+
+    /// The tile with the given index cannot be [joined][crate::join()] with the other tiles.
+    #[error("tile {0} cannot be joined: {1}")]
+    BadTile(usize, &'static str),
+
+    /// The samples of an image are not stored as packed pixels: the first number is the distance
+    /// between the channels of a pixel (must be 1) and the second number is the distance between
+    /// pixels in a row (must be the number of channels).
+    #[error("samples with channel stride {0} and pixel stride {1} are not packed pixels")]
+    UnpackedSamples(usize, usize),
+
+    /// The pixels of an image cannot be viewed as `u32` values (see
+    /// [`Image::pixels_u32()`][crate::Image::pixels_u32]).
+    #[error("pixels cannot be viewed as u32: {0}")]
+    NotU32Pixels(&'static str),
+
+    /// The image does not have the expected pixel format: the first format is the expected one,
+    /// the second is the format of the image (see [`TypedImage`][crate::typed::TypedImage]).
+    #[error("expected pixel format {0:?}, but the image has pixel format {1:?}")]
+    UnexpectedPixelFormat(PixelFormat, PixelFormat),
+
+    /// The markers and segments of JPEG data cannot be parsed (see [`validate()`][crate::validate]
+    /// for a detailed check of the data).
+    #[error("invalid JPEG data: {0}")]
+    BadJpeg(&'static str),
+
+    /// The data is not a valid AVI file (see [`avi::Reader`][crate::avi::Reader]).
+    #[error("invalid AVI file: {0}")]
+    BadAvi(&'static str),
+
+    /// The frame rate of an AVI file is not a positive finite number (see
+    /// [`avi::Writer`][crate::avi::Writer]).
+    #[error("frame rate {0} is not a positive finite number")]
+    BadFrameRate(f64),
+
+    /// The data is not a valid MPO file, or the images cannot be assembled into an MPO file (see
+    /// [`mpo`][crate::mpo]).
+    #[error("invalid MPO file: {0}")]
+    BadMpo(&'static str),
+
+    /// The DCT coefficients of the image cannot be read (see
+    /// [`read_coefficients()`][crate::read_coefficients]).
+    #[error("cannot read DCT coefficients: {0}")]
+    BadCoefficients(&'static str),
+
+    /// The image cannot be compressed in strips (see
+    /// [`StripCompressor`][crate::StripCompressor]).
+    #[error("cannot compress image in strips: {0}")]
+    StripCompression(&'static str),
+
+    /// A callback that was called from TurboJPEG panicked (see
+    /// [`Transformer::transform_with_filter()`][crate::Transformer::transform_with_filter]). The
+    /// panic is not propagated across the C code of TurboJPEG, but it is converted into this error
+    /// with the panic message.
+    #[error("callback panicked: {0}")]
+    CallbackPanicked(String),
+
+    /// A resource limit was exceeded (see [`Limits`][crate::Limits]). The error contains the name
+    /// of the limit, the actual value and the limit.
+    #[error("limit {0} exceeded: {1} is more than {2}")]
+    LimitExceeded(&'static str, usize, usize),
+
+    /// The operation was cancelled using a [`CancelToken`][crate::CancelToken].
+    #[error("operation was cancelled")]
+    Cancelled,
+
+    /// An I/O error occurred when reading or writing a file.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
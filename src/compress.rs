@@ -1,8 +1,17 @@
 use std::convert::TryInto as _;
-use crate::{Image, YuvImage, raw};
+use std::io::Write;
+use crate::{Image, raw};
+#[cfg(feature = "yuv")]
+use crate::YuvImage;
 use crate::buf::{OwnedBuf, OutputBuf};
-use crate::common::{Subsamp, Result, Error};
+use crate::cancel::CancelToken;
+use crate::common::{PixelFormat, Subsamp, Result, Error};
 use crate::handle::Handle;
+use crate::limits::{Frame, Limits};
+use crate::markers;
+use crate::metrics::Timer;
+use crate::progress::Progress;
+use crate::settings::Settings;
 
 /// Compresses raw pixel data into JPEG.
 #[derive(Debug)]
@@ -10,10 +19,12 @@ use crate::handle::Handle;
 pub struct Compressor {
     handle: Handle,
     subsamp: Subsamp,
+    deterministic: bool,
+    background: Option<[u8; 3]>,
 }
 
-static DEFAULT_QUALITY: i32 = 95;
-static DEFAULT_SUBSAMP: Subsamp = Subsamp::None;
+pub(crate) static DEFAULT_QUALITY: i32 = 95;
+pub(crate) static DEFAULT_SUBSAMP: Subsamp = Subsamp::None;
 
 unsafe impl Send for Compressor {}
 
@@ -24,7 +35,7 @@ impl Compressor {
         let mut handle = Handle::new(raw::TJINIT_TJINIT_COMPRESS)?;
         handle.set(raw::TJPARAM_TJPARAM_QUALITY, DEFAULT_QUALITY as libc::c_int)?;
         handle.set(raw::TJPARAM_TJPARAM_SUBSAMP, DEFAULT_SUBSAMP as i32 as libc::c_int)?;
-        Ok(Compressor { handle, subsamp: DEFAULT_SUBSAMP })
+        Ok(Compressor { handle, subsamp: DEFAULT_SUBSAMP, deterministic: false, background: None })
     }
 
     /// Set the quality of the compressed JPEG images.
@@ -57,7 +68,9 @@ impl Compressor {
     /// quality (see [`Subsamp`] for more).
     #[doc(alias = "TJPARAM_SUBSAMP")]
     pub fn set_subsamp(&mut self, subsamp: Subsamp) -> Result<()> {
-        self.handle.set(raw::TJPARAM_TJPARAM_SUBSAMP, subsamp as i32 as libc::c_int)
+        self.handle.set(raw::TJPARAM_TJPARAM_SUBSAMP, subsamp as i32 as libc::c_int)?;
+        self.subsamp = subsamp;
+        Ok(())
     }
 
     /// Enable/disable optimized baseline entropy coding.
@@ -86,6 +99,212 @@ impl Compressor {
         self.handle.set(raw::TJPARAM_TJPARAM_OPTIMIZE, optimize as libc::c_int)
     }
 
+    /// Enable/disable bottom-up row order of the source images.
+    ///
+    /// When enabled, the first row of the [`Image`] passed to the compressor is the bottom row of
+    /// the JPEG image, so the rows are stored bottom-up. This is the layout of Windows DIB bitmaps
+    /// (for example, BGRA screen captures), which can then be compressed directly, without first
+    /// copying them into a top-down buffer. The [`pitch`][Image::pitch] is still the (positive)
+    /// distance from the start of one row in memory to the start of the next row.
+    ///
+    /// This has no effect on compression from YUV images.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // a BGRA image with rows stored bottom-up
+    /// let image = turbojpeg::Image::mandelbrot(64, 48, turbojpeg::PixelFormat::BGRA);
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// compressor.set_bottom_up(true)?;
+    /// let jpeg_data = compressor.compress_to_vec(image.as_deref())?;
+    ///
+    /// let decompressed = turbojpeg::decompress(&jpeg_data, turbojpeg::PixelFormat::BGRA)?;
+    /// assert_eq!((decompressed.width, decompressed.height), (64, 48));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[doc(alias = "TJPARAM_BOTTOMUP")]
+    pub fn set_bottom_up(&mut self, bottom_up: bool) -> Result<()> {
+        self.handle.set(raw::TJPARAM_TJPARAM_BOTTOMUP, bottom_up as libc::c_int)
+    }
+
+    /// Set the identifier of the image that is attached to logged warnings.
+    ///
+    /// With the `log` feature, the warnings that TurboJPEG reports about corrupted data are logged
+    /// using the [`log`] crate (with target `turbojpeg`), together with the operation and this
+    /// identifier (such as a file name or URL). Use `None` to remove the identifier.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "log")] {
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// compressor.set_image_id(Some("frame-0042"));
+    /// # }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "log")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "log")))]
+    pub fn set_image_id(&mut self, image_id: Option<&str>) {
+        self.handle.set_image_id(image_id);
+    }
+
+    /// Set the limits on the resources used by this compressor.
+    ///
+    /// The limits are initialized from [`Limits::global()`] when the compressor is created. See
+    /// [`Limits`] for details.
+    pub fn set_limits(&mut self, limits: Limits) -> Result<()> {
+        self.handle.set_limits(limits)
+    }
+
+    /// Get the limits on the resources used by this compressor.
+    pub fn limits(&self) -> Limits {
+        *self.handle.limits()
+    }
+
+    /// Set the limit on the estimated memory in bytes that TurboJPEG needs to process an image.
+    ///
+    /// This changes only [`Limits::max_memory`] in the [limits][Self::limits] of this compressor
+    /// (`None` means no limit).
+    #[doc(alias = "TJPARAM_MAXMEMORY")]
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) -> Result<()> {
+        self.set_limits(Limits { max_memory, ..self.limits() })
+    }
+
+    /// Set the limit on the number of pixels (width × height) of an image.
+    ///
+    /// This changes only [`Limits::max_pixels`] in the [limits][Self::limits] of this compressor
+    /// (`None` means no limit).
+    #[doc(alias = "TJPARAM_MAXPIXELS")]
+    pub fn set_max_pixels(&mut self, max_pixels: Option<usize>) -> Result<()> {
+        self.set_limits(Limits { max_pixels, ..self.limits() })
+    }
+
+    /// Apply the settings to this compressor.
+    ///
+    /// This sets the quality, subsampling, entropy coding, restart markers and limits of the
+    /// compressor at once (see [`Settings`]). If the settings are not supported (for example,
+    /// arithmetic coding with the `compat-2x` feature), an error is returned and the compressor
+    /// may be left with some of the settings applied.
+    #[doc(alias = "TJPARAM_PROGRESSIVE")]
+    #[doc(alias = "TJPARAM_ARITHMETIC")]
+    #[doc(alias = "TJPARAM_RESTARTBLOCKS")]
+    pub fn apply(&mut self, settings: &Settings) -> Result<()> {
+        self.set_quality(settings.quality)?;
+        self.set_subsamp(settings.subsamp)?;
+        self.handle.set(raw::TJPARAM_TJPARAM_PROGRESSIVE, settings.progressive as libc::c_int)?;
+        self.handle.set(raw::TJPARAM_TJPARAM_OPTIMIZE, settings.optimize as libc::c_int)?;
+        self.handle.set(raw::TJPARAM_TJPARAM_ARITHMETIC, settings.arithmetic as libc::c_int)?;
+        let restart_rows = settings.restart_rows.try_into()
+            .map_err(|_| Error::IntegerOverflow("restart_rows"))?;
+        let restart_blocks = settings.restart_blocks.try_into()
+            .map_err(|_| Error::IntegerOverflow("restart_blocks"))?;
+        // TurboJPEG resets the other interval when one of them is set to a non-zero value, so the
+        // blocks must be set last to take precedence
+        self.handle.set(raw::TJPARAM_TJPARAM_RESTARTROWS, restart_rows)?;
+        self.handle.set(raw::TJPARAM_TJPARAM_RESTARTBLOCKS, restart_blocks)?;
+        self.set_limits(settings.limits)
+    }
+
+    /// Get the current settings of this compressor.
+    ///
+    /// Applying the returned settings to another compressor makes it produce the same JPEG data
+    /// (assuming the same version of TurboJPEG and the same environment, see
+    /// [`set_deterministic()`][Self::set_deterministic]).
+    pub fn settings(&self) -> Settings {
+        Settings {
+            quality: self.handle.get(raw::TJPARAM_TJPARAM_QUALITY),
+            subsamp: self.subsamp,
+            progressive: self.handle.get(raw::TJPARAM_TJPARAM_PROGRESSIVE) == 1,
+            optimize: self.handle.get(raw::TJPARAM_TJPARAM_OPTIMIZE) == 1,
+            arithmetic: self.handle.get(raw::TJPARAM_TJPARAM_ARITHMETIC) == 1,
+            restart_blocks: self.handle.get(raw::TJPARAM_TJPARAM_RESTARTBLOCKS).max(0) as usize,
+            restart_rows: self.handle.get(raw::TJPARAM_TJPARAM_RESTARTROWS).max(0) as usize,
+            limits: self.limits(),
+        }
+    }
+
+    /// Enable/disable deterministic mode.
+    ///
+    /// The output of TurboJPEG depends only on the input image and the parameters of the
+    /// compressor, with one exception: depending on its version and build options, TurboJPEG may
+    /// read the environment variables `TJ_OPTIMIZE`, `TJ_ARITHMETIC`, `TJ_RESTART` and
+    /// `TJ_PROGRESSIVE`, which override the parameters and change the compressed data. In
+    /// deterministic mode, the compressor returns [`Error::NondeterministicEnv`] instead of
+    /// compressing the image if any of these variables is set, so that identical images compressed
+    /// with identical settings produce byte-identical JPEG data regardless of the process
+    /// environment or the number of threads that compress images in parallel.
+    ///
+    /// This mode only checks the environment variables; it does not change any parameters of the
+    /// compressor. The output may still differ between versions or builds of TurboJPEG.
+    ///
+    /// This is useful for content-addressed storage or for reproducible tests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image::mandelbrot(100, 100, turbojpeg::PixelFormat::RGB);
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// compressor.set_deterministic(true);
+    ///
+    /// let jpeg_1 = compressor.compress_to_vec(image.as_deref())?;
+    /// let jpeg_2 = turbojpeg::Compressor::new()?.compress_to_vec(image.as_deref())?;
+    /// assert_eq!(jpeg_1, jpeg_2);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Set the background color for compositing images with an alpha channel.
+    ///
+    /// JPEG does not support transparency, so TurboJPEG ignores the alpha channel of
+    /// [`PixelFormat::RGBA`] (and the other formats with alpha) and compresses only the color
+    /// components. Semi-transparent pixels in renders usually have color components that are
+    /// meaningful only together with their alpha, so they come out as dark or wrongly colored
+    /// fringes.
+    ///
+    /// When the background is set to an RGB color, images with an alpha channel are composited
+    /// over this color (assuming alpha that is not premultiplied, see
+    /// [`Image::unpremultiply_alpha()`]) before compression. This allocates a temporary copy of the
+    /// image. By default, the background is `None` and the alpha channel is ignored. This has no
+    /// effect on images without an alpha channel.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // a fully transparent image
+    /// let pixels = vec![0; 64 * 64 * 4];
+    /// let image = turbojpeg::Image {
+    ///     pixels: &pixels[..],
+    ///     width: 64,
+    ///     pitch: 0,
+    ///     height: 64,
+    ///     format: turbojpeg::PixelFormat::RGBA,
+    /// };
+    ///
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// compressor.set_background(Some([255, 255, 255]));
+    /// let jpeg_data = compressor.compress_to_vec(image)?;
+    ///
+    /// let decompressed = turbojpeg::decompress(&jpeg_data, turbojpeg::PixelFormat::RGB)?;
+    /// assert!(decompressed.pixels.iter().all(|&value| value == 255));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_background(&mut self, background: Option<[u8; 3]>) {
+        self.background = background;
+    }
+
+    fn check_deterministic(&self) -> Result<()> {
+        if self.deterministic {
+            for var in ["TJ_OPTIMIZE", "TJ_ARITHMETIC", "TJ_RESTART", "TJ_PROGRESSIVE"] {
+                if std::env::var_os(var).is_some() {
+                    return Err(Error::NondeterministicEnv(var))
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Compresses the `image` into `output` buffer.
     ///
     /// This is the main compression method, which gives you full control of the output buffer. If
@@ -117,8 +336,27 @@ impl Compressor {
     #[doc(alias = "tj3Compress8")]
     pub fn compress(&mut self, image: Image<&[u8]>, output: &mut OutputBuf) -> Result<()> {
         image.assert_valid(image.pixels.len());
+        self.check_deterministic()?;
 
-        let Image { pixels, width, pitch, height, format } = image;
+        let composited;
+        let image = match self.background {
+            Some(background) if image.format.has_alpha() => {
+                composited = composite(image, background);
+                composited.as_deref()
+            },
+            _ => image,
+        };
+
+        let components = match image.format {
+            PixelFormat::GRAY => 1,
+            PixelFormat::CMYK => 4,
+            _ => 3,
+        };
+        self.check_limits(image.width, image.height, components)?;
+
+        let pitch = image.row_pitch();
+        let pixels_len = image.packed_pitch() * image.height;
+        let Image { pixels, width, pitch: _, height, format } = image;
         let width = width.try_into().map_err(|_| Error::IntegerOverflow("width"))?;
         let pitch = pitch.try_into().map_err(|_| Error::IntegerOverflow("pitch"))?;
         let height = height.try_into().map_err(|_| Error::IntegerOverflow("height"))?;
@@ -128,6 +366,7 @@ impl Compressor {
             if output.is_owned { 0 } else { 1 } as libc::c_int,
         )?;
         let mut output_len = output.len as raw::size_t;
+        let timer = Timer::start();
         let res = unsafe {
             raw::tj3Compress8(
                 self.handle.as_ptr(),
@@ -137,12 +376,28 @@ impl Compressor {
         };
         output.len = output_len as usize;
         if res != 0 {
-            return Err(self.handle.get_error())
+            return Err(self.handle.get_error_in("compression"))
         } else if output.ptr.is_null() {
             output.len = 0;
             return Err(Error::Null)
         }
+        timer.compressed(pixels_len, output.len);
+
+        self.handle.limits().check_output_bytes(output.len)
+    }
 
+    /// Checks the pixel and memory limits before compressing an image.
+    fn check_limits(&mut self, width: usize, height: usize, components: usize) -> Result<()> {
+        let limits = *self.handle.limits();
+        limits.check_pixels(width, height)?;
+        if limits.max_memory.is_some() {
+            // progressive and optimized images are compressed in multiple passes, so TurboJPEG
+            // buffers the DCT coefficients of the whole image
+            let whole_image = self.handle.get(raw::TJPARAM_TJPARAM_PROGRESSIVE) == 1
+                || self.handle.get(raw::TJPARAM_TJPARAM_OPTIMIZE) == 1;
+            let frame = Frame::for_compression(width, height, self.subsamp, components);
+            limits.check_memory(frame.memory(whole_image))?;
+        }
         Ok(())
     }
 
@@ -177,6 +432,114 @@ impl Compressor {
         Ok(buf.len())
     }
 
+    /// Compresses a batch of images into owned buffers.
+    ///
+    /// All images are compressed with the same TurboJPEG handle and settings. Each image is first
+    /// compressed into a scratch buffer, which is shared by all images in the batch, and then
+    /// copied into an [`OwnedBuf`] of the exact size. This avoids allocating a worst-case-sized
+    /// buffer for every image, which makes this method faster than calling
+    /// [`compress_to_owned()`][Self::compress_to_owned] in a loop when you compress many images
+    /// at once (for example, the pages of a multi-page scan).
+    ///
+    /// If any image fails to compress, the error is returned and the remaining images are not
+    /// compressed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::{Image, PixelFormat};
+    ///
+    /// let pages = (1..=3)
+    ///     .map(|i| Image::mandelbrot(200 * i, 300, PixelFormat::RGB))
+    ///     .collect::<Vec<_>>();
+    ///
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// compressor.set_quality(80)?;
+    /// let jpegs = compressor.compress_batch(pages.iter().map(|page| page.as_deref()))?;
+    /// assert_eq!(jpegs.len(), 3);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn compress_batch<'a, I>(&mut self, images: I) -> Result<Vec<OwnedBuf>>
+        where I: IntoIterator<Item = Image<&'a [u8]>>
+    {
+        self.compress_batch_cancellable(images, &CancelToken::new())
+    }
+
+    /// Compresses a batch of images into owned buffers, checking for cancellation between images.
+    ///
+    /// This is the same as [`compress_batch()`][Self::compress_batch], but if `cancel` is
+    /// cancelled, the remaining images are not compressed and [`Error::Cancelled`] is returned.
+    /// See [`CancelToken`] for an example.
+    pub fn compress_batch_cancellable<'a, I>(&mut self, images: I, cancel: &CancelToken) -> Result<Vec<OwnedBuf>>
+        where I: IntoIterator<Item = Image<&'a [u8]>>
+    {
+        self.compress_batch_with_progress(images, cancel, |_| {})
+    }
+
+    /// Compresses a batch of images into owned buffers, reporting the progress after every image.
+    ///
+    /// This is the same as [`compress_batch_cancellable()`][Self::compress_batch_cancellable], but
+    /// `progress` is called after every image with the number of compressed images and the total
+    /// size of the compressed data. The total number of images is known if the iterator reports an
+    /// exact [size hint][Iterator::size_hint] (as iterators over slices and vectors do).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::{CancelToken, Image, PixelFormat};
+    ///
+    /// let pages = (0..4)
+    ///     .map(|_| Image::mandelbrot(64, 64, PixelFormat::RGB))
+    ///     .collect::<Vec<_>>();
+    ///
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// let mut last_progress = None;
+    /// let jpegs = compressor.compress_batch_with_progress(
+    ///     pages.iter().map(|page| page.as_deref()),
+    ///     &CancelToken::new(),
+    ///     |progress| {
+    ///         println!("{} of {:?} images, {} bytes", progress.done, progress.total, progress.bytes);
+    ///         last_progress = Some(progress);
+    ///     },
+    /// )?;
+    ///
+    /// let last_progress = last_progress.unwrap();
+    /// assert_eq!(last_progress.fraction(), Some(1.0));
+    /// assert_eq!(last_progress.bytes, jpegs.iter().map(|jpeg| jpeg.len()).sum::<usize>());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn compress_batch_with_progress<'a, I, F>(
+        &mut self,
+        images: I,
+        cancel: &CancelToken,
+        mut progress: F,
+    ) -> Result<Vec<OwnedBuf>>
+        where I: IntoIterator<Item = Image<&'a [u8]>>, F: FnMut(Progress)
+    {
+        let images = images.into_iter();
+        let total = match images.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        };
+        let mut bytes = 0;
+        let mut outputs = Vec::with_capacity(images.size_hint().0);
+        let mut scratch = Vec::new();
+        for image in images {
+            cancel.check()?;
+            let buf_len = self.buf_len(image.width, image.height)?;
+            if scratch.len() < buf_len {
+                scratch.resize(buf_len, 0);
+            }
+            let len = self.compress_to_slice(image, &mut scratch)?;
+            outputs.push(OwnedBuf::copy_from_slice(&scratch[..len]));
+            bytes += len;
+            progress(Progress { done: outputs.len(), total, bytes });
+        }
+        Ok(outputs)
+    }
+
     /// Compresses the [`YuvImage`] into `output` buffer.
     ///
     /// This is similar to [`compress()`][Self::compress], but encodes a YUV image instead of RGB
@@ -223,12 +586,17 @@ impl Compressor {
     ///
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
+    #[cfg(feature = "yuv")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
     #[doc(alias = "tj3CompressFromYUV8")]
     pub fn compress_yuv(&mut self, image: YuvImage<&[u8]>, output: &mut OutputBuf) -> Result<()> {
         image.assert_valid(image.pixels.len());
+        self.check_deterministic()?;
 
+        let pixels_len = image.min_pixels_len()?;
         let YuvImage { pixels, width, align, height, subsamp } = image;
         self.set_subsamp(subsamp)?;
+        self.check_limits(width, height, if subsamp == Subsamp::Gray { 1 } else { 3 })?;
         let width: libc::c_int = width.try_into().map_err(|_| Error::IntegerOverflow("width"))?;
         let align = align.try_into().map_err(|_| Error::IntegerOverflow("align"))?;
         let height: libc::c_int = height.try_into().map_err(|_| Error::IntegerOverflow("height"))?;
@@ -239,6 +607,7 @@ impl Compressor {
         )?;
 
         let mut output_len = output.len as raw::size_t;
+        let timer = Timer::start();
         let res = unsafe {
             raw::tj3CompressFromYUV8(
                 self.handle.as_ptr(),
@@ -248,17 +617,20 @@ impl Compressor {
         };
         output.len = output_len as usize;
         if res != 0 {
-            return Err(self.handle.get_error())
+            return Err(self.handle.get_error_in("YUV compression"))
         } else if output.ptr.is_null() {
             output.len = 0;
             return Err(Error::Null)
         }
-        Ok(())
+        timer.compressed(pixels_len, output.len);
+        self.handle.limits().check_output_bytes(output.len)
     }
 
     /// Compresses the [`YuvImage`] into an owned buffer.
     ///
     /// This method automatically allocates the memory for output and avoids needless copying.
+    #[cfg(feature = "yuv")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
     pub fn compress_yuv_to_owned(&mut self, image: YuvImage<&[u8]>) -> Result<OwnedBuf> {
         let mut buf = OutputBuf::new_owned();
         self.compress_yuv(image, &mut buf)?;
@@ -270,6 +642,8 @@ impl Compressor {
     /// This method copies the compressed data into a new `Vec`. If you would like to avoid the
     /// extra allocation and copying, consider using
     /// [`compress_yuv_to_owned()`][Self::compress_yuv_to_owned] instead.
+    #[cfg(feature = "yuv")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
     pub fn compress_yuv_to_vec(&mut self, image: YuvImage<&[u8]>) -> Result<Vec<u8>> {
         let mut buf = OutputBuf::new_owned();
         self.compress_yuv(image, &mut buf)?;
@@ -281,6 +655,8 @@ impl Compressor {
     /// Returns the size of the compressed JPEG data. If the compressed image does not fit into
     /// `dest`, this method returns an error. Use [`compressed_buf_len()`] to determine buffer size
     /// that is guaranteed to be large enough for the compressed image.
+    #[cfg(feature = "yuv")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
     pub fn compress_yuv_to_slice(&mut self, image: YuvImage<&[u8]>, output: &mut [u8]) -> Result<usize> {
         let mut buf = OutputBuf::borrowed(output);
         self.compress_yuv(image, &mut buf)?;
@@ -289,14 +665,97 @@ impl Compressor {
 
     /// Compute the maximum size of a compressed image.
     ///
-    /// This depends on image `width` and `height`, and also on the current setting of chrominance
-    /// subsampling (see [`set_subsamp()`](Compressor::set_subsamp)).
+    /// This depends on image `width` and `height`, on the current setting of chrominance
+    /// subsampling (see [`set_subsamp()`](Compressor::set_subsamp)), and also on the entropy
+    /// coding parameters of this compressor, including the parameters that TurboJPEG reads from
+    /// the environment variables `TJ_PROGRESSIVE`, `TJ_ARITHMETIC` and `TJ_RESTART`:
+    ///
+    /// - Progressive, arithmetic and lossless JPEG images can be larger than the worst case of
+    ///   baseline JPEG computed by [`compressed_buf_len()`], so the size is doubled (and
+    ///   progressive images get extra space for the headers of their scans). This is a generous
+    ///   estimate, not a proven bound.
+    /// - Every restart marker takes two bytes.
+    ///
+    /// The result should be large enough for [`compress_to_slice()`][Self::compress_to_slice] in
+    /// practice, whereas [`compressed_buf_len()`] is sufficient only for compressors with the
+    /// default parameters. If the compressed image still does not fit, `compress_to_slice()`
+    /// returns an error, so be prepared to retry with a larger buffer (or use
+    /// [`compress_to_owned()`][Self::compress_to_owned], which grows the buffer as needed).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image::mandelbrot(200, 100, turbojpeg::PixelFormat::RGB);
+    /// let mut compressor = turbojpeg::Compressor::new()?;
     ///
-    /// You can also use [`compressed_buf_len()`] directly.
+    /// let mut output = vec![0; compressor.buf_len(image.width, image.height)?];
+    /// let len = compressor.compress_to_slice(image.as_deref(), &mut output)?;
+    /// output.truncate(len);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
     #[doc(alias = "tj3JPEGBufSize")]
     pub fn buf_len(&self, width: usize, height: usize) -> Result<usize> {
-        compressed_buf_len(width, height, self.subsamp)
+        let mut len = compressed_buf_len(width, height, self.subsamp)?;
+
+        let env = |var| std::env::var(var).ok();
+        let progressive = self.handle.get(raw::TJPARAM_TJPARAM_PROGRESSIVE) == 1
+            || env("TJ_PROGRESSIVE").as_deref() == Some("1");
+        let arithmetic = self.handle.get(raw::TJPARAM_TJPARAM_ARITHMETIC) == 1
+            || env("TJ_ARITHMETIC").as_deref() == Some("1");
+        let lossless = self.handle.get(raw::TJPARAM_TJPARAM_LOSSLESS) == 1;
+        if progressive || arithmetic || lossless {
+            len = len.checked_mul(2).ok_or(Error::IntegerOverflow("buf len"))?;
+        }
+        if progressive {
+            len = len.checked_add(PROGRESSIVE_HEADERS_LEN).ok_or(Error::IntegerOverflow("buf len"))?;
+        }
+
+        let (mut restart_blocks, mut restart_rows) = (
+            self.handle.get(raw::TJPARAM_TJPARAM_RESTARTBLOCKS).max(0) as usize,
+            self.handle.get(raw::TJPARAM_TJPARAM_RESTARTROWS).max(0) as usize,
+        );
+        if let Some(restart) = env("TJ_RESTART") {
+            // "N" is the interval in MCU rows, "Nb" or "NB" is the interval in MCU blocks
+            match restart.strip_suffix(['b', 'B']) {
+                Some(blocks) => restart_blocks = blocks.parse().unwrap_or(restart_blocks),
+                None => restart_rows = restart.parse().unwrap_or(restart_rows),
+            }
+        }
+        let mcu_columns = width.div_ceil(self.subsamp.mcu_width());
+        let mcu_rows = height.div_ceil(self.subsamp.mcu_height());
+        let restarts = if restart_blocks > 0 {
+            mcu_columns.saturating_mul(mcu_rows).div_ceil(restart_blocks)
+        } else if restart_rows > 0 {
+            mcu_rows.div_ceil(restart_rows)
+        } else {
+            0
+        };
+        len.checked_add(restarts.saturating_mul(2)).ok_or(Error::IntegerOverflow("buf len"))
+    }
+}
+
+/// Space for the headers (SOS and DHT segments) of the scans of a progressive JPEG image, whose
+/// Huffman tables are optimized for every scan. The default progressive script of TurboJPEG
+/// uses at most 10 scans.
+const PROGRESSIVE_HEADERS_LEN: usize = 10 * (14 + 2 * 276);
+
+/// Composites an image with alpha channel over the background color, producing an opaque image in
+/// the same pixel format.
+fn composite(image: Image<&[u8]>, background: [u8; 3]) -> Image<Vec<u8>> {
+    let mut image = image.to_owned();
+    let format = image.format;
+    let (Some(r), Some(g), Some(b), Some(a)) =
+        (format.red_offset(), format.green_offset(), format.blue_offset(), format.alpha_offset())
+        else { return image };
+    for pixel in image.pixels.chunks_exact_mut(format.size()) {
+        let alpha = pixel[a] as u32;
+        for (offset, background) in [(r, background[0]), (g, background[1]), (b, background[2])] {
+            let value = pixel[offset] as u32 * alpha + background as u32 * (255 - alpha);
+            pixel[offset] = ((value + 127) / 255) as u8;
+        }
+        pixel[a] = 255;
     }
+    image
 }
 
 /// Compress an image to JPEG.
@@ -347,6 +806,8 @@ pub fn compress(image: Image<&[u8]>, quality: i32, subsamp: Subsamp) -> Result<O
 ///
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
+#[cfg(feature = "yuv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
 pub fn compress_yuv(image: YuvImage<&[u8]>, quality: i32) -> Result<OwnedBuf> {
     let mut compressor = Compressor::new()?;
     compressor.set_quality(quality)?;
@@ -357,6 +818,10 @@ pub fn compress_yuv(image: YuvImage<&[u8]>, quality: i32) -> Result<OwnedBuf> {
 ///
 /// This depends on image `width` and `height` and also on the chrominance subsampling method.
 ///
+/// The result is the worst case for baseline JPEG compressed with the default parameters. If you
+/// enable progressive or lossless JPEG or restart markers, use [`Compressor::buf_len()`], which
+/// takes these parameters into account.
+///
 /// Returns an error on integer overflow. You can just `.unwrap()` the result if you don't care
 /// about this edge case.
 #[doc(alias = "tj3JPEGBufSize")]
@@ -367,3 +832,227 @@ pub fn compressed_buf_len(width: usize, height: usize, subsamp: Subsamp) -> Resu
     let len = len.try_into().map_err(|_| Error::IntegerOverflow("buf len"))?;
     Ok(len)
 }
+
+/// Compresses an image that is produced incrementally, in strips of rows.
+///
+/// TurboJPEG can only compress a whole image at once, which requires the whole uncompressed image
+/// in memory. The strip compressor instead compresses every strip of rows as soon as it contains
+/// complete MCU rows (see [`Subsamp::mcu_height()`]), and joins the compressed strips into a single
+/// JPEG image, using restart markers at the start of every MCU row. The compressed strips are kept
+/// in memory, but the uncompressed rows are needed only until their MCU row is complete, so
+/// renderers that produce the image row by row don't need to hold the whole uncompressed image.
+///
+/// Because every restart interval is compressed independently, the result is the same as if the
+/// whole image was compressed with a restart marker at the start of every MCU row. The strips use
+/// the standard Huffman tables, so [optimized entropy coding][Compressor::set_optimize] is
+/// disabled.
+///
+/// # Example
+///
+/// ```
+/// let image = turbojpeg::Image::mandelbrot(200, 100, turbojpeg::PixelFormat::RGB);
+/// let mut compressor = turbojpeg::Compressor::new()?;
+/// compressor.set_subsamp(turbojpeg::Subsamp::Sub2x2)?;
+/// let mut strips = turbojpeg::StripCompressor::new(compressor, 200, turbojpeg::PixelFormat::RGB)?;
+///
+/// // write the image in strips of 10 rows
+/// for y in (0..100).step_by(10) {
+///     let pixels = &image.pixels[y * image.pitch..(y + 10) * image.pitch];
+///     strips.write_rows(turbojpeg::Image { pixels, height: 10, ..image.as_deref() })?;
+/// }
+/// let jpeg_data = strips.finish()?;
+///
+/// let header = turbojpeg::read_header(&jpeg_data)?;
+/// assert_eq!((header.width, header.height), (200, 100));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "TJPARAM_RESTARTROWS")]
+pub struct StripCompressor {
+    compressor: Compressor,
+    width: usize,
+    format: PixelFormat,
+    /// Height of the rows that must be compressed together.
+    mcu_height: usize,
+    /// Rows that were written, but not compressed yet, without padding.
+    pending: Vec<u8>,
+    /// Header of the first compressed strip, up to and including the SOS segment.
+    header: Vec<u8>,
+    /// Offset of the SOF segment in `header`.
+    sof_offset: usize,
+    /// Whether `header` was already written by [`write_compressed()`][Self::write_compressed].
+    header_written: bool,
+    /// Entropy-coded data of all compressed strips, separated by restart markers.
+    scan: Vec<u8>,
+    /// Number of restart intervals in `scan`.
+    intervals: usize,
+    /// Number of compressed rows.
+    height: usize,
+}
+
+impl StripCompressor {
+    /// Create a strip compressor for images with the given width and pixel format.
+    ///
+    /// The quality, chrominance subsampling and other settings are taken from `compressor`.
+    pub fn new(mut compressor: Compressor, width: usize, format: PixelFormat) -> Result<StripCompressor> {
+        compressor.handle.set(raw::TJPARAM_TJPARAM_RESTARTROWS, 1)?;
+        compressor.handle.set(raw::TJPARAM_TJPARAM_OPTIMIZE, 0)?;
+        // grayscale images have a single component, so an MCU is a single block
+        let mcu_height = if format == PixelFormat::GRAY { 8 } else { compressor.subsamp.mcu_height() };
+        Ok(StripCompressor {
+            compressor, width, format, mcu_height,
+            pending: Vec::new(),
+            header: Vec::new(),
+            sof_offset: 0,
+            header_written: false,
+            scan: Vec::new(),
+            intervals: 0,
+            height: 0,
+        })
+    }
+
+    /// Write the next strip of rows.
+    ///
+    /// The strip may have any height (including rows that do not form a complete MCU row). The
+    /// rows are compressed as soon as they form complete MCU rows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the width or pixel format of `rows` differs from the strip compressor, or if
+    /// `rows` is invalid (see [`Image::check()`]).
+    pub fn write_rows(&mut self, rows: Image<&[u8]>) -> Result<()> {
+        assert_eq!(rows.width, self.width, "rows must have the same width as the image");
+        assert_eq!(rows.format, self.format, "rows must have the same pixel format as the image");
+        rows.assert_valid(rows.pixels.len());
+
+        let pitch = rows.row_pitch();
+        for y in 0..rows.height {
+            self.pending.extend_from_slice(&rows.pixels[y * pitch..y * pitch + rows.packed_pitch()]);
+        }
+
+        let pending_rows = self.pending.len() / self.packed_pitch();
+        let complete_rows = pending_rows / self.mcu_height * self.mcu_height;
+        if complete_rows > 0 {
+            self.compress_pending(complete_rows)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of rows written so far.
+    pub fn height(&self) -> usize {
+        self.height + self.pending.len() / self.packed_pitch()
+    }
+
+    /// Compress the remaining rows and return the JPEG image.
+    ///
+    /// Returns an error if no rows were written.
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        self.compress_remaining()?;
+        self.set_header_height(self.height)?;
+
+        let mut jpeg_data = self.header;
+        jpeg_data.reserve(self.scan.len() + 2);
+        jpeg_data.extend_from_slice(&self.scan);
+        jpeg_data.extend_from_slice(&[0xff, markers::EOI]);
+        Ok(jpeg_data)
+    }
+
+    /// Writes the compressed data to `writer` and removes it from the strip compressor.
+    ///
+    /// The height of the image must be known in advance, because it is written in the header.
+    /// Returns the number of written bytes.
+    pub(crate) fn write_compressed<W: Write>(&mut self, height: usize, writer: &mut W) -> Result<usize> {
+        let mut len = 0;
+        if !self.header_written && !self.header.is_empty() {
+            self.set_header_height(height)?;
+            writer.write_all(&self.header)?;
+            self.header_written = true;
+            len += self.header.len();
+        }
+        writer.write_all(&self.scan)?;
+        len += self.scan.len();
+        self.scan.clear();
+        Ok(len)
+    }
+
+    /// Compresses the remaining rows and writes the rest of the compressed data to `writer`
+    /// (see [`write_compressed()`][Self::write_compressed]).
+    pub(crate) fn finish_to<W: Write>(mut self, height: usize, writer: &mut W) -> Result<usize> {
+        self.compress_remaining()?;
+        if self.height != height {
+            return Err(Error::StripCompression("number of rows differs from the declared height"))
+        }
+        let len = self.write_compressed(height, writer)?;
+        writer.write_all(&[0xff, markers::EOI])?;
+        Ok(len + 2)
+    }
+
+    fn compress_remaining(&mut self) -> Result<()> {
+        let pending_rows = self.pending.len() / self.packed_pitch();
+        if pending_rows > 0 {
+            self.compress_pending(pending_rows)?;
+        }
+        if self.header.is_empty() {
+            return Err(Error::StripCompression("no rows were written"))
+        }
+        Ok(())
+    }
+
+    fn set_header_height(&mut self, height: usize) -> Result<()> {
+        let height: u16 = height.try_into().map_err(|_| Error::IntegerOverflow("height"))?;
+        let height_offset = self.sof_offset + 5;
+        self.header[height_offset..height_offset + 2].copy_from_slice(&height.to_be_bytes());
+        Ok(())
+    }
+
+    fn packed_pitch(&self) -> usize {
+        (self.width * self.format.size()).max(1)
+    }
+
+    /// Compresses the first `rows` pending rows and appends them to the scan.
+    fn compress_pending(&mut self, rows: usize) -> Result<()> {
+        let pitch = self.packed_pitch();
+        let image = Image {
+            pixels: &self.pending[..rows * pitch],
+            width: self.width,
+            pitch,
+            height: rows,
+            format: self.format,
+        };
+        let jpeg_data = self.compressor.compress_to_vec(image)?;
+
+        let segments = markers::read_header_segments(&jpeg_data)
+            .ok_or(Error::StripCompression("cannot read the compressed header"))?;
+        let sof = segments.iter().find(|segment| markers::is_sof(segment.marker))
+            .ok_or(Error::StripCompression("cannot read the compressed header"))?;
+        // the environment variables TJ_PROGRESSIVE, TJ_ARITHMETIC and TJ_RESTART may override our
+        // parameters
+        if !matches!(sof.marker, 0xc0 | 0xc1) {
+            return Err(Error::StripCompression("progressive and arithmetic coding are not supported"))
+        }
+        let sos = segments.last().filter(|segment| segment.marker == markers::SOS)
+            .ok_or(Error::StripCompression("cannot read the compressed header"))?;
+        let (intervals, _) = markers::split_restart_intervals(&jpeg_data, sos.end())
+            .ok_or(Error::StripCompression("compressed data is truncated"))?;
+        if intervals.len() != rows.div_ceil(self.mcu_height) {
+            return Err(Error::StripCompression("restart markers are not at the start of MCU rows"))
+        }
+
+        if self.header.is_empty() {
+            self.header = jpeg_data[..sos.end()].to_vec();
+            self.sof_offset = sof.offset;
+        }
+        for interval in intervals {
+            if self.intervals > 0 {
+                self.scan.extend_from_slice(&[0xff, markers::RST0 + ((self.intervals - 1) % 8) as u8]);
+            }
+            self.scan.extend_from_slice(interval);
+            self.intervals += 1;
+        }
+
+        self.height += rows;
+        self.pending.drain(..rows * pitch);
+        Ok(())
+    }
+}
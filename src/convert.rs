@@ -0,0 +1,761 @@
+//! Conversions between pixel layouts that are commonly used together with JPEG.
+//!
+//! Cameras and video decoders often produce NV12 images (a Y plane followed by a plane with
+//! interleaved U and V samples), but TurboJPEG works with planar [`YuvImage`]s (also called I420
+//! when using 2x2 subsampling). Similarly, graphics APIs usually work with RGBA pixels, but RGB
+//! images are a third smaller. This module provides conversions between these layouts.
+//!
+//! The layout conversions use SIMD instructions on x86-64 (SSE2 and SSSE3, detected at runtime)
+//! and AArch64 (Neon), with a scalar fallback on other platforms. They are typically limited by
+//! memory bandwidth.
+//!
+//! The module also provides conversions between RGB images and planar YUV images
+//! ([`encode_yuv()`] and [`decode_yuv()`]) and between the full range of YUV samples used by JPEG
+//! and the limited range used by video ([`convert_yuv_range()`]), with the color matrices and
+//! chrominance sample positions used by JPEG and by video, see [`YuvOptions`].
+//!
+//! # Example
+//!
+//! ```
+//! use turbojpeg::convert;
+//!
+//! // an NV12 frame from a camera
+//! let (width, height) = (64, 48);
+//! let y = vec![128; width * height];
+//! let uv = vec![128; width * height / 2];
+//!
+//! // convert it to a planar YUV image and compress it without converting to RGB
+//! let image = convert::nv12_to_yuv(&y, &uv, width, height);
+//! let jpeg_data = turbojpeg::compress_yuv(image.as_deref(), 90)?;
+//!
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::common::{PixelFormat, Subsamp};
+use crate::image_internal::{Image, YuvImage};
+
+/// Splits interleaved chrominance samples (`UVUVUV...`) into separate `U` and `V` planes.
+///
+/// # Panics
+///
+/// Panics if `u` and `v` do not have the same length, or if `uv` is not twice as long as `u`.
+///
+/// # Example
+///
+/// ```
+/// let mut u = [0; 3];
+/// let mut v = [0; 3];
+/// turbojpeg::convert::deinterleave_uv(&[1, 2, 3, 4, 5, 6], &mut u, &mut v);
+/// assert_eq!((u, v), ([1, 3, 5], [2, 4, 6]));
+/// ```
+pub fn deinterleave_uv(uv: &[u8], u: &mut [u8], v: &mut [u8]) {
+    assert_eq!(u.len(), v.len(), "U and V must have the same length");
+    assert_eq!(uv.len(), 2 * u.len(), "UV must be twice as long as U and V");
+    let done = simd::deinterleave_uv(uv, u, v);
+    for ((uv, u), v) in uv[2 * done..].chunks_exact(2).zip(&mut u[done..]).zip(&mut v[done..]) {
+        *u = uv[0];
+        *v = uv[1];
+    }
+}
+
+/// Interleaves separate `U` and `V` planes into interleaved chrominance samples (`UVUVUV...`).
+///
+/// # Panics
+///
+/// Panics if `u` and `v` do not have the same length, or if `uv` is not twice as long as `u`.
+///
+/// # Example
+///
+/// ```
+/// let mut uv = [0; 6];
+/// turbojpeg::convert::interleave_uv(&[1, 3, 5], &[2, 4, 6], &mut uv);
+/// assert_eq!(uv, [1, 2, 3, 4, 5, 6]);
+///
+/// // converting back gives the original samples
+/// let uv: Vec<u8> = (0..100).collect();
+/// let (mut u, mut v) = (vec![0; 50], vec![0; 50]);
+/// turbojpeg::convert::deinterleave_uv(&uv, &mut u, &mut v);
+/// let mut uv_2 = vec![0; 100];
+/// turbojpeg::convert::interleave_uv(&u, &v, &mut uv_2);
+/// assert_eq!(uv, uv_2);
+/// ```
+pub fn interleave_uv(u: &[u8], v: &[u8], uv: &mut [u8]) {
+    assert_eq!(u.len(), v.len(), "U and V must have the same length");
+    assert_eq!(uv.len(), 2 * u.len(), "UV must be twice as long as U and V");
+    let done = simd::interleave_uv(u, v, uv);
+    for ((uv, u), v) in uv[2 * done..].chunks_exact_mut(2).zip(&u[done..]).zip(&v[done..]) {
+        uv[0] = *u;
+        uv[1] = *v;
+    }
+}
+
+/// Converts RGBA pixels into RGB pixels by dropping the alpha channel.
+///
+/// This also works for other 4-byte formats with the padding or alpha in the last byte (such as
+/// BGRA into BGR).
+///
+/// # Panics
+///
+/// Panics if `rgba` does not contain whole pixels, or if `rgb` does not have space for the same
+/// number of pixels.
+///
+/// # Example
+///
+/// ```
+/// let mut rgb = [0; 6];
+/// turbojpeg::convert::rgba_to_rgb(&[1, 2, 3, 255, 4, 5, 6, 255], &mut rgb);
+/// assert_eq!(rgb, [1, 2, 3, 4, 5, 6]);
+/// ```
+pub fn rgba_to_rgb(rgba: &[u8], rgb: &mut [u8]) {
+    assert_eq!(rgba.len() % 4, 0, "RGBA must contain whole pixels");
+    assert_eq!(rgb.len(), rgba.len() / 4 * 3, "RGB must have the same number of pixels as RGBA");
+    let done = simd::rgba_to_rgb(rgba, rgb);
+    for (rgba, rgb) in rgba[4 * done..].chunks_exact(4).zip(rgb[3 * done..].chunks_exact_mut(3)) {
+        rgb.copy_from_slice(&rgba[..3]);
+    }
+}
+
+/// Converts RGB pixels into RGBA pixels with opaque alpha (255).
+///
+/// This also works for other 3-byte formats (such as BGR into BGRA).
+///
+/// # Panics
+///
+/// Panics if `rgb` does not contain whole pixels, or if `rgba` does not have space for the same
+/// number of pixels.
+///
+/// # Example
+///
+/// ```
+/// let mut rgba = [0; 8];
+/// turbojpeg::convert::rgb_to_rgba(&[1, 2, 3, 4, 5, 6], &mut rgba);
+/// assert_eq!(rgba, [1, 2, 3, 255, 4, 5, 6, 255]);
+///
+/// // converting back gives the original pixels
+/// let rgb: Vec<u8> = (0..300).map(|i| i as u8).collect();
+/// let mut rgba = vec![0; 400];
+/// turbojpeg::convert::rgb_to_rgba(&rgb, &mut rgba);
+/// let mut rgb_2 = vec![0; 300];
+/// turbojpeg::convert::rgba_to_rgb(&rgba, &mut rgb_2);
+/// assert_eq!(rgb, rgb_2);
+/// ```
+pub fn rgb_to_rgba(rgb: &[u8], rgba: &mut [u8]) {
+    assert_eq!(rgb.len() % 3, 0, "RGB must contain whole pixels");
+    assert_eq!(rgba.len(), rgb.len() / 3 * 4, "RGBA must have the same number of pixels as RGB");
+    let done = simd::rgb_to_rgba(rgb, rgba);
+    for (rgb, rgba) in rgb[3 * done..].chunks_exact(3).zip(rgba[4 * done..].chunks_exact_mut(4)) {
+        rgba[..3].copy_from_slice(rgb);
+        rgba[3] = 255;
+    }
+}
+
+/// Converts an NV12 image into a planar [`YuvImage`] with [2x2 subsampling][Subsamp::Sub2x2].
+///
+/// The NV12 image consists of the `y` plane with `width` bytes per row, followed by the `uv` plane
+/// with interleaved chrominance samples, with `2 * ceil(width / 2)` bytes per row and
+/// `ceil(height / 2)` rows. The returned image has row alignment 1, so it can be directly
+/// compressed with [`compress_yuv()`][crate::compress_yuv].
+///
+/// # Panics
+///
+/// Panics if the planes are too small for the given size.
+pub fn nv12_to_yuv(y: &[u8], uv: &[u8], width: usize, height: usize) -> YuvImage<Vec<u8>> {
+    let mut image = YuvImage { pixels: Vec::new(), width, align: 1, height, subsamp: Subsamp::Sub2x2 };
+    let (y_width, y_height) = image.y_size();
+    let (uv_width, uv_height) = image.uv_size();
+    assert!(y.len() >= width * height, "Y plane is too small");
+    assert!(uv.len() >= 2 * uv_width * uv_height, "UV plane is too small");
+
+    let mut pixels = vec![0; y_width * y_height + 2 * uv_width * uv_height];
+    let (y_plane, uv_planes) = pixels.split_at_mut(y_width * y_height);
+    let (u_plane, v_plane) = uv_planes.split_at_mut(uv_width * uv_height);
+
+    // the Y plane is padded to even size by repeating the last column and row
+    for row in 0..y_height {
+        let src = &y[row.min(height.saturating_sub(1)) * width..][..width];
+        let dst = &mut y_plane[row * y_width..][..y_width];
+        dst[..width].copy_from_slice(src);
+        if let Some(&last) = src.last() {
+            dst[width..].fill(last);
+        }
+    }
+
+    for row in 0..uv_height {
+        deinterleave_uv(
+            &uv[row * 2 * uv_width..][..2 * uv_width],
+            &mut u_plane[row * uv_width..][..uv_width],
+            &mut v_plane[row * uv_width..][..uv_width],
+        );
+    }
+
+    image.pixels = pixels;
+    image
+}
+
+/// Converts a planar [`YuvImage`] with [2x2 subsampling][Subsamp::Sub2x2] into an NV12 image.
+///
+/// Returns the Y plane (with `width` bytes per row) and the interleaved UV plane (with
+/// `2 * ceil(width / 2)` bytes per row), in the layout expected by [`nv12_to_yuv()`].
+///
+/// # Panics
+///
+/// Panics if the image does not use 2x2 subsampling or if it is not valid.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{Image, PixelFormat, Subsamp, convert};
+///
+/// let image = Image::mandelbrot(64, 48, PixelFormat::RGB);
+/// let jpeg_data = turbojpeg::compress(image.as_deref(), 90, Subsamp::Sub2x2)?;
+///
+/// let yuv_image = turbojpeg::decompress_to_yuv(&jpeg_data)?;
+/// let (y, uv) = convert::yuv_to_nv12(yuv_image.as_deref());
+/// assert_eq!((y.len(), uv.len()), (64 * 48, 64 * 24));
+///
+/// // converting back gives the same planes
+/// let yuv_image_2 = convert::nv12_to_yuv(&y, &uv, 64, 48);
+/// assert_eq!(convert::yuv_to_nv12(yuv_image_2.as_deref()), (y, uv));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn yuv_to_nv12(image: YuvImage<&[u8]>) -> (Vec<u8>, Vec<u8>) {
+    assert_eq!(image.subsamp, Subsamp::Sub2x2, "NV12 requires 2x2 subsampling");
+    image.assert_valid(image.pixels.len());
+
+    let (y_width, y_height) = image.y_size();
+    let (uv_width, uv_height) = image.uv_size();
+    let u_plane = &image.pixels[y_width * y_height..];
+    let v_plane = &u_plane[uv_width * uv_height..];
+
+    let mut y = Vec::with_capacity(image.width * image.height);
+    for row in 0..image.height {
+        y.extend_from_slice(&image.pixels[row * y_width..][..image.width]);
+    }
+
+    let chroma_width = image.width.div_ceil(2);
+    let mut uv = vec![0; 2 * chroma_width * uv_height];
+    for row in 0..uv_height {
+        interleave_uv(
+            &u_plane[row * uv_width..][..chroma_width],
+            &v_plane[row * uv_width..][..chroma_width],
+            &mut uv[row * 2 * chroma_width..][..2 * chroma_width],
+        );
+    }
+    (y, uv)
+}
+
+/// Range of the samples in a YUV image.
+///
+/// JPEG uses the full range of 8-bit values for all samples, but video (such as frames from
+/// H.264/H.265 decoders and many camera pipelines) usually uses the limited range from the
+/// ITU-R BT.601 and BT.709 standards. Interpreting a limited-range image as full-range makes it
+/// look washed out (black is dark gray and white is light gray), while interpreting a full-range
+/// image as limited-range clips the shadows and highlights.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum YuvRange {
+    /// Full range (also called "PC" or "JPEG" range): Y, U and V samples from 0 to 255. This is
+    /// used by JPEG and by TurboJPEG ([`compress_yuv()`][crate::compress_yuv] and
+    /// [`decompress_to_yuv()`][crate::decompress_to_yuv]).
+    #[default]
+    Full,
+    /// Limited range (also called "TV" or "video" range): Y samples from 16 to 235, U and V
+    /// samples from 16 to 240.
+    Limited,
+}
+
+impl YuvRange {
+    /// Converts a luminance sample in this range to the full range.
+    fn luma_to_full(self, value: f32) -> f32 {
+        match self {
+            YuvRange::Full => value,
+            YuvRange::Limited => (value - 16.0) * (255.0 / 219.0),
+        }
+    }
+
+    /// Converts a luminance sample in the full range to this range.
+    fn luma_from_full(self, value: f32) -> f32 {
+        match self {
+            YuvRange::Full => value,
+            YuvRange::Limited => 16.0 + value * (219.0 / 255.0),
+        }
+    }
+
+    /// Converts a chrominance sample in this range to the full range.
+    fn chroma_to_full(self, value: f32) -> f32 {
+        match self {
+            YuvRange::Full => value,
+            YuvRange::Limited => 128.0 + (value - 128.0) * (255.0 / 224.0),
+        }
+    }
+
+    /// Converts a chrominance sample in the full range to this range.
+    fn chroma_from_full(self, value: f32) -> f32 {
+        match self {
+            YuvRange::Full => value,
+            YuvRange::Limited => 128.0 + (value - 128.0) * (224.0 / 255.0),
+        }
+    }
+}
+
+/// Options for the conversions between RGB and YUV images ([`encode_yuv()`] and
+/// [`decode_yuv()`]).
+///
+/// The default options match the conventions of JPEG, so [`encode_yuv()`] produces the same
+/// (up to rounding) YUV images as TurboJPEG.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct YuvOptions {
+    /// Range of the YUV samples.
+    pub range: YuvRange,
+    /// Matrix of the conversion between RGB and YUV.
+    pub matrix: YuvMatrix,
+    /// Position of the chrominance samples relative to the luminance samples.
+    pub chroma_siting: ChromaSiting,
+}
+
+/// Matrix of the conversion between RGB and YUV.
+///
+/// JPEG uses the BT.601 matrix, but high-definition video (such as H.264 and H.265 streams)
+/// usually uses BT.709, and ultra-high-definition video uses BT.2020. Using the wrong matrix causes
+/// a subtle color shift (for example, reds and greens become slightly more orange).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum YuvMatrix {
+    /// ITU-R BT.601, used by JPEG, TurboJPEG and standard-definition video.
+    #[default]
+    Bt601,
+    /// ITU-R BT.709, used by high-definition video.
+    Bt709,
+    /// ITU-R BT.2020 (non-constant luminance), used by ultra-high-definition video.
+    Bt2020,
+}
+
+impl YuvMatrix {
+    /// Returns the luma coefficients of red and blue.
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+            YuvMatrix::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+/// Position of the subsampled chrominance samples relative to the luminance samples.
+///
+/// JPEG places every chrominance sample in the center of the block of pixels that it covers, but
+/// video codecs and camera ISPs usually place it on top of the first (left or top-left) luminance
+/// sample in the block. Using the wrong siting shifts the colors by a fraction of a pixel, which
+/// is visible as colored fringes around sharp edges. The siting has no effect in the directions
+/// where the chrominance is not subsampled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ChromaSiting {
+    /// The chrominance samples are centered between the luminance samples, both horizontally and
+    /// vertically. This is used by JPEG, TurboJPEG and MPEG-1.
+    #[default]
+    Centered,
+    /// The chrominance samples are co-sited with the left luminance samples horizontally, and
+    /// centered vertically. This is used by MPEG-2, H.264 and H.265 by default (chroma sample
+    /// location type 0).
+    Left,
+    /// The chrominance samples are co-sited with the top-left luminance samples. This is used by
+    /// BT.2020 and BT.2100 video (chroma sample location type 2).
+    TopLeft,
+}
+
+impl ChromaSiting {
+    /// Returns whether the chrominance samples are co-sited horizontally and vertically.
+    fn co_sited(self) -> (bool, bool) {
+        match self {
+            ChromaSiting::Centered => (false, false),
+            ChromaSiting::Left => (true, false),
+            ChromaSiting::TopLeft => (true, true),
+        }
+    }
+}
+
+/// Converts an RGB (or grayscale) image into a planar [`YuvImage`].
+///
+/// The returned image uses the given chrominance subsampling and has row alignment 1. With
+/// [`ChromaSiting::Centered`], the chrominance samples are averages of the pixels that they
+/// cover; in the co-sited directions, they are weighted averages (with a triangle filter) of the
+/// pixels around the co-sited pixel. The image is padded by repeating the last column and row. If
+/// the source image is grayscale, the chrominance samples are neutral (128).
+///
+/// # Panics
+///
+/// Panics if `subsamp` is [`Subsamp::Unknown`], if the pixel format is [`PixelFormat::CMYK`], or
+/// if the image is not valid.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{Image, PixelFormat, Subsamp, convert};
+///
+/// let image = Image::mandelbrot(64, 48, PixelFormat::RGB);
+///
+/// // convert the image to YUV for a video encoder that expects limited range and BT.709
+/// let options = convert::YuvOptions {
+///     range: convert::YuvRange::Limited,
+///     matrix: convert::YuvMatrix::Bt709,
+///     chroma_siting: convert::ChromaSiting::Left,
+/// };
+/// let yuv_image = convert::encode_yuv(image.as_deref(), Subsamp::Sub2x2, &options);
+/// assert_eq!(yuv_image.y_size(), (64, 48));
+///
+/// // convert it back to RGB
+/// let decoded = convert::decode_yuv(yuv_image.as_deref(), PixelFormat::RGB, &options);
+/// assert_eq!((decoded.width, decoded.height), (64, 48));
+/// ```
+pub fn encode_yuv(image: Image<&[u8]>, subsamp: Subsamp, options: &YuvOptions) -> YuvImage<Vec<u8>> {
+    assert!(subsamp != Subsamp::Unknown, "subsampling must be known");
+    assert!(image.format != PixelFormat::CMYK, "CMYK images cannot be converted to YUV");
+    image.assert_valid(image.pixels.len());
+
+    let mut yuv = YuvImage { pixels: Vec::new(), width: image.width, align: 1, height: image.height, subsamp };
+    let (y_width, y_height) = yuv.y_size();
+    let (uv_width, uv_height) = yuv_uv_size(&yuv);
+    let mut pixels = vec![0; y_width * y_height + 2 * uv_width * uv_height];
+    if image.width == 0 || image.height == 0 {
+        yuv.pixels = pixels;
+        return yuv
+    }
+
+    let pitch = image.row_pitch();
+    let size = image.format.size();
+    let offsets = match (image.format.red_offset(), image.format.green_offset(), image.format.blue_offset()) {
+        (Some(r), Some(g), Some(b)) => [r, g, b],
+        _ => [0, 0, 0],
+    };
+    // returns the RGB components of the pixel, repeating the first and last column and row
+    let rgb = |x: isize, y: isize| -> [f32; 3] {
+        let x = (x.max(0) as usize).min(image.width - 1);
+        let y = (y.max(0) as usize).min(image.height - 1);
+        let pixel = &image.pixels[y * pitch + x * size..];
+        offsets.map(|offset| pixel[offset] as f32)
+    };
+    let (kr, kb) = options.matrix.coefficients();
+    let luma = |[r, g, b]: [f32; 3]| kr * r + (1.0 - kr - kb) * g + kb * b;
+
+    let (y_plane, uv_planes) = pixels.split_at_mut(y_width * y_height);
+    for (y, row) in y_plane.chunks_exact_mut(y_width).enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = quantize(options.range.luma_from_full(luma(rgb(x as isize, y as isize))));
+        }
+    }
+
+    let (u_plane, v_plane) = uv_planes.split_at_mut(uv_width * uv_height);
+    let (sub_width, sub_height) = subsamp.size();
+    let (co_sited_x, co_sited_y) = options.chroma_siting.co_sited();
+    let filter_x = downsampling_filter(sub_width, co_sited_x);
+    let filter_y = downsampling_filter(sub_height, co_sited_y);
+    for uv_y in 0..uv_height {
+        for uv_x in 0..uv_width {
+            let (mut u, mut v) = (0.0, 0.0);
+            for &(dy, weight_y) in filter_y.iter() {
+                for &(dx, weight_x) in filter_x.iter() {
+                    let [r, g, b] = rgb((uv_x * sub_width) as isize + dx, (uv_y * sub_height) as isize + dy);
+                    let luma = luma([r, g, b]);
+                    let weight = weight_x * weight_y;
+                    u += weight * (b - luma) / (2.0 * (1.0 - kb));
+                    v += weight * (r - luma) / (2.0 * (1.0 - kr));
+                }
+            }
+            u_plane[uv_y * uv_width + uv_x] = quantize(options.range.chroma_from_full(128.0 + u));
+            v_plane[uv_y * uv_width + uv_x] = quantize(options.range.chroma_from_full(128.0 + v));
+        }
+    }
+
+    yuv.pixels = pixels;
+    yuv
+}
+
+/// Converts a planar [`YuvImage`] into an RGB (or grayscale) image.
+///
+/// The returned image has no padding between rows. The chrominance is interpolated bilinearly
+/// between the chrominance samples, at the positions given by [`ChromaSiting`]. If the pixel
+/// format has an alpha channel, it is set to 255 (opaque); if the pixel format is grayscale, only
+/// the luminance is used.
+///
+/// See [`encode_yuv()`] for an example.
+///
+/// # Panics
+///
+/// Panics if the subsampling is [`Subsamp::Unknown`], if `format` is [`PixelFormat::CMYK`], or
+/// if the image is not valid.
+pub fn decode_yuv(image: YuvImage<&[u8]>, format: PixelFormat, options: &YuvOptions) -> Image<Vec<u8>> {
+    assert!(image.subsamp != Subsamp::Unknown, "subsampling must be known");
+    assert!(format != PixelFormat::CMYK, "YUV images cannot be converted to CMYK");
+    let (y_width, y_height) = image.y_size();
+    let (uv_width, uv_height) = yuv_uv_size(&image);
+    assert!(image.pixels.len() >= y_width * y_height + 2 * uv_width * uv_height, "YUV image is too small");
+
+    let (y_plane, uv_planes) = image.pixels.split_at(y_width * y_height);
+    let (u_plane, v_plane) = uv_planes.split_at(uv_width * uv_height);
+    let (sub_width, sub_height) = image.subsamp.size();
+    let (kr, kb) = options.matrix.coefficients();
+    let (co_sited_x, co_sited_y) = options.chroma_siting.co_sited();
+    let uv_columns = image.width.div_ceil(sub_width);
+    let uv_rows = image.height.div_ceil(sub_height);
+    let taps_x = (0..image.width)
+        .map(|x| upsampling_taps(x, sub_width, co_sited_x, uv_columns))
+        .collect::<Vec<_>>();
+    let taps_y = (0..image.height)
+        .map(|y| upsampling_taps(y, sub_height, co_sited_y, uv_rows))
+        .collect::<Vec<_>>();
+    // bilinear interpolation of the chrominance samples around the pixel
+    let chroma = |plane: &[u8], x: usize, y: usize| -> f32 {
+        let ((x0, x1, fx), (y0, y1, fy)) = (taps_x[x], taps_y[y]);
+        let row = |y: usize| plane[y * uv_width + x0] as f32 * (1.0 - fx) + plane[y * uv_width + x1] as f32 * fx;
+        row(y0) * (1.0 - fy) + row(y1) * fy
+    };
+    let size = format.size();
+    let pitch = image.width * size;
+    let mut pixels = vec![255; pitch * image.height];
+    for (y, row) in pixels.chunks_exact_mut(pitch.max(1)).enumerate() {
+        for (x, pixel) in row.chunks_exact_mut(size).enumerate() {
+            let luma = options.range.luma_to_full(y_plane[y * y_width + x] as f32);
+            let (Some(r), Some(g), Some(b)) = (format.red_offset(), format.green_offset(), format.blue_offset()) else {
+                pixel[0] = quantize(luma);
+                continue
+            };
+            let (u, v) = if image.subsamp == Subsamp::Gray {
+                (0.0, 0.0)
+            } else {
+                let u = options.range.chroma_to_full(chroma(u_plane, x, y));
+                let v = options.range.chroma_to_full(chroma(v_plane, x, y));
+                (u - 128.0, v - 128.0)
+            };
+            let red = luma + 2.0 * (1.0 - kr) * v;
+            let blue = luma + 2.0 * (1.0 - kb) * u;
+            let green = (luma - kr * red - kb * blue) / (1.0 - kr - kb);
+            pixel[r] = quantize(red);
+            pixel[g] = quantize(green);
+            pixel[b] = quantize(blue);
+        }
+    }
+    Image { pixels, width: image.width, pitch, height: image.height, format }
+}
+
+/// Converts the samples of a planar [`YuvImage`] between the full and limited range in place.
+///
+/// Use this to convert YUV images produced by TurboJPEG (which are always full-range) into
+/// limited-range images for video encoders, or to convert limited-range video frames into
+/// full-range images before compressing them with [`compress_yuv()`][crate::compress_yuv].
+///
+/// # Panics
+///
+/// Panics if the subsampling is [`Subsamp::Unknown`] or if the image is not valid.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{Subsamp, YuvImage, convert};
+/// use convert::YuvRange::{Full, Limited};
+///
+/// // a limited-range frame from a video decoder, with black luminance and neutral chrominance
+/// let mut pixels = vec![16; 16 * 16];
+/// pixels.extend_from_slice(&[128; 2 * 8 * 8]);
+/// let mut image = YuvImage { pixels, width: 16, align: 1, height: 16, subsamp: Subsamp::Sub2x2 };
+///
+/// convert::convert_yuv_range(image.as_deref_mut(), Limited, Full);
+/// assert_eq!(image.pixels[0], 0);
+/// assert_eq!(image.pixels[16 * 16], 128);
+/// ```
+pub fn convert_yuv_range(image: YuvImage<&mut [u8]>, from: YuvRange, to: YuvRange) {
+    assert!(image.subsamp != Subsamp::Unknown, "subsampling must be known");
+    let (y_width, y_height) = image.y_size();
+    let (uv_width, uv_height) = yuv_uv_size(&image);
+    assert!(image.pixels.len() >= y_width * y_height + 2 * uv_width * uv_height, "YUV image is too small");
+    if from == to {
+        return
+    }
+
+    let luma_table: [u8; 256] = std::array::from_fn(|i| quantize(to.luma_from_full(from.luma_to_full(i as f32))));
+    let chroma_table: [u8; 256] = std::array::from_fn(|i| quantize(to.chroma_from_full(from.chroma_to_full(i as f32))));
+    let (y_plane, uv_planes) = image.pixels.split_at_mut(y_width * y_height);
+    for value in y_plane.iter_mut() {
+        *value = luma_table[*value as usize];
+    }
+    for value in uv_planes[..2 * uv_width * uv_height].iter_mut() {
+        *value = chroma_table[*value as usize];
+    }
+}
+
+/// Returns the taps of the filter that computes a chrominance sample from the luminance samples in
+/// one direction, as offsets from the first luminance sample in the block and weights.
+fn downsampling_filter(factor: usize, co_sited: bool) -> Vec<(isize, f32)> {
+    let factor = factor as isize;
+    if co_sited {
+        // triangle filter centered on the first sample, the weights sum to `factor * factor`
+        let norm = (factor * factor) as f32;
+        (1 - factor..factor).map(|d| (d, (factor - d.abs()) as f32 / norm)).collect()
+    } else {
+        (0..factor).map(|d| (d, 1.0 / factor as f32)).collect()
+    }
+}
+
+/// Returns the two chrominance samples (out of `len`) that are interpolated for the luminance
+/// sample at `position` in one direction, and the weight of the second sample.
+fn upsampling_taps(position: usize, factor: usize, co_sited: bool, len: usize) -> (usize, usize, f32) {
+    let offset = if co_sited { 0.0 } else { (factor - 1) as f32 / 2.0 };
+    let position = ((position as f32 - offset) / factor as f32).max(0.0);
+    let first = (position.floor() as usize).min(len - 1);
+    let second = (first + 1).min(len - 1);
+    (first, second, position.fract())
+}
+
+/// Returns the size of the chrominance planes, which is zero for grayscale images.
+fn yuv_uv_size<T>(image: &YuvImage<T>) -> (usize, usize) {
+    if image.subsamp == Subsamp::Gray { (0, 0) } else { image.uv_size() }
+}
+
+/// Rounds and clamps a sample to 8 bits.
+fn quantize(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// SIMD kernels. Each kernel processes a prefix of the input and returns the number of processed
+/// elements (UV pairs or pixels); the rest is processed by the scalar code.
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+
+    pub fn deinterleave_uv(uv: &[u8], u: &mut [u8], v: &mut [u8]) -> usize {
+        let n = u.len() / 16 * 16;
+        // SSE2 is always available on x86-64
+        unsafe {
+            let mask = _mm_set1_epi16(0x00ff);
+            for i in (0..n).step_by(16) {
+                let a = _mm_loadu_si128(uv.as_ptr().add(2 * i) as *const __m128i);
+                let b = _mm_loadu_si128(uv.as_ptr().add(2 * i + 16) as *const __m128i);
+                let u_vec = _mm_packus_epi16(_mm_and_si128(a, mask), _mm_and_si128(b, mask));
+                let v_vec = _mm_packus_epi16(_mm_srli_epi16(a, 8), _mm_srli_epi16(b, 8));
+                _mm_storeu_si128(u.as_mut_ptr().add(i) as *mut __m128i, u_vec);
+                _mm_storeu_si128(v.as_mut_ptr().add(i) as *mut __m128i, v_vec);
+            }
+        }
+        n
+    }
+
+    pub fn interleave_uv(u: &[u8], v: &[u8], uv: &mut [u8]) -> usize {
+        let n = u.len() / 16 * 16;
+        unsafe {
+            for i in (0..n).step_by(16) {
+                let u_vec = _mm_loadu_si128(u.as_ptr().add(i) as *const __m128i);
+                let v_vec = _mm_loadu_si128(v.as_ptr().add(i) as *const __m128i);
+                let dst = uv.as_mut_ptr().add(2 * i) as *mut __m128i;
+                _mm_storeu_si128(dst, _mm_unpacklo_epi8(u_vec, v_vec));
+                _mm_storeu_si128(dst.add(1), _mm_unpackhi_epi8(u_vec, v_vec));
+            }
+        }
+        n
+    }
+
+    pub fn rgba_to_rgb(rgba: &[u8], rgb: &mut [u8]) -> usize {
+        if is_x86_feature_detected!("ssse3") {
+            unsafe { rgba_to_rgb_ssse3(rgba, rgb) }
+        } else {
+            0
+        }
+    }
+
+    #[target_feature(enable = "ssse3")]
+    unsafe fn rgba_to_rgb_ssse3(rgba: &[u8], rgb: &mut [u8]) -> usize {
+        let pixels = rgba.len() / 4;
+        let shuffle = _mm_setr_epi8(0, 1, 2, 4, 5, 6, 8, 9, 10, 12, 13, 14, -1, -1, -1, -1);
+        // each iteration converts 4 pixels, but stores 16 bytes (4 bytes past the 12 converted
+        // bytes), so we must stop while there is enough space in the output
+        let mut i = 0;
+        while i + 6 <= pixels {
+            let src = _mm_loadu_si128(rgba.as_ptr().add(4 * i) as *const __m128i);
+            _mm_storeu_si128(rgb.as_mut_ptr().add(3 * i) as *mut __m128i, _mm_shuffle_epi8(src, shuffle));
+            i += 4;
+        }
+        i
+    }
+
+    pub fn rgb_to_rgba(rgb: &[u8], rgba: &mut [u8]) -> usize {
+        if is_x86_feature_detected!("ssse3") {
+            unsafe { rgb_to_rgba_ssse3(rgb, rgba) }
+        } else {
+            0
+        }
+    }
+
+    #[target_feature(enable = "ssse3")]
+    unsafe fn rgb_to_rgba_ssse3(rgb: &[u8], rgba: &mut [u8]) -> usize {
+        let pixels = rgb.len() / 3;
+        let shuffle = _mm_setr_epi8(0, 1, 2, -1, 3, 4, 5, -1, 6, 7, 8, -1, 9, 10, 11, -1);
+        let alpha = _mm_set1_epi32(0xff000000u32 as i32);
+        // each iteration loads 16 bytes (4 bytes past the 12 converted bytes), so we must stop
+        // while there is enough data in the input
+        let mut i = 0;
+        while i + 6 <= pixels {
+            let src = _mm_loadu_si128(rgb.as_ptr().add(3 * i) as *const __m128i);
+            let dst = _mm_or_si128(_mm_shuffle_epi8(src, shuffle), alpha);
+            _mm_storeu_si128(rgba.as_mut_ptr().add(4 * i) as *mut __m128i, dst);
+            i += 4;
+        }
+        i
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod simd {
+    use std::arch::aarch64::*;
+
+    // Neon is always available on AArch64
+
+    pub fn deinterleave_uv(uv: &[u8], u: &mut [u8], v: &mut [u8]) -> usize {
+        let n = u.len() / 16 * 16;
+        unsafe {
+            for i in (0..n).step_by(16) {
+                let uv_vec = vld2q_u8(uv.as_ptr().add(2 * i));
+                vst1q_u8(u.as_mut_ptr().add(i), uv_vec.0);
+                vst1q_u8(v.as_mut_ptr().add(i), uv_vec.1);
+            }
+        }
+        n
+    }
+
+    pub fn interleave_uv(u: &[u8], v: &[u8], uv: &mut [u8]) -> usize {
+        let n = u.len() / 16 * 16;
+        unsafe {
+            for i in (0..n).step_by(16) {
+                let uv_vec = uint8x16x2_t(vld1q_u8(u.as_ptr().add(i)), vld1q_u8(v.as_ptr().add(i)));
+                vst2q_u8(uv.as_mut_ptr().add(2 * i), uv_vec);
+            }
+        }
+        n
+    }
+
+    pub fn rgba_to_rgb(rgba: &[u8], rgb: &mut [u8]) -> usize {
+        let n = rgba.len() / 4 / 16 * 16;
+        unsafe {
+            for i in (0..n).step_by(16) {
+                let src = vld4q_u8(rgba.as_ptr().add(4 * i));
+                vst3q_u8(rgb.as_mut_ptr().add(3 * i), uint8x16x3_t(src.0, src.1, src.2));
+            }
+        }
+        n
+    }
+
+    pub fn rgb_to_rgba(rgb: &[u8], rgba: &mut [u8]) -> usize {
+        let n = rgb.len() / 3 / 16 * 16;
+        unsafe {
+            let alpha = vdupq_n_u8(255);
+            for i in (0..n).step_by(16) {
+                let src = vld3q_u8(rgb.as_ptr().add(3 * i));
+                vst4q_u8(rgba.as_mut_ptr().add(4 * i), uint8x16x4_t(src.0, src.1, src.2, alpha));
+            }
+        }
+        n
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod simd {
+    pub fn deinterleave_uv(_: &[u8], _: &mut [u8], _: &mut [u8]) -> usize { 0 }
+    pub fn interleave_uv(_: &[u8], _: &[u8], _: &mut [u8]) -> usize { 0 }
+    pub fn rgba_to_rgb(_: &[u8], _: &mut [u8]) -> usize { 0 }
+    pub fn rgb_to_rgba(_: &[u8], _: &mut [u8]) -> usize { 0 }
+}
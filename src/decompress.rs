@@ -1,13 +1,25 @@
 use std::convert::TryInto as _;
-use crate::{Image, YuvImage, raw};
+use std::io::IoSlice;
+use std::ops::Range;
+use crate::{Image, raw};
+#[cfg(feature = "yuv")]
+use crate::YuvImage;
 use crate::common::{PixelFormat, Subsamp, Colorspace, Result, Error};
 use crate::handle::Handle;
+use crate::limits::{Limits, read_frame};
+use crate::markers::{self, ReadSegment, SegmentReader};
+use crate::metrics::Timer;
+use crate::parallel;
 
 /// Decompresses JPEG data into raw pixels.
 #[derive(Debug)]
 #[doc(alias = "tjhandle")]
 pub struct Decompressor {
     handle: Handle,
+    // buffer for coalescing JPEG data that is split into multiple chunks
+    scratch: Vec<u8>,
+    output_colorspace: Option<Colorspace>,
+    naive_cmyk: bool,
 }
 
 unsafe impl Send for Decompressor {}
@@ -27,6 +39,265 @@ pub struct DecompressHeader {
     pub subsamp: Subsamp,
     /// Colorspace of the compressed image.
     pub colorspace: Colorspace,
+    /// True if the image is in the [CMYK][Colorspace::CMYK] or [YCCK][Colorspace::YCCK]
+    /// colorspace and it uses the inverted convention of Adobe applications.
+    ///
+    /// Photoshop and other Adobe applications store CMYK components inverted (0 means full ink),
+    /// and the images that they produce contain an Adobe APP14 marker. TurboJPEG decompresses the
+    /// components as they are stored, so if you interpret the [CMYK][PixelFormat::CMYK] pixels of
+    /// such an image as ordinary CMYK, the image looks like a photographic negative. Following the
+    /// convention of most decoders, this is true for every CMYK image with the Adobe marker.
+    pub inverted_cmyk: bool,
+    /// Number of MCUs between the restart markers, as defined by the DRI marker segment in the
+    /// header, or 0 if the image does not use restart markers.
+    ///
+    /// If the restart interval is a multiple of the number of MCUs in a row, the image can be
+    /// decompressed in independent strips (see [`decompress_parallel()`][crate::decompress_parallel]
+    /// and [`Decompressor::decompress_strips()`]).
+    pub restart_interval: usize,
+    /// Number of scans (SOS marker segments) in the image.
+    ///
+    /// Baseline images have one scan for all components and progressive images usually have about
+    /// ten scans, so an image with many more scans is expensive to decompress (see
+    /// [`Limits::max_scans`][crate::Limits::max_scans]). The scans are counted by skipping over
+    /// the compressed data of every scan; if the data is truncated, only the scans before the end
+    /// of the data are counted.
+    pub scans: usize,
+}
+
+impl DecompressHeader {
+    /// Creates a header with the given parameters.
+    ///
+    /// Headers are normally obtained from [`Decompressor::read_header()`]. This constructor is
+    /// useful when you implement [`JpegDecode`][crate::JpegDecode] yourself.
+    ///
+    /// The other fields are set to the values of a typical baseline image:
+    /// [`inverted_cmyk`][Self::inverted_cmyk] is false,
+    /// [`restart_interval`][Self::restart_interval] is 0 and [`scans`][Self::scans] is 1.
+    pub fn new(width: usize, height: usize, subsamp: Subsamp, colorspace: Colorspace) -> Self {
+        DecompressHeader {
+            width, height, subsamp, colorspace,
+            inverted_cmyk: false,
+            restart_interval: 0,
+            scans: 1,
+        }
+    }
+
+    /// Get the size of the MCU block of the image as (width, height).
+    ///
+    /// This is the MCU size of the [chrominance subsampling][Self::subsamp] (see
+    /// [`Subsamp::mcu_size()`]). Lossless transformations and cropping during decompression work
+    /// on whole MCU blocks.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let header = turbojpeg::read_header(&jpeg_data)?;
+    /// assert_eq!(header.mcu_size(), (8, 8));
+    /// assert_eq!((header.mcus_wide(), header.mcus_high()), (48, 32));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn mcu_size(&self) -> (usize, usize) {
+        self.subsamp.mcu_size()
+    }
+
+    /// Get the number of MCU columns in the image, including the partial MCU at the right edge.
+    pub fn mcus_wide(&self) -> usize {
+        self.width.div_ceil(self.subsamp.mcu_width())
+    }
+
+    /// Get the number of MCU rows in the image, including the partial MCU at the bottom edge.
+    pub fn mcus_high(&self) -> usize {
+        self.height.div_ceil(self.subsamp.mcu_height())
+    }
+
+    /// Expands a region of the image to the MCU grid and clamps it to the image.
+    ///
+    /// The left and upper boundaries of the region are rounded down to a multiple of the MCU size,
+    /// and the right and lower boundaries are rounded up (but never beyond the image). The
+    /// returned region contains the original region, it always lies inside the image and it has
+    /// an explicit width and height, so it can be used both for cropping during decompression (see
+    /// [`DecompressCrop`]) and for lossless cropping (see
+    /// [`TransformCrop`][crate::TransformCrop]), which require the boundaries to be aligned.
+    ///
+    /// The region is given in the coordinates of the unscaled image.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let header = turbojpeg::read_header(&jpeg_data)?;
+    ///
+    /// let crop = turbojpeg::DecompressCrop { x: 100, y: 50, width: Some(40), height: None };
+    /// let aligned = header.align_crop(crop);
+    /// assert_eq!((aligned.x, aligned.y), (96, 48));
+    /// assert_eq!((aligned.width, aligned.height), (Some(48), Some(208)));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn align_crop(&self, crop: DecompressCrop) -> DecompressCrop {
+        let (mcu_width, mcu_height) = self.mcu_size();
+        let align = |start: usize, len: Option<usize>, size: usize, mcu: usize| {
+            let end = len.map_or(size, |len| start.saturating_add(len)).min(size);
+            let start = start.min(size) / mcu * mcu;
+            let end = end.div_ceil(mcu).saturating_mul(mcu).min(size);
+            (start, end.saturating_sub(start))
+        };
+        let (x, width) = align(crop.x, crop.width, self.width, mcu_width);
+        let (y, height) = align(crop.y, crop.height, self.height, mcu_height);
+        DecompressCrop { x, y, width: Some(width), height: Some(height) }
+    }
+}
+
+/// Result of reading a JPEG header from data that may be incomplete.
+///
+/// Returned by [`Decompressor::read_header_partial()`] and
+/// [`read_header_partial()`][crate::read_header_partial].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeaderStatus {
+    /// The data contains the complete header.
+    Complete(DecompressHeader),
+    /// The data looks like the beginning of a JPEG image, but the header is not complete. At least
+    /// this many more bytes are needed to make progress (the header may need more bytes than that).
+    NeedMoreData(usize),
+    /// The data is not a JPEG image.
+    NotJpeg,
+}
+
+/// Scaling factor for decompression.
+///
+/// TurboJPEG can scale the image during decompression by a factor `num / denom`. This is much
+/// faster than decompressing the full image and resizing it, because the scaling is performed as a
+/// part of the inverse DCT. Only some factors are supported, see
+/// [`ScalingFactor::supported()`].
+///
+/// # Example
+///
+/// ```
+/// let half = turbojpeg::ScalingFactor::new(1, 2);
+/// assert_eq!(half.scale(384), 192);
+/// assert!(turbojpeg::ScalingFactor::supported().contains(&half));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[doc(alias = "tjscalingfactor")]
+pub struct ScalingFactor {
+    /// Numerator of the factor.
+    pub num: usize,
+    /// Denominator of the factor.
+    pub denom: usize,
+}
+
+impl ScalingFactor {
+    /// No scaling (factor 1/1).
+    #[doc(alias = "TJUNSCALED")]
+    pub const ONE: ScalingFactor = ScalingFactor { num: 1, denom: 1 };
+
+    /// Creates a scaling factor `num / denom`.
+    pub const fn new(num: usize, denom: usize) -> ScalingFactor {
+        ScalingFactor { num, denom }
+    }
+
+    /// Returns the list of scaling factors that are supported by TurboJPEG.
+    #[doc(alias = "tj3GetScalingFactors")]
+    pub fn supported() -> Vec<ScalingFactor> {
+        let mut count: libc::c_int = 0;
+        let factors = unsafe { raw::tj3GetScalingFactors(&mut count) };
+        if factors.is_null() {
+            return Vec::new()
+        }
+        let factors = unsafe { std::slice::from_raw_parts(factors, count as usize) };
+        factors.iter()
+            .map(|factor| ScalingFactor::new(factor.num as usize, factor.denom as usize))
+            .collect()
+    }
+
+    /// Returns the largest supported scaling factor that fits the image into a bounding box.
+    ///
+    /// The image of size `width` x `height` scaled by the returned factor is at most `max_width`
+    /// pixels wide and `max_height` pixels high. This method never returns a factor larger than
+    /// 1, so images that already fit into the box are not enlarged. Returns `None` if the image
+    /// does not fit into the box even with the smallest supported factor.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // read the JPEG header to get the image size
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let header = turbojpeg::read_header(&jpeg_data)?;
+    ///
+    /// // find a factor to make a thumbnail that fits into 100x100 pixels
+    /// let factor = turbojpeg::ScalingFactor::fit_within(header.width, header.height, 100, 100);
+    /// assert_eq!(factor, Some(turbojpeg::ScalingFactor::new(1, 4)));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn fit_within(
+        width: usize,
+        height: usize,
+        max_width: usize,
+        max_height: usize,
+    ) -> Option<ScalingFactor> {
+        ScalingFactor::supported().into_iter()
+            .filter(|factor| factor.num <= factor.denom)
+            .filter(|factor| factor.scale(width) <= max_width && factor.scale(height) <= max_height)
+            .max_by(|a, b| (a.num * b.denom).cmp(&(b.num * a.denom)))
+    }
+
+    /// Scales the `dimension` (image width or height) by this factor, rounding up.
+    #[doc(alias = "TJSCALED")]
+    pub fn scale(self, dimension: usize) -> usize {
+        (dimension * self.num).div_ceil(self.denom)
+    }
+}
+
+impl Default for ScalingFactor {
+    fn default() -> Self {
+        ScalingFactor::ONE
+    }
+}
+
+/// Cropping region for partial decompression.
+///
+/// The region is specified relative to the scaled image (see [`ScalingFactor`]). The left boundary
+/// [`x`][Self::x] must be divisible by the scaled MCU width, which is the
+/// [MCU width][crate::Subsamp::mcu_width] of the JPEG image scaled by the scaling factor.
+///
+/// The default instance performs no cropping.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[doc(alias = "tjregion")]
+pub struct DecompressCrop {
+    /// Left boundary of the region. This must be divisible by the scaled MCU width.
+    pub x: usize,
+    /// Upper boundary of the region.
+    pub y: usize,
+    /// Width of the region. If None is given, the region ends at the right boundary of the image.
+    pub width: Option<usize>,
+    /// Height of the region. If None is given, the region ends at the bottom boundary of the
+    /// image.
+    pub height: Option<usize>,
+}
+
+impl DecompressCrop {
+    /// Crops a band with the given width that starts at column `x` and spans the whole height of
+    /// the image. The column `x` must be divisible by the scaled MCU width.
+    ///
+    /// The columns outside the band are not decompressed (TurboJPEG only decodes their
+    /// entropy-coded data).
+    pub const fn full_height(x: usize, width: usize) -> DecompressCrop {
+        DecompressCrop { x, y: 0, width: Some(width), height: None }
+    }
+
+    /// Crops a band with the given height that starts at row `y` and spans the whole width of the
+    /// image.
+    ///
+    /// TurboJPEG skips the rows above the band without decompressing them (but it still needs to
+    /// decode the entropy-coded data) and stops after the last row of the band.
+    pub const fn full_width(y: usize, height: usize) -> DecompressCrop {
+        DecompressCrop { x: 0, y, width: None, height: Some(height) }
+    }
 }
 
 impl Decompressor {
@@ -34,7 +305,262 @@ impl Decompressor {
     #[doc(alias = "tj3Init")]
     pub fn new() -> Result<Decompressor> {
         let handle = Handle::new(raw::TJINIT_TJINIT_DECOMPRESS)?;
-        Ok(Self { handle })
+        Ok(Self { handle, scratch: Vec::new(), output_colorspace: None, naive_cmyk: false })
+    }
+
+    /// Set the colorspace of the decompressed pixels.
+    ///
+    /// By default (`None`), the output colorspace is determined implicitly by the
+    /// [`PixelFormat`] of the output image. If you request a colorspace explicitly, the decompressor
+    /// checks that the colorspace of the JPEG image can be converted into the requested colorspace
+    /// and that the pixel format of the output image has this colorspace, and returns
+    /// [`Error::UnsupportedOutputColorspace`] otherwise. The supported combinations are:
+    ///
+    /// - [`Colorspace::RGB`]: images in RGB, YCbCr or grayscale colorspace, decompressed into one
+    ///   of the RGB pixel formats (such as [`PixelFormat::RGB`] or [`PixelFormat::BGRA`]).
+    /// - [`Colorspace::Gray`]: images in RGB, YCbCr or grayscale colorspace, decompressed into
+    ///   [`PixelFormat::GRAY`].
+    /// - [`Colorspace::CMYK`]: images in CMYK or YCCK colorspace, decompressed into
+    ///   [`PixelFormat::CMYK`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    ///
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.set_output_colorspace(Some(turbojpeg::Colorspace::CMYK));
+    ///
+    /// // the image is in YCbCr colorspace, so it cannot be decompressed into CMYK
+    /// let header = decompressor.read_header(&jpeg_data)?;
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: vec![0; 4 * header.width * header.height],
+    ///     width: header.width,
+    ///     pitch: 4 * header.width,
+    ///     height: header.height,
+    ///     format: turbojpeg::PixelFormat::CMYK,
+    /// };
+    /// let res = decompressor.decompress(&jpeg_data, image.as_deref_mut());
+    /// assert!(matches!(res, Err(turbojpeg::Error::UnsupportedOutputColorspace(..))));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_output_colorspace(&mut self, colorspace: Option<Colorspace>) {
+        self.output_colorspace = colorspace;
+    }
+
+    /// Get the colorspace of the decompressed pixels.
+    ///
+    /// See [`set_output_colorspace()`][Self::set_output_colorspace].
+    pub fn output_colorspace(&self) -> Option<Colorspace> {
+        self.output_colorspace
+    }
+
+    /// Enable/disable naive conversion of CMYK images into RGB.
+    ///
+    /// TurboJPEG can decompress images in the CMYK or YCCK colorspace (typically produced by
+    /// prepress software and found in scanned PDFs) only into [`PixelFormat::CMYK`]. When this
+    /// option is enabled, such images can also be decompressed into the RGB pixel formats and into
+    /// [`PixelFormat::GRAY`] by [`decompress()`][Self::decompress] and
+    /// [`decompress_region()`][Self::decompress_region]: the image is decompressed into a temporary
+    /// CMYK buffer, and every pixel is converted as `R = (255 - C) * (255 - K) / 255` (and
+    /// similarly for G with M and B with Y). Images written by Adobe applications (with an APP14
+    /// marker) store the CMYK components inverted, which is taken into account (see
+    /// [`DecompressHeader::inverted_cmyk`]).
+    ///
+    /// This conversion is not color-managed, so the colors are only approximate (usually too
+    /// saturated). If you need accurate colors, decompress into CMYK and convert the pixels using
+    /// the ICC profile of the image (see [`read_icc_profile()`][crate::read_icc_profile]) and a
+    /// color management system. By default, this option is disabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // create a CMYK image
+    /// let cmyk = turbojpeg::Image::mandelbrot(64, 48, turbojpeg::PixelFormat::CMYK);
+    /// let jpeg_data = turbojpeg::compress(cmyk.as_deref(), 90, turbojpeg::Subsamp::None)?;
+    ///
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.set_naive_cmyk_conversion(true);
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: vec![0; 64 * 48 * 3],
+    ///     width: 64,
+    ///     pitch: 0,
+    ///     height: 48,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    /// decompressor.decompress(&jpeg_data, image.as_deref_mut())?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_naive_cmyk_conversion(&mut self, naive_cmyk: bool) {
+        self.naive_cmyk = naive_cmyk;
+    }
+
+    /// Enable/disable fast upsampling of the chrominance components.
+    ///
+    /// By default, TurboJPEG upsamples the subsampled chrominance components (see [`Subsamp`])
+    /// with a "fancy" triangle filter, which produces smooth color edges. When this option is
+    /// enabled, the components are upsampled by replicating the samples ("nearest neighbor"), which
+    /// makes the decompression of subsampled images noticeably faster, but the color edges become
+    /// blocky. This is a good trade-off for previews or video frames. It has no effect on images
+    /// without chrominance subsampling.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image::mandelbrot(300, 200, turbojpeg::PixelFormat::RGB);
+    /// let jpeg_data = turbojpeg::compress(image.as_deref(), 90, turbojpeg::Subsamp::Sub2x2)?;
+    ///
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.set_fast_upsample(true)?;
+    /// let mut fast = turbojpeg::Image::mandelbrot(300, 200, turbojpeg::PixelFormat::RGB);
+    /// decompressor.decompress(&jpeg_data, fast.as_deref_mut())?;
+    ///
+    /// // the pixels are slightly different from the default upsampling
+    /// let smooth = turbojpeg::decompress(&jpeg_data, turbojpeg::PixelFormat::RGB)?;
+    /// assert_ne!(fast.pixels, smooth.pixels);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[doc(alias = "TJPARAM_FASTUPSAMPLE")]
+    pub fn set_fast_upsample(&mut self, fast_upsample: bool) -> Result<()> {
+        self.handle.set(raw::TJPARAM_TJPARAM_FASTUPSAMPLE, fast_upsample as libc::c_int)
+    }
+
+    /// Enable/disable the fast inverse DCT.
+    ///
+    /// By default, TurboJPEG uses the accurate integer inverse DCT. When this option is enabled,
+    /// it uses the fast integer inverse DCT, which is slightly faster (depending on the SIMD
+    /// extensions of the CPU) but less accurate, so it is useful when latency matters more than a
+    /// small loss of quality. The difference is usually invisible for images compressed with
+    /// quality 90 or less; for higher qualities, the fast DCT can add noticeable errors.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    ///
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.set_fast_dct(true)?;
+    /// let header = decompressor.read_header(&jpeg_data)?;
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: vec![0; 3 * header.width * header.height],
+    ///     width: header.width,
+    ///     pitch: 0,
+    ///     height: header.height,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    /// decompressor.decompress(&jpeg_data, image.as_deref_mut())?;
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[doc(alias = "TJPARAM_FASTDCT")]
+    pub fn set_fast_dct(&mut self, fast_dct: bool) -> Result<()> {
+        self.handle.set(raw::TJPARAM_TJPARAM_FASTDCT, fast_dct as libc::c_int)
+    }
+
+    /// Enable/disable stopping the decompression at the first warning.
+    ///
+    /// TurboJPEG reports problems in the compressed data that it can recover from (such as
+    /// truncated data or invalid Huffman codes) as warnings. [`decompress()`][Self::decompress]
+    /// always returns such warnings as errors, but by default TurboJPEG first finishes the
+    /// decompression, filling the damaged part of the output with garbage. When this option is
+    /// enabled, TurboJPEG stops at the first warning, so broken images are rejected without
+    /// spending the time to decompress the rest of the image. This is useful for services that
+    /// must reject corrupted uploads. [`decompress_salvage()`][Self::decompress_salvage] ignores
+    /// this option. By default, this option is disabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let truncated = &jpeg_data[..jpeg_data.len() / 2];
+    ///
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.set_stop_on_warning(true)?;
+    /// let header = decompressor.read_header(truncated)?;
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: vec![0; 3 * header.width * header.height],
+    ///     width: header.width,
+    ///     pitch: 0,
+    ///     height: header.height,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    /// assert!(decompressor.decompress(truncated, image.as_deref_mut()).is_err());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[doc(alias = "TJPARAM_STOPONWARNING")]
+    pub fn set_stop_on_warning(&mut self, stop_on_warning: bool) -> Result<()> {
+        self.handle.set(raw::TJPARAM_TJPARAM_STOPONWARNING, stop_on_warning as libc::c_int)
+    }
+
+    /// Set the identifier of the image that is attached to logged warnings.
+    ///
+    /// With the `log` feature, the warnings that TurboJPEG reports about corrupted data are logged
+    /// using the [`log`] crate (with target `turbojpeg`), together with the operation and this
+    /// identifier (such as a file name or URL). Use `None` to remove the identifier.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "log")] {
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let truncated = &jpeg_data[..jpeg_data.len() / 2];
+    ///
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.set_image_id(Some("uploads/parrots.jpg"));
+    /// let header = decompressor.read_header(truncated)?;
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: vec![0; 3 * header.width * header.height],
+    ///     width: header.width,
+    ///     pitch: 3 * header.width,
+    ///     height: header.height,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    ///
+    /// // the warning about the truncated data is logged as
+    /// // "decompression of uploads/parrots.jpg: TurboJPEG error: ..."
+    /// decompressor.decompress_salvage(truncated, image.as_deref_mut(), &[0, 0, 0])?;
+    /// # }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "log")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "log")))]
+    pub fn set_image_id(&mut self, image_id: Option<&str>) {
+        self.handle.set_image_id(image_id);
+    }
+
+    /// Set the limits on the resources used by this decompressor.
+    ///
+    /// The limits are initialized from [`Limits::global()`] when the decompressor is created. See
+    /// [`Limits`] for details.
+    #[doc(alias = "TJPARAM_SCANLIMIT")]
+    pub fn set_limits(&mut self, limits: Limits) -> Result<()> {
+        self.handle.set_limits(limits)
+    }
+
+    /// Get the limits on the resources used by this decompressor.
+    pub fn limits(&self) -> Limits {
+        *self.handle.limits()
+    }
+
+    /// Set the limit on the estimated memory in bytes that TurboJPEG needs to process an image.
+    ///
+    /// This changes only [`Limits::max_memory`] in the [limits][Self::limits] of this decompressor
+    /// (`None` means no limit).
+    #[doc(alias = "TJPARAM_MAXMEMORY")]
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) -> Result<()> {
+        self.set_limits(Limits { max_memory, ..self.limits() })
+    }
+
+    /// Set the limit on the number of pixels (width × height) of an image.
+    ///
+    /// This changes only [`Limits::max_pixels`] in the [limits][Self::limits] of this decompressor
+    /// (`None` means no limit).
+    #[doc(alias = "TJPARAM_MAXPIXELS")]
+    pub fn set_max_pixels(&mut self, max_pixels: Option<usize>) -> Result<()> {
+        self.set_limits(Limits { max_pixels, ..self.limits() })
     }
 
     /// Read the JPEG header without decompressing the image.
@@ -62,7 +588,7 @@ impl Decompressor {
             raw::tj3DecompressHeader(self.handle.as_ptr(), jpeg_data.as_ptr(), jpeg_data_len)
         };
         if res != 0 {
-            return Err(self.handle.get_error())
+            return Err(self.handle.get_error_in("header reading"))
         }
 
         let width = self.handle.get(raw::TJPARAM_TJPARAM_JPEGWIDTH)
@@ -71,7 +597,80 @@ impl Decompressor {
             .try_into().map_err(|_| Error::IntegerOverflow("height"))?;
         let subsamp = Subsamp::from_int(self.handle.get(raw::TJPARAM_TJPARAM_SUBSAMP))?;
         let colorspace = Colorspace::from_int(self.handle.get(raw::TJPARAM_TJPARAM_COLORSPACE))?;
-        Ok(DecompressHeader { width, height, subsamp, colorspace })
+        let segments = markers::read_header_segments(jpeg_data).unwrap_or_default();
+        let inverted_cmyk = matches!(colorspace, Colorspace::CMYK | Colorspace::YCCK)
+            && segments.iter().any(markers::is_adobe);
+        let restart_interval = segments.iter()
+            .find(|segment| segment.marker == markers::DRI)
+            .and_then(|segment| segment.data.get(0..2))
+            .map_or(0, |interval| u16::from_be_bytes([interval[0], interval[1]]) as usize);
+        let scans = markers::read_all_segments(jpeg_data).unwrap_or_default().iter()
+            .filter(|segment| segment.marker == markers::SOS)
+            .count();
+        Ok(DecompressHeader { width, height, subsamp, colorspace, inverted_cmyk, restart_interval, scans })
+    }
+
+    /// Read the JPEG header from data that may be incomplete.
+    ///
+    /// Unlike [`read_header()`](Decompressor::read_header), this method can tell apart data that
+    /// is not a JPEG image from data that only ends before the complete header. This is useful when
+    /// you receive the image in pieces (for example, from the network): you can reject invalid
+    /// images or size your buffers after receiving just the first few kilobytes.
+    ///
+    /// Returns an error if the header is complete, but TurboJPEG fails to parse it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::HeaderStatus;
+    ///
+    /// // read JPEG data from file
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    ///
+    /// // initialize a decompressor
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    ///
+    /// // the first 100 bytes are not enough
+    /// let status = decompressor.read_header_partial(&jpeg_data[..100])?;
+    /// assert!(matches!(status, HeaderStatus::NeedMoreData(_)));
+    ///
+    /// // but we can quickly reject data that is not a JPEG
+    /// let status = decompressor.read_header_partial(b"GIF89a")?;
+    /// assert_eq!(status, HeaderStatus::NotJpeg);
+    ///
+    /// // with enough data, we get the header
+    /// match decompressor.read_header_partial(&jpeg_data[..2000])? {
+    ///     HeaderStatus::Complete(header) => assert_eq!((header.width, header.height), (384, 256)),
+    ///     status => panic!("unexpected {:?}", status),
+    /// }
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn read_header_partial(&mut self, jpeg_data: &[u8]) -> Result<HeaderStatus> {
+        // TurboJPEG reads the header up to the first start-of-scan (SOS) marker segment, so we walk
+        // the marker segments to find out whether the data contains this segment
+        let mut reader = SegmentReader::new(jpeg_data);
+        let mut is_first = true;
+        loop {
+            match reader.next_segment() {
+                ReadSegment::Segment(segment) => {
+                    if is_first != (segment.marker == markers::SOI) {
+                        return Ok(HeaderStatus::NotJpeg)
+                    }
+                    is_first = false;
+                    match segment.marker {
+                        markers::SOS => break,
+                        markers::EOI => return Ok(HeaderStatus::NotJpeg),
+                        _ => {},
+                    }
+                },
+                ReadSegment::NeedMore(len) => return Ok(HeaderStatus::NeedMoreData(len)),
+                ReadSegment::Invalid => return Ok(HeaderStatus::NotJpeg),
+            }
+        }
+
+        let header = self.read_header(jpeg_data)?;
+        Ok(HeaderStatus::Complete(header))
     }
 
     /// Decompress a JPEG image in `jpeg_data` into `output`.
@@ -110,11 +709,284 @@ impl Decompressor {
     /// ```
     #[doc(alias = "tj3Decompress8")]
     pub fn decompress(&mut self, jpeg_data: &[u8], output: Image<&mut [u8]>) -> Result<()> {
-        output.assert_valid(output.pixels.len());
+        self.decompress_impl(jpeg_data, ScalingFactor::ONE, None, output)
+    }
+
+    /// Decompress a truncated or corrupted JPEG image as far as possible.
+    ///
+    /// TurboJPEG can decompress images with truncated or corrupted data with a warning, which
+    /// [`decompress()`][Self::decompress] reports as an error. This method ignores such warnings,
+    /// determines how many rows of the image were decoded from valid data, fills the remaining rows
+    /// with the `fill` pixel and returns the number of valid rows. If the image is complete, the
+    /// returned number is equal to the height of the image. Fatal errors (for example, when the
+    /// header is corrupted) are still returned as errors.
+    ///
+    /// The `fill` pixel must be given in the pixel format of `output`, so it must have
+    /// `output.format.size()` bytes.
+    ///
+    /// To find the valid rows, the image is decompressed a second time with garbage appended after
+    /// the end of the data; the first row that differs between the two decompressions was not
+    /// decoded from valid data. This works best for sequential (baseline) images. For progressive
+    /// images, the rows after the truncation point may contain a lower-quality version of the
+    /// image from the earlier scans, but they are filled as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fill` does not have `output.format.size()` bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let truncated = &jpeg_data[..jpeg_data.len() / 2];
+    ///
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// let header = decompressor.read_header(truncated)?;
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: vec![0; 3 * header.width * header.height],
+    ///     width: header.width,
+    ///     pitch: 3 * header.width,
+    ///     height: header.height,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    ///
+    /// // fill the missing rows with magenta
+    /// let rows = decompressor.decompress_salvage(truncated, image.as_deref_mut(), &[255, 0, 255])?;
+    /// assert!(rows < header.height);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn decompress_salvage(&mut self, jpeg_data: &[u8], output: Image<&mut [u8]>, fill: &[u8]) -> Result<usize> {
+        assert_eq!(fill.len(), output.format.size(), "the fill pixel must have {} bytes", output.format.size());
+        // the salvaging relies on decompressing the image despite warnings
+        let stop_on_warning = self.handle.get(raw::TJPARAM_TJPARAM_STOPONWARNING);
+        self.handle.set(raw::TJPARAM_TJPARAM_STOPONWARNING, 0)?;
+        let result = self.decompress_salvage_impl(jpeg_data, output, fill);
+        self.handle.set(raw::TJPARAM_TJPARAM_STOPONWARNING, stop_on_warning)?;
+        result
+    }
+
+    fn decompress_salvage_impl(&mut self, jpeg_data: &[u8], output: Image<&mut [u8]>, fill: &[u8]) -> Result<usize> {
         let Image { pixels, width, pitch, height, format } = output;
-        let width: libc::c_int = width.try_into().map_err(|_| Error::IntegerOverflow("width"))?;
+        let header = self.read_header(jpeg_data)?;
+
+        let output = Image { pixels: &mut *pixels, width, pitch, height, format };
+        let row_pitch = output.row_pitch();
+        match self.decompress(jpeg_data, output) {
+            Ok(()) => return Ok(header.height),
+            Err(Error::TurboJpegError(_)) if self.handle.is_warning() => {},
+            Err(err) => return Err(err),
+        }
+
+        // decompress the image again with garbage after the end of the data
+        let mut probe = Image {
+            pixels: vec![0; header.width * format.size() * header.height],
+            width: header.width,
+            pitch: 0,
+            height: header.height,
+            format,
+        };
+        match self.decompress(&salvage_probe_data(jpeg_data), probe.as_deref_mut()) {
+            Ok(()) => {},
+            Err(Error::TurboJpegError(_)) if self.handle.is_warning() => {},
+            Err(err) => return Err(err),
+        }
+
+        let row_len = probe.packed_pitch();
+        let valid_rows = (0..header.height)
+            .find(|&y| pixels[y * row_pitch..][..row_len] != probe.pixels[y * row_len..][..row_len])
+            .unwrap_or(header.height);
+        for y in valid_rows..header.height {
+            for pixel in pixels[y * row_pitch..][..row_len].chunks_exact_mut(fill.len()) {
+                pixel.copy_from_slice(fill);
+            }
+        }
+        Ok(valid_rows)
+    }
+
+    /// Decompress a region of a scaled JPEG image in `jpeg_data` into `output`.
+    ///
+    /// The image is scaled by `scaling_factor` and then cropped to `crop` (if given), so the crop
+    /// region is specified in the coordinates of the scaled image. The size of `output` must be at
+    /// least the size of the cropped region. Only the region is decompressed, so this is much
+    /// faster than decompressing the whole image and cropping it afterwards (for example, to
+    /// serve map tiles from a large image). The scaling factor and the cropping region apply only
+    /// to this call, they are not remembered by the decompressor.
+    ///
+    /// Returns [`Error::BadScalingFactor`] if the scaling factor is not supported,
+    /// [`Error::CropNotAligned`] if the left boundary of the region is not divisible by the scaled
+    /// MCU width, [`Error::CropOutOfBounds`] if the region does not fit into the scaled image and
+    /// [`Error::UnsupportedSubsampForCrop`] if the image cannot be cropped because it uses
+    /// [unknown subsampling][Subsamp::Unknown].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::{DecompressCrop, ScalingFactor};
+    ///
+    /// // read JPEG data from file
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    ///
+    /// // scale the 384x256 image to 192x128 and decompress its right half
+    /// let scaling_factor = ScalingFactor::new(1, 2);
+    /// let crop = DecompressCrop::full_height(96, 96);
+    ///
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: vec![0; 3 * 96 * 128],
+    ///     width: 96,
+    ///     pitch: 3 * 96,
+    ///     height: 128,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.decompress_region(&jpeg_data, scaling_factor, Some(crop), image.as_deref_mut())?;
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[doc(alias = "tj3SetScalingFactor")]
+    #[doc(alias = "tj3SetCroppingRegion")]
+    #[doc(alias = "set_crop_region")]
+    pub fn decompress_region(
+        &mut self,
+        jpeg_data: &[u8],
+        scaling_factor: ScalingFactor,
+        crop: Option<DecompressCrop>,
+        output: Image<&mut [u8]>,
+    ) -> Result<()> {
+        self.decompress_impl(jpeg_data, scaling_factor, crop, output)
+    }
+
+    /// Decompress a JPEG image in horizontal strips of rows, passing every strip to `callback`.
+    ///
+    /// The image is decompressed into a buffer that holds only a strip of rows, so large images
+    /// can be processed without allocating memory for the whole decompressed image (for example,
+    /// to write the rows directly into a file). The callback receives the index of the first row
+    /// of the strip and the strip as an image with pixels in the given `format`. The strips are
+    /// passed in order from top to bottom and contain at most `max_strip_height` rows. If the
+    /// callback returns an error, the decompression stops and the error is returned (so you can use
+    /// [`CancelToken::check()`][crate::CancelToken::check] in the callback to support cancellation).
+    ///
+    /// If the image is sequential and contains restart markers at the start of MCU rows, the strips
+    /// are decompressed independently, so every row is decompressed only once; in this case, the
+    /// buffer holds one restart interval, which may have more than `max_strip_height` rows. When
+    /// the chrominance is subsampled vertically, the pixels at the boundaries between the restart
+    /// intervals may then slightly differ from the pixels produced by
+    /// [`decompress()`][Self::decompress], because TurboJPEG cannot use the neighboring interval
+    /// when upsampling the chrominance (the same applies to
+    /// [`decompress_parallel()`][crate::decompress_parallel]).
+    /// Otherwise, every strip is decompressed as a [cropped region][Self::decompress_region] of
+    /// the image, which needs to decode the compressed data again for every strip, so it is slower
+    /// for small strips. Images with [unknown subsampling][Subsamp::Unknown] cannot be cropped, so
+    /// they are decompressed into a single buffer. Note that TurboJPEG must hold the DCT
+    /// coefficients of the whole image in memory to decompress a progressive image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_strip_height` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    ///
+    /// let mut rows = 0;
+    /// decompressor.decompress_strips(&jpeg_data, turbojpeg::PixelFormat::RGB, 64, |y, strip| {
+    ///     assert_eq!(y, rows);
+    ///     assert!(strip.height <= 64);
+    ///     rows += strip.height;
+    ///     Ok(())
+    /// })?;
+    /// assert_eq!(rows, 256);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[doc(alias = "tj3SetCroppingRegion")]
+    pub fn decompress_strips<F>(
+        &mut self,
+        jpeg_data: &[u8],
+        format: PixelFormat,
+        max_strip_height: usize,
+        callback: F,
+    ) -> Result<()>
+        where F: FnMut(usize, Image<&[u8]>) -> Result<()>
+    {
+        assert!(max_strip_height > 0, "strip height must be positive");
+        self.for_each_strip(jpeg_data, format, max_strip_height, true, callback)
+    }
+
+    /// Implementation of [`decompress_strips()`][Self::decompress_strips].
+    ///
+    /// If `crop_strips` is false, images that cannot be split at restart markers are decompressed
+    /// at once (instead of decompressing every strip as a cropped region), which is faster, but
+    /// needs a buffer for the whole image.
+    /// As in `decompress_strips()`, the pixels at the boundaries of restart intervals may differ
+    /// from [`decompress()`][Self::decompress].
+    pub(crate) fn for_each_strip<F>(
+        &mut self,
+        jpeg_data: &[u8],
+        format: PixelFormat,
+        max_strip_height: usize,
+        crop_strips: bool,
+        mut callback: F,
+    ) -> Result<()>
+        where F: FnMut(usize, Image<&[u8]>) -> Result<()>
+    {
+        let header = self.read_header(jpeg_data)?;
+        let (width, height) = (header.width, header.height);
+        let pitch = width * format.size();
+        let mut strip = Vec::new();
+
+        let split = parallel::split_bands(jpeg_data, usize::MAX);
+        let bands: Vec<(Range<usize>, &[u8])> = match split {
+            Some(ref split) => split.bands.iter().map(|band| (band.rows.clone(), &band.jpeg_data[..])).collect(),
+            // images with unknown subsampling cannot be cropped, so we decompress them at once
+            None if header.subsamp == Subsamp::Unknown || !crop_strips => vec![(0..height, jpeg_data)],
+            None => Vec::new(),
+        };
+        if !bands.is_empty() {
+            for (rows, band_data) in bands {
+                strip.resize(rows.len() * pitch, 0);
+                let image = Image { pixels: &mut strip[..], width, pitch, height: rows.len(), format };
+                self.decompress(band_data, image)?;
+                for (i, pixels) in strip.chunks(max_strip_height * pitch).enumerate() {
+                    let image = Image { pixels, width, pitch, height: pixels.len() / pitch, format };
+                    callback(rows.start + i * max_strip_height, image)?;
+                }
+            }
+            return Ok(())
+        }
+
+        let mut y = 0;
+        while y < height {
+            let strip_height = max_strip_height.min(height - y);
+            strip.resize(strip_height * pitch, 0);
+            let crop = DecompressCrop { x: 0, y, width: None, height: Some(strip_height) };
+            let image = Image { pixels: &mut strip[..], width, pitch, height: strip_height, format };
+            self.decompress_region(jpeg_data, ScalingFactor::ONE, Some(crop), image)?;
+            callback(y, Image { pixels: &strip[..], width, pitch, height: strip_height, format })?;
+            y += strip_height;
+        }
+        Ok(())
+    }
+
+    fn decompress_impl(
+        &mut self,
+        jpeg_data: &[u8],
+        scaling_factor: ScalingFactor,
+        crop: Option<DecompressCrop>,
+        output: Image<&mut [u8]>,
+    ) -> Result<()> {
+        output.assert_valid(output.pixels.len());
+        if self.naive_cmyk && output.format != PixelFormat::CMYK
+            && read_frame(jpeg_data).is_some_and(|frame| frame.sampling.len() == 4)
+        {
+            return self.decompress_cmyk_naive(jpeg_data, scaling_factor, crop, output)
+        }
+
+        let pitch = output.row_pitch();
+        let Image { pixels, width, pitch: _, height, format } = output;
         let pitch: libc::c_int = pitch.try_into().map_err(|_| Error::IntegerOverflow("pitch"))?;
-        let height: libc::c_int = height.try_into().map_err(|_| Error::IntegerOverflow("height"))?;
 
         let res = unsafe {
             raw::tj3DecompressHeader(
@@ -124,35 +996,211 @@ impl Decompressor {
             )
         };
         if res != 0 {
-            return Err(self.handle.get_error())
+            return Err(self.handle.get_error_in("decompression"))
         }
+        let limits = *self.handle.limits();
+        limits.check_jpeg(jpeg_data, false)?;
 
-        let jpeg_width = self.handle.get(raw::TJPARAM_TJPARAM_JPEGWIDTH);
-        let jpeg_height = self.handle.get(raw::TJPARAM_TJPARAM_JPEGHEIGHT);
-        if width < jpeg_width || height < jpeg_height {
-            return Err(Error::OutputTooSmall(jpeg_width as i32, jpeg_height as i32))
+        if let Some(output_colorspace) = self.output_colorspace {
+            let colorspace = Colorspace::from_int(self.handle.get(raw::TJPARAM_TJPARAM_COLORSPACE))?;
+            check_output_colorspace(colorspace, output_colorspace, format)?;
         }
 
-        let res = unsafe {
-            raw::tj3Decompress8(
-                self.handle.as_ptr(),
-                jpeg_data.as_ptr(), jpeg_data.len() as raw::size_t,
-                pixels.as_mut_ptr(), pitch, format as i32,
-            )
+        let jpeg_width = self.handle.get(raw::TJPARAM_TJPARAM_JPEGWIDTH) as usize;
+        let jpeg_height = self.handle.get(raw::TJPARAM_TJPARAM_JPEGHEIGHT) as usize;
+        let is_region = scaling_factor != ScalingFactor::ONE || crop.is_some();
+        let region = if is_region {
+            let subsamp = Subsamp::from_int(self.handle.get(raw::TJPARAM_TJPARAM_SUBSAMP))?;
+            resolve_region(jpeg_width, jpeg_height, subsamp, scaling_factor, crop)?
+        } else {
+            (0, 0, jpeg_width, jpeg_height)
+        };
+
+        let (_, _, region_width, region_height) = region;
+        if width < region_width || height < region_height {
+            return Err(Error::OutputTooSmall(region_width as i32, region_height as i32))
+        }
+        limits.check_output_bytes(region_width.saturating_mul(region_height).saturating_mul(format.size()))?;
+
+        let mut decompress = || -> Result<()> {
+            if is_region {
+                self.set_scaling_factor(scaling_factor)?;
+                if crop.is_some() {
+                    self.set_cropping_region(region)?;
+                }
+            }
+            let res = unsafe {
+                raw::tj3Decompress8(
+                    self.handle.as_ptr(),
+                    jpeg_data.as_ptr(), jpeg_data.len() as raw::size_t,
+                    pixels.as_mut_ptr(), pitch, format as i32,
+                )
+            };
+            if res != 0 {
+                return Err(self.handle.get_error_in("decompression"))
+            }
+            Ok(())
         };
+
+        let timer = Timer::start();
+        let mut res = decompress();
+        if is_region {
+            // reset the scaling and cropping even if the decompression failed, so that they don't
+            // affect other operations
+            let reset_crop = self.set_cropping_region((0, 0, 0, 0));
+            let reset_scaling = self.set_scaling_factor(ScalingFactor::ONE);
+            res = res.and(reset_crop).and(reset_scaling);
+        }
+        res?;
+        timer.decompressed(jpeg_data.len(), region_width * region_height * format.size());
+
+        Ok(())
+    }
+
+    /// Decompresses a CMYK (or YCCK) image into CMYK and converts it to the output pixel format (see
+    /// [`set_naive_cmyk_conversion()`][Self::set_naive_cmyk_conversion]).
+    fn decompress_cmyk_naive(
+        &mut self,
+        jpeg_data: &[u8],
+        scaling_factor: ScalingFactor,
+        crop: Option<DecompressCrop>,
+        output: Image<&mut [u8]>,
+    ) -> Result<()> {
+        // the temporary image has the size of the decompressed region, which may be smaller than
+        // `output`
+        let header = self.read_header(jpeg_data)?;
+        let (_, _, width, height) = resolve_region(header.width, header.height, header.subsamp, scaling_factor, crop)?;
+        if output.width < width || output.height < height {
+            return Err(Error::OutputTooSmall(width as i32, height as i32))
+        }
+        let len = width.checked_mul(height).and_then(|len| len.checked_mul(4))
+            .ok_or(Error::IntegerOverflow("CMYK image size"))?;
+        self.handle.limits().check_output_bytes(len)?;
+        let mut cmyk = Image { pixels: vec![0; len], width, pitch: 0, height, format: PixelFormat::CMYK };
+        // the output colorspace applies to `output`, not to the temporary CMYK image
+        let output_colorspace = self.output_colorspace.take();
+        let res = self.decompress_impl(jpeg_data, scaling_factor, crop, cmyk.as_deref_mut());
+        self.output_colorspace = output_colorspace;
+        res?;
+
+        let inverted = markers::has_adobe_segment(jpeg_data);
+        let format = output.format;
+        let (pitch, size) = (output.row_pitch(), format.size());
+        for (y, cmyk_row) in cmyk.pixels.chunks_exact(4 * width).enumerate() {
+            let row = &mut output.pixels[y * pitch..][..width * size];
+            for (pixel, cmyk) in row.chunks_exact_mut(size).zip(cmyk_row.chunks_exact(4)) {
+                // the amount of light that is not absorbed by the inks
+                let [cyan, magenta, yellow, key] = [cmyk[0], cmyk[1], cmyk[2], cmyk[3]]
+                    .map(|value| if inverted { value as u32 } else { 255 - value as u32 });
+                let [r, g, b] = [cyan, magenta, yellow].map(|value| ((value * key + 127) / 255) as u8);
+                match (format.red_offset(), format.green_offset(), format.blue_offset()) {
+                    (Some(r_offset), Some(g_offset), Some(b_offset)) => {
+                        pixel[r_offset] = r;
+                        pixel[g_offset] = g;
+                        pixel[b_offset] = b;
+                    },
+                    _ => pixel[0] = ((77 * r as u32 + 150 * g as u32 + 29 * b as u32 + 128) >> 8) as u8,
+                }
+                if let Some(alpha_offset) = format.alpha_offset() {
+                    pixel[alpha_offset] = 255;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_scaling_factor(&mut self, scaling_factor: ScalingFactor) -> Result<()> {
+        let scaling_factor = raw::tjscalingfactor {
+            num: scaling_factor.num as libc::c_int,
+            denom: scaling_factor.denom as libc::c_int,
+        };
+        let res = unsafe { raw::tj3SetScalingFactor(self.handle.as_ptr(), scaling_factor) };
         if res != 0 {
-            return Err(self.handle.get_error())
+            return Err(self.handle.get_error_in("scaling"))
         }
+        Ok(())
+    }
 
+    fn set_cropping_region(&mut self, region: (usize, usize, usize, usize)) -> Result<()> {
+        let (x, y, w, h) = region;
+        let region = raw::tjregion {
+            x: x.try_into().map_err(|_| Error::IntegerOverflow("crop.x"))?,
+            y: y.try_into().map_err(|_| Error::IntegerOverflow("crop.y"))?,
+            w: w.try_into().map_err(|_| Error::IntegerOverflow("crop.width"))?,
+            h: h.try_into().map_err(|_| Error::IntegerOverflow("crop.height"))?,
+        };
+        let res = unsafe { raw::tj3SetCroppingRegion(self.handle.as_ptr(), region) };
+        if res != 0 {
+            return Err(self.handle.get_error_in("cropping"))
+        }
         Ok(())
     }
 
+    /// Decompress a JPEG image that is split into multiple chunks.
+    ///
+    /// This is the same as [`decompress()`](Decompressor::decompress), but the JPEG data is given
+    /// as a sequence of slices, which is useful if the data is stored in a ring buffer or a rope.
+    /// TurboJPEG needs the data in contiguous memory, so if there is more than one non-empty
+    /// chunk, the chunks are copied into a buffer owned by the decompressor. This buffer is reused
+    /// in subsequent calls, so the copying does not allocate memory in the steady state.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::IoSlice;
+    ///
+    /// // read JPEG data from file and split it into two parts
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let (part_1, part_2) = jpeg_data.split_at(1000);
+    ///
+    /// // decompress the parts into the image
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: vec![0; 3 * 384 * 256],
+    ///     width: 384,
+    ///     pitch: 3 * 384,
+    ///     height: 256,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    /// let chunks = [IoSlice::new(part_1), IoSlice::new(part_2)];
+    /// decompressor.decompress_vectored(&chunks, image.as_deref_mut())?;
+    /// assert_eq!(&image.pixels[0..3], &[122, 118, 89]);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn decompress_vectored(
+        &mut self,
+        jpeg_chunks: &[IoSlice],
+        output: Image<&mut [u8]>,
+    ) -> Result<()> {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        let res = self.decompress(coalesce(jpeg_chunks, &mut scratch), output);
+        self.scratch = scratch;
+        res
+    }
+
+    /// Read the JPEG header from data that is split into multiple chunks.
+    ///
+    /// This is the same as [`read_header()`](Decompressor::read_header), but the JPEG data is
+    /// given as a sequence of slices (see
+    /// [`decompress_vectored()`](Decompressor::decompress_vectored)).
+    pub fn read_header_vectored(&mut self, jpeg_chunks: &[IoSlice]) -> Result<DecompressHeader> {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        let res = self.read_header(coalesce(jpeg_chunks, &mut scratch));
+        self.scratch = scratch;
+        res
+    }
+
     /// Decompress a JPEG image in `jpeg_data` into `output` as YUV without changing color space.
     ///
     /// The decompressed image is stored in the pixel data of the given `output` image, which must
     /// be fully initialized by the caller. Use [`read_header()`](Decompressor::read_header) to
     /// determine the image size before calling this method.
     ///
+    /// Returns [`Error::UnsupportedSubsampForYuv`] if the image uses [unknown
+    /// subsampling][Subsamp::Unknown]; such images must be decompressed into packed pixels using
+    /// [`decompress()`](Decompressor::decompress).
+    ///
     /// # Example
     ///
     /// ```
@@ -184,6 +1232,8 @@ impl Decompressor {
     ///
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
+    #[cfg(feature = "yuv")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
     #[doc(alias = "tj3DecompressToYUV8")]
     pub fn decompress_to_yuv(&mut self, jpeg_data: &[u8], output: YuvImage<&mut [u8]>) -> Result<()> {
         output.assert_valid(output.pixels.len());
@@ -198,15 +1248,25 @@ impl Decompressor {
             raw::tj3DecompressHeader(self.handle.as_ptr(), jpeg_data.as_ptr(), jpeg_data_len)
         };
         if res != 0 {
-            return Err(self.handle.get_error())
+            return Err(self.handle.get_error_in("YUV decompression"))
         }
 
+        if self.handle.get(raw::TJPARAM_TJPARAM_SUBSAMP) == raw::TJSAMP_TJSAMP_UNKNOWN {
+            return Err(Error::UnsupportedSubsampForYuv)
+        }
+        let limits = *self.handle.limits();
+        limits.check_jpeg(jpeg_data, false)?;
+
         let jpeg_width = self.handle.get(raw::TJPARAM_TJPARAM_JPEGWIDTH);
         let jpeg_height = self.handle.get(raw::TJPARAM_TJPARAM_JPEGHEIGHT);
         if width < jpeg_width || height < jpeg_height {
-            return Err(Error::OutputTooSmall(jpeg_width as i32, jpeg_height as i32))
+            return Err(Error::OutputTooSmall(jpeg_width, jpeg_height))
         }
+        let subsamp = Subsamp::from_int(self.handle.get(raw::TJPARAM_TJPARAM_SUBSAMP))?;
+        let pixels_len = yuv_pixels_len(jpeg_width as usize, 1, jpeg_height as usize, subsamp)?;
+        limits.check_output_bytes(pixels_len)?;
 
+        let timer = Timer::start();
         let res = unsafe {
             raw::tj3DecompressToYUV8(
                 self.handle.as_ptr(),
@@ -215,13 +1275,35 @@ impl Decompressor {
             )
         };
         if res != 0 {
-            return Err(self.handle.get_error())
+            return Err(self.handle.get_error_in("YUV decompression"))
         }
+        timer.decompressed(jpeg_data.len(), pixels_len);
 
         Ok(())
     }
 }
 
+/// Checks that an image in `colorspace` can be decompressed into `output_colorspace` using pixel
+/// `format`.
+fn check_output_colorspace(
+    colorspace: Colorspace,
+    output_colorspace: Colorspace,
+    format: PixelFormat,
+) -> Result<()> {
+    let is_color = matches!(colorspace, Colorspace::RGB | Colorspace::YCbCr | Colorspace::Gray);
+    let is_cmyk = matches!(colorspace, Colorspace::CMYK | Colorspace::YCCK);
+    let ok = match output_colorspace {
+        Colorspace::RGB => is_color && !matches!(format, PixelFormat::GRAY | PixelFormat::CMYK),
+        Colorspace::Gray => is_color && format == PixelFormat::GRAY,
+        Colorspace::CMYK => is_cmyk && format == PixelFormat::CMYK,
+        Colorspace::YCbCr | Colorspace::YCCK => false,
+    };
+    if !ok {
+        return Err(Error::UnsupportedOutputColorspace(colorspace, output_colorspace, format))
+    }
+    Ok(())
+}
+
 /// Decompress a JPEG image.
 ///
 /// Returns a newly allocated image with the given pixel `format`. If you have specific
@@ -258,11 +1340,136 @@ pub fn decompress(jpeg_data: &[u8], format: PixelFormat) -> Result<Image<Vec<u8>
     Ok(image)
 }
 
+/// Returns a copy of `jpeg_data` with garbage inserted after the entropy-coded data of the last
+/// scan, for [`Decompressor::decompress_salvage()`].
+fn salvage_probe_data(jpeg_data: &[u8]) -> Vec<u8> {
+    let report = crate::validate(jpeg_data);
+    let mut end = if report.is_valid() { report.end - 2 } else { jpeg_data.len() };
+    // remove a partial marker at the end of the data
+    while end > 0 && jpeg_data[end - 1] == 0xff {
+        end -= 1;
+    }
+
+    let mut data = Vec::with_capacity(end + 258);
+    data.extend_from_slice(&jpeg_data[..end]);
+    data.extend((0..256).map(|i| ((i * 37 + 11) % 255) as u8));
+    data.extend_from_slice(&[0xff, markers::EOI]);
+    data
+}
+
+/// Returns the JPEG data in `chunks` as a contiguous slice.
+///
+/// If there is at most one non-empty chunk, we return it directly, otherwise we copy the chunks
+/// into `scratch`.
+fn coalesce<'a>(chunks: &'a [IoSlice], scratch: &'a mut Vec<u8>) -> &'a [u8] {
+    let mut non_empty = chunks.iter().filter(|chunk| !chunk.is_empty());
+    match (non_empty.next(), non_empty.next()) {
+        (None, _) => &[],
+        (Some(chunk), None) => chunk,
+        _ => {
+            scratch.clear();
+            for chunk in chunks.iter() {
+                scratch.extend_from_slice(chunk);
+            }
+            scratch
+        },
+    }
+}
+
+/// Decompress a region of a scaled JPEG image.
+///
+/// Returns a newly allocated image with the given pixel `format`, which contains the region `crop`
+/// (or the whole image, if `crop` is `None`) of the JPEG image scaled by `scaling_factor`. See
+/// [`Decompressor::decompress_region()`] for details.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{DecompressCrop, PixelFormat, ScalingFactor};
+///
+/// // read JPEG data from file
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+///
+/// // decompress a 64x64 region from the image scaled to 1/4
+/// let crop = DecompressCrop { x: 16, y: 0, width: Some(64), height: Some(64) };
+/// let image = turbojpeg::decompress_region(
+///     &jpeg_data, PixelFormat::RGB, ScalingFactor::new(1, 4), Some(crop))?;
+/// assert_eq!((image.width, image.height), (64, 64));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn decompress_region(
+    jpeg_data: &[u8],
+    format: PixelFormat,
+    scaling_factor: ScalingFactor,
+    crop: Option<DecompressCrop>,
+) -> Result<Image<Vec<u8>>> {
+    let mut decompressor = Decompressor::new()?;
+    let header = decompressor.read_header(jpeg_data)?;
+    let (_, _, width, height) = resolve_region(
+        header.width, header.height, header.subsamp, scaling_factor, crop)?;
+
+    let pitch = width * format.size();
+    let mut image = Image {
+        pixels: vec![0; height * pitch],
+        width,
+        pitch,
+        height,
+        format,
+    };
+    decompressor.decompress_region(jpeg_data, scaling_factor, crop, image.as_deref_mut())?;
+
+    Ok(image)
+}
+
+/// Validates the scaling factor and cropping region and returns the region as (x, y, width,
+/// height) in the coordinates of the scaled image.
+fn resolve_region(
+    jpeg_width: usize,
+    jpeg_height: usize,
+    subsamp: Subsamp,
+    scaling_factor: ScalingFactor,
+    crop: Option<DecompressCrop>,
+) -> Result<(usize, usize, usize, usize)> {
+    if !ScalingFactor::supported().contains(&scaling_factor) {
+        return Err(Error::BadScalingFactor(scaling_factor.num, scaling_factor.denom))
+    }
+
+    let scaled_width = scaling_factor.scale(jpeg_width);
+    let scaled_height = scaling_factor.scale(jpeg_height);
+    let crop = match crop {
+        Some(crop) => crop,
+        None => return Ok((0, 0, scaled_width, scaled_height)),
+    };
+
+    if subsamp == Subsamp::Unknown {
+        return Err(Error::UnsupportedSubsampForCrop)
+    }
+    let scaled_mcu_width = scaling_factor.scale(subsamp.mcu_width());
+    if crop.x % scaled_mcu_width != 0 {
+        return Err(Error::CropNotAligned(crop.x, scaled_mcu_width))
+    }
+
+    let width = crop.width.unwrap_or(scaled_width.saturating_sub(crop.x));
+    let height = crop.height.unwrap_or(scaled_height.saturating_sub(crop.y));
+    if width == 0 || height == 0 ||
+        crop.x > scaled_width || width > scaled_width - crop.x ||
+        crop.y > scaled_height || height > scaled_height - crop.y
+    {
+        return Err(Error::CropOutOfBounds(scaled_width, scaled_height))
+    }
+
+    Ok((crop.x, crop.y, width, height))
+}
+
 /// Decompress a JPEG image to YUV.
 ///
 /// Returns a newly allocated YUV image with row alignment of 4. If you have specific requirements
 /// regarding memory layout or allocations, please see [`Decompressor`].
 ///
+/// Returns [`Error::UnsupportedSubsampForYuv`] if the image uses [unknown
+/// subsampling][Subsamp::Unknown].
+///
 /// # Example
 ///
 /// ```
@@ -276,9 +1483,14 @@ pub fn decompress(jpeg_data: &[u8], format: PixelFormat) -> Result<Image<Vec<u8>
 ///
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
+#[cfg(feature = "yuv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
 pub fn decompress_to_yuv(jpeg_data: &[u8]) -> Result<YuvImage<Vec<u8>>> {
     let mut decompressor = Decompressor::new()?;
     let header = decompressor.read_header(jpeg_data)?;
+    if header.subsamp == Subsamp::Unknown {
+        return Err(Error::UnsupportedSubsampForYuv)
+    }
     let align = 4;
     let yuv_pixels_len = yuv_pixels_len(
         header.width,
@@ -322,6 +1534,8 @@ pub fn decompress_to_yuv(jpeg_data: &[u8]) -> Result<YuvImage<Vec<u8>>> {
 ///
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
+#[cfg(feature = "yuv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
 #[doc(alias = "tj3YUVBufSize")]
 pub fn yuv_pixels_len(width: usize, align: usize, height: usize, subsamp: Subsamp) -> Result<usize> {
     let width = width.try_into().map_err(|_| Error::IntegerOverflow("width"))?;
@@ -350,3 +1564,24 @@ pub fn read_header(jpeg_data: &[u8]) -> Result<DecompressHeader> {
     let mut decompressor = Decompressor::new()?;
     decompressor.read_header(jpeg_data)
 }
+
+/// Read the JPEG header from data that may be incomplete.
+///
+/// See [`Decompressor::read_header_partial()`] for details.
+///
+/// # Example
+///
+/// ```
+/// // read JPEG data from file
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+///
+/// // read the JPEG header from the first 10 bytes
+/// let status = turbojpeg::read_header_partial(&jpeg_data[..10])?;
+/// assert!(matches!(status, turbojpeg::HeaderStatus::NeedMoreData(_)));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_header_partial(jpeg_data: &[u8]) -> Result<HeaderStatus> {
+    let mut decompressor = Decompressor::new()?;
+    decompressor.read_header_partial(jpeg_data)
+}
@@ -0,0 +1,244 @@
+use crate::Image;
+use crate::common::PixelFormat;
+
+/// Amplification of the differences in [`diff()`].
+const DEFAULT_AMPLIFICATION: u8 = 8;
+
+/// Computes an image of the differences between two images.
+///
+/// Every color component of the result is the absolute difference between the components of `a`
+/// and `b`, multiplied by 8 (and saturated to 255), so that even small differences are visible.
+/// Use [`diff_amplified()`] to select a different amplification, and [`diff_stats()`] to
+/// summarize the differences with a few numbers.
+///
+/// The result has the same size and pixel format as the inputs, with no padding between the rows.
+/// The alpha (or unused X) components are not compared and are set to 255 (opaque). The inputs may
+/// have different pitches.
+///
+/// This is useful to visualize the information that is lost in compression, for example to compare
+/// the effect of different qualities or chrominance subsampling options.
+///
+/// # Panics
+///
+/// Panics if the images have different sizes or pixel formats, or if any of the images is invalid
+/// (see [`Image::check()`]).
+///
+/// # Example
+///
+/// ```
+/// let image = turbojpeg::Image::mandelbrot(128, 128, turbojpeg::PixelFormat::RGB);
+/// let jpeg_data = turbojpeg::compress(image.as_deref(), 50, turbojpeg::Subsamp::Sub2x2)?;
+/// let decompressed = turbojpeg::decompress(&jpeg_data, turbojpeg::PixelFormat::RGB)?;
+///
+/// let diff = turbojpeg::diff(image.as_deref(), decompressed.as_deref());
+/// assert_eq!((diff.width, diff.height), (128, 128));
+///
+/// let stats = turbojpeg::diff_stats(image.as_deref(), decompressed.as_deref());
+/// println!("max difference {}, PSNR {:.2} dB", stats.max, stats.psnr);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn diff(a: Image<&[u8]>, b: Image<&[u8]>) -> Image<Vec<u8>> {
+    diff_amplified(a, b, DEFAULT_AMPLIFICATION)
+}
+
+/// Computes an image of the differences between two images with the given amplification.
+///
+/// This is the same as [`diff()`], but the differences are multiplied by `amplification` instead
+/// of 8. Use 1 to get the exact absolute differences.
+///
+/// # Panics
+///
+/// Panics if the images have different sizes or pixel formats, or if any of the images is invalid
+/// (see [`Image::check()`]).
+pub fn diff_amplified(a: Image<&[u8]>, b: Image<&[u8]>, amplification: u8) -> Image<Vec<u8>> {
+    assert_comparable(&a, &b);
+    let format = a.format;
+    let pitch = a.packed_pitch();
+    let alpha_offset = format.alpha_offset();
+    let mut pixels = vec![0; pitch * a.height];
+    for ((row_a, row_b), row) in rows(&a).zip(rows(&b)).zip(pixels.chunks_exact_mut(pitch.max(1))) {
+        for ((pixel_a, pixel_b), pixel) in row_a.chunks_exact(format.size())
+            .zip(row_b.chunks_exact(format.size()))
+            .zip(row.chunks_exact_mut(format.size()))
+        {
+            for (i, value) in pixel.iter_mut().enumerate() {
+                *value = if Some(i) == alpha_offset {
+                    255
+                } else {
+                    pixel_a[i].abs_diff(pixel_b[i]).saturating_mul(amplification)
+                };
+            }
+        }
+    }
+    Image { pixels, width: a.width, pitch, height: a.height, format }
+}
+
+/// Summary statistics of the differences between two images.
+///
+/// Returned by [`diff_stats()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct DiffStats {
+    /// Maximal absolute difference of a color component.
+    pub max: u8,
+    /// Mean absolute difference of the color components.
+    pub mean: f64,
+    /// Mean squared error of the color components.
+    pub mse: f64,
+    /// Peak signal-to-noise ratio in decibels (infinite if the images are identical).
+    pub psnr: f64,
+    /// Number of pixels that differ in at least one color component.
+    pub differing_pixels: usize,
+}
+
+/// Computes summary statistics of the differences between two images.
+///
+/// Only the color components are compared; the alpha (or unused X) components are ignored. See
+/// [`diff()`] for an example.
+///
+/// # Panics
+///
+/// Panics if the images have different sizes or pixel formats, or if any of the images is invalid
+/// (see [`Image::check()`]).
+pub fn diff_stats(a: Image<&[u8]>, b: Image<&[u8]>) -> DiffStats {
+    assert_comparable(&a, &b);
+    let format = a.format;
+    let alpha_offset = format.alpha_offset();
+    let mut max = 0;
+    let mut sum = 0u64;
+    let mut square_sum = 0u64;
+    let mut differing_pixels = 0;
+    for (row_a, row_b) in rows(&a).zip(rows(&b)) {
+        for (pixel_a, pixel_b) in row_a.chunks_exact(format.size()).zip(row_b.chunks_exact(format.size())) {
+            let mut differs = false;
+            for i in (0..format.size()).filter(|&i| Some(i) != alpha_offset) {
+                let diff = pixel_a[i].abs_diff(pixel_b[i]);
+                max = max.max(diff);
+                sum += diff as u64;
+                square_sum += diff as u64 * diff as u64;
+                differs |= diff != 0;
+            }
+            differing_pixels += differs as usize;
+        }
+    }
+
+    let channels = format.size() - alpha_offset.is_some() as usize;
+    let count = (a.width * a.height * channels).max(1) as f64;
+    let mse = square_sum as f64 / count;
+    let psnr = if mse > 0.0 { 10.0 * (255.0 * 255.0 / mse).log10() } else { f64::INFINITY };
+    DiffStats { max, mean: sum as f64 / count, mse, psnr, differing_pixels }
+}
+
+/// Computes the structural similarity (SSIM) of two images.
+///
+/// SSIM compares the local means, variances and covariance of the luminance of the images, which
+/// models the perceived quality better than [PSNR][DiffStats::psnr]. The result is 1 for identical
+/// images and decreases towards 0 (or even below 0) as the images become less similar; values
+/// above 0.99 are usually considered visually lossless. Only the luminance of the pixels is
+/// compared (CMYK pixels are converted to RGB first, and the alpha components are ignored).
+///
+/// The SSIM is averaged over 8×8 windows with a step of 4 pixels, so that the blocking artifacts
+/// on the boundaries of the 8×8 JPEG blocks are included. This is faster than the usual 11×11
+/// Gaussian window, so the values differ slightly from other implementations.
+///
+/// # Panics
+///
+/// Panics if the images have different sizes or pixel formats, or if any of the images is invalid
+/// (see [`Image::check()`]).
+///
+/// # Example
+///
+/// ```
+/// let image = turbojpeg::Image::mandelbrot(128, 128, turbojpeg::PixelFormat::RGB);
+/// assert_eq!(turbojpeg::ssim(image.as_deref(), image.as_deref()), 1.0);
+///
+/// let jpeg_data = turbojpeg::compress(image.as_deref(), 20, turbojpeg::Subsamp::Sub2x2)?;
+/// let decompressed = turbojpeg::decompress(&jpeg_data, turbojpeg::PixelFormat::RGB)?;
+/// assert!(turbojpeg::ssim(image.as_deref(), decompressed.as_deref()) < 1.0);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn ssim(a: Image<&[u8]>, b: Image<&[u8]>) -> f64 {
+    assert_comparable(&a, &b);
+    let (mut luma_a, mut luma_b) = (Vec::new(), Vec::new());
+    luma_plane(&a, &mut luma_a);
+    luma_plane(&b, &mut luma_b);
+    ssim_planes(&luma_a, &luma_b, a.width, a.height)
+}
+
+/// Size of the SSIM window.
+const SSIM_WINDOW: usize = 8;
+/// Step between the SSIM windows.
+const SSIM_STEP: usize = 4;
+
+/// Writes the luminance of the pixels of `image` into `plane` (without padding).
+pub(crate) fn luma_plane(image: &Image<&[u8]>, plane: &mut Vec<u8>) {
+    let format = image.format;
+    let rgb_offsets = format.red_offset().zip(format.green_offset()).zip(format.blue_offset());
+    plane.clear();
+    plane.reserve(image.width * image.height);
+    for row in rows(image) {
+        for pixel in row.chunks_exact(format.size()) {
+            let [r, g, b] = match rgb_offsets {
+                Some(((r, g), b)) => [r, g, b].map(|i| pixel[i] as u32),
+                None if format == PixelFormat::CMYK => {
+                    let k = 255 - pixel[3] as u32;
+                    [0, 1, 2].map(|i| (255 - pixel[i] as u32) * k / 255)
+                },
+                None => [pixel[0] as u32; 3],
+            };
+            plane.push(((77 * r + 150 * g + 29 * b + 128) >> 8) as u8);
+        }
+    }
+}
+
+/// Computes the SSIM of two luminance planes of the given size.
+pub(crate) fn ssim_planes(a: &[u8], b: &[u8], width: usize, height: usize) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let window_width = width.min(SSIM_WINDOW);
+    let window_height = height.min(SSIM_WINDOW);
+    let window_origins = |size: usize, window: usize| (0..=size.saturating_sub(window)).step_by(SSIM_STEP);
+
+    let mut sum = 0.0;
+    let mut count = 0;
+    for y0 in window_origins(height, window_height) {
+        for x0 in window_origins(width, window_width) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0u64, 0u64, 0u64, 0u64, 0u64);
+            for y in y0..y0 + window_height {
+                let row = y * width + x0..y * width + x0 + window_width;
+                for (&va, &vb) in a[row.clone()].iter().zip(&b[row]) {
+                    let (va, vb) = (va as u64, vb as u64);
+                    sum_a += va;
+                    sum_b += vb;
+                    sum_aa += va * va;
+                    sum_bb += vb * vb;
+                    sum_ab += va * vb;
+                }
+            }
+            let n = (window_width * window_height) as f64;
+            let (mean_a, mean_b) = (sum_a as f64 / n, sum_b as f64 / n);
+            let var_a = sum_aa as f64 / n - mean_a * mean_a;
+            let var_b = sum_bb as f64 / n - mean_b * mean_b;
+            let covar = sum_ab as f64 / n - mean_a * mean_b;
+            sum += (2.0 * mean_a * mean_b + C1) * (2.0 * covar + C2)
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            count += 1;
+        }
+    }
+    if count > 0 { sum / count as f64 } else { 1.0 }
+}
+
+fn assert_comparable(a: &Image<&[u8]>, b: &Image<&[u8]>) {
+    assert_eq!((a.width, a.height), (b.width, b.height), "images must have the same size");
+    assert_eq!(a.format, b.format, "images must have the same pixel format");
+    a.assert_valid(a.pixels.len());
+    b.assert_valid(b.pixels.len());
+}
+
+/// Returns an iterator over the rows of the image, without the padding.
+fn rows<'a>(image: &'a Image<&[u8]>) -> impl Iterator<Item = &'a [u8]> {
+    let pitch = image.row_pitch();
+    (0..image.height).map(move |y| &image.pixels[y * pitch..y * pitch + image.packed_pitch()])
+}
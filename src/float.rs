@@ -0,0 +1,165 @@
+use crate::Image;
+use crate::buf::OwnedBuf;
+use crate::common::Result;
+use crate::compress::Compressor;
+
+/// Type of the samples in a floating-point image for [`quantize_float()`].
+///
+/// This is implemented for `f32` and, with the `half` feature, for [`half::f16`].
+pub trait FloatSample: Copy {
+    /// Converts the sample to `f32`.
+    fn to_f32(self) -> f32;
+}
+
+impl FloatSample for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+#[cfg(feature = "half")]
+#[cfg_attr(docsrs, doc(cfg(feature = "half")))]
+impl FloatSample for half::f16 {
+    fn to_f32(self) -> f32 {
+        half::f16::to_f32(self)
+    }
+}
+
+/// Dithering used by [`quantize_float()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Dithering {
+    /// Round every sample to the nearest 8-bit value. Smooth gradients may show visible bands.
+    #[default]
+    None,
+    /// Add an 8×8 ordered (Bayer) dither pattern before rounding, which breaks the bands in smooth
+    /// gradients. The pattern is deterministic, so identical images produce identical results.
+    Ordered,
+}
+
+/// Conversion of floating-point samples to 8-bit samples in [`quantize_float()`].
+///
+/// Samples equal to `min` are converted to 0 and samples equal to `max` are converted to 255;
+/// samples outside of this range are clamped, and NaN is converted to 0. The default range is
+/// from 0 to 1, without dithering.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FloatQuantization {
+    /// The sample value that is converted to 0.
+    pub min: f32,
+    /// The sample value that is converted to 255.
+    pub max: f32,
+    /// Dithering applied before rounding.
+    pub dithering: Dithering,
+}
+
+impl Default for FloatQuantization {
+    fn default() -> FloatQuantization {
+        FloatQuantization { min: 0.0, max: 1.0, dithering: Dithering::None }
+    }
+}
+
+/// 8×8 Bayer matrix for ordered dithering.
+const BAYER: [[u8; 8]; 8] = [
+    [ 0, 32,  8, 40,  2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44,  4, 36, 14, 46,  6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [ 3, 35, 11, 43,  1, 33,  9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47,  7, 39, 13, 45,  5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Converts an image with floating-point samples to an image with 8-bit samples.
+///
+/// The `pitch` of the input image and the length of its `pixels` are measured in samples (not in
+/// bytes), so `image.pitch` is at least `image.width * image.format.size()`; the pitch may also
+/// be 0 if there is no padding between rows. The samples are converted as described in
+/// [`FloatQuantization`]. The result has no padding between rows.
+///
+/// This is useful for rendering and tone-mapping pipelines that produce floating-point images:
+/// use [`Compressor::compress_float()`] to quantize and compress the image in one step.
+///
+/// # Panics
+///
+/// Panics if the image is invalid (see [`Image::check()`], with lengths in samples).
+///
+/// # Example
+///
+/// ```
+/// let width = 256;
+/// let gradient = (0..width).map(|x| x as f32 / (width - 1) as f32).collect::<Vec<_>>();
+/// let image = turbojpeg::Image {
+///     pixels: &gradient[..],
+///     width,
+///     pitch: 0,
+///     height: 1,
+///     format: turbojpeg::PixelFormat::GRAY,
+/// };
+///
+/// let quantized = turbojpeg::quantize_float(image, &Default::default());
+/// assert_eq!(quantized.pixels, (0..=255).collect::<Vec<u8>>());
+/// ```
+pub fn quantize_float<T: FloatSample>(image: Image<&[T]>, quantization: &FloatQuantization) -> Image<Vec<u8>> {
+    image.assert_valid(image.pixels.len());
+    let row_len = image.packed_pitch();
+    let pitch = image.row_pitch();
+    let scale = 255.0 / (quantization.max - quantization.min);
+    let channels = image.format.size();
+
+    let mut pixels = vec![0; row_len * image.height];
+    for (y, row) in pixels.chunks_exact_mut(row_len.max(1)).enumerate() {
+        let samples = &image.pixels[y * pitch..y * pitch + row_len];
+        for (i, (value, &sample)) in row.iter_mut().zip(samples).enumerate() {
+            let offset = match quantization.dithering {
+                Dithering::None => 0.5,
+                Dithering::Ordered => (BAYER[y % 8][i / channels % 8] as f32 + 0.5) / 64.0,
+            };
+            // `as u8` saturates and converts NaN to 0
+            *value = ((sample.to_f32() - quantization.min) * scale + offset).floor().clamp(0.0, 255.0) as u8;
+        }
+    }
+    Image { pixels, width: image.width, pitch: row_len, height: image.height, format: image.format }
+}
+
+impl Compressor {
+    /// Compresses an image with floating-point samples.
+    ///
+    /// The image is first converted to 8-bit samples using [`quantize_float()`] (see there for
+    /// the layout of the image), and then compressed. This allocates a temporary buffer for the
+    /// converted image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is invalid (see [`quantize_float()`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let (width, height) = (64, 32);
+    /// let pixels = (0..width * height * 3).map(|i| (i % 100) as f32 / 100.0).collect::<Vec<_>>();
+    /// let image = turbojpeg::Image {
+    ///     pixels: &pixels[..],
+    ///     width,
+    ///     pitch: 0,
+    ///     height,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    ///
+    /// let quantization = turbojpeg::FloatQuantization {
+    ///     dithering: turbojpeg::Dithering::Ordered,
+    ///     ..Default::default()
+    /// };
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// let jpeg_data = compressor.compress_float(image, &quantization)?;
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn compress_float<T: FloatSample>(
+        &mut self,
+        image: Image<&[T]>,
+        quantization: &FloatQuantization,
+    ) -> Result<OwnedBuf> {
+        let image = quantize_float(image, quantization);
+        self.compress_to_owned(image.as_deref())
+    }
+}
@@ -1,27 +1,82 @@
 use std::ffi::CStr;
 use crate::common::{Result, Error};
+use crate::limits::Limits;
 
 #[derive(Debug)]
 pub struct Handle {
     ptr: raw::tjhandle,
+    init: raw::TJINIT,
+    limits: Limits,
+    /// Identifier of the image that is attached to logged warnings.
+    #[cfg(feature = "log")]
+    image_id: Option<String>,
 }
 
 impl Handle {
     pub fn new(init: raw::TJINIT) -> Result<Self> {
         let ptr = unsafe { raw::tj3Init(init as libc::c_int) };
-        let mut this = Self { ptr };
+        let mut this = Self {
+            ptr,
+            init,
+            limits: Limits::NONE,
+            #[cfg(feature = "log")]
+            image_id: None,
+        };
         if this.ptr.is_null() {
             return Err(this.get_error())
         }
+        this.set_limits(Limits::global())?;
         Ok(this)
     }
 
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) -> Result<()> {
+        if self.init != raw::TJINIT_TJINIT_COMPRESS {
+            // TurboJPEG uses 0 for no limit
+            let max_scans = limits.max_scans.map_or(0, |max_scans| max_scans.max(1));
+            let max_scans = max_scans.try_into().map_err(|_| Error::IntegerOverflow("max_scans"))?;
+            self.set(raw::TJPARAM_TJPARAM_SCANLIMIT, max_scans)?;
+        }
+        self.limits = limits;
+        Ok(())
+    }
+
     pub fn get_error(&mut self) -> Error {
         let msg = unsafe { CStr::from_ptr(raw::tj3GetErrorStr(self.ptr)) };
         Error::TurboJpegError(msg.to_string_lossy().into_owned())
     }
 
-    pub fn get(&mut self, param: raw::TJPARAM) -> libc::c_int {
+    /// Returns the error from `operation`. With the `log` feature, warnings are also logged.
+    pub fn get_error_in(&mut self, operation: &'static str) -> Error {
+        let error = self.get_error();
+        #[cfg(feature = "log")]
+        if self.is_warning() {
+            match self.image_id {
+                Some(ref image_id) => log::warn!(target: "turbojpeg", "{} of {}: {}", operation, image_id, error),
+                None => log::warn!(target: "turbojpeg", "{}: {}", operation, error),
+            }
+        }
+        #[cfg(not(feature = "log"))]
+        let _ = operation;
+        error
+    }
+
+    #[cfg(feature = "log")]
+    pub fn set_image_id(&mut self, image_id: Option<&str>) {
+        self.image_id = image_id.map(str::to_owned);
+    }
+
+    /// Returns true if the last error was a warning (the operation could continue, but the data
+    /// may be corrupted).
+    pub fn is_warning(&mut self) -> bool {
+        let code = unsafe { raw::tj3GetErrorCode(self.ptr) };
+        code == raw::TJERR_TJERR_WARNING as libc::c_int
+    }
+
+    pub fn get(&self, param: raw::TJPARAM) -> libc::c_int {
         unsafe { raw::tj3Get(self.ptr, param as libc::c_int) }
     }
 
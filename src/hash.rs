@@ -0,0 +1,129 @@
+use crate::Image;
+use crate::common::{PixelFormat, Result};
+use crate::decompress::Decompressor;
+
+/// Hashes of the decompressed pixels of a JPEG image, returned by [`Decompressor::hash_pixels()`]
+/// and [`hash_pixels()`].
+///
+/// The hashes depend only on the decompressed pixels, not on the metadata (EXIF, ICC profile,
+/// comments) or on the way the pixels are encoded (progressive or baseline, optimized Huffman
+/// tables, restart markers), so they can identify duplicate images that differ only in these
+/// details. Images that were compressed with different quality or subsampling have slightly
+/// different pixels, so they get a different [`exact`][Self::exact] hash, but usually a similar
+/// [`perceptual`][Self::perceptual] hash.
+///
+/// The decompressed pixels (and therefore the hashes) may differ between versions and builds of
+/// TurboJPEG (for example, with and without the SIMD extensions), so do not store the exact hashes
+/// across upgrades of the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PixelHash {
+    /// 64-bit FNV-1a hash of the width and height (as little-endian 64-bit integers) followed by
+    /// the decompressed RGB pixels without padding.
+    pub exact: u64,
+    /// 64-bit average hash: the image is divided into a grid of 8×8 cells, and bit `8*row + col`
+    /// is set if the mean luminance of the cell is greater than the mean of all cells.
+    pub perceptual: u64,
+}
+
+impl PixelHash {
+    /// Returns the number of bits that differ between the perceptual hashes (the Hamming distance).
+    ///
+    /// Identical images have distance 0; images that look the same usually have a distance of a
+    /// few bits, while unrelated images have a distance of about 32.
+    pub fn perceptual_distance(&self, other: &PixelHash) -> u32 {
+        (self.perceptual ^ other.perceptual).count_ones()
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+impl Decompressor {
+    /// Decompress a JPEG image and compute the hashes of its pixels.
+    ///
+    /// The whole image is decompressed into RGB at once. It is not split into strips at the restart
+    /// markers (unlike [`decompress_strips()`][Self::decompress_strips]), because the strips would
+    /// be upsampled independently and the pixels at their boundaries would depend on the restart
+    /// markers. See [`PixelHash`] for the description of the hashes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image::mandelbrot(300, 200, turbojpeg::PixelFormat::RGB);
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// let baseline = compressor.compress_to_vec(image.as_deref())?;
+    /// compressor.apply(&turbojpeg::Settings { progressive: true, ..Default::default() })?;
+    /// let progressive = compressor.compress_to_vec(image.as_deref())?;
+    ///
+    /// // the entropy coding does not change the decompressed pixels
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// let hash = decompressor.hash_pixels(&baseline)?;
+    /// assert_ne!(baseline, progressive);
+    /// assert_eq!(decompressor.hash_pixels(&progressive)?, hash);
+    ///
+    /// // lower quality changes the pixels slightly, but not the perceptual hash
+    /// let low_quality = turbojpeg::compress(image.as_deref(), 60, turbojpeg::Subsamp::Sub2x2)?;
+    /// let low_quality_hash = decompressor.hash_pixels(&low_quality)?;
+    /// assert_ne!(low_quality_hash.exact, hash.exact);
+    /// assert!(low_quality_hash.perceptual_distance(&hash) < 8);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn hash_pixels(&mut self, jpeg_data: &[u8]) -> Result<PixelHash> {
+        let header = self.read_header(jpeg_data)?;
+        let (width, height) = (header.width, header.height);
+
+        let mut exact = FNV_OFFSET;
+        for byte in (width as u64).to_le_bytes().into_iter().chain((height as u64).to_le_bytes()) {
+            exact = (exact ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+        let mut cell_sums = [0u64; 64];
+        let mut cell_counts = [0u64; 64];
+
+        let pitch = width * 3;
+        let mut image = Image { pixels: vec![0; pitch * height], width, pitch, height, format: PixelFormat::RGB };
+        self.decompress(jpeg_data, image.as_deref_mut())?;
+        for (y, row) in image.pixels.chunks_exact(pitch).enumerate() {
+            for &byte in row {
+                exact = (exact ^ byte as u64).wrapping_mul(FNV_PRIME);
+            }
+            let cell_row = y * 8 / height;
+            for (x, pixel) in row.chunks_exact(3).enumerate() {
+                let luma = (77 * pixel[0] as u64 + 150 * pixel[1] as u64 + 29 * pixel[2] as u64) >> 8;
+                let cell = cell_row * 8 + x * 8 / width;
+                cell_sums[cell] += luma;
+                cell_counts[cell] += 1;
+            }
+        }
+
+        // images smaller than 8×8 pixels have empty cells, which are treated as black
+        let mut cell_means = [0u64; 64];
+        for (mean, (&sum, &count)) in cell_means.iter_mut().zip(cell_sums.iter().zip(&cell_counts)) {
+            *mean = (sum * 256).checked_div(count).unwrap_or(0);
+        }
+        let total_mean = cell_means.iter().sum::<u64>() / 64;
+        let perceptual = cell_means.iter().enumerate()
+            .filter(|&(_, &mean)| mean > total_mean)
+            .fold(0, |hash, (cell, _)| hash | 1 << cell);
+
+        Ok(PixelHash { exact, perceptual })
+    }
+}
+
+/// Decompress a JPEG image and compute the hashes of its pixels.
+///
+/// See [`Decompressor::hash_pixels()`] for details.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let hash = turbojpeg::hash_pixels(&jpeg_data)?;
+/// assert_eq!(hash.perceptual_distance(&hash), 0);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn hash_pixels(jpeg_data: &[u8]) -> Result<PixelHash> {
+    let mut decompressor = Decompressor::new()?;
+    decompressor.hash_pixels(jpeg_data)
+}
@@ -1,5 +1,9 @@
 use std::ops::{Deref, DerefMut};
-use crate::common::{PixelFormat, Subsamp};
+use crate::buf::AlignedBuf;
+use crate::common::{PixelFormat, Result, Error};
+#[cfg(feature = "yuv")]
+use crate::common::Subsamp;
+#[cfg(feature = "yuv")]
 use crate::decompress::yuv_pixels_len;
 
 /// An image with pixels of type `T`.
@@ -7,11 +11,11 @@ use crate::decompress::yuv_pixels_len;
 /// Three variants of this type are commonly used:
 ///
 /// - `Image<&[u8]>`: immutable reference to image data (input image for compression by
-/// [`Compressor`][crate::Compressor])
+///   [`Compressor`][crate::Compressor])
 /// - `Image<&mut [u8]>`: mutable reference to image data (output image for decompression by
-/// [`Decompressor`][crate::Decompressor]).
+///   [`Decompressor`][crate::Decompressor]).
 /// - `Image<Vec<u8>>`: owned image data (you can convert it to a reference using
-/// [`.as_deref()`][Image::as_deref] or [`.as_deref_mut()`][Image::as_deref_mut]).
+///   [`.as_deref()`][Image::as_deref] or [`.as_deref_mut()`][Image::as_deref_mut]).
 ///
 /// Data for pixel in column `x` and row `y` is stored in `pixels` at offset `y*pitch +
 /// x*format.size()`.
@@ -23,6 +27,9 @@ pub struct Image<T> {
     pub width: usize,
     /// Pitch (stride) defines the size of one image row in bytes. Overlapping rows are not
     /// supported, we require that `pitch >= width * format.size()`.
+    ///
+    /// As in the TurboJPEG C API, pitch 0 means that the rows are not padded, so the pitch is
+    /// equal to [`packed_pitch()`][Self::packed_pitch].
     pub pitch: usize,
     /// Height of the image in pixels (number of rows).
     pub height: usize,
@@ -58,17 +65,509 @@ impl<T> Image<T> {
         }
     }
 
+    /// Returns the pitch of this image without any padding between rows.
+    ///
+    /// This is equal to `width * format.size()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image {
+    ///     pixels: (),
+    ///     width: 100,
+    ///     pitch: 0,
+    ///     height: 50,
+    ///     format: turbojpeg::PixelFormat::RGBA,
+    /// };
+    /// assert_eq!(image.packed_pitch(), 400);
+    /// ```
+    pub fn packed_pitch(&self) -> usize {
+        self.width.saturating_mul(self.format.size())
+    }
+
+    /// Returns the pitch with the zero value replaced by [`packed_pitch()`][Self::packed_pitch].
+    pub(crate) fn row_pitch(&self) -> usize {
+        if self.pitch == 0 { self.packed_pitch() } else { self.pitch }
+    }
+
+    /// Returns the minimal length of [`pixels`][Self::pixels] for this image.
+    ///
+    /// This is the size of all rows except the last one (`pitch * (height - 1)`), plus the size of
+    /// the pixels in the last row (`width * format.size()`). If the size overflows `usize`, returns
+    /// `usize::MAX` ([`check()`][Self::check] returns [`Error::IntegerOverflow`] in this case).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image {
+    ///     pixels: (),
+    ///     width: 100,
+    ///     pitch: 320,
+    ///     height: 50,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    /// assert_eq!(image.min_pixels_len(), 320 * 49 + 300);
+    /// ```
+    pub fn min_pixels_len(&self) -> usize {
+        if self.height == 0 {
+            0
+        } else {
+            self.row_pitch().saturating_mul(self.height - 1).saturating_add(self.packed_pitch())
+        }
+    }
+
+    /// Checks that pixel data of length `pixels_len` is valid for this image.
+    ///
+    /// Compression and decompression panic if the image is not valid; you can use this method to
+    /// validate images that you receive from elsewhere and report an error instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image {
+    ///     pixels: vec![0; 1000],
+    ///     width: 100,
+    ///     pitch: 300,
+    ///     height: 50,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    /// assert!(image.check(image.pixels.len()).is_err());
+    /// ```
+    pub fn check(&self, pixels_len: usize) -> Result<()> {
+        let packed_pitch = self.width.checked_mul(self.format.size())
+            .ok_or(Error::IntegerOverflow("width"))?;
+        let pitch = if self.pitch == 0 { packed_pitch } else { self.pitch };
+        if pitch < packed_pitch {
+            return Err(Error::PitchTooSmall(pitch, self.width, self.format))
+        }
+        let min_pixels_len = match self.height {
+            0 => 0,
+            height => pitch.checked_mul(height - 1)
+                .and_then(|len| len.checked_add(packed_pitch))
+                .ok_or(Error::IntegerOverflow("pixels length"))?,
+        };
+        if pixels_len < min_pixels_len {
+            return Err(Error::PixelsTooSmall(pixels_len, min_pixels_len))
+        }
+        Ok(())
+    }
+
     pub(crate) fn assert_valid(&self, pixels_len: usize) {
-        let Image { pixels: _, width, pitch, height, format } = *self;
-        assert!(pitch >= width*format.size(),
-            "pitch {} is too small for width {} and pixel format {:?}", pitch, width, format);
-        assert!(height == 0 || pitch*(height - 1) + width*format.size() <= pixels_len,
-            "pixels length {} is too small for width {}, height {}, pitch {} and pixel format {:?}",
-            pixels_len, width, height, pitch, format);
+        if let Err(err) = self.check(pixels_len) {
+            panic!("{}", err)
+        }
+    }
+
+    /// Returns the packed pitch rounded up to a multiple of `align` bytes.
+    ///
+    /// Many consumers of image data (GPU uploads, video frames or shared memory protocols such as
+    /// Wayland `wl_shm`) require that every row starts at an aligned offset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image {
+    ///     pixels: (),
+    ///     width: 100,
+    ///     pitch: 0,
+    ///     height: 50,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// };
+    /// assert_eq!(image.aligned_pitch(64), 320);
+    /// ```
+    pub fn aligned_pitch(&self, align: usize) -> usize {
+        assert!(align > 0, "alignment must not be zero");
+        self.packed_pitch().div_ceil(align) * align
+    }
+
+    /// Sets the pitch to [`aligned_pitch(align)`][Self::aligned_pitch].
+    ///
+    /// You can use this to compute the size of a buffer for an image with aligned rows, before you
+    /// allocate the buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let layout = turbojpeg::Image {
+    ///     pixels: (),
+    ///     width: 100,
+    ///     pitch: 0,
+    ///     height: 50,
+    ///     format: turbojpeg::PixelFormat::RGB,
+    /// }.with_aligned_pitch(64);
+    /// assert_eq!(layout.pitch, 320);
+    /// assert_eq!(layout.min_pixels_len(), 320 * 49 + 300);
+    /// ```
+    pub fn with_aligned_pitch(self, align: usize) -> Self {
+        let pitch = self.aligned_pitch(align);
+        Image { pitch, ..self }
+    }
+}
+
+impl<T: Deref<Target = [u8]>> Image<T> {
+    /// Returns the pixels of an image with 4-byte pixels as `u32` values.
+    ///
+    /// Every `u32` contains the bytes of one pixel in memory order, so the meaning of the bits
+    /// depends on the endianness of the platform; use
+    /// [`PixelFormat::from_u32_order()`] to select a pixel format where the components have a
+    /// fixed position in the `u32`. The pixel in column `x` and row `y` is at index
+    /// `y * pitch / 4 + x`, where `pitch` is the pitch in bytes (or
+    /// [`packed_pitch()`][Self::packed_pitch] if the pitch is 0). Trailing bytes that do not form a
+    /// whole `u32` are not included.
+    ///
+    /// Returns [`Error::NotU32Pixels`] if the pixel format does not have 4-byte pixels, if the
+    /// pitch is not a multiple of 4, or if the pixels are not aligned to 4 bytes (a `Vec<u8>` is
+    /// usually aligned, but this is not guaranteed; use [`Image::allocate_aligned()`] to be sure).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::{AlignedBuf, Image, PixelFormat};
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let header = turbojpeg::read_header(&jpeg_data)?;
+    ///
+    /// // decompress into pixels of the form 0xAARRGGBB
+    /// let format = PixelFormat::ARGB.from_u32_order().unwrap();
+    /// let mut image = Image::allocate_aligned(header.width, header.height, format, AlignedBuf::DEFAULT_ALIGN);
+    /// turbojpeg::Decompressor::new()?.decompress(&jpeg_data, image.as_deref_mut())?;
+    ///
+    /// let pixels = image.pixels_u32()?;
+    /// assert!(pixels[..header.width].iter().all(|&pixel| pixel >> 24 == 0xff));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn pixels_u32(&self) -> Result<&[u32]> {
+        self.check_u32()?;
+        // SAFETY: every bit pattern is a valid `u32`
+        let (prefix, pixels, _) = unsafe { self.pixels.align_to::<u32>() };
+        if !prefix.is_empty() {
+            return Err(Error::NotU32Pixels("pixels are not aligned to 4 bytes"))
+        }
+        Ok(pixels)
+    }
+
+    fn check_u32(&self) -> Result<()> {
+        if self.format.size() != 4 {
+            return Err(Error::NotU32Pixels("pixel format does not have 4-byte pixels"))
+        }
+        if self.pitch % 4 != 0 {
+            return Err(Error::NotU32Pixels("pitch is not a multiple of 4"))
+        }
+        Ok(())
+    }
+}
+
+impl<T: DerefMut<Target = [u8]>> Image<T> {
+    /// Returns the pixels of an image with 4-byte pixels as mutable `u32` values.
+    ///
+    /// See [`pixels_u32()`][Self::pixels_u32] for details.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::{AlignedBuf, Image, PixelFormat};
+    /// let format = PixelFormat::RGBA.from_u32_order().unwrap();
+    /// let mut image = Image::allocate_aligned(16, 16, format, AlignedBuf::DEFAULT_ALIGN);
+    ///
+    /// // fill the image with opaque orange (0xRRGGBBAA)
+    /// image.pixels_u32_mut()?.fill(0xff8000ff);
+    /// assert_eq!(image.pixels[format.red_offset().unwrap()], 0xff);
+    /// assert_eq!(image.pixels[format.green_offset().unwrap()], 0x80);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn pixels_u32_mut(&mut self) -> Result<&mut [u32]> {
+        self.check_u32()?;
+        // SAFETY: every bit pattern is a valid `u32` and every `u32` is a valid `[u8; 4]`
+        let (prefix, pixels, _) = unsafe { self.pixels.align_to_mut::<u32>() };
+        if !prefix.is_empty() {
+            return Err(Error::NotU32Pixels("pixels are not aligned to 4 bytes"))
+        }
+        Ok(pixels)
+    }
+
+    /// Multiplies the color components of every pixel by its alpha.
+    ///
+    /// Compositing pipelines often work with premultiplied alpha, where every color component is
+    /// already multiplied by the alpha (from 0 to 255, rounded to the nearest integer). TurboJPEG
+    /// ignores alpha when compressing and sets it to 255 when decompressing, so decompressed
+    /// images are already premultiplied, but images that you compress may not be. If the pixel
+    /// format has no alpha channel (including the formats with an unused X component), the image is
+    /// not changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is not [valid][Self::check].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut pixels = vec![200, 100, 50, 128];
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: &mut pixels[..],
+    ///     width: 1,
+    ///     pitch: 0,
+    ///     height: 1,
+    ///     format: turbojpeg::PixelFormat::RGBA,
+    /// };
+    /// image.premultiply_alpha();
+    /// assert_eq!(image.pixels, [100, 50, 25, 128]);
+    /// ```
+    pub fn premultiply_alpha(&mut self) {
+        self.map_alpha_pixels(|color, alpha| {
+            ((color as u32 * alpha as u32 + 127) / 255) as u8
+        });
+    }
+
+    /// Divides the color components of every pixel by its alpha.
+    ///
+    /// This is the inverse of [`premultiply_alpha()`][Self::premultiply_alpha] (up to rounding).
+    /// Use it to convert a premultiplied image before compressing it. Pixels with zero alpha
+    /// become black, and the components are clamped to 255. If the pixel format has no alpha
+    /// channel, the image is not changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is not [valid][Self::check].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut pixels = vec![128, 100, 50, 25];
+    /// let mut image = turbojpeg::Image {
+    ///     pixels: &mut pixels[..],
+    ///     width: 1,
+    ///     pitch: 0,
+    ///     height: 1,
+    ///     format: turbojpeg::PixelFormat::ARGB,
+    /// };
+    /// image.unpremultiply_alpha();
+    /// assert_eq!(image.pixels, [128, 199, 100, 50]);
+    /// ```
+    pub fn unpremultiply_alpha(&mut self) {
+        self.map_alpha_pixels(|color, alpha| match alpha {
+            0 => 0,
+            _ => ((color as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8,
+        });
+    }
+
+    /// Replaces every color component with `f(color, alpha)`.
+    fn map_alpha_pixels(&mut self, f: impl Fn(u8, u8) -> u8) {
+        self.assert_valid(self.pixels.len());
+        let Some(alpha_offset) = self.format.alpha_offset().filter(|_| self.format.has_alpha()) else {
+            return
+        };
+        let (pitch, row_len, size) = (self.row_pitch(), self.packed_pitch(), self.format.size());
+        for y in 0..self.height {
+            for pixel in self.pixels[y * pitch..][..row_len].chunks_exact_mut(size) {
+                let alpha = pixel[alpha_offset];
+                for (i, component) in pixel.iter_mut().enumerate() {
+                    if i != alpha_offset {
+                        *component = f(*component, alpha);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Image<&'a [u8]> {
+    /// Creates an image from a raw pointer to `len` bytes of pixel data.
+    ///
+    /// This is useful for images in memory that is not managed by Rust, such as shared memory
+    /// mapped from another process (POSIX shm or memfd). Returns an error if the pointer is null
+    /// or if the image is not [valid][Self::check] for `len` bytes of pixel data.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must satisfy the same requirements as in [`std::slice::from_raw_parts()`]: it must be
+    /// valid for reads of `len` bytes, and the memory must not be mutated during lifetime `'a`
+    /// (in particular, not by another process).
+    pub unsafe fn from_raw_parts(
+        ptr: *const u8,
+        len: usize,
+        width: usize,
+        pitch: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Result<Image<&'a [u8]>> {
+        if ptr.is_null() {
+            return Err(Error::Null)
+        }
+        let image = Image { pixels: (), width, pitch, height, format };
+        image.check(len)?;
+        Ok(Image { pixels: std::slice::from_raw_parts(ptr, len), width, pitch, height, format })
+    }
+
+    /// Copies the image into an owned image without padding between rows.
+    ///
+    /// The pitch of the returned image is [`packed_pitch()`][Self::packed_pitch], so the pixels
+    /// can be passed to APIs that require tightly packed rows (such as
+    /// `image::ImageBuffer::from_raw()`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is not [valid][Self::check].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let padded = turbojpeg::Image::mandelbrot(100, 50, turbojpeg::PixelFormat::RGB);
+    /// assert_eq!(padded.pitch, 320);
+    ///
+    /// let packed = padded.as_deref().to_owned();
+    /// assert_eq!(packed.pitch, 300);
+    /// assert_eq!(packed.pixels.len(), 300 * 50);
+    /// assert_eq!(packed.pixels[300..600], padded.pixels[320..620]);
+    /// ```
+    pub fn to_owned(&self) -> Image<Vec<u8>> {
+        self.assert_valid(self.pixels.len());
+        let (pitch, row_len) = (self.row_pitch(), self.packed_pitch());
+        let mut pixels = Vec::with_capacity(row_len * self.height);
+        for y in 0..self.height {
+            pixels.extend_from_slice(&self.pixels[y * pitch..][..row_len]);
+        }
+        Image { pixels, width: self.width, pitch: row_len, height: self.height, format: self.format }
+    }
+}
+
+impl<'a> Image<&'a mut [u8]> {
+    /// Creates a mutable image from a raw pointer to `len` bytes of pixel data.
+    ///
+    /// This allows you to decompress images directly into memory that is not managed by Rust,
+    /// such as shared memory that is mapped from another process (POSIX shm or memfd), without
+    /// an extra copy. Returns an error if the pointer is null or if the image is not
+    /// [valid][Self::check] for `len` bytes of pixel data.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must satisfy the same requirements as in [`std::slice::from_raw_parts_mut()`]: it must
+    /// be valid for reads and writes of `len` bytes, and the memory must not be accessed through
+    /// any other pointer during lifetime `'a` (in particular, not by another process).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let header = turbojpeg::read_header(&jpeg_data)?;
+    ///
+    /// // compute the layout of the image with rows aligned to 64 bytes
+    /// let layout = turbojpeg::Image {
+    ///     pixels: (),
+    ///     width: header.width,
+    ///     pitch: 0,
+    ///     height: header.height,
+    ///     format: turbojpeg::PixelFormat::BGRA,
+    /// }.with_aligned_pitch(64);
+    ///
+    /// // this would typically be a memory-mapped shared memory region
+    /// let mut region = vec![0u8; layout.pitch * layout.height];
+    ///
+    /// let image = unsafe {
+    ///     turbojpeg::Image::from_raw_parts_mut(
+    ///         region.as_mut_ptr(), region.len(),
+    ///         layout.width, layout.pitch, layout.height, layout.format,
+    ///     )?
+    /// };
+    /// turbojpeg::Decompressor::new()?.decompress(&jpeg_data, image)?;
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub unsafe fn from_raw_parts_mut(
+        ptr: *mut u8,
+        len: usize,
+        width: usize,
+        pitch: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Result<Image<&'a mut [u8]>> {
+        if ptr.is_null() {
+            return Err(Error::Null)
+        }
+        let image = Image { pixels: (), width, pitch, height, format };
+        image.check(len)?;
+        Ok(Image { pixels: std::slice::from_raw_parts_mut(ptr, len), width, pitch, height, format })
+    }
+}
+
+impl Image<AlignedBuf> {
+    /// Allocates a zeroed image whose rows start at multiples of `align` bytes.
+    ///
+    /// Both the start of the pixels (see [`AlignedBuf`]) and the pitch (see
+    /// [`with_aligned_pitch()`][Self::with_aligned_pitch]) are aligned, so every row is aligned,
+    /// which lets the SIMD code in TurboJPEG access the pixels faster. Use
+    /// [`AlignedBuf::DEFAULT_ALIGN`] unless you need a specific alignment. The buffer contains
+    /// `pitch * height` bytes (including the padding after the last row).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two or if the size of the image overflows.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::{AlignedBuf, Image, PixelFormat};
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let header = turbojpeg::read_header(&jpeg_data)?;
+    ///
+    /// let mut image = Image::allocate_aligned(header.width, header.height, PixelFormat::RGB, AlignedBuf::DEFAULT_ALIGN);
+    /// assert_eq!(image.pitch % 64, 0);
+    /// assert_eq!(image.pixels.as_ptr() as usize % 64, 0);
+    ///
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.decompress(&jpeg_data, image.as_deref_mut())?;
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn allocate_aligned(width: usize, height: usize, format: PixelFormat, align: usize) -> Image<AlignedBuf> {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        width.checked_mul(format.size())
+            .and_then(|row_len| row_len.checked_next_multiple_of(align))
+            .expect("image size overflowed");
+        let layout = Image { pixels: (), width, pitch: 0, height, format }.with_aligned_pitch(align);
+        let len = layout.pitch.checked_mul(height).expect("image size overflowed");
+        Image { pixels: AlignedBuf::zeroed(len, align), width, pitch: layout.pitch, height, format }
     }
 }
 
 impl Image<Vec<u8>> {
+    /// Removes the padding between rows in place.
+    ///
+    /// The rows are moved to the start of [`pixels`][Self::pixels], which is truncated to
+    /// `packed_pitch() * height` bytes, and the pitch is set to
+    /// [`packed_pitch()`][Self::packed_pitch]. This does not allocate, so it is cheaper than
+    /// [`to_owned()`][Image::to_owned] when you already own the pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is not [valid][Self::check].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut image = turbojpeg::Image::mandelbrot(100, 50, turbojpeg::PixelFormat::GRAY);
+    /// assert_eq!(image.pitch, 128);
+    ///
+    /// image.compact();
+    /// assert_eq!(image.pitch, 100);
+    /// assert_eq!(image.pixels.len(), 100 * 50);
+    /// ```
+    pub fn compact(&mut self) {
+        self.assert_valid(self.pixels.len());
+        let (pitch, row_len) = (self.row_pitch(), self.packed_pitch());
+        if pitch != row_len {
+            for y in 1..self.height {
+                self.pixels.copy_within(y * pitch..y * pitch + row_len, y * row_len);
+            }
+        }
+        self.pixels.truncate(row_len * self.height);
+        self.pitch = row_len;
+    }
+
     /// Generates an image of the Mandelbrot set.
     ///
     /// The generated image has the given width and height and uses the given pixel format. This
@@ -176,9 +675,9 @@ impl Image<Vec<u8>> {
 /// Two variants of this type are commonly used:
 ///
 /// - `YuvImage<&mut [u8]>`: mutable reference to YUV image data (output image for decompression by
-/// [`Decompressor`][crate::Decompressor]).
+///   [`Decompressor`][crate::Decompressor]).
 /// - `YuvImage<Vec<u8>>`: owned YUV image data (you can convert it to a reference using
-/// [`.as_deref()`][YuvImage::as_deref] or [`.as_deref_mut()`][YuvImage::as_deref_mut]).
+///   [`.as_deref()`][YuvImage::as_deref] or [`.as_deref_mut()`][YuvImage::as_deref_mut]).
 ///
 /// # Image format
 ///
@@ -186,13 +685,13 @@ impl Image<Vec<u8>> {
 /// [chrominance subsampling][Self::subsamp] and [row alignment][Self::align] of the image:
 ///
 /// - [Luminance (Y) plane width][Self::y_width()] is the image width padded to the nearest
-/// multiple of the [horizontal subsampling factor][Subsamp::width()].
+///   multiple of the [horizontal subsampling factor][Subsamp::width()].
 /// - [Luminance (Y) plane height][Self::y_height()] is the image height padded to the nearest
-/// multiple of the [vertical subsampling factor][Subsamp::height()].
+///   multiple of the [vertical subsampling factor][Subsamp::height()].
 /// - [Chrominance (U and V) plane width][Self::uv_width()] is the luminance plane width divided by
-/// the horizontal subsampling factor.
+///   the horizontal subsampling factor.
 /// - [Chrominance (U and V) plane height][Self::uv_height()] is the luminance plane height divided
-/// by the vertical subsampling factor.
+///   by the vertical subsampling factor.
 /// - Each row is further padded to the nearest multiple of the [row alignment][Self::align].
 ///
 /// ## Example
@@ -218,6 +717,8 @@ impl Image<Vec<u8>> {
 /// assert_eq!(img2.y_size(), (36, 35));
 /// assert_eq!(img2.uv_size(), (20, 35));
 /// ```
+#[cfg(feature = "yuv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
 pub struct YuvImage<T> {
     /// Pixel data of the image (typically `&mut [u8]` or `Vec<u8>`).
     pub pixels: T,
@@ -232,6 +733,8 @@ pub struct YuvImage<T> {
     pub subsamp: Subsamp,
 }
 
+#[cfg(feature = "yuv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
 impl<T> YuvImage<T> {
     /// Converts from `&YuvImage<T>` to `YuvImage<&T::Target>`.
     ///
@@ -264,8 +767,8 @@ impl<T> YuvImage<T> {
     /// This is the [image width][Self::width] padded to the nearest multiple of the [horizontal subsampling
     /// factor][Subsamp::width()] and then aligned to the [row alignment][Self::align].
     pub fn y_width(&self) -> usize {
-        let width = next_multiple_of(self.width, self.subsamp.width());
-        next_multiple_of(width, self.align)
+        let width = self.width.next_multiple_of(self.subsamp.width());
+        width.next_multiple_of(self.align)
     }
 
     /// Computes height of the luminance (Y) plane.
@@ -273,7 +776,7 @@ impl<T> YuvImage<T> {
     /// This is the [image height][Self::height] padded to the nearest multiple of the [vertical
     /// subsampling factor][Subsamp::height()].
     pub fn y_height(&self) -> usize {
-        next_multiple_of(self.height, self.subsamp.height())
+        self.height.next_multiple_of(self.subsamp.height())
     }
 
     /// Computes size of the luminance (Y) plane.
@@ -286,8 +789,8 @@ impl<T> YuvImage<T> {
     /// This is the [Y plane width][Self::y_width()] divided by the [horizontal subsampling
     /// factor][Subsamp::width()] and then aligned to the [row alignment][Self::align].
     pub fn uv_width(&self) -> usize {
-        let width = div_ceil(self.width, self.subsamp.width());
-        next_multiple_of(width, self.align)
+        let width = self.width.div_ceil(self.subsamp.width());
+        width.next_multiple_of(self.align)
     }
 
     /// Computes height of each chrominance (U, V) plane.
@@ -295,7 +798,7 @@ impl<T> YuvImage<T> {
     /// This is the [Y plane height][Self::y_height()] divided by the [vertical subsampling
     /// factor][Subsamp::height()].
     pub fn uv_height(&self) -> usize {
-        div_ceil(self.height, self.subsamp.height())
+        self.height.div_ceil(self.subsamp.height())
     }
 
     /// Computes size of each chrominance (U, V) plane.
@@ -303,21 +806,28 @@ impl<T> YuvImage<T> {
         (self.uv_width(), self.uv_height())
     }
 
-    pub(crate) fn assert_valid(&self, pixels_len: usize) {
-        let YuvImage { pixels: _, width, align, height, subsamp } = *self;
-        let min_yuv_pixels_len = yuv_pixels_len(width, align, height, subsamp).unwrap();
-        assert!(min_yuv_pixels_len <= pixels_len,
-            "YUV pixels length {} is too small for width {}, height {}, align {} and subsamp {:?}",
-            pixels_len, width, height, align, subsamp);
+    /// Returns the minimal length of [`pixels`][Self::pixels] for this image.
+    ///
+    /// This is the same as [`yuv_pixels_len()`][crate::yuv_pixels_len].
+    pub fn min_pixels_len(&self) -> Result<usize> {
+        yuv_pixels_len(self.width, self.align, self.height, self.subsamp)
     }
-}
 
-// TODO: these two functions will eventually be stabilized into the standard library
-
-fn next_multiple_of(n: usize, divisor: usize) -> usize {
-    div_ceil(n, divisor) * divisor
-}
+    /// Checks that pixel data of length `pixels_len` is valid for this image.
+    ///
+    /// Compression and decompression panic if the image is not valid; you can use this method to
+    /// validate images that you receive from elsewhere and report an error instead.
+    pub fn check(&self, pixels_len: usize) -> Result<()> {
+        let min_pixels_len = self.min_pixels_len()?;
+        if pixels_len < min_pixels_len {
+            return Err(Error::PixelsTooSmall(pixels_len, min_pixels_len))
+        }
+        Ok(())
+    }
 
-fn div_ceil(n: usize, divisor: usize) -> usize {
-    (n + divisor - 1) / divisor
+    pub(crate) fn assert_valid(&self, pixels_len: usize) {
+        if let Err(err) = self.check(pixels_len) {
+            panic!("{}", err)
+        }
+    }
 }
@@ -1,7 +1,10 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek};
+use std::path::Path;
 use crate::Image;
 use crate::buf::OwnedBuf;
 use crate::compress::Compressor;
-use crate::common::{PixelFormat, Result, Subsamp};
+use crate::common::{Colorspace, Error, PixelFormat, Result, Subsamp};
 use crate::decompress::Decompressor;
 
 /// Decompresses image from JPEG into an [`image::ImageBuffer`].
@@ -43,6 +46,56 @@ pub fn decompress_image<P>(jpeg_data: &[u8]) -> Result<image::ImageBuffer<P, Vec
     Ok(image_buf)
 }
 
+/// Decompresses image from JPEG into an existing [`image::ImageBuffer`].
+///
+/// If the size of `image_buf` does not match the size of the JPEG image, the buffer is resized
+/// (reusing its allocation when possible). This is useful when decompressing a stream of frames
+/// in a loop, because you can reuse the same buffer for all frames instead of allocating a new
+/// one for each frame.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+///
+/// // the buffer is resized to the size of the JPEG image on the first frame
+/// let mut image = image::RgbImage::new(0, 0);
+/// for _ in 0..3 {
+///     turbojpeg::decompress_image_into(&jpeg_data, &mut image)?;
+///     assert_eq!(image.dimensions(), (384, 256));
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+pub fn decompress_image_into<P>(
+    jpeg_data: &[u8],
+    image_buf: &mut image::ImageBuffer<P, Vec<u8>>,
+) -> Result<()>
+    where P: JpegPixel + 'static
+{
+    let mut decompressor = Decompressor::new()?;
+    let header = decompressor.read_header(jpeg_data)?;
+
+    let width: u32 = header.width.try_into().map_err(|_| Error::IntegerOverflow("width"))?;
+    let height: u32 = header.height.try_into().map_err(|_| Error::IntegerOverflow("height"))?;
+    let pitch = header.width * P::PIXEL_FORMAT.size();
+    if image_buf.dimensions() != (width, height) {
+        let mut image_data = std::mem::replace(image_buf, image::ImageBuffer::new(0, 0)).into_raw();
+        image_data.resize(pitch * header.height, 0);
+        *image_buf = image::ImageBuffer::from_raw(width, height, image_data).unwrap();
+    }
+
+    let image = Image {
+        pixels: &mut **image_buf,
+        width: header.width,
+        pitch,
+        height: header.height,
+        format: P::PIXEL_FORMAT,
+    };
+    decompressor.decompress(jpeg_data, image)
+}
+
 /// Compresses an [`image::ImageBuffer`] into JPEG.
 ///
 /// `quality` controls the tradeoff between image quality and size of the compressed image. It
@@ -91,6 +144,234 @@ pub fn compress_image<P>(
     compressor.compress_to_owned(image)
 }
 
+/// Compresses a view of [`image::flat::FlatSamples`] into JPEG.
+///
+/// Unlike [`compress_image()`], the rows of the image do not need to be tightly packed: the row
+/// stride of the samples (`height_stride` in the [layout][image::flat::SampleLayout]) is used as
+/// the pitch. This means that you can compress a part of a larger image without copying it into a
+/// packed buffer. However, the pixels in each row must be packed; otherwise, returns
+/// [`Error::UnpackedSamples`].
+///
+/// See [`compress_image()`] for the meaning of `quality` and `subsamp`.
+///
+/// # Example
+///
+/// ```
+/// let image = image::RgbImage::from_fn(256, 256, |x, y| image::Rgb([x as u8, y as u8, 128]));
+///
+/// // describe the 128x64 region at (32, 16) without copying the pixels
+/// let mut samples = image.as_flat_samples();
+/// let offset = samples.layout.index(0, 32, 16).unwrap();
+/// samples.samples = &samples.samples[offset..];
+/// samples.layout.width = 128;
+/// samples.layout.height = 64;
+///
+/// let view = samples.as_view::<image::Rgb<u8>>()?;
+/// let jpeg_data = turbojpeg::compress_flat_samples(&view, 95, turbojpeg::Subsamp::Sub2x2)?;
+///
+/// let header = turbojpeg::Decompressor::new()?.read_header(&jpeg_data)?;
+/// assert_eq!((header.width, header.height), (128, 64));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+pub fn compress_flat_samples<P, B>(
+    view: &image::flat::View<B, P>,
+    quality: i32,
+    subsamp: Subsamp,
+) -> Result<OwnedBuf>
+    where P: JpegPixel + 'static, B: AsRef<[u8]>
+{
+    let samples = view.flat();
+    let layout = samples.layout;
+    if layout.channel_stride != 1 || layout.width_stride != layout.channels as usize {
+        return Err(Error::UnpackedSamples(layout.channel_stride, layout.width_stride))
+    }
+
+    let image = Image {
+        pixels: samples.samples.as_ref(),
+        width: layout.width as usize,
+        pitch: layout.height_stride,
+        height: layout.height as usize,
+        format: P::PIXEL_FORMAT,
+    };
+    image.check(image.pixels.len())?;
+
+    let mut compressor = Compressor::new()?;
+    compressor.set_quality(quality)?;
+    compressor.set_subsamp(subsamp)?;
+    compressor.compress_to_owned(image)
+}
+
+/// Reader for images in any format supported by `image`, which decodes JPEG images with
+/// TurboJPEG.
+///
+/// This is a drop-in replacement for [`image::io::Reader`]: if the format of the image is
+/// [JPEG][image::ImageFormat::Jpeg], the image is decoded with TurboJPEG (which is usually much
+/// faster than the JPEG decoder in `image`), and all other formats are delegated to
+/// `image::io::Reader`. In both cases, you get an [`image::DynamicImage`].
+///
+/// Grayscale JPEG images are decoded into [`image::DynamicImage::ImageLuma8`] and color images
+/// into [`image::DynamicImage::ImageRgb8`]. CMYK images cannot be converted to RGB by TurboJPEG,
+/// so they are delegated to `image` as well.
+///
+/// # Example
+///
+/// ```
+/// // the format is guessed from the file extension, just like with `image::io::Reader`
+/// let image = turbojpeg::ImageReader::open("examples/parrots.jpg")?.decode()?;
+/// assert_eq!((image.width(), image.height()), (384, 256));
+///
+/// // or from the content
+/// let data = std::fs::read("examples/parrots.jpg")?;
+/// let image = turbojpeg::ImageReader::new(std::io::Cursor::new(data))
+///     .with_guessed_format()?
+///     .decode()?;
+/// assert!(matches!(image, image::DynamicImage::ImageRgb8(_)));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+pub struct ImageReader<R: Read> {
+    inner: R,
+    format: Option<image::ImageFormat>,
+}
+
+impl<R: Read> ImageReader<R> {
+    /// Creates a new image reader without a preset format (see
+    /// [`image::io::Reader::new()`]).
+    pub fn new(buffered_reader: R) -> Self {
+        ImageReader { inner: buffered_reader, format: None }
+    }
+
+    /// Creates a new image reader with the given format (see
+    /// [`image::io::Reader::with_format()`]).
+    pub fn with_format(buffered_reader: R, format: image::ImageFormat) -> Self {
+        ImageReader { inner: buffered_reader, format: Some(format) }
+    }
+
+    /// Returns the format of the image, if it is known.
+    pub fn format(&self) -> Option<image::ImageFormat> {
+        self.format
+    }
+
+    /// Sets the format of the image.
+    pub fn set_format(&mut self, format: image::ImageFormat) {
+        self.format = Some(format);
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn into_image_reader(self) -> image::io::Reader<R> {
+        match self.format {
+            Some(format) => image::io::Reader::with_format(self.inner, format),
+            None => image::io::Reader::new(self.inner),
+        }
+    }
+}
+
+impl ImageReader<BufReader<File>> {
+    /// Opens a file, guessing the format from the path (see [`image::io::Reader::open()`]).
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let format = image::ImageFormat::from_path(path).ok();
+        Ok(ImageReader { inner: BufReader::new(File::open(path)?), format })
+    }
+}
+
+impl<R: BufRead + Seek> ImageReader<R> {
+    /// Guesses the format of the image from its content (see
+    /// [`image::io::Reader::with_guessed_format()`]).
+    pub fn with_guessed_format(self) -> io::Result<Self> {
+        let reader = self.into_image_reader().with_guessed_format()?;
+        Ok(ImageReader { format: reader.format(), inner: reader.into_inner() })
+    }
+
+    /// Returns the ICC color profile of the image, if it has one.
+    ///
+    /// This corresponds to `image::ImageDecoder::icc_profile()`, so that you don't lose the color
+    /// information when you switch to this reader. The profile is only read from JPEG images
+    /// (using [`read_icc_profile()`][crate::read_icc_profile]); for other formats, this returns
+    /// `None`. Call this method before [`decode()`][Self::decode].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut reader = turbojpeg::ImageReader::open("examples/parrots.jpg")?;
+    /// let icc_profile = reader.icc_profile()?;
+    /// let exif = reader.exif_metadata()?;
+    /// let image = reader.decode()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn icc_profile(&mut self) -> image::ImageResult<Option<Vec<u8>>> {
+        self.read_metadata(crate::metadata::read_icc_profile)
+    }
+
+    /// Returns the raw EXIF metadata of the image, if it has any.
+    ///
+    /// This corresponds to `image::ImageDecoder::exif_metadata()`. The metadata are only read from
+    /// JPEG images (using [`read_exif()`][crate::read_exif]); for other formats, this returns
+    /// `None`. Note that the image returned by [`decode()`][Self::decode] is not rotated
+    /// according to the EXIF orientation.
+    pub fn exif_metadata(&mut self) -> image::ImageResult<Option<Vec<u8>>> {
+        self.read_metadata(|jpeg_data| crate::metadata::read_exif(jpeg_data).map(|exif| exif.to_vec()))
+    }
+
+    /// Reads the remaining data from the reader, passes them to `f` and seeks back.
+    fn read_metadata<F>(&mut self, f: F) -> image::ImageResult<Option<Vec<u8>>>
+        where F: FnOnce(&[u8]) -> Option<Vec<u8>>
+    {
+        if self.format != Some(image::ImageFormat::Jpeg) {
+            return Ok(None)
+        }
+        let position = self.inner.stream_position()?;
+        let mut jpeg_data = Vec::new();
+        self.inner.read_to_end(&mut jpeg_data)?;
+        self.inner.seek(io::SeekFrom::Start(position))?;
+        Ok(f(&jpeg_data))
+    }
+
+    /// Decodes the image.
+    ///
+    /// JPEG images are decoded with TurboJPEG and other images with `image`.
+    pub fn decode(mut self) -> image::ImageResult<image::DynamicImage> {
+        if self.format != Some(image::ImageFormat::Jpeg) {
+            return self.into_image_reader().decode()
+        }
+
+        let mut jpeg_data = Vec::new();
+        self.inner.read_to_end(&mut jpeg_data)?;
+        let decoding_error = |err: Error| image::ImageError::Decoding(image::error::DecodingError::new(
+            image::error::ImageFormatHint::Exact(image::ImageFormat::Jpeg), err,
+        ));
+
+        let header = Decompressor::new()
+            .and_then(|mut decompressor| decompressor.read_header(&jpeg_data))
+            .map_err(decoding_error)?;
+        match header.colorspace {
+            Colorspace::Gray => decompress_image(&jpeg_data)
+                .map(image::DynamicImage::ImageLuma8)
+                .map_err(decoding_error),
+            Colorspace::CMYK | Colorspace::YCCK =>
+                image::load_from_memory_with_format(&jpeg_data, image::ImageFormat::Jpeg),
+            _ => decompress_image(&jpeg_data)
+                .map(image::DynamicImage::ImageRgb8)
+                .map_err(decoding_error),
+        }
+    }
+}
+
+impl<R: Read> From<image::io::Reader<R>> for ImageReader<R> {
+    /// Converts an `image::io::Reader`, keeping its format. Note that the decoding limits of the
+    /// `image::io::Reader` are not preserved.
+    fn from(reader: image::io::Reader<R>) -> Self {
+        ImageReader { format: reader.format(), inner: reader.into_inner() }
+    }
+}
+
 /// Trait implemented for [`image::Pixel`s][image::Pixel] that correspond to a [`PixelFormat`] supported
 /// by TurboJPEG.
 #[cfg_attr(docsrs, doc(cfg(feature = "image")))]
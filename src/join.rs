@@ -0,0 +1,416 @@
+use std::convert::TryInto as _;
+use std::panic::{self, AssertUnwindSafe};
+use crate::{Image, raw};
+use crate::buf::{OwnedBuf, OutputBuf};
+use crate::common::{Colorspace, Error, PixelFormat, Result, Subsamp};
+use crate::compress::Compressor;
+use crate::handle::Handle;
+use crate::markers::{self, FrameComponent};
+
+/// A JPEG tile placed into the image assembled by [`join()`].
+#[derive(Debug, Copy, Clone)]
+pub struct JoinTile<'a> {
+    /// The JPEG data of the tile.
+    pub jpeg_data: &'a [u8],
+    /// The left boundary of the tile in the assembled image (in pixels). Must be divisible by the
+    /// MCU width.
+    pub x: usize,
+    /// The upper boundary of the tile in the assembled image (in pixels). Must be divisible by the
+    /// MCU height.
+    pub y: usize,
+}
+
+/// Losslessly assembles JPEG tiles into a single JPEG image of size `width`×`height`.
+///
+/// This is the inverse of cropping with [`transform()`][crate::transform]: the DCT coefficients of
+/// the tiles are copied into the assembled image, without decompressing the tiles into pixels, so
+/// the result is exactly the same as if the tiles were cut from a larger image. This is useful for
+/// stitching images that were captured separately, such as strips from a scanner.
+///
+/// All tiles must use the same chrominance subsampling, the same colorspace (YCbCr or grayscale)
+/// and byte-identical quantization tables, which is usually the case for tiles produced by the
+/// same encoder with the same quality. The position of each tile must be aligned to the MCU
+/// size (see [`Subsamp::mcu_size()`]), the tile must fit into the image, and only the tiles on the
+/// right and bottom edges of the image may have a size that is not a multiple of the MCU size.
+///
+/// Parts of the image that are not covered by any tile are filled with gray. If the tiles overlap,
+/// later tiles overwrite earlier ones.
+///
+/// # Panics
+///
+/// Panics if `tiles` is empty.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "transform")] {
+/// // cut an image into left and right half...
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let header = turbojpeg::read_header(&jpeg_data)?;
+/// let (width, height) = (header.width, header.height);
+/// let mid = width / 2 / header.subsamp.mcu_width() * header.subsamp.mcu_width();
+///
+/// let mut left = turbojpeg::Transform::default();
+/// left.crop = Some(turbojpeg::TransformCrop::full_height(0, mid));
+/// let left = turbojpeg::transform(&left, &jpeg_data)?;
+/// let mut right = turbojpeg::Transform::default();
+/// right.crop = Some(turbojpeg::TransformCrop { x: mid, y: 0, width: None, height: None });
+/// let right = turbojpeg::transform(&right, &jpeg_data)?;
+///
+/// // ...and join them back together
+/// let joined = turbojpeg::join(&[
+///     turbojpeg::JoinTile { jpeg_data: &left, x: 0, y: 0 },
+///     turbojpeg::JoinTile { jpeg_data: &right, x: mid, y: 0 },
+/// ], width, height)?;
+///
+/// let original = turbojpeg::decompress(&jpeg_data, turbojpeg::PixelFormat::RGB)?;
+/// let joined = turbojpeg::decompress(&joined, turbojpeg::PixelFormat::RGB)?;
+/// assert_eq!(original.pixels, joined.pixels);
+/// # }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn join(tiles: &[JoinTile], width: usize, height: usize) -> Result<OwnedBuf> {
+    assert!(!tiles.is_empty(), "there must be at least one tile to join");
+    let mut handle = Handle::new(raw::TJINIT_TJINIT_TRANSFORM)?;
+
+    let first = read_tile_tables(tiles[0].jpeg_data).ok_or(Error::BadTile(0, "invalid JPEG header"))?;
+    let max_h = first.components.iter().map(|c| c.h).max().unwrap_or(1);
+    let max_v = first.components.iter().map(|c| c.v).max().unwrap_or(1);
+    let (mcu_width, mcu_height) = (8*max_h, 8*max_v);
+
+    let mut subsamp = Subsamp::None;
+    let mut placed = Vec::with_capacity(tiles.len());
+    for (i, tile) in tiles.iter().enumerate() {
+        let tables = read_tile_tables(tile.jpeg_data).ok_or(Error::BadTile(i, "invalid JPEG header"))?;
+        if tables.dqt != first.dqt {
+            return Err(Error::BadTile(i, "quantization tables differ from the first tile"))
+        }
+        if tables.components != first.components {
+            return Err(Error::BadTile(i, "components differ from the first tile"))
+        }
+
+        let (tile_width, tile_height, tile_subsamp, colorspace) = read_header(&mut handle, tile.jpeg_data)?;
+        if tile_subsamp == Subsamp::Unknown {
+            return Err(Error::BadTile(i, "unknown chrominance subsampling"))
+        }
+        if !matches!(colorspace, Colorspace::YCbCr | Colorspace::Gray) {
+            return Err(Error::BadTile(i, "colorspace is not YCbCr or grayscale"))
+        }
+        subsamp = tile_subsamp;
+
+        if tile.x % mcu_width != 0 || tile.y % mcu_height != 0 {
+            return Err(Error::BadTile(i, "position is not aligned to the MCU size"))
+        }
+        if tile.x > width || tile_width > width - tile.x ||
+            tile.y > height || tile_height > height - tile.y
+        {
+            return Err(Error::BadTile(i, "tile does not fit into the image"))
+        }
+        if (tile.x + tile_width < width && tile_width % mcu_width != 0) ||
+            (tile.y + tile_height < height && tile_height % mcu_height != 0)
+        {
+            return Err(Error::BadTile(i, "size of a tile inside the image is not a multiple of the MCU size"))
+        }
+
+        let mut capture = Capture { planes: Vec::new(), failed: false };
+        run_filter(&mut handle, tile.jpeg_data, capture_filter, &mut capture as *mut Capture as *mut _, 0)?;
+        if capture.failed || capture.planes.len() != first.components.len() {
+            return Err(Error::BadTile(i, "could not read the DCT coefficients"))
+        }
+        placed.push(PlacedTile { x: tile.x / mcu_width, y: tile.y / mcu_height, planes: capture.planes });
+    }
+
+    // create a blank image with the same structure as the tiles and replace its coefficients
+    let format = if subsamp == Subsamp::Gray { PixelFormat::GRAY } else { PixelFormat::RGB };
+    let blank = Image {
+        pixels: vec![0; width * height * format.size()],
+        width,
+        pitch: width * format.size(),
+        height,
+        format,
+    };
+    let mut compressor = Compressor::new()?;
+    compressor.set_subsamp(subsamp)?;
+    let canvas = compressor.compress_to_owned(blank.as_deref())?;
+
+    let mut fill = Fill { tiles: placed, components: first.components.clone(), failed: false };
+    let joined = run_filter(
+        &mut handle, &canvas, fill_filter, &mut fill as *mut Fill as *mut _, raw::TJXOPT_OPTIMIZE,
+    )?;
+    if fill.failed {
+        return Err(Error::BadTile(0, "could not write the DCT coefficients"))
+    }
+
+    // the coefficients are quantized with the tables of the tiles, so we must use their tables
+    let joined_tables = read_tile_tables(&joined).ok_or(Error::Null)?;
+    if joined_tables.components != first.components {
+        return Err(Error::BadTile(0, "assignment of quantization tables to components is not supported"))
+    }
+    let mut output = Vec::with_capacity(joined.len() + first.dqt.len());
+    let mut copied = 0;
+    for (i, &(begin, end)) in joined_tables.dqt_segments.iter().enumerate() {
+        output.extend_from_slice(&joined[copied..begin]);
+        if i == 0 {
+            output.extend_from_slice(&first.dqt);
+        }
+        copied = end;
+    }
+    output.extend_from_slice(&joined[copied..]);
+    Ok(OwnedBuf::copy_from_slice(&output))
+}
+
+/// Quantization tables and components of a JPEG image.
+#[derive(Debug)]
+struct TileTables {
+    /// Concatenated bytes of all DQT segments.
+    dqt: Vec<u8>,
+    /// Start and end offsets of all DQT segments.
+    dqt_segments: Vec<(usize, usize)>,
+    components: Vec<FrameComponent>,
+}
+
+fn read_tile_tables(jpeg_data: &[u8]) -> Option<TileTables> {
+    let mut tables = TileTables { dqt: Vec::new(), dqt_segments: Vec::new(), components: Vec::new() };
+    for segment in markers::read_header_segments(jpeg_data)? {
+        if segment.marker == markers::DQT {
+            tables.dqt.extend_from_slice(&jpeg_data[segment.offset..segment.end()]);
+            tables.dqt_segments.push((segment.offset, segment.end()));
+        } else if markers::is_sof(segment.marker) {
+            tables.components = markers::parse_frame_components(segment.data)?;
+        }
+    }
+    if tables.dqt_segments.is_empty() || tables.components.is_empty() {
+        return None
+    }
+    Some(tables)
+}
+
+fn read_header(handle: &mut Handle, jpeg_data: &[u8]) -> Result<(usize, usize, Subsamp, Colorspace)> {
+    let res = unsafe {
+        raw::tj3DecompressHeader(handle.as_ptr(), jpeg_data.as_ptr(), jpeg_data.len() as raw::size_t)
+    };
+    if res != 0 {
+        return Err(handle.get_error_in("join"))
+    }
+    let width = handle.get(raw::TJPARAM_TJPARAM_JPEGWIDTH) as usize;
+    let height = handle.get(raw::TJPARAM_TJPARAM_JPEGHEIGHT) as usize;
+    let subsamp = Subsamp::from_int(handle.get(raw::TJPARAM_TJPARAM_SUBSAMP))?;
+    let colorspace = Colorspace::from_int(handle.get(raw::TJPARAM_TJPARAM_COLORSPACE))?;
+    Ok((width, height, subsamp, colorspace))
+}
+
+pub(crate) type Filter = unsafe extern "C" fn(
+    *mut libc::c_short, raw::tjregion, raw::tjregion, libc::c_int, libc::c_int, *mut raw::tjtransform,
+) -> libc::c_int;
+
+/// Runs a lossless transform of `jpeg_data` that calls `filter` on the DCT coefficients.
+pub(crate) fn run_filter(
+    handle: &mut Handle,
+    jpeg_data: &[u8],
+    filter: Filter,
+    data: *mut libc::c_void,
+    options: libc::c_uint,
+) -> Result<OwnedBuf> {
+    let transform = raw::tjtransform {
+        r: raw::tjregion { x: 0, y: 0, w: 0, h: 0 },
+        op: raw::TJXOP_TJXOP_NONE as libc::c_int,
+        options: options as libc::c_int,
+        data,
+        customFilter: Some(filter),
+    };
+
+    handle.set(raw::TJPARAM_TJPARAM_NOREALLOC, 0)?;
+    let mut output = OutputBuf::new_owned();
+    let mut output_len = output.len as raw::size_t;
+    let res = unsafe {
+        raw::tj3Transform(
+            handle.as_ptr(),
+            jpeg_data.as_ptr(), jpeg_data.len() as raw::size_t,
+            1, &mut output.ptr, &mut output_len,
+            &transform,
+        )
+    };
+    output.len = output_len as usize;
+    if res != 0 {
+        return Err(handle.get_error_in("join"))
+    } else if output.ptr.is_null() {
+        output.len = 0;
+        return Err(Error::Null)
+    }
+    Ok(output.into_owned())
+}
+
+/// DCT coefficients of one component, 64 coefficients per block, blocks in row-major order.
+#[derive(Debug, Default)]
+pub(crate) struct Plane {
+    pub width_in_blocks: usize,
+    pub height_in_blocks: usize,
+    pub coeffs: Vec<i16>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Capture {
+    pub planes: Vec<Plane>,
+    pub failed: bool,
+}
+
+#[derive(Debug)]
+struct PlacedTile {
+    /// Position of the tile in MCUs.
+    x: usize,
+    y: usize,
+    planes: Vec<Plane>,
+}
+
+#[derive(Debug)]
+struct Fill {
+    tiles: Vec<PlacedTile>,
+    components: Vec<FrameComponent>,
+    failed: bool,
+}
+
+/// Position and size of the block rows passed to a custom filter.
+pub(crate) struct FilterRegion {
+    /// Component index.
+    pub component: usize,
+    /// First block column and row of the array in the component plane.
+    pub x: usize,
+    pub y: usize,
+    /// Number of block columns and rows in the array.
+    pub width: usize,
+    pub height: usize,
+    /// Size of the component plane in blocks.
+    pub plane_width: usize,
+    pub plane_height: usize,
+}
+
+impl FilterRegion {
+    pub(crate) fn new(array_region: raw::tjregion, plane_region: raw::tjregion, component: libc::c_int) -> Option<Self> {
+        let blocks = |pixels: libc::c_int| -> Option<usize> { usize::try_from(pixels).ok().map(|pixels| pixels / 8) };
+        Some(FilterRegion {
+            component: component.try_into().ok()?,
+            x: blocks(array_region.x)?,
+            y: blocks(array_region.y)?,
+            width: blocks(array_region.w)?,
+            height: blocks(array_region.h)?,
+            plane_width: blocks(plane_region.w)?,
+            plane_height: blocks(plane_region.h)?,
+        })
+    }
+}
+
+// TurboJPEG calls the filters from C code, so they must never unwind; instead, they report errors
+// by setting the `failed` flag and returning -1. Panics are caught by `catch_filter_panic()`.
+
+/// Runs the body of a filter that is called from C code.
+///
+/// Unwinding across the C frames is undefined behavior, so panics are caught and their message is
+/// returned as an error.
+pub(crate) fn catch_filter_panic<R>(body: impl FnOnce() -> R) -> std::result::Result<R, String> {
+    panic::catch_unwind(AssertUnwindSafe(body)).map_err(|payload| {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".into()
+        }
+    })
+}
+
+pub(crate) unsafe extern "C" fn capture_filter(
+    coeffs: *mut libc::c_short,
+    array_region: raw::tjregion,
+    plane_region: raw::tjregion,
+    component: libc::c_int,
+    _transform_id: libc::c_int,
+    transform: *mut raw::tjtransform,
+) -> libc::c_int {
+    let capture = &mut *((*transform).data as *mut Capture);
+    let region = match FilterRegion::new(array_region, plane_region, component) {
+        Some(region) => region,
+        None => { capture.failed = true; return -1 },
+    };
+    let array = std::slice::from_raw_parts(coeffs, region.width * region.height * 64);
+    match catch_filter_panic(|| capture_rows(capture, &region, array)) {
+        Ok(Some(())) => 0,
+        Ok(None) | Err(_) => { capture.failed = true; -1 },
+    }
+}
+
+fn capture_rows(capture: &mut Capture, region: &FilterRegion, array: &[i16]) -> Option<()> {
+    while capture.planes.len() <= region.component {
+        capture.planes.push(Plane::default());
+    }
+    let plane = &mut capture.planes[region.component];
+    if plane.coeffs.is_empty() {
+        plane.width_in_blocks = region.plane_width;
+        plane.height_in_blocks = region.plane_height;
+        plane.coeffs = vec![0; region.plane_width * region.plane_height * 64];
+    }
+
+    for row in 0..region.height {
+        let plane_row = region.y + row;
+        if plane_row >= plane.height_in_blocks {
+            // dummy blocks below the image
+            break
+        }
+        let src = array.get(row * region.width * 64..)?.get(..region.width * 64)?;
+        let dst = plane.coeffs
+            .get_mut((plane_row * plane.width_in_blocks + region.x) * 64..)?
+            .get_mut(..region.width * 64)?;
+        dst.copy_from_slice(src);
+    }
+    Some(())
+}
+
+unsafe extern "C" fn fill_filter(
+    coeffs: *mut libc::c_short,
+    array_region: raw::tjregion,
+    plane_region: raw::tjregion,
+    component: libc::c_int,
+    _transform_id: libc::c_int,
+    transform: *mut raw::tjtransform,
+) -> libc::c_int {
+    let fill = &mut *((*transform).data as *mut Fill);
+    let region = match FilterRegion::new(array_region, plane_region, component) {
+        Some(region) => region,
+        None => { fill.failed = true; return -1 },
+    };
+    let array = std::slice::from_raw_parts_mut(coeffs, region.width * region.height * 64);
+    match catch_filter_panic(|| fill_rows(fill, &region, array)) {
+        Ok(Some(())) => 0,
+        Ok(None) | Err(_) => { fill.failed = true; -1 },
+    }
+}
+
+fn fill_rows(fill: &Fill, region: &FilterRegion, array: &mut [i16]) -> Option<()> {
+    let component = fill.components.get(region.component)?;
+    array.fill(0);
+
+    for tile in fill.tiles.iter() {
+        let plane = tile.planes.get(region.component)?;
+        let tile_x = tile.x * component.h;
+        let tile_y = tile.y * component.v;
+        for row in 0..region.height {
+            let plane_row = region.y + row;
+            if plane_row < tile_y || plane_row >= tile_y + plane.height_in_blocks {
+                continue
+            }
+
+            // intersect the block columns of the tile with the block columns of the array
+            let begin = tile_x.max(region.x);
+            let end = (tile_x + plane.width_in_blocks).min(region.x + region.width);
+            if begin >= end {
+                continue
+            }
+            let src_offset = ((plane_row - tile_y) * plane.width_in_blocks + begin - tile_x) * 64;
+            let src = plane.coeffs.get(src_offset..)?.get(..(end - begin) * 64)?;
+            let dst_offset = (row * region.width + begin - region.x) * 64;
+            let dst = array.get_mut(dst_offset..)?.get_mut(..(end - begin) * 64)?;
+            dst.copy_from_slice(src);
+        }
+    }
+    Some(())
+}
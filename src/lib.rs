@@ -3,10 +3,10 @@
 //! - [Compression][compress()]: encode images into JPEG.
 //! - [Decompression][decompress()]: decode JPEGs into pixels.
 //! - [Lossless transformations][transform()]: apply basic geometric transformations (rotate, mirror,
-//! ...) without going through decompression and compression, so that the image does not lose
-//! quality.
+//!   ...) without going through decompression and compression, so that the image does not lose
+//!   quality.
 //! - [Decompression into YUV][decompress_to_yuv()]: decode JPEG into YUV (YCbCr), without
-//! performing the color transform into RGB.
+//!   performing the color transform into RGB.
 //!
 //! # Integration with image-rs (version 0.24)
 //! 
@@ -34,7 +34,7 @@
 //! 
 //! - [`image::RgbImage`]
 //! - [`image::RgbaImage`] (JPEG does not support alpha channel, so alpha is ignored when encoding
-//! and set to 255 when decoding)
+//!   and set to 255 when decoding)
 //! - [`image::GrayImage`]
 //! 
 //! [image-rs]: https://docs.rs/image/*/image/index.html
@@ -49,16 +49,96 @@
 //! argument for compression, `Image<&out [u8]>` as output argument for decompression, and you may
 //! also find `Image<Vec<u8>>` useful as an owned container of image data in you application.
 //!
+//! If the pixels live in memory that is not managed by Rust (for example, in shared memory mapped
+//! from another process), you can create the image using [`Image::from_raw_parts_mut()`] and
+//! decompress directly into that memory. [`Image::with_aligned_pitch()`] helps you to compute the
+//! layout of images with aligned rows, and [`Image::allocate_aligned()`] allocates such an image in
+//! an [`AlignedBuf`], which speeds up the SIMD color conversion in TurboJPEG. GUI toolkits that
+//! work with `u32` pixels (such as `0xAARRGGBB`) can use [`PixelFormat::from_u32_order()`] and
+//! [`Image::pixels_u32()`].
+//!
+//! If you know the pixel format at compile time, you can use
+//! [`TypedImage`][typed::TypedImage] from the [`typed`] module, which encodes the pixel format in
+//! the type and checks the layout of the image when it is created.
+//!
 //! # Operations
 //!
 //! - **Decompress** images from JPEG using [`decompress()`] or [`Decompressor`].
 //! - **Compress** images into JPEG using [`compress()`] or [`Compressor`].
 //! - **Transform** images without recompression using [`transform()`] or [`Transformer`]. The
-//! transformations are described in the [`Transform`] struct.
+//!   transformations are described in the [`Transform`] struct.
+//! - **Modify the DCT coefficients** during a transformation using
+//!   [`Transformer::transform_with_filter()`].
+//! - **Convert** images **to grayscale** without recompression using [`to_grayscale()`].
+//! - **Join** MCU-aligned tiles into a single image without recompression using [`join()`].
 //! - **Read header** of JPEG image to get its size without decompression using
-//! [`Decompressor::read_header()`] or [`read_header()`].
+//!   [`Decompressor::read_header()`] or [`read_header()`]. If the data may be incomplete, use
+//!   [`read_header_partial()`] to find out whether more data is needed.
+//! - **Validate** the structure of JPEG data without decompression using [`validate()`], which
+//!   reports the offset of the first problem (such as truncation).
+//! - **Find JPEG images embedded** in other data (such as previews in camera RAW files) using
+//!   [`find_jpeg()`] and [`find_jpegs()`].
+//! - **Read metadata** (the ICC color profile and EXIF) using [`read_icc_profile()`] and
+//!   [`read_exif()`], and **extract the embedded thumbnail** using [`extract_thumbnail()`].
+//! - **Remove private metadata** (such as the GPS location) without recompression using
+//!   [`scrub_metadata()`].
+//! - **Recompress** images **preserving their metadata** using
+//!   [`recompress_preserving_metadata()`].
+//! - **Estimate the quality** of an existing JPEG image using [`estimate_quality()`].
+//! - **Inspect** the quantization and Huffman **tables** using [`read_quant_tables()`] and
+//!   [`read_huffman_tables()`].
+//! - **Read the DCT coefficients** and compute their **statistics** (histograms and blockiness) using
+//!   [`read_coefficients()`] and [`coefficient_stats()`].
+//! - **Decompress** a **scaled** or **cropped** image using [`decompress_region()`] or
+//!   [`Decompressor::decompress_region()`].
+//! - **Decompress** images **resized** to an exact size (such as thumbnails) using
+//!   [`decompress_resized()`] (with the `resize` feature).
+//! - **Decompress** large images **in parallel** (if they contain restart markers) using
+//!   [`decompress_parallel()`].
+//! - **Decompress** large images **in strips** of rows with constant memory using
+//!   [`Decompressor::decompress_strips()`].
+//! - **Compress** images produced **in strips** of rows using [`StripCompressor`].
+//! - **Recompress** images from a reader to a writer **with bounded memory** using
+//!   [`recompress_stream()`].
+//! - **Compare** images and visualize their **differences** using [`diff()`], [`diff_stats()`]
+//!   and [`ssim()`].
+//! - **Compress** images with the **lowest quality** that reaches a structural similarity (SSIM)
+//!   threshold using [`Compressor::compress_to_quality_metric()`].
+//! - **Hash** the decompressed pixels to find **duplicate** images using [`hash_pixels()`].
+//! - **Decompress** images **into normalized `f32` tensors** for machine learning models using
+//!   [`decompress_to_tensor()`] or [`Decompressor::decompress_to_tensor()`].
+//! - **Decompress** images with an **arbitrary order of channels** or into **separate planes** using
+//!   [`Decompressor::decompress_swizzled()`] and [`Decompressor::decompress_planar()`].
 //! - **Decompress** images **into YUV** using [`decompress_to_yuv()`] or [`Decompressor`].
+//! - **Compress** images with **floating-point samples** using [`Compressor::compress_float()`].
 //! - **Compress** images **from YUV** using [`compress_yuv()`] or [`Compressor`].
+//! - **Read and write Motion JPEG videos** in AVI files using the [`avi`] module.
+//! - **Split and assemble multi-picture (MPO) files**, such as stereo pairs, using the [`mpo`]
+//!   module.
+//! - **Generate tile pyramids** for deep-zoom viewers using the [`pyramid`] module.
+//!
+//! Long-running operations (such as [`Compressor::compress_batch_cancellable()`]) can be aborted
+//! using a [`CancelToken`], and some of them report their [`Progress`] to a callback (such as
+//! [`Compressor::compress_batch_with_progress()`]).
+//!
+//! With the `metrics` feature, the crate counts the compressed and decompressed images (and their
+//! bytes and time) in global counters, which you can read using `metrics()` and export to your
+//! monitoring system (such as Prometheus).
+//!
+//! When you process untrusted images, you can limit the resources used by TurboJPEG (the number of
+//! pixels, memory, progressive scans and output size) using [`Limits`].
+//!
+//! The parameters of a compressor can be captured in [`Settings`], which can be applied to other
+//! compressors using [`Compressor::apply()`] (and, with the `serde` feature, loaded from
+//! configuration files).
+//!
+//! To make sure that you did not ship a slow build of TurboJPEG without the SIMD extensions, you
+//! can check [`simd_status()`] at startup. The features that are supported by the linked library
+//! (such as lossless JPEG, which is missing in TurboJPEG 2) are described by [`capabilities()`].
+//!
+//! If you need to replace the codec (for example, with a mock in tests), you can write your code
+//! against the [`JpegEncode`], [`JpegDecode`] and [`JpegTransform`] traits, which are implemented
+//! by [`Compressor`], [`Decompressor`] and [`Transformer`].
 //! 
 //! # The [`OutputBuf`] and [`OwnedBuf`] types
 //!
@@ -66,26 +146,81 @@
 //! two options:
 //!
 //! - Write the data into a mutable slice (`&mut [u8]`) that you already allocated and initialized.
-//! This has the disadvantage that you must allocate all memory up front, so you need to make the
-//! buffer very large to ensure that it can hold the compressed image even in the worst case, when
-//! the compression does not reduce the image size at all. You will also need to initialize the
-//! memory to comply with the Rust safety requirements.
+//!   This has the disadvantage that you must allocate all memory up front, so you need to make the
+//!   buffer very large to ensure that it can hold the compressed image even in the worst case, when
+//!   the compression does not reduce the image size at all. You will also need to initialize the
+//!   memory to comply with the Rust safety requirements.
 //!
 //! - Write the data into a memory buffer managed by TurboJPEG. This has the advantage that
-//! TurboJPEG can automatically resize the buffer, so we don't have to conservatively allocate and
-//! initialize a large chunk of memory, but we can let TurboJPEG grow the buffer as needed. This
-//! kind of buffer is exposed as the [`OwnedBuf`].
+//!   TurboJPEG can automatically resize the buffer, so we don't have to conservatively allocate and
+//!   initialize a large chunk of memory, but we can let TurboJPEG grow the buffer as needed. This
+//!   kind of buffer is exposed as the [`OwnedBuf`].
 //!
 //! To handle both of these cases, this crate provides the [`OutputBuf`] type, which can hold
 //! either a `&mut [u8]` or an `OwnedBuf`.
 //!
+//! When you process many images, you can reuse the memory for decompressed images and compressed
+//! data using a [`BufferPool`].
+//!
+//! If you need to pass the compressed data to multiple consumers, you can convert the `OwnedBuf`
+//! into a [`SharedBuf`], which is reference-counted and can be cloned without copying the data.
+//!
 //! # Features
 //!
 //! - `image`: enables the optional dependency on the [`image`][image-rs] crate.
+//! - `yuv` (default): enables compression from and decompression into planar YUV images
+//!   (`YuvImage`, `compress_yuv()`, `decompress_to_yuv()` and the `convert` module).
+//! - `transform` (default): enables lossless transformations (`Transformer`, `transform()`,
+//!   `to_grayscale()` and the `JpegTransform` trait).
 //! - `pkg-config`: uses pkg-config to find the `libturbojpeg` library.
+//! - `download`: allows downloading the TurboJPEG source instead of using the bundled copy (with
+//!   `TURBOJPEG_SOURCE=download`).
 //! - `bindgen`: uses [bindgen] to generate the `libturbojpeg` bindings.
+//! - `compat-2x`: allows linking to TurboJPEG 2.0 or 2.1 (as shipped by many Linux distributions)
+//!   instead of TurboJPEG 3. Features that are missing from TurboJPEG 2 (such as lossless JPEG,
+//!   partial decompression or [`Compressor::set_optimize()`]) return an error.
+//! - `jpeglib`: also generates bindings for the libjpeg API of the linked library (the
+//!   `raw::jpeglib` module) for advanced uses that TurboJPEG does not cover, such as custom source
+//!   and destination managers. Implies `bindgen`.
+//! - `wgpu`: adds helpers for decompressing images into wgpu staging buffers
+//!   (`Decompressor::decompress_to_staging()`).
+//! - `test-util`: enables the [`test_util`][mod@crate::test_util] module with generators of
+//!   synthetic images and roundtrip assertions for tests.
+//! - `pipeline`: enables the [`pipeline`][mod@crate::pipeline] module, which compresses a stream
+//!   of images (such as video frames) on multiple threads.
+//! - `resize`: allows decompressing images resized to an exact size, using DCT scaling followed
+//!   by resampling (`decompress_resized()` and `Decompressor::decompress_resized()`).
+//! - `metrics`: maintains global counters of compressed and decompressed images, bytes and time
+//!   (`metrics()`), which services can export to their monitoring.
+//! - `bumpalo`: allows decompressing images into memory allocated in a [`bumpalo`][bumpalo] arena
+//!   (`Decompressor::decompress_in()` and `Decompressor::decompress_to_yuv_in()`).
+//! - `half`: allows decompressing images into [`f16`][half::f16] tensors (see
+//!   [`decompress_to_tensor()`]) and compressing images with `f16` samples (see
+//!   [`quantize_float()`]).
+//! - `log`: logs the warnings that TurboJPEG reports about corrupted data using the [`log`][log]
+//!   crate (which can be forwarded to [`tracing`][tracing]), together with an identifier of the
+//!   image (`Decompressor::set_image_id()`).
+//! - `serde`: implements `Serialize` and `Deserialize` for [`Settings`], [`Limits`] and
+//!   [`Subsamp`].
+//! - `tools`: builds the `turbojpeg-cli` binary with `compress`, `decompress`, `transform` and
+//!   `inspect` subcommands (install it with `cargo install turbojpeg --features tools`).
+//! - `arbitrary`: implements [`Arbitrary`][arbitrary] for `Transform` and related types, for
+//!   use in fuzzing (enables `transform`).
+//!
+//! If you do not need YUV images or transformations (for example, on constrained embedded
+//! targets), you can disable the default features to reduce the code size, but remember to enable
+//! the default features that select how TurboJPEG is built (`cmake`, `pkg-config` and
+//! `require-simd`):
+//!
+//! ```toml
+//! turbojpeg = {version = "1.2", default-features = false, features = ["cmake", "require-simd"]}
+//! ```
 //!
 //! [bindgen]: https://rust-lang.github.io/rust-bindgen/
+//! [bumpalo]: https://docs.rs/bumpalo/3/bumpalo/
+//! [arbitrary]: https://docs.rs/arbitrary/1/arbitrary/trait.Arbitrary.html
+//! [log]: https://docs.rs/log/0.4/log/
+//! [tracing]: https://docs.rs/tracing/0.1/tracing/
 #![warn(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
@@ -93,22 +228,128 @@ pub extern crate turbojpeg_sys as raw;
 pub extern crate libc;
 #[cfg(feature = "image")]
 pub extern crate image as image;
+#[cfg(feature = "wgpu")]
+pub extern crate wgpu_types;
+#[cfg(feature = "half")]
+pub extern crate half;
 
+pub mod avi;
 mod buf;
+mod cancel;
+mod capabilities;
+mod codec;
+mod coefficients;
 mod common;
 mod compress;
+#[cfg(feature = "yuv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "yuv")))]
+pub mod convert;
 mod decompress;
+mod diff;
+mod float;
 mod handle;
+mod hash;
 mod image_internal;
+mod join;
+mod limits;
+mod markers;
+mod metadata;
+mod metrics;
+pub mod mpo;
+mod parallel;
+mod pool;
+mod progress;
+pub mod pyramid;
+mod search;
+mod settings;
+mod simd;
+mod stream;
+mod swizzle;
+mod tables;
+mod tensor;
+#[cfg(feature = "transform")]
 mod transform;
-pub use self::buf::{OwnedBuf, OutputBuf};
-pub use self::common::{PixelFormat, Subsamp, Colorspace, Result, Error};
-pub use self::compress::{Compressor, compress, compress_yuv, compressed_buf_len};
-pub use self::decompress::{Decompressor, DecompressHeader, decompress, read_header, decompress_to_yuv, yuv_pixels_len};
-pub use self::image_internal::{Image, YuvImage};
-pub use self::transform::{Transformer, Transform, TransformOp, TransformCrop, transform};
+pub mod typed;
+mod validate;
+pub use self::buf::{AlignedBuf, OwnedBuf, OutputBuf, SharedBuf};
+pub use self::cancel::CancelToken;
+pub use self::capabilities::{Capabilities, capabilities};
+pub use self::codec::{JpegEncode, JpegDecode};
+pub use self::coefficients::{
+    CoefficientHistogram, ComponentCoefficients, ComponentStats, coefficient_stats, read_coefficients,
+};
+pub use self::common::{PixelFormat, PixelLayout, Subsamp, Colorspace, Result, Error};
+pub use self::compress::{Compressor, StripCompressor, compress, compressed_buf_len};
+pub use self::decompress::{
+    Decompressor, DecompressHeader, HeaderStatus, ScalingFactor, DecompressCrop,
+    decompress, decompress_region, read_header, read_header_partial,
+};
+pub use self::diff::{DiffStats, diff, diff_amplified, diff_stats, ssim};
+pub use self::float::{Dithering, FloatQuantization, FloatSample, quantize_float};
+pub use self::hash::{PixelHash, hash_pixels};
+pub use self::image_internal::Image;
+pub use self::join::{JoinTile, join};
+pub use self::limits::Limits;
+pub use self::metadata::{
+    ScrubPolicy, extract_thumbnail, read_exif, read_icc_profile, recompress_preserving_metadata, scrub_metadata,
+};
+pub use self::parallel::decompress_parallel;
+pub use self::pool::BufferPool;
+pub use self::progress::Progress;
+pub use self::search::QualitySearch;
+pub use self::settings::Settings;
+pub use self::simd::{SimdStatus, simd_status};
+pub use self::stream::recompress_stream;
+pub use self::swizzle::SwizzleChannel;
+pub use self::tables::{
+    HuffmanClass, HuffmanTable, QuantTable, estimate_quality, read_huffman_tables, read_quant_tables,
+};
+pub use self::tensor::{Tensor, TensorElement, TensorFormat, TensorLayout, decompress_to_tensor};
+pub use self::validate::{Problem, ProblemKind, ValidationReport, find_jpeg, find_jpegs, validate};
+
+#[cfg(feature = "yuv")]
+pub use self::compress::compress_yuv;
+#[cfg(feature = "yuv")]
+pub use self::decompress::{decompress_to_yuv, yuv_pixels_len};
+#[cfg(feature = "yuv")]
+pub use self::image_internal::YuvImage;
+
+#[cfg(feature = "transform")]
+pub use self::codec::JpegTransform;
+#[cfg(feature = "transform")]
+pub use self::transform::{
+    Transformer, Transform, TransformOp, TransformCrop, TransformReport, CoefficientBlocks, transform,
+    transform_into, transform_to_slice, to_grayscale,
+};
 
 #[cfg(feature = "image")]
 mod image_rs;
 #[cfg(feature = "image")]
-pub use self::image_rs::{JpegPixel, compress_image, decompress_image};
+pub use self::image_rs::{
+    ImageReader, JpegPixel, compress_flat_samples, compress_image, decompress_image,
+    decompress_image_into,
+};
+
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod test_util;
+
+#[cfg(feature = "pipeline")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pipeline")))]
+pub mod pipeline;
+
+#[cfg(feature = "metrics")]
+pub use self::metrics::{Metrics, metrics};
+
+#[cfg(feature = "resize")]
+mod resize;
+#[cfg(feature = "resize")]
+pub use self::resize::decompress_resized;
+
+#[cfg(feature = "bumpalo")]
+mod bump;
+
+#[cfg(feature = "wgpu")]
+mod wgpu_rs;
+#[cfg(feature = "wgpu")]
+pub use self::wgpu_rs::{StagingLayout, texture_format};
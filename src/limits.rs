@@ -0,0 +1,216 @@
+use std::sync::RwLock;
+use crate::common::{Error, Result, Subsamp};
+use crate::markers::{self, Segment};
+
+/// Limits on the resources used by [`Compressor`][crate::Compressor],
+/// [`Decompressor`][crate::Decompressor] and [`Transformer`][crate::Transformer].
+///
+/// When you process untrusted images (for example, in a web service), a small JPEG file can
+/// declare a huge image or contain thousands of progressive scans, which take a lot of memory and
+/// time to decompress. The limits reject such images with [`Error::LimitExceeded`] before (or, for
+/// `max_output_bytes` in compression, after) the work is done. Every limit is optional, `None`
+/// means no limit. The default limits are [`Limits::NONE`].
+///
+/// Use `set_limits()` on the [compressor][crate::Compressor::set_limits],
+/// [decompressor][crate::Decompressor::set_limits] or
+/// [transformer][crate::Transformer::set_limits] to limit a single instance, or
+/// [`Limits::set_global()`] to set the limits of all instances that are created afterwards,
+/// including the instances that are created by the convenience functions such as
+/// [`decompress()`][crate::decompress()].
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{Error, Limits, PixelFormat};
+///
+/// let limits = Limits {
+///     max_pixels: Some(10_000),
+///     max_scans: Some(100),
+///     ..Limits::NONE
+/// };
+///
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let mut decompressor = turbojpeg::Decompressor::new()?;
+/// decompressor.set_limits(limits)?;
+///
+/// let mut image = turbojpeg::Image::mandelbrot(384, 256, PixelFormat::RGB);
+/// let result = decompressor.decompress(&jpeg_data, image.as_deref_mut());
+/// assert!(matches!(result, Err(Error::LimitExceeded("max_pixels", 98304, 10_000))));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Limits {
+    /// Maximal number of pixels (width × height) of the compressed, decompressed or transformed
+    /// image. For decompression and transformation, this is the size of the JPEG image declared in
+    /// its header (regardless of scaling and cropping).
+    ///
+    /// This is the equivalent of `TJPARAM_MAXPIXELS` of newer TurboJPEG versions (which is not
+    /// available in the bundled TurboJPEG 3.0.1). Images that declare more pixels are rejected
+    /// with [`Error::LimitExceeded`] with the name `"max_pixels"` before any memory is allocated
+    /// for them, which protects against "decompression bombs": tiny files that declare a huge
+    /// image.
+    #[doc(alias = "TJPARAM_MAXPIXELS")]
+    pub max_pixels: Option<usize>,
+    /// Maximal estimated memory in bytes that TurboJPEG needs to process the image. The estimate
+    /// is dominated by the DCT coefficients of the whole image, which TurboJPEG must hold in memory
+    /// to decompress progressive images, to compress progressive or optimized images and to
+    /// transform images; other images are processed a few rows at a time. The output buffers are
+    /// not included.
+    ///
+    /// Unlike `TJPARAM_MAXMEMORY` of newer TurboJPEG versions (which is not available in the
+    /// bundled TurboJPEG 3.0.1), the limit is given in bytes rather than megabytes, and it is
+    /// checked before TurboJPEG allocates any memory, so it works with every version of the
+    /// library.
+    #[doc(alias = "TJPARAM_MAXMEMORY")]
+    pub max_memory: Option<usize>,
+    /// Maximal number of scans in a progressive JPEG image that is decompressed or transformed
+    /// (TurboJPEG returns an error when it reaches a scan over the limit, and `Some(0)` is treated
+    /// as `Some(1)`). Images with many tiny scans are a common way to make decompression very
+    /// slow.
+    ///
+    /// This limit is enforced by TurboJPEG itself (as `TJPARAM_SCANLIMIT`), so the rest of the
+    /// image is not decoded, and the error is returned as [`Error::TurboJpegError`] rather than
+    /// [`Error::LimitExceeded`].
+    ///
+    /// With the `compat-2x` feature, TurboJPEG 2 can only limit the number of scans to 500
+    /// (`TJFLAG_LIMITSCANS`), so other values of `max_scans` are rejected with an error when the
+    /// limits are set (see [`Capabilities::scan_limit`][crate::Capabilities::scan_limit]).
+    ///
+    /// ```
+    /// # if !turbojpeg::capabilities().scan_limit { return Ok(()) }
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// decompressor.set_limits(turbojpeg::Limits { max_scans: Some(2), ..turbojpeg::Limits::NONE })?;
+    ///
+    /// // the image is progressive with more than 2 scans
+    /// let mut image = turbojpeg::Image::mandelbrot(384, 256, turbojpeg::PixelFormat::RGB);
+    /// let result = decompressor.decompress(&jpeg_data, image.as_deref_mut());
+    /// assert!(matches!(result, Err(turbojpeg::Error::TurboJpegError(_))));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[doc(alias = "TJPARAM_SCANLIMIT")]
+    pub max_scans: Option<usize>,
+    /// Maximal size of the output in bytes: the size of the decompressed pixels (without padding)
+    /// or of the compressed or transformed JPEG data. The size of the compressed data is known only
+    /// after compression, so the compression is done before the error is returned.
+    pub max_output_bytes: Option<usize>,
+}
+
+static GLOBAL_LIMITS: RwLock<Limits> = RwLock::new(Limits::NONE);
+
+impl Limits {
+    /// No limits.
+    pub const NONE: Limits = Limits {
+        max_pixels: None,
+        max_memory: None,
+        max_scans: None,
+        max_output_bytes: None,
+    };
+
+    /// Returns the limits that are used by new compressors, decompressors and transformers.
+    pub fn global() -> Limits {
+        *GLOBAL_LIMITS.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Sets the limits that are used by new compressors, decompressors and transformers.
+    ///
+    /// The existing instances are not affected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // reject images larger than 50 megapixels in the whole process
+    /// turbojpeg::Limits::set_global(turbojpeg::Limits {
+    ///     max_pixels: Some(50_000_000),
+    ///     ..turbojpeg::Limits::global()
+    /// });
+    /// ```
+    pub fn set_global(limits: Limits) {
+        *GLOBAL_LIMITS.write().unwrap_or_else(|err| err.into_inner()) = limits;
+    }
+
+    pub(crate) fn check_pixels(&self, width: usize, height: usize) -> Result<()> {
+        check("max_pixels", width.saturating_mul(height), self.max_pixels)
+    }
+
+    pub(crate) fn check_memory(&self, memory: usize) -> Result<()> {
+        check("max_memory", memory, self.max_memory)
+    }
+
+    pub(crate) fn check_output_bytes(&self, bytes: usize) -> Result<()> {
+        check("max_output_bytes", bytes, self.max_output_bytes)
+    }
+
+    /// Checks the limits that can be determined from the header of `jpeg_data` before it is
+    /// decompressed or transformed.
+    pub(crate) fn check_jpeg(&self, jpeg_data: &[u8], whole_image: bool) -> Result<()> {
+        if self.max_pixels.is_none() && self.max_memory.is_none() {
+            return Ok(())
+        }
+        // if the header cannot be parsed, TurboJPEG will report the error
+        let Some(frame) = read_frame(jpeg_data) else { return Ok(()) };
+        self.check_pixels(frame.width, frame.height)?;
+        let progressive = matches!(frame.marker, 0xc2 | 0xc6 | 0xca | 0xce);
+        self.check_memory(frame.memory(whole_image || progressive))
+    }
+}
+
+fn check(limit: &'static str, value: usize, max: Option<usize>) -> Result<()> {
+    match max {
+        Some(max) if value > max => Err(Error::LimitExceeded(limit, value, max)),
+        _ => Ok(()),
+    }
+}
+
+/// Geometry of a JPEG image from its start-of-frame (SOF) segment.
+pub(crate) struct Frame {
+    pub marker: u8,
+    pub width: usize,
+    pub height: usize,
+    /// Sampling factors of the components.
+    pub sampling: Vec<(usize, usize)>,
+}
+
+impl Frame {
+    /// Returns the geometry of an image that is compressed with the given subsampling.
+    pub fn for_compression(width: usize, height: usize, subsamp: Subsamp, components: usize) -> Frame {
+        let mut sampling = vec![subsamp.size()];
+        if subsamp != Subsamp::Gray {
+            sampling.resize(components.max(1), (1, 1));
+            // the K component of CMYK (YCCK) images is not subsampled, just like Y
+            if components == 4 {
+                sampling[3] = subsamp.size();
+            }
+        }
+        Frame { marker: 0, width, height, sampling }
+    }
+
+    /// Estimates the memory for the DCT coefficients (2 bytes each) and samples (1 byte each) of
+    /// the whole image, or of a single row of MCUs.
+    pub fn memory(&self, whole_image: bool) -> usize {
+        let max_h = self.sampling.iter().map(|&(h, _)| h).max().unwrap_or(1).max(1);
+        let max_v = self.sampling.iter().map(|&(_, v)| v).max().unwrap_or(1).max(1);
+        let mcu_columns = self.width.div_ceil(8 * max_h);
+        let mcu_rows = if whole_image { self.height.div_ceil(8 * max_v) } else { 1 };
+        let blocks = self.sampling.iter()
+            .map(|&(h, v)| mcu_columns.saturating_mul(mcu_rows).saturating_mul(h * v))
+            .fold(0usize, |sum, blocks| sum.saturating_add(blocks));
+        blocks.saturating_mul(64 * (2 + 1))
+    }
+}
+
+pub(crate) fn read_frame(jpeg_data: &[u8]) -> Option<Frame> {
+    let segments = markers::read_header_segments(jpeg_data)?;
+    let sof = segments.iter().find(|segment: &&Segment| markers::is_sof(segment.marker))?;
+    let height = u16::from_be_bytes([*sof.data.get(1)?, *sof.data.get(2)?]) as usize;
+    let width = u16::from_be_bytes([*sof.data.get(3)?, *sof.data.get(4)?]) as usize;
+    let sampling = markers::parse_frame_components(sof.data)?
+        .into_iter()
+        .map(|component| (component.h, component.v))
+        .collect();
+    Some(Frame { marker: sof.marker, width, height, sampling })
+}
@@ -0,0 +1,275 @@
+//! Minimal reader of JPEG marker segments.
+//!
+//! TurboJPEG does not expose the marker structure of JPEG images, so we parse it ourselves where
+//! we need it. This reader only understands the framing of marker segments; the contents of the
+//! segments are interpreted by the callers.
+
+pub(crate) const SOI: u8 = 0xd8;
+pub(crate) const EOI: u8 = 0xd9;
+pub(crate) const SOS: u8 = 0xda;
+pub(crate) const DQT: u8 = 0xdb;
+pub(crate) const DHT: u8 = 0xc4;
+pub(crate) const DRI: u8 = 0xdd;
+pub(crate) const TEM: u8 = 0x01;
+pub(crate) const APP0: u8 = 0xe0;
+pub(crate) const APP1: u8 = 0xe1;
+pub(crate) const APP2: u8 = 0xe2;
+pub(crate) const APP14: u8 = 0xee;
+pub(crate) const APP15: u8 = 0xef;
+pub(crate) const COM: u8 = 0xfe;
+pub(crate) const RST0: u8 = 0xd0;
+pub(crate) const RST7: u8 = 0xd7;
+
+/// A marker segment.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Segment<'a> {
+    /// The marker code (the byte after 0xFF).
+    pub marker: u8,
+    /// Offset of the 0xFF byte that starts the marker in the JPEG data.
+    pub offset: usize,
+    /// Payload of the segment, without the marker and the length field. Empty for standalone
+    /// markers.
+    pub data: &'a [u8],
+}
+
+impl<'a> Segment<'a> {
+    /// Offset of the first byte after the segment.
+    pub fn end(&self) -> usize {
+        if is_standalone(self.marker) {
+            self.offset + 2
+        } else {
+            self.offset + 4 + self.data.len()
+        }
+    }
+}
+
+/// Result of reading the next marker segment.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum ReadSegment<'a> {
+    /// The segment was read successfully.
+    Segment(Segment<'a>),
+    /// The data ends before the end of the segment; at least this many more bytes are needed.
+    NeedMore(usize),
+    /// The data does not contain a valid marker at the current position.
+    Invalid,
+}
+
+/// Returns true for markers that are not followed by a length field and payload.
+pub(crate) fn is_standalone(marker: u8) -> bool {
+    matches!(marker, SOI | EOI | TEM | RST0..=RST7)
+}
+
+/// Returns true for start-of-frame markers (SOF0 to SOF15, excluding DHT, JPG and DAC).
+pub(crate) fn is_sof(marker: u8) -> bool {
+    matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc)
+}
+
+/// Sequential reader of marker segments.
+#[derive(Debug, Clone)]
+pub(crate) struct SegmentReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SegmentReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        SegmentReader { data, pos: 0 }
+    }
+
+    /// Creates a reader that starts at the given position.
+    pub fn with_position(data: &'a [u8], pos: usize) -> Self {
+        SegmentReader { data, pos }
+    }
+
+    /// Returns the current position.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Reads the marker segment at the current position.
+    ///
+    /// The position is advanced only if the segment was read successfully.
+    pub fn next_segment(&mut self) -> ReadSegment<'a> {
+        let data = self.data;
+        let mut pos = self.pos;
+        match data.get(pos) {
+            Some(0xff) => {},
+            Some(_) => return ReadSegment::Invalid,
+            None => return ReadSegment::NeedMore(2),
+        }
+
+        // a marker may be preceded by any number of 0xFF fill bytes
+        while data.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        let marker = match data.get(pos + 1) {
+            Some(0x00) => return ReadSegment::Invalid,
+            Some(&marker) => marker,
+            None => return ReadSegment::NeedMore(1),
+        };
+
+        if is_standalone(marker) {
+            self.pos = pos + 2;
+            return ReadSegment::Segment(Segment { marker, offset: pos, data: &[] })
+        }
+
+        let len = match data.get(pos + 2..pos + 4) {
+            Some(len) => u16::from_be_bytes([len[0], len[1]]) as usize,
+            None => return ReadSegment::NeedMore(pos + 4 - data.len()),
+        };
+        if len < 2 {
+            return ReadSegment::Invalid
+        }
+
+        let end = pos + 2 + len;
+        if end > data.len() {
+            return ReadSegment::NeedMore(end - data.len())
+        }
+
+        self.pos = end;
+        ReadSegment::Segment(Segment { marker, offset: pos, data: &data[pos + 4..end] })
+    }
+}
+
+/// Splits the entropy-coded data of a scan that starts at `start` into restart intervals.
+///
+/// Returns the intervals (without the RST markers that separate them) and the offset of the marker
+/// that ends the scan, or `None` if the data ends before the end of the scan.
+pub(crate) fn split_restart_intervals(data: &[u8], start: usize) -> Option<(Vec<&[u8]>, usize)> {
+    let mut intervals = Vec::new();
+    let mut interval_start = start;
+    let mut pos = start;
+    loop {
+        pos += data.get(pos..)?.iter().position(|&b| b == 0xff)?;
+        // skip fill bytes
+        while data.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        match *data.get(pos + 1)? {
+            // stuffed zero byte
+            0x00 => pos += 2,
+            RST0..=RST7 => {
+                intervals.push(&data[interval_start..pos]);
+                pos += 2;
+                interval_start = pos;
+            },
+            _ => {
+                intervals.push(&data[interval_start..pos]);
+                return Some((intervals, pos))
+            },
+        }
+    }
+}
+
+/// Reads the marker segments up to and including the first start-of-scan (SOS) segment.
+///
+/// Returns `None` if the data does not start with SOI or if it ends before SOS.
+pub(crate) fn read_header_segments(data: &[u8]) -> Option<Vec<Segment<'_>>> {
+    let mut reader = SegmentReader::new(data);
+    let mut segments = Vec::new();
+    loop {
+        match reader.next_segment() {
+            ReadSegment::Segment(segment) => {
+                if segments.is_empty() != (segment.marker == SOI) {
+                    return None
+                }
+                let marker = segment.marker;
+                segments.push(segment);
+                match marker {
+                    SOS => return Some(segments),
+                    EOI => return None,
+                    _ => {},
+                }
+            },
+            ReadSegment::NeedMore(_) | ReadSegment::Invalid => return None,
+        }
+    }
+}
+
+/// Reads all marker segments in the image, skipping the entropy-coded data of the scans.
+///
+/// Unlike [`read_header_segments()`], this also returns the segments between the scans of
+/// progressive images (such as DHT) and the final EOI. If the data is truncated or corrupted,
+/// returns the segments that were read before the problem. Returns `None` if the data does not
+/// start with SOI.
+pub(crate) fn read_all_segments(data: &[u8]) -> Option<Vec<Segment<'_>>> {
+    if data.get(0..2) != Some(&[0xff, SOI]) {
+        return None
+    }
+    let mut reader = SegmentReader::new(data);
+    let mut segments = Vec::new();
+    while let ReadSegment::Segment(segment) = reader.next_segment() {
+        segments.push(segment);
+        match segment.marker {
+            SOS => match split_restart_intervals(data, segment.end()) {
+                Some((_, end)) => reader = SegmentReader::with_position(data, end),
+                None => break,
+            },
+            EOI => break,
+            _ => {},
+        }
+    }
+    Some(segments)
+}
+
+/// Returns true if the segment is an APP2 segment with an embedded ICC profile.
+pub(crate) fn is_icc_profile(segment: &Segment) -> bool {
+    segment.marker == APP2 && segment.data.starts_with(b"ICC_PROFILE\0")
+}
+
+/// Returns true if the segment is an APP1 segment with EXIF metadata.
+pub(crate) fn is_exif(segment: &Segment) -> bool {
+    segment.marker == APP1 && segment.data.starts_with(b"Exif\0\0")
+}
+
+/// Returns true if the segment is an APP14 segment written by Adobe applications, which store CMYK
+/// images with inverted components.
+pub(crate) fn is_adobe(segment: &Segment) -> bool {
+    segment.marker == APP14 && segment.data.starts_with(b"Adobe")
+}
+
+/// Returns true if the header of the JPEG data contains an Adobe APP14 segment.
+pub(crate) fn has_adobe_segment(data: &[u8]) -> bool {
+    read_header_segments(data).is_some_and(|segments| segments.iter().any(is_adobe))
+}
+
+/// Returns a copy of the JPEG data without the header segments for which `remove` returns true.
+///
+/// Returns `None` if the header of the JPEG data cannot be read.
+#[cfg(feature = "transform")]
+pub(crate) fn remove_segments<F>(data: &[u8], mut remove: F) -> Option<Vec<u8>>
+    where F: FnMut(&Segment) -> bool
+{
+    let mut output = Vec::with_capacity(data.len());
+    let mut copied = 0;
+    for segment in read_header_segments(data)? {
+        if remove(&segment) {
+            output.extend_from_slice(&data[copied..segment.offset]);
+            copied = segment.end();
+        }
+    }
+    output.extend_from_slice(&data[copied..]);
+    Some(output)
+}
+
+/// A component in the start-of-frame (SOF) segment.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct FrameComponent {
+    /// Horizontal sampling factor.
+    pub h: usize,
+    /// Vertical sampling factor.
+    pub v: usize,
+    /// Index of the quantization table.
+    pub tq: u8,
+}
+
+/// Parses the components from the payload of a start-of-frame (SOF) segment.
+pub(crate) fn parse_frame_components(data: &[u8]) -> Option<Vec<FrameComponent>> {
+    let count = *data.get(5)? as usize;
+    let components = data.get(6..6 + 3*count)?;
+    Some(components.chunks(3).map(|c| FrameComponent {
+        h: (c[1] >> 4) as usize,
+        v: (c[1] & 0xf) as usize,
+        tq: c[2],
+    }).collect())
+}
@@ -0,0 +1,443 @@
+use std::convert::TryInto as _;
+use std::ops::Range;
+use crate::Image;
+use crate::buf::OwnedBuf;
+use crate::common::{Colorspace, Error, PixelFormat, Result, Subsamp};
+use crate::compress::Compressor;
+use crate::decompress::Decompressor;
+use crate::markers;
+
+/// Reads the ICC color profile embedded in a JPEG image.
+///
+/// ICC profiles are stored in APP2 marker segments. Large profiles are split into multiple
+/// segments, which are concatenated in the correct order. Returns `None` if the image does not
+/// contain an ICC profile, if some of its segments are missing, or if the JPEG header cannot be
+/// read.
+///
+/// TurboJPEG ignores the ICC profile when decompressing, so you may want to apply it yourself (or
+/// pass it along, for example to [`image::ImageEncoder::set_icc_profile()`][set_icc_profile] in
+/// recent versions of `image`).
+///
+/// [set_icc_profile]: https://docs.rs/image/0.25/image/trait.ImageEncoder.html#method.set_icc_profile
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// if let Some(icc_profile) = turbojpeg::read_icc_profile(&jpeg_data) {
+///     println!("the image has an ICC profile with {} bytes", icc_profile.len());
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_icc_profile(jpeg_data: &[u8]) -> Option<Vec<u8>> {
+    // each segment contains the identifier, sequence number (starting from 1), number of segments
+    // and a chunk of the profile
+    let mut chunks = Vec::new();
+    for segment in markers::read_header_segments(jpeg_data)? {
+        if markers::is_icc_profile(&segment) && segment.data.len() >= 14 {
+            chunks.push((segment.data[12], segment.data[13], &segment.data[14..]));
+        }
+    }
+    if chunks.is_empty() {
+        return None
+    }
+
+    chunks.sort_by_key(|&(seq_no, _, _)| seq_no);
+    let count = chunks[0].1;
+    let complete = chunks.len() == count as usize &&
+        chunks.iter().enumerate().all(|(i, &(seq_no, c, _))| seq_no as usize == i + 1 && c == count);
+    if !complete {
+        return None
+    }
+    Some(chunks.into_iter().flat_map(|(_, _, chunk)| chunk).copied().collect())
+}
+
+/// Reads the EXIF metadata embedded in a JPEG image.
+///
+/// EXIF metadata are stored in an APP1 marker segment. Returns the raw EXIF data (starting with
+/// the TIFF header, without the `Exif\0\0` identifier), which can be parsed with a crate such as
+/// `kamadak-exif`. Returns `None` if the image does not contain EXIF metadata or if the JPEG
+/// header cannot be read.
+///
+/// Note that TurboJPEG ignores the EXIF metadata, including the orientation of the image.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// if let Some(exif) = turbojpeg::read_exif(&jpeg_data) {
+///     // the TIFF header starts with the byte order (`II` or `MM`)
+///     assert!(exif.starts_with(b"II") || exif.starts_with(b"MM"));
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_exif(jpeg_data: &[u8]) -> Option<&[u8]> {
+    markers::read_header_segments(jpeg_data)?
+        .into_iter()
+        .find(markers::is_exif)
+        .map(|segment| &segment.data[6..])
+}
+
+/// Extracts the JPEG thumbnail embedded in a JPEG image, without decompressing the image.
+///
+/// Cameras and phones usually embed a small JPEG thumbnail (typically 160x120 pixels) in the EXIF
+/// metadata. Some images also contain a JPEG thumbnail in a JFIF extension (JFXX) segment. This
+/// function returns the compressed data of the thumbnail, which is much faster to decompress than
+/// the full image (for example, to show previews in a gallery). Returns `None` if the image does
+/// not contain a JPEG thumbnail or if the JPEG header cannot be read.
+///
+/// Note that the thumbnail may not match the image if the image was edited by a program that did
+/// not update the thumbnail.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// if let Some(thumbnail) = turbojpeg::extract_thumbnail(&jpeg_data) {
+///     let thumbnail = turbojpeg::decompress(&thumbnail, turbojpeg::PixelFormat::RGB)?;
+///     println!("thumbnail has size {}x{}", thumbnail.width, thumbnail.height);
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn extract_thumbnail(jpeg_data: &[u8]) -> Option<OwnedBuf> {
+    let segments = markers::read_header_segments(jpeg_data)?;
+    let exif_thumbnail = segments.iter()
+        .find(|segment| markers::is_exif(segment))
+        .and_then(|segment| exif_thumbnail(&segment.data[6..]));
+    // JFIF extension segment with a thumbnail coded using JPEG (extension code 0x10)
+    let jfxx_thumbnail = || segments.iter()
+        .find(|segment| segment.marker == markers::APP0 && segment.data.starts_with(b"JFXX\0\x10"))
+        .map(|segment| &segment.data[6..]);
+
+    exif_thumbnail.or_else(jfxx_thumbnail)
+        .filter(|thumbnail| thumbnail.starts_with(&[0xff, markers::SOI]))
+        .map(OwnedBuf::copy_from_slice)
+}
+
+/// Finds the thumbnail in EXIF data (the TIFF structure).
+///
+/// The thumbnail is described by the tags JPEGInterchangeFormat (offset) and
+/// JPEGInterchangeFormatLength in the second IFD (IFD1).
+fn exif_thumbnail(tiff: &[u8]) -> Option<&[u8]> {
+    let tiff = Tiff::new(tiff)?;
+    let ifd0 = tiff.u32(4)? as usize;
+    let ifd0_count = tiff.u16(ifd0)? as usize;
+    let ifd1 = tiff.u32(ifd0 + 2 + 12 * ifd0_count)? as usize;
+    if ifd1 == 0 {
+        return None
+    }
+
+    let (mut offset, mut len) = (None, None);
+    for i in 0..tiff.u16(ifd1)? as usize {
+        let entry = ifd1 + 2 + 12 * i;
+        match tiff.u16(entry)? {
+            0x0201 => offset = Some(tiff.u32(entry + 8)? as usize),
+            0x0202 => len = Some(tiff.u32(entry + 8)? as usize),
+            _ => {},
+        }
+    }
+    let offset = offset?;
+    tiff.data.get(offset..offset.checked_add(len?)?)
+}
+
+/// TIFF data with the byte order from its header.
+pub(crate) struct Tiff<'a> {
+    pub data: &'a [u8],
+    pub big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        let big_endian = match data.get(0..2)? {
+            b"II" => false,
+            b"MM" => true,
+            _ => return None,
+        };
+        Some(Tiff { data, big_endian })
+    }
+
+    pub fn u16(&self, pos: usize) -> Option<u16> {
+        let bytes = self.data.get(pos..pos.checked_add(2)?)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    pub fn u32(&self, pos: usize) -> Option<u32> {
+        let bytes = self.data.get(pos..pos.checked_add(4)?)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    /// Finds the entry with the given tag in the IFD and returns its offset.
+    ///
+    /// Returns `None` if the IFD cannot be read and `Some(None)` if it does not contain the tag.
+    pub fn find_entry(&self, ifd: usize, tag: u16) -> Option<Option<usize>> {
+        for i in 0..self.u16(ifd)? as usize {
+            let entry = ifd.checked_add(2 + 12 * i)?;
+            if self.u16(entry)? == tag {
+                return Some(Some(entry))
+            }
+        }
+        Some(None)
+    }
+
+    /// Returns the range of the value of the IFD entry, which is either stored inline in the entry
+    /// or at an offset.
+    fn entry_value(&self, entry: usize) -> Option<Range<usize>> {
+        let size = match self.u16(entry + 2)? {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => 0,
+        };
+        let len = (self.u32(entry + 4)? as usize).checked_mul(size)?;
+        let start = if len <= 4 { entry + 8 } else { self.u32(entry + 8)? as usize };
+        let range = start..start.checked_add(len)?;
+        self.data.get(range.clone())?;
+        Some(range)
+    }
+}
+
+/// Selects the metadata that are removed by [`scrub_metadata()`].
+///
+/// The default policy removes the GPS location, the serial numbers and the owner from the EXIF
+/// metadata, and the XMP metadata, but keeps the other EXIF fields (such as the orientation or
+/// the exposure) and the other APP segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScrubPolicy {
+    /// Remove the GPS information (the GPS IFD) from EXIF.
+    pub gps: bool,
+    /// Remove the serial numbers of the camera body and lens, the unique image ID and the maker
+    /// note (which is vendor-specific and usually contains the serial number) from EXIF.
+    pub serial_numbers: bool,
+    /// Remove the artist, copyright, author and camera owner from EXIF.
+    pub owner: bool,
+    /// Remove the XMP metadata, which may contain the same information as EXIF.
+    pub xmp: bool,
+    /// Remove all APP segments and comments except the ICC color profile and the JFIF and Adobe
+    /// segments (which affect the decompression). This also removes the whole EXIF metadata.
+    pub all_app_segments: bool,
+}
+
+impl Default for ScrubPolicy {
+    fn default() -> ScrubPolicy {
+        ScrubPolicy { gps: true, serial_numbers: true, owner: true, xmp: true, all_app_segments: false }
+    }
+}
+
+/// Removes privacy-sensitive metadata from a JPEG image without recompression.
+///
+/// Only the marker segments in the header of the image are rewritten (see [`ScrubPolicy`]); the
+/// compressed image data are copied unchanged, so the image does not lose any quality and this
+/// is much faster than decompression and compression. The removed EXIF fields are unlinked from
+/// their IFDs and their values are overwritten with zeros, so they cannot be recovered from the
+/// scrubbed data. If the EXIF metadata cannot be parsed, the whole EXIF segment is removed.
+///
+/// Returns `None` if the header of the JPEG data cannot be read.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let scrubbed = turbojpeg::scrub_metadata(&jpeg_data, &Default::default()).unwrap();
+///
+/// // remove all metadata except the ICC profile
+/// let policy = turbojpeg::ScrubPolicy { all_app_segments: true, ..Default::default() };
+/// let minimal = turbojpeg::scrub_metadata(&jpeg_data, &policy).unwrap();
+/// assert!(turbojpeg::read_exif(&minimal).is_none());
+/// assert_eq!(turbojpeg::read_icc_profile(&minimal), turbojpeg::read_icc_profile(&jpeg_data));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn scrub_metadata(jpeg_data: &[u8], policy: &ScrubPolicy) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(jpeg_data.len());
+    let mut copied = 0;
+    for segment in markers::read_header_segments(jpeg_data)? {
+        let is_app = (markers::APP0..=markers::APP15).contains(&segment.marker) || segment.marker == markers::COM;
+        let keep = markers::is_icc_profile(&segment) || markers::is_adobe(&segment) ||
+            (segment.marker == markers::APP0 && segment.data.starts_with(b"JFIF\0"));
+        let remove = (policy.all_app_segments && is_app && !keep) || (policy.xmp && is_xmp(&segment));
+
+        if remove || markers::is_exif(&segment) {
+            output.extend_from_slice(&jpeg_data[copied..segment.offset]);
+            copied = segment.end();
+        }
+        if !remove && markers::is_exif(&segment) {
+            let mut exif = jpeg_data[segment.offset..segment.end()].to_vec();
+            // the TIFF structure starts after the marker, length and `Exif\0\0` identifier
+            if scrub_exif(&mut exif[10..], policy).is_some() {
+                output.extend_from_slice(&exif);
+            }
+        }
+    }
+    output.extend_from_slice(&jpeg_data[copied..]);
+    Some(output)
+}
+
+/// EXIF tags removed by [`ScrubPolicy::owner`] from IFD0.
+const OWNER_TAGS: &[u16] = &[0x013b, 0x8298, 0x9c9d];
+/// EXIF tags removed by [`ScrubPolicy::owner`] from the EXIF IFD.
+const EXIF_OWNER_TAGS: &[u16] = &[0xa430];
+/// EXIF tags removed by [`ScrubPolicy::serial_numbers`] from the EXIF IFD.
+const EXIF_SERIAL_TAGS: &[u16] = &[0x927c, 0xa420, 0xa431, 0xa435];
+const GPS_IFD_TAG: u16 = 0x8825;
+const EXIF_IFD_TAG: u16 = 0x8769;
+
+/// Removes the tags selected by `policy` from the EXIF data (the TIFF structure) in place.
+///
+/// Returns `None` if the data cannot be parsed.
+fn scrub_exif(tiff_data: &mut [u8], policy: &ScrubPolicy) -> Option<()> {
+    if !policy.gps && !policy.owner && !policy.serial_numbers {
+        return Some(())
+    }
+    let tiff = Tiff::new(tiff_data)?;
+    let ifd0 = tiff.u32(4)? as usize;
+    let exif_ifd = tiff.find_entry(ifd0, EXIF_IFD_TAG)?.and_then(|entry| tiff.u32(entry + 8));
+
+    let mut ifd0_tags = Vec::new();
+    let mut exif_tags = Vec::new();
+    if policy.gps {
+        ifd0_tags.push(GPS_IFD_TAG);
+    }
+    if policy.owner {
+        ifd0_tags.extend_from_slice(OWNER_TAGS);
+        exif_tags.extend_from_slice(EXIF_OWNER_TAGS);
+    }
+    if policy.serial_numbers {
+        exif_tags.extend_from_slice(EXIF_SERIAL_TAGS);
+    }
+
+    // collect the byte ranges to zero and the entries to unlink before modifying the data
+    let mut zero_ranges = Vec::new();
+    let mut removals = vec![(ifd0, ifd0_tags)];
+    if let Some(exif_ifd) = exif_ifd {
+        removals.push((exif_ifd as usize, exif_tags));
+    }
+    for (ifd, tags) in &removals {
+        for &tag in tags {
+            let Some(entry) = tiff.find_entry(*ifd, tag)? else { continue };
+            zero_ranges.push(tiff.entry_value(entry)?);
+            if tag == GPS_IFD_TAG {
+                let gps_ifd = tiff.u32(entry + 8)? as usize;
+                let count = tiff.u16(gps_ifd)? as usize;
+                for i in 0..count {
+                    zero_ranges.push(tiff.entry_value(gps_ifd + 2 + 12 * i)?);
+                }
+                zero_ranges.push(gps_ifd..gps_ifd + 2 + 12 * count + 4);
+            }
+        }
+    }
+    let big_endian = tiff.big_endian;
+
+    for range in zero_ranges {
+        tiff_data.get_mut(range)?.fill(0);
+    }
+    for (ifd, tags) in removals {
+        unlink_entries(tiff_data, big_endian, ifd, &tags)?;
+    }
+    Some(())
+}
+
+/// Removes the entries with the given tags from the IFD, shifting the following entries and the
+/// offset of the next IFD, and zeroes the space that was freed at the end.
+fn unlink_entries(data: &mut [u8], big_endian: bool, ifd: usize, tags: &[u16]) -> Option<()> {
+    let tiff = Tiff { data, big_endian };
+    let count = tiff.u16(ifd)? as usize;
+    let entries_end = ifd + 2 + 12 * count;
+    let mut kept = Vec::with_capacity(12 * count + 4);
+    for i in 0..count {
+        let entry = ifd + 2 + 12 * i;
+        if !tags.contains(&tiff.u16(entry)?) {
+            kept.extend_from_slice(data.get(entry..entry + 12)?);
+        }
+    }
+    let kept_count = (kept.len() / 12) as u16;
+    kept.extend_from_slice(data.get(entries_end..entries_end + 4)?);
+
+    let ifd_data = data.get_mut(ifd..entries_end + 4)?;
+    let count_bytes = if big_endian { kept_count.to_be_bytes() } else { kept_count.to_le_bytes() };
+    ifd_data[..2].copy_from_slice(&count_bytes);
+    ifd_data[2..2 + kept.len()].copy_from_slice(&kept);
+    ifd_data[2 + kept.len()..].fill(0);
+    Some(())
+}
+
+/// Decompresses and compresses a JPEG image again, preserving its metadata.
+///
+/// [`decompress()`][crate::decompress()] followed by [`compress()`][crate::compress()] loses all
+/// metadata of the image. This function recompresses the image with the given `quality` and
+/// chrominance subsampling `subsamp`, and copies the EXIF metadata, the ICC color profile and the
+/// XMP metadata from the original image into the new image. Other metadata (such as comments or
+/// vendor-specific APP segments) are not copied.
+///
+/// Grayscale images are recompressed in grayscale (`subsamp` is ignored) and CMYK images are
+/// recompressed in CMYK.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let smaller = turbojpeg::recompress_preserving_metadata(&jpeg_data, 60, turbojpeg::Subsamp::Sub2x2)?;
+/// assert_eq!(turbojpeg::read_exif(&smaller), turbojpeg::read_exif(&jpeg_data));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn recompress_preserving_metadata(jpeg_data: &[u8], quality: i32, subsamp: Subsamp) -> Result<OwnedBuf> {
+    let mut decompressor = Decompressor::new()?;
+    let header = decompressor.read_header(jpeg_data)?;
+    let (format, subsamp) = match header.colorspace {
+        Colorspace::Gray => (PixelFormat::GRAY, Subsamp::Gray),
+        Colorspace::CMYK | Colorspace::YCCK => (PixelFormat::CMYK, subsamp),
+        _ => (PixelFormat::RGB, subsamp),
+    };
+    let mut image = Image {
+        pixels: vec![0; header.width * header.height * format.size()],
+        width: header.width,
+        pitch: 0,
+        height: header.height,
+        format,
+    };
+    decompressor.decompress(jpeg_data, image.as_deref_mut())?;
+
+    let mut compressor = Compressor::new()?;
+    compressor.set_quality(quality)?;
+    compressor.set_subsamp(subsamp)?;
+    let compressed = compressor.compress_to_owned(image.as_deref())?;
+
+    let segments = markers::read_header_segments(jpeg_data)
+        .ok_or(Error::BadJpeg("cannot read the header segments of the image"))?;
+    let metadata = segments.iter()
+        .filter(|segment| markers::is_exif(segment) || markers::is_icc_profile(segment) || is_xmp(segment))
+        .collect::<Vec<_>>();
+    if metadata.is_empty() {
+        return Ok(compressed)
+    }
+
+    // insert the metadata after SOI and the JFIF segment (which must come first)
+    let mut insert_at = 2;
+    let compressed_segments = markers::read_header_segments(&compressed)
+        .ok_or(Error::BadJpeg("cannot read the header segments of the recompressed image"))?;
+    if let Some(&jfif) = compressed_segments.get(1) {
+        if jfif.marker == markers::APP0 {
+            insert_at = jfif.end();
+        }
+    }
+    let metadata_len = metadata.iter().map(|segment| segment.end() - segment.offset).sum::<usize>();
+    let mut output = Vec::with_capacity(compressed.len() + metadata_len);
+    output.extend_from_slice(&compressed[..insert_at]);
+    for segment in metadata {
+        output.extend_from_slice(&jpeg_data[segment.offset..segment.end()]);
+    }
+    output.extend_from_slice(&compressed[insert_at..]);
+    Ok(OwnedBuf::copy_from_slice(&output))
+}
+
+/// Returns true if the segment is an APP1 segment with XMP metadata (including the extended XMP).
+fn is_xmp(segment: &markers::Segment) -> bool {
+    segment.marker == markers::APP1 && (
+        segment.data.starts_with(b"http://ns.adobe.com/xap/1.0/\0") ||
+        segment.data.starts_with(b"http://ns.adobe.com/xmp/extension/\0")
+    )
+}
@@ -0,0 +1,171 @@
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "metrics")]
+use std::time::{Duration, Instant};
+
+/// Snapshot of the global counters of compression and decompression, returned by [`metrics()`].
+///
+/// The counters are shared by all compressors and decompressors in the process (including the
+/// instances that are created by the convenience functions such as
+/// [`decompress()`][crate::decompress()]) and they only increase, so they can be exported directly
+/// as Prometheus counters. To measure a period of time, take two snapshots and subtract them using
+/// [`since()`][Self::since].
+///
+/// Only successful calls of TurboJPEG are counted: compression of images and YUV images by
+/// [`Compressor`][crate::Compressor] and decompression of images and YUV images by
+/// [`Decompressor`][crate::Decompressor]. Operations that decompress an image in parts (such as
+/// [`Decompressor::decompress_strips()`][crate::Decompressor::decompress_strips] for images
+/// without restart markers) count every part as an image. Transformations are not counted.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub struct Metrics {
+    /// Number of compressed images.
+    pub images_compressed: u64,
+    /// Number of decompressed images.
+    pub images_decompressed: u64,
+    /// Total size of the pixels of the compressed images in bytes.
+    pub compress_input_bytes: u64,
+    /// Total size of the compressed JPEG data in bytes.
+    pub compress_output_bytes: u64,
+    /// Total size of the JPEG data of the decompressed images in bytes.
+    pub decompress_input_bytes: u64,
+    /// Total size of the decompressed pixels in bytes.
+    pub decompress_output_bytes: u64,
+    /// Total time spent in compression (summed over all threads).
+    pub compress_time: Duration,
+    /// Total time spent in decompression (summed over all threads).
+    pub decompress_time: Duration,
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    /// Returns the difference between this snapshot and an `earlier` snapshot.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let before = turbojpeg::metrics();
+    /// let image = turbojpeg::Image::mandelbrot(300, 200, turbojpeg::PixelFormat::RGB);
+    /// let jpeg_data = turbojpeg::compress(image.as_deref(), 90, turbojpeg::Subsamp::Sub2x2)?;
+    ///
+    /// // other threads may compress images at the same time
+    /// let delta = turbojpeg::metrics().since(&before);
+    /// assert!(delta.images_compressed >= 1);
+    /// assert!(delta.compress_output_bytes >= jpeg_data.len() as u64);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn since(&self, earlier: &Metrics) -> Metrics {
+        Metrics {
+            images_compressed: self.images_compressed.saturating_sub(earlier.images_compressed),
+            images_decompressed: self.images_decompressed.saturating_sub(earlier.images_decompressed),
+            compress_input_bytes: self.compress_input_bytes.saturating_sub(earlier.compress_input_bytes),
+            compress_output_bytes: self.compress_output_bytes.saturating_sub(earlier.compress_output_bytes),
+            decompress_input_bytes: self.decompress_input_bytes.saturating_sub(earlier.decompress_input_bytes),
+            decompress_output_bytes: self.decompress_output_bytes.saturating_sub(earlier.decompress_output_bytes),
+            compress_time: self.compress_time.saturating_sub(earlier.compress_time),
+            decompress_time: self.decompress_time.saturating_sub(earlier.decompress_time),
+        }
+    }
+}
+
+/// Returns a snapshot of the global counters of compression and decompression.
+///
+/// The counters are updated with relaxed atomic operations, so a snapshot that is taken while
+/// other threads compress or decompress images may include only some counters of an image. See
+/// [`Metrics`] for details.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// turbojpeg::decompress(&jpeg_data, turbojpeg::PixelFormat::RGB)?;
+///
+/// let metrics = turbojpeg::metrics();
+/// assert!(metrics.images_decompressed >= 1);
+/// println!("decompressed {} bytes of JPEG data in {:?}", metrics.decompress_input_bytes, metrics.decompress_time);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub fn metrics() -> Metrics {
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    Metrics {
+        images_compressed: load(&COMPRESS.images),
+        images_decompressed: load(&DECOMPRESS.images),
+        compress_input_bytes: load(&COMPRESS.input_bytes),
+        compress_output_bytes: load(&COMPRESS.output_bytes),
+        decompress_input_bytes: load(&DECOMPRESS.input_bytes),
+        decompress_output_bytes: load(&DECOMPRESS.output_bytes),
+        compress_time: Duration::from_nanos(load(&COMPRESS.nanos)),
+        decompress_time: Duration::from_nanos(load(&DECOMPRESS.nanos)),
+    }
+}
+
+/// Counters of one kind of operation.
+#[cfg(feature = "metrics")]
+struct Counters {
+    images: AtomicU64,
+    input_bytes: AtomicU64,
+    output_bytes: AtomicU64,
+    nanos: AtomicU64,
+}
+
+#[cfg(feature = "metrics")]
+impl Counters {
+    const fn new() -> Counters {
+        Counters {
+            images: AtomicU64::new(0),
+            input_bytes: AtomicU64::new(0),
+            output_bytes: AtomicU64::new(0),
+            nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, start: Instant, input_bytes: usize, output_bytes: usize) {
+        let nanos = start.elapsed().as_nanos().try_into().unwrap_or(u64::MAX);
+        self.images.fetch_add(1, Ordering::Relaxed);
+        self.input_bytes.fetch_add(input_bytes as u64, Ordering::Relaxed);
+        self.output_bytes.fetch_add(output_bytes as u64, Ordering::Relaxed);
+        self.nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "metrics")]
+static COMPRESS: Counters = Counters::new();
+#[cfg(feature = "metrics")]
+static DECOMPRESS: Counters = Counters::new();
+
+/// Measures a call of TurboJPEG for the global counters.
+///
+/// Without the `metrics` feature, this does nothing (and it is optimized out).
+pub(crate) struct Timer {
+    #[cfg(feature = "metrics")]
+    start: Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Timer {
+        Timer {
+            #[cfg(feature = "metrics")]
+            start: Instant::now(),
+        }
+    }
+
+    /// Records a successful compression.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn compressed(self, input_bytes: usize, output_bytes: usize) {
+        #[cfg(feature = "metrics")]
+        COMPRESS.record(self.start, input_bytes, output_bytes);
+    }
+
+    /// Records a successful decompression.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn decompressed(self, input_bytes: usize, output_bytes: usize) {
+        #[cfg(feature = "metrics")]
+        DECOMPRESS.record(self.start, input_bytes, output_bytes);
+    }
+}
@@ -0,0 +1,273 @@
+//! Splitting and assembling multi-picture (MPO) files.
+//!
+//! The Multi-Picture Format (MPF) stores several JPEG images in a single file, simply by
+//! concatenating them. The first image contains an APP2 segment with an index of all images, so
+//! programs that do not understand MPF see just the first image. This format is used by stereo
+//! cameras (the images are the left and right views) and by some phones, which store additional
+//! images (such as depth maps or gain maps) after the main image.
+//!
+//! - [`is_mpo()`] detects whether JPEG data contain an MPF index.
+//! - [`split()`] returns the JPEG data of the individual images, which you can decompress with
+//!   [`Decompressor`][crate::Decompressor].
+//! - [`assemble()`] concatenates JPEG images into an MPO file with an MPF index.
+//!
+//! # Example
+//!
+//! ```
+//! use turbojpeg::{mpo, Image, PixelFormat, Subsamp};
+//!
+//! // compress the left and right views of a stereo pair
+//! let left = Image::mandelbrot(320, 240, PixelFormat::RGB);
+//! let right = Image::mandelbrot(330, 240, PixelFormat::RGB);
+//! let left = turbojpeg::compress(left.as_deref(), 90, Subsamp::Sub2x2)?;
+//! let right = turbojpeg::compress(right.as_deref(), 90, Subsamp::Sub2x2)?;
+//!
+//! let mpo_data = mpo::assemble(&[
+//!     mpo::Entry { data: &left, kind: mpo::ImageKind::Primary },
+//!     mpo::Entry { data: &right, kind: mpo::ImageKind::Disparity },
+//! ])?;
+//! assert!(mpo::is_mpo(&mpo_data));
+//!
+//! // the first image can be decompressed directly, but the others must be split
+//! let entries = mpo::split(&mpo_data)?;
+//! assert_eq!(entries.len(), 2);
+//! assert_eq!(entries[1].kind, mpo::ImageKind::Disparity);
+//! let right = turbojpeg::decompress(entries[1].data, PixelFormat::RGB)?;
+//! assert_eq!(right.width, 330);
+//!
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::common::{Error, Result};
+use crate::markers;
+use crate::metadata::Tiff;
+
+const MPF_VERSION_TAG: u16 = 0xb000;
+const NUMBER_OF_IMAGES_TAG: u16 = 0xb001;
+const MP_ENTRY_TAG: u16 = 0xb002;
+const INDIVIDUAL_NUM_TAG: u16 = 0xb101;
+
+/// Flag of the representative image in the individual image attribute.
+const REPRESENTATIVE_FLAG: u32 = 0x2000_0000;
+/// Mask of the image type in the individual image attribute.
+const TYPE_MASK: u32 = 0x00ff_ffff;
+
+/// An individual image in an MPO file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+    /// The JPEG data of the image.
+    pub data: &'a [u8],
+    /// The type of the image.
+    pub kind: ImageKind,
+}
+
+/// Type of an individual image in an MPO file, as stored in the MPF index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageKind {
+    /// The primary image (the first image in the file).
+    Primary,
+    /// A larger thumbnail of the primary image in the VGA resolution.
+    LargeThumbnailVga,
+    /// A larger thumbnail of the primary image in the Full HD resolution.
+    LargeThumbnailFullHd,
+    /// A view of a stereo (or multi-view) image, taken from a different position.
+    Disparity,
+    /// A view of the same object, taken from a different angle.
+    MultiAngle,
+    /// Any other type, with the raw 24-bit type code from the MPF index (0 means undefined,
+    /// which is used by phones for depth maps and gain maps).
+    Other(u32),
+}
+
+impl ImageKind {
+    fn from_code(code: u32) -> ImageKind {
+        match code {
+            0x03_0000 => ImageKind::Primary,
+            0x01_0001 => ImageKind::LargeThumbnailVga,
+            0x01_0002 => ImageKind::LargeThumbnailFullHd,
+            0x02_0001 => ImageKind::Disparity,
+            0x02_0002 => ImageKind::MultiAngle,
+            code => ImageKind::Other(code),
+        }
+    }
+
+    fn code(self) -> u32 {
+        match self {
+            ImageKind::Primary => 0x03_0000,
+            ImageKind::LargeThumbnailVga => 0x01_0001,
+            ImageKind::LargeThumbnailFullHd => 0x01_0002,
+            ImageKind::Disparity => 0x02_0001,
+            ImageKind::MultiAngle => 0x02_0002,
+            ImageKind::Other(code) => code & TYPE_MASK,
+        }
+    }
+}
+
+/// Returns true if the header of the JPEG data contains an MPF segment, so the data may contain
+/// more images than the first one (see [`split()`]).
+pub fn is_mpo(data: &[u8]) -> bool {
+    markers::read_header_segments(data).is_some_and(|segments| segments.iter().any(is_mpf))
+}
+
+/// Splits an MPO file into the individual JPEG images.
+///
+/// The images are found using the MPF index in the first image, so the returned slices contain
+/// exactly the data of the images (including their own headers and metadata) and can be
+/// decompressed independently. The first entry is always the first image in the file.
+///
+/// Returns an error if the data does not contain a valid MPF index or if an image in the index is
+/// out of bounds or does not start with a JPEG marker. Use [`is_mpo()`] to check whether the data
+/// should be split at all.
+pub fn split(data: &[u8]) -> Result<Vec<Entry<'_>>> {
+    let segments = markers::read_header_segments(data)
+        .ok_or(Error::BadMpo("the data does not start with a JPEG header"))?;
+    let segment = segments.iter().find(|segment| is_mpf(segment))
+        .ok_or(Error::BadMpo("the data does not contain an MPF segment"))?;
+    // the offsets of the images are relative to the TIFF header after the `MPF\0` identifier
+    let base = segment.offset + 8;
+    let entries = read_index(&segment.data[4..])
+        .ok_or(Error::BadMpo("the MPF index cannot be parsed"))?;
+
+    let mut images = Vec::with_capacity(entries.len());
+    for (i, (attribute, size, offset)) in entries.into_iter().enumerate() {
+        // the offset of the first image is always 0
+        let start = if i == 0 { Some(0) } else { base.checked_add(offset as usize) };
+        let image = start.and_then(|start| data.get(start..start.checked_add(size as usize)?))
+            .ok_or(Error::BadMpo("an image in the MPF index is out of bounds"))?;
+        if !image.starts_with(&[0xff, markers::SOI]) {
+            return Err(Error::BadMpo("an image in the MPF index does not start with SOI"))
+        }
+        images.push(Entry { data: image, kind: ImageKind::from_code(attribute & TYPE_MASK) });
+    }
+    Ok(images)
+}
+
+/// Reads the MP entries (attribute, size and offset of every image) from the MP index IFD.
+fn read_index(tiff_data: &[u8]) -> Option<Vec<(u32, u32, u32)>> {
+    let tiff = Tiff::new(tiff_data)?;
+    let ifd = tiff.u32(4)? as usize;
+    let entry = tiff.find_entry(ifd, MP_ENTRY_TAG)??;
+    let len = tiff.u32(entry + 4)? as usize;
+    let offset = tiff.u32(entry + 8)? as usize;
+    if len == 0 || len % 16 != 0 || tiff.data.get(offset..offset.checked_add(len)?).is_none() {
+        return None
+    }
+
+    let mut entries = Vec::with_capacity(len / 16);
+    for pos in (offset..offset + len).step_by(16) {
+        entries.push((tiff.u32(pos)?, tiff.u32(pos + 4)?, tiff.u32(pos + 8)?));
+    }
+    Some(entries)
+}
+
+/// Assembles JPEG images into an MPO file.
+///
+/// The images are concatenated and an MPF segment is inserted into each image (after the JFIF and
+/// EXIF segments): the first image gets the index of all images and the other images get their
+/// individual number. MPF segments that are already in the images (for example, if the images
+/// come from [`split()`]) are replaced. The first image is marked as the representative image,
+/// so it should usually be [`ImageKind::Primary`].
+///
+/// Returns an error if there are no images, if an image does not start with a valid JPEG header,
+/// or if the file would be larger than 4 GB (the limit of MPF).
+pub fn assemble(entries: &[Entry]) -> Result<Vec<u8>> {
+    if entries.is_empty() {
+        return Err(Error::BadMpo("there are no images to assemble"))
+    }
+    let count: u32 = entries.len().try_into()
+        .map_err(|_| Error::IntegerOverflow("number of images"))?;
+
+    let mut output = Vec::with_capacity(entries.iter().map(|entry| entry.data.len() + 64).sum());
+    let mut index_pos = 0;
+    let mut ranges = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let start = output.len();
+        let segments = markers::read_header_segments(entry.data)
+            .ok_or(Error::BadMpo("an image does not start with a valid JPEG header"))?;
+        // the MPF segment should follow the JFIF and EXIF segments
+        let insert_at = segments.iter().skip(1)
+            .take_while(|segment| matches!(segment.marker, markers::APP0 | markers::APP1))
+            .last()
+            .map_or(2, |segment| segment.end());
+
+        output.extend_from_slice(&entry.data[..insert_at]);
+        if i == 0 {
+            index_pos = output.len();
+            write_index_segment(&mut output, count)?;
+        } else {
+            write_attribute_segment(&mut output, i as u32 + 1);
+        }
+
+        let mut copied = insert_at;
+        for segment in segments.iter().filter(|segment| is_mpf(segment)) {
+            output.extend_from_slice(&entry.data[copied..segment.offset]);
+            copied = segment.end();
+        }
+        output.extend_from_slice(&entry.data[copied..]);
+        ranges.push(start..output.len());
+    }
+
+    // fill the MP entries, now that we know where the images are
+    let base = index_pos + 8;
+    let mut pos = base + INDEX_ENTRIES_OFFSET;
+    for (i, (entry, range)) in entries.iter().zip(ranges).enumerate() {
+        let flags = if i == 0 { REPRESENTATIVE_FLAG } else { 0 };
+        let size: u32 = range.len().try_into().map_err(|_| Error::IntegerOverflow("image size"))?;
+        let offset: u32 = if i == 0 { 0 } else {
+            (range.start - base).try_into().map_err(|_| Error::IntegerOverflow("image offset"))?
+        };
+        for value in [flags | entry.kind.code(), size, offset, 0] {
+            output[pos..pos + 4].copy_from_slice(&value.to_be_bytes());
+            pos += 4;
+        }
+    }
+    Ok(output)
+}
+
+/// Offset of the MP entries from the TIFF header in the index segment (after the header and an
+/// IFD with 3 entries).
+const INDEX_ENTRIES_OFFSET: usize = 8 + 2 + 3 * 12 + 4;
+
+/// Writes the MPF segment of the first image with the MP index IFD. The MP entries are filled with
+/// zeros.
+fn write_index_segment(output: &mut Vec<u8>, count: u32) -> Result<()> {
+    let len = INDEX_ENTRIES_OFFSET + 16 * count as usize;
+    let segment_len: u16 = (2 + 4 + len).try_into()
+        .map_err(|_| Error::BadMpo("too many images for the MPF index"))?;
+    write_segment_header(output, segment_len, 3);
+    write_ifd_entry(output, MPF_VERSION_TAG, 7, 4, u32::from_be_bytes(*b"0100"));
+    write_ifd_entry(output, NUMBER_OF_IMAGES_TAG, 4, 1, count);
+    write_ifd_entry(output, MP_ENTRY_TAG, 7, 16 * count, INDEX_ENTRIES_OFFSET as u32);
+    output.extend_from_slice(&0u32.to_be_bytes());
+    output.resize(output.len() + 16 * count as usize, 0);
+    Ok(())
+}
+
+/// Writes the MPF segment of the other images with the MP attribute IFD.
+fn write_attribute_segment(output: &mut Vec<u8>, number: u32) {
+    write_segment_header(output, 2 + 4 + 8 + 2 + 2 * 12 + 4, 2);
+    write_ifd_entry(output, MPF_VERSION_TAG, 7, 4, u32::from_be_bytes(*b"0100"));
+    write_ifd_entry(output, INDIVIDUAL_NUM_TAG, 4, 1, number);
+    output.extend_from_slice(&0u32.to_be_bytes());
+}
+
+/// Writes the APP2 marker, the `MPF\0` identifier, a big-endian TIFF header and the number of
+/// entries of the first IFD.
+fn write_segment_header(output: &mut Vec<u8>, segment_len: u16, ifd_entries: u16) {
+    output.extend_from_slice(&[0xff, markers::APP2]);
+    output.extend_from_slice(&segment_len.to_be_bytes());
+    output.extend_from_slice(b"MPF\0MM\0\x2a");
+    output.extend_from_slice(&8u32.to_be_bytes());
+    output.extend_from_slice(&ifd_entries.to_be_bytes());
+}
+
+fn write_ifd_entry(output: &mut Vec<u8>, tag: u16, value_type: u16, count: u32, value: u32) {
+    output.extend_from_slice(&tag.to_be_bytes());
+    output.extend_from_slice(&value_type.to_be_bytes());
+    output.extend_from_slice(&count.to_be_bytes());
+    output.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Returns true if the segment is an APP2 segment with MPF data.
+fn is_mpf(segment: &markers::Segment) -> bool {
+    segment.marker == markers::APP2 && segment.data.starts_with(b"MPF\0")
+}
@@ -0,0 +1,180 @@
+use std::ops::Range;
+use crate::Image;
+use crate::common::{Error, Result};
+use crate::decompress::Decompressor;
+use crate::markers::{self, Segment};
+
+/// Decompress a JPEG image on multiple threads, using the restart markers in the image.
+///
+/// Restart markers split the compressed data into intervals that can be decoded independently.
+/// If the image is sequential (not progressive), contains restart markers at the start of MCU rows
+/// (which is common for large images from drones and satellites), this function splits the image
+/// into horizontal bands of rows and decompresses them in parallel on up to `threads` threads
+/// (if `threads` is 0, uses [`std::thread::available_parallelism()`]). Otherwise, the image is
+/// decompressed on the current thread, just like with [`Decompressor::decompress()`].
+///
+/// The requirements for `output` are the same as for [`Decompressor::decompress()`].
+///
+/// When the chrominance is subsampled vertically, the pixels at the boundaries between the bands
+/// may slightly differ from the pixels produced by single-threaded decompression, because
+/// TurboJPEG cannot use the neighboring band when upsampling the chrominance.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let header = turbojpeg::read_header(&jpeg_data)?;
+///
+/// let mut image = turbojpeg::Image {
+///     pixels: vec![0; 3 * header.width * header.height],
+///     width: header.width,
+///     pitch: 3 * header.width,
+///     height: header.height,
+///     format: turbojpeg::PixelFormat::RGB,
+/// };
+/// turbojpeg::decompress_parallel(&jpeg_data, image.as_deref_mut(), 4)?;
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn decompress_parallel(jpeg_data: &[u8], output: Image<&mut [u8]>, threads: usize) -> Result<()> {
+    output.assert_valid(output.pixels.len());
+    let threads = match threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+
+    let bands = match split_bands(jpeg_data, threads) {
+        Some(bands) if bands.len() > 1 => bands,
+        _ => return Decompressor::new()?.decompress(jpeg_data, output),
+    };
+
+    let (width, height) = bands.size;
+    if output.width < width || output.height < height {
+        return Err(Error::OutputTooSmall(width as i32, height as i32))
+    }
+
+    // split the output into disjoint bands of rows
+    let pitch = output.row_pitch();
+    let Image { mut pixels, width: output_width, pitch: _, height: _, format } = output;
+    let mut band_images = Vec::with_capacity(bands.len());
+    for (i, band) in bands.bands.iter().enumerate() {
+        let band_height = band.rows.end - band.rows.start;
+        let band_pixels = if i + 1 < bands.len() {
+            let (band_pixels, rest) = std::mem::take(&mut pixels).split_at_mut(band_height * pitch);
+            pixels = rest;
+            band_pixels
+        } else {
+            std::mem::take(&mut pixels)
+        };
+        band_images.push(Image { pixels: band_pixels, width: output_width, pitch, height: band_height, format });
+    }
+
+    std::thread::scope(|scope| {
+        let handles = bands.bands.iter().zip(band_images)
+            .map(|(band, image)| scope.spawn(move || {
+                Decompressor::new()?.decompress(&band.jpeg_data, image)
+            }))
+            .collect::<Vec<_>>();
+        handles.into_iter()
+            .try_for_each(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+    })
+}
+
+/// The image split into bands that can be decompressed independently.
+pub(crate) struct Bands {
+    /// Size of the whole image.
+    pub size: (usize, usize),
+    pub bands: Vec<Band>,
+}
+
+impl Bands {
+    fn len(&self) -> usize {
+        self.bands.len()
+    }
+}
+
+/// A standalone JPEG image that contains a band of rows from the original image.
+pub(crate) struct Band {
+    pub rows: Range<usize>,
+    pub jpeg_data: Vec<u8>,
+}
+
+/// Splits the image into at most `max_bands` bands at restart markers.
+///
+/// Returns `None` if the image cannot be split (for example, because it is progressive or does not
+/// use restart markers at the start of MCU rows).
+pub(crate) fn split_bands(data: &[u8], max_bands: usize) -> Option<Bands> {
+    let segments = markers::read_header_segments(data)?;
+    let find = |marker| segments.iter().find(|s: &&Segment| s.marker == marker);
+
+    // only baseline and extended sequential Huffman-coded images (SOF0 and SOF1) are supported
+    let sof = segments.iter().find(|s| markers::is_sof(s.marker))?;
+    if !matches!(sof.marker, 0xc0 | 0xc1) {
+        return None
+    }
+    let height = u16::from_be_bytes([*sof.data.get(1)?, *sof.data.get(2)?]) as usize;
+    let width = u16::from_be_bytes([*sof.data.get(3)?, *sof.data.get(4)?]) as usize;
+    let components = markers::parse_frame_components(sof.data)?;
+    if width == 0 || height == 0 {
+        return None
+    }
+
+    // the scan must contain all components, otherwise there are multiple scans
+    let sos = find(markers::SOS)?;
+    if *sos.data.first()? as usize != components.len() {
+        return None
+    }
+
+    let dri = find(markers::DRI)?;
+    let restart_interval = u16::from_be_bytes([*dri.data.first()?, *dri.data.get(1)?]) as usize;
+
+    let (mcu_width, mcu_height) = if components.len() == 1 {
+        (8, 8)
+    } else {
+        let h_max = components.iter().map(|c| c.h).max()?;
+        let v_max = components.iter().map(|c| c.v).max()?;
+        // sampling factors of zero are invalid, TurboJPEG reports the error when decompressing
+        if h_max == 0 || v_max == 0 {
+            return None
+        }
+        (8 * h_max, 8 * v_max)
+    };
+    let mcus_per_row = width.div_ceil(mcu_width);
+    let mcu_rows = height.div_ceil(mcu_height);
+    // every restart interval must contain a whole number of MCU rows
+    let mcu_rows_per_interval = restart_interval / mcus_per_row;
+    if mcu_rows_per_interval == 0 || mcu_rows_per_interval * mcus_per_row != restart_interval {
+        return None
+    }
+    let rows_per_interval = mcu_rows_per_interval * mcu_height;
+
+    let (intervals, end) = markers::split_restart_intervals(data, sos.end())?;
+    if data.get(end + 1) != Some(&markers::EOI) {
+        return None
+    }
+    if intervals.len() != (mcu_rows * mcu_height).div_ceil(rows_per_interval) {
+        return None
+    }
+
+    let intervals_per_band = intervals.len().div_ceil(max_bands.max(1));
+    let bands = intervals.chunks(intervals_per_band).enumerate().map(|(i, band_intervals)| {
+        let first_row = i * intervals_per_band * rows_per_interval;
+        let rows = first_row..(first_row + band_intervals.len() * rows_per_interval).min(height);
+
+        let mut jpeg_data = Vec::with_capacity(sos.end() + band_intervals.iter().map(|i| i.len() + 2).sum::<usize>());
+        jpeg_data.extend_from_slice(&data[..sos.end()]);
+        // patch the height in the SOF segment
+        let height_offset = sof.offset + 5;
+        jpeg_data[height_offset..height_offset + 2].copy_from_slice(&(rows.len() as u16).to_be_bytes());
+        for (j, interval) in band_intervals.iter().enumerate() {
+            if j > 0 {
+                jpeg_data.extend_from_slice(&[0xff, markers::RST0 + ((j - 1) % 8) as u8]);
+            }
+            jpeg_data.extend_from_slice(interval);
+        }
+        jpeg_data.extend_from_slice(&[0xff, markers::EOI]);
+        Band { rows, jpeg_data }
+    }).collect();
+
+    Some(Bands { size: (width, height), bands })
+}
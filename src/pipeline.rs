@@ -0,0 +1,293 @@
+//! Multi-threaded pipeline for compressing a stream of images (such as video frames).
+//!
+//! This module is available with the `pipeline` feature. [`spawn()`] starts a number of encoder
+//! threads, each with its own [`Compressor`], and returns a [`Sender`] and a [`Receiver`]:
+//!
+//! - Images are submitted using the [`Sender`], which can be cloned and used from multiple
+//!   threads. The queue between the senders and the encoder threads is bounded, so
+//!   [`Sender::submit()`] blocks when the encoders cannot keep up (backpressure) and
+//!   [`Sender::try_submit()`] returns an error (so you can drop the frame).
+//! - The compressed images are received from the [`Receiver`] in the same order in which they
+//!   were submitted, regardless of the order in which the encoder threads finish them. The encoder
+//!   threads wait when they get too far ahead of the receiver, so a slow receiver also applies
+//!   backpressure, and the number of compressed images that wait to be received is bounded.
+//!
+//! The encoder threads stop when all senders are dropped and the queue is drained, or when the
+//! receiver is dropped. To abort a long export promptly, start the pipeline using
+//! [`spawn_cancellable()`] and cancel its [`CancelToken`].
+//!
+//! # Example
+//!
+//! ```
+//! use turbojpeg::{Compressor, Image, PixelFormat, Subsamp};
+//!
+//! let (sender, receiver) = turbojpeg::pipeline::spawn(4, 8, || {
+//!     let mut compressor = Compressor::new()?;
+//!     compressor.set_quality(80)?;
+//!     compressor.set_subsamp(Subsamp::Sub2x2)?;
+//!     Ok(compressor)
+//! })?;
+//!
+//! // produce the frames on another thread
+//! let producer = std::thread::spawn(move || {
+//!     for _ in 0..16 {
+//!         let frame = Image::mandelbrot(320, 240, PixelFormat::RGB);
+//!         sender.submit(frame).unwrap();
+//!     }
+//!     // dropping the sender closes the pipeline
+//! });
+//!
+//! // receive the compressed frames in order
+//! for (index, (frame_index, jpeg_data)) in receiver.enumerate() {
+//!     assert_eq!(index as u64, frame_index);
+//!     let jpeg_data = jpeg_data?;
+//!     assert!(!jpeg_data.is_empty());
+//! }
+//! producer.join().unwrap();
+//!
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use crate::Image;
+use crate::buf::OwnedBuf;
+use crate::cancel::CancelToken;
+use crate::common::{Error, Result};
+use crate::compress::Compressor;
+
+type Job = (u64, Image<Vec<u8>>);
+type JobResult = (u64, thread::Result<Result<OwnedBuf>>);
+
+/// Starts a pipeline with `threads` encoder threads.
+///
+/// The encoder threads use compressors created by `make_compressor`, which is called once for
+/// every thread (on the current thread), so you can use it to configure the quality, subsampling
+/// and other settings. At most `queue_len` submitted images wait in the queue for an encoder
+/// thread, and at most `threads + queue_len` images are compressed ahead of the image that the
+/// [`Receiver`] waits for.
+///
+/// Returns an error if `make_compressor` fails.
+///
+/// # Panics
+///
+/// Panics if `threads` is zero.
+pub fn spawn<F>(threads: usize, queue_len: usize, make_compressor: F) -> Result<(Sender, Receiver)>
+    where F: FnMut() -> Result<Compressor>
+{
+    spawn_cancellable(threads, queue_len, CancelToken::new(), make_compressor)
+}
+
+/// Starts a pipeline with `threads` encoder threads that can be cancelled using `cancel`.
+///
+/// This is the same as [`spawn()`], but after `cancel` is cancelled, the encoder threads stop
+/// compressing: every image that is waiting in the queue (or submitted later) is received with
+/// [`Error::Cancelled`], without being compressed. The images that are already being compressed
+/// are finished normally.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{CancelToken, Compressor, Error, Image, PixelFormat};
+///
+/// let cancel = CancelToken::new();
+/// let (sender, receiver) =
+///     turbojpeg::pipeline::spawn_cancellable(2, 8, cancel.clone(), Compressor::new)?;
+///
+/// cancel.cancel();
+/// for _ in 0..4 {
+///     sender.submit(Image::mandelbrot(64, 64, PixelFormat::RGB)).unwrap();
+/// }
+/// drop(sender);
+///
+/// for (_, jpeg_data) in receiver {
+///     assert!(matches!(jpeg_data, Err(Error::Cancelled)));
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn spawn_cancellable<F>(
+    threads: usize,
+    queue_len: usize,
+    cancel: CancelToken,
+    mut make_compressor: F,
+) -> Result<(Sender, Receiver)>
+    where F: FnMut() -> Result<Compressor>
+{
+    assert!(threads > 0, "the pipeline needs at least one encoder thread");
+    let compressors = (0..threads).map(|_| make_compressor()).collect::<Result<Vec<_>>>()?;
+
+    let (job_sender, job_receiver) = mpsc::sync_channel::<Job>(queue_len);
+    let window = Arc::new(Window {
+        received: Mutex::new(0),
+        changed: Condvar::new(),
+        len: (threads + queue_len) as u64,
+    });
+    // the encoder threads wait for the window, so there are never more results than this
+    let (result_sender, result_receiver) = mpsc::sync_channel::<JobResult>(threads + queue_len);
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+    for compressor in compressors {
+        let job_receiver = job_receiver.clone();
+        let result_sender = result_sender.clone();
+        let window = window.clone();
+        let cancel = cancel.clone();
+        thread::spawn(move || encode_jobs(compressor, &job_receiver, &result_sender, &window, &cancel));
+    }
+
+    let sender = Sender {
+        jobs: job_sender,
+        next_index: Arc::new(Mutex::new(0)),
+    };
+    let receiver = Receiver {
+        results: result_receiver,
+        next_index: 0,
+        pending: BTreeMap::new(),
+        window,
+    };
+    Ok((sender, receiver))
+}
+
+fn encode_jobs(
+    mut compressor: Compressor,
+    jobs: &Mutex<mpsc::Receiver<Job>>,
+    results: &mpsc::SyncSender<JobResult>,
+    window: &Window,
+    cancel: &CancelToken,
+) {
+    loop {
+        // the lock is released before we start compressing
+        let job = lock(jobs).recv();
+        let Ok((index, image)) = job else { break };
+        window.wait(index);
+        let result = if cancel.is_cancelled() {
+            Ok(Err(Error::Cancelled))
+        } else {
+            panic::catch_unwind(AssertUnwindSafe(|| compressor.compress_to_owned(image.as_deref())))
+        };
+        if results.send((index, result)).is_err() {
+            break
+        }
+    }
+}
+
+/// Submits images into the pipeline.
+///
+/// The sender can be cloned to submit images from multiple threads. Every submitted image gets an
+/// index (starting from zero), which determines the order in which the compressed images are
+/// received from the [`Receiver`].
+#[derive(Debug, Clone)]
+pub struct Sender {
+    jobs: mpsc::SyncSender<Job>,
+    next_index: Arc<Mutex<u64>>,
+}
+
+impl Sender {
+    /// Submits an image for compression, blocking while the queue is full.
+    ///
+    /// Returns the index of the image, or gives the image back if the pipeline has been closed
+    /// (because the [`Receiver`] was dropped).
+    pub fn submit(&self, image: Image<Vec<u8>>) -> std::result::Result<u64, mpsc::SendError<Image<Vec<u8>>>> {
+        // the index is locked while sending, so that the indices are always contiguous
+        let mut next_index = lock(&self.next_index);
+        let index = *next_index;
+        self.jobs.send((index, image))
+            .map_err(|mpsc::SendError((_, image))| mpsc::SendError(image))?;
+        *next_index += 1;
+        Ok(index)
+    }
+
+    /// Submits an image for compression without blocking.
+    ///
+    /// Returns the index of the image, or gives the image back if the queue is full or if the
+    /// pipeline has been closed.
+    pub fn try_submit(&self, image: Image<Vec<u8>>) -> std::result::Result<u64, mpsc::TrySendError<Image<Vec<u8>>>> {
+        let mut next_index = lock(&self.next_index);
+        let index = *next_index;
+        self.jobs.try_send((index, image)).map_err(|err| match err {
+            mpsc::TrySendError::Full((_, image)) => mpsc::TrySendError::Full(image),
+            mpsc::TrySendError::Disconnected((_, image)) => mpsc::TrySendError::Disconnected(image),
+        })?;
+        *next_index += 1;
+        Ok(index)
+    }
+}
+
+/// Receives compressed images from the pipeline, in the order in which they were submitted.
+///
+/// This is an iterator that yields the index of the image with the compressed JPEG data (or the
+/// error from compression). The iterator blocks until the next image is compressed, and it ends
+/// when all [`Sender`]s have been dropped and all submitted images have been received.
+///
+/// If the compression of an image panics (for example, because the image is invalid), the panic
+/// is propagated to the thread that receives the image.
+#[derive(Debug)]
+pub struct Receiver {
+    results: mpsc::Receiver<JobResult>,
+    next_index: u64,
+    pending: BTreeMap<u64, thread::Result<Result<OwnedBuf>>>,
+    window: Arc<Window>,
+}
+
+impl Receiver {
+    /// Returns the index of the next image that will be received.
+    pub fn next_index(&self) -> u64 {
+        self.next_index
+    }
+}
+
+impl Iterator for Receiver {
+    type Item = (u64, Result<OwnedBuf>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.pending.remove(&self.next_index) {
+                let index = self.next_index;
+                self.next_index += 1;
+                self.window.advance(self.next_index);
+                let result = result.unwrap_or_else(|payload| panic::resume_unwind(payload));
+                return Some((index, result))
+            }
+
+            let (index, result) = self.results.recv().ok()?;
+            self.pending.insert(index, result);
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        // wake up the encoder threads, which then find out that the receiver is gone
+        self.window.advance(u64::MAX);
+    }
+}
+
+/// Images that the encoder threads may compress: the encoder threads wait while the index of their
+/// image is `len` or more ahead of the next image that the receiver waits for.
+///
+/// The images are taken from the queue in the order of their indices, so the image that the
+/// receiver waits for is never blocked by this window.
+#[derive(Debug)]
+struct Window {
+    received: Mutex<u64>,
+    changed: Condvar,
+    len: u64,
+}
+
+impl Window {
+    fn wait(&self, index: u64) {
+        let mut received = lock(&self.received);
+        while index >= received.saturating_add(self.len) {
+            received = self.changed.wait(received).unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    fn advance(&self, received: u64) {
+        *lock(&self.received) = received;
+        self.changed.notify_all();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
@@ -0,0 +1,204 @@
+use std::sync::Mutex;
+use crate::Image;
+use crate::buf::OwnedBuf;
+use crate::common::PixelFormat;
+
+/// Pool of reusable memory buffers.
+///
+/// When you process many images, allocating a new buffer for every decompressed image and every
+/// compressed output puts a lot of pressure on the allocator. `BufferPool` keeps buffers that are
+/// no longer needed and hands them out again, so that the memory is allocated only once.
+///
+/// Take a buffer from the pool using [`take_vec()`][Self::take_vec],
+/// [`take_image()`][Self::take_image] or [`take_owned_buf()`][Self::take_owned_buf] and return it
+/// using the matching `put_*` method when you are done with it. Buffers that are not returned are
+/// simply dropped. The pool keeps at most [`max_buffers()`][Self::max_buffers] buffers of each
+/// kind; excess buffers are dropped as well.
+///
+/// The pool uses a [`Mutex`] internally, so you can share it between threads (for example, in an
+/// [`Arc`][std::sync::Arc]).
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{BufferPool, Compressor, Decompressor, PixelFormat};
+///
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let pool = BufferPool::new();
+/// let mut decompressor = Decompressor::new()?;
+/// let mut compressor = Compressor::new()?;
+///
+/// for _ in 0..3 {
+///     // decompress into a pooled image
+///     let header = decompressor.read_header(&jpeg_data)?;
+///     let mut image = pool.take_image(header.width, header.height, PixelFormat::RGB);
+///     decompressor.decompress(&jpeg_data, image.as_deref_mut())?;
+///
+///     // compress into a pooled vector
+///     let mut output = pool.take_vec(compressor.buf_len(image.width, image.height)?);
+///     let len = compressor.compress_to_slice(image.as_deref(), &mut output)?;
+///     output.truncate(len);
+///
+///     // ... use the compressed data in `output` ...
+///
+///     pool.put_image(image);
+///     pool.put_vec(output);
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct BufferPool {
+    vecs: Mutex<Vec<Vec<u8>>>,
+    owned_bufs: Mutex<Vec<OwnedBuf>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Creates an empty pool that keeps at most 16 buffers of each kind.
+    pub fn new() -> BufferPool {
+        Self::with_max_buffers(16)
+    }
+
+    /// Creates an empty pool that keeps at most `max_buffers` buffers of each kind.
+    pub fn with_max_buffers(max_buffers: usize) -> BufferPool {
+        BufferPool {
+            vecs: Mutex::new(Vec::new()),
+            owned_bufs: Mutex::new(Vec::new()),
+            max_buffers,
+        }
+    }
+
+    /// Returns the maximal number of buffers of each kind that are kept in the pool.
+    pub fn max_buffers(&self) -> usize {
+        self.max_buffers
+    }
+
+    /// Takes a vector with `len` bytes from the pool.
+    ///
+    /// Returns the smallest pooled vector with enough capacity, or allocates a new vector if there
+    /// is no such vector in the pool. The contents of the vector are set to zero.
+    pub fn take_vec(&self, len: usize) -> Vec<u8> {
+        let mut vecs = lock(&self.vecs);
+        let best = vecs.iter().enumerate()
+            .filter(|(_, vec)| vec.capacity() >= len)
+            .min_by_key(|(_, vec)| vec.capacity())
+            .map(|(i, _)| i);
+        match best {
+            Some(i) => {
+                let mut vec = vecs.swap_remove(i);
+                drop(vecs);
+                vec.clear();
+                vec.resize(len, 0);
+                vec
+            },
+            None => vec![0; len],
+        }
+    }
+
+    /// Returns a vector to the pool.
+    ///
+    /// The vector is kept in the pool with its full capacity, so it is fine to
+    /// [`truncate()`][Vec::truncate] the vector before you return it.
+    pub fn put_vec(&self, vec: Vec<u8>) {
+        if vec.capacity() == 0 {
+            return
+        }
+        let mut vecs = lock(&self.vecs);
+        put(&mut vecs, vec, self.max_buffers, Vec::capacity);
+    }
+
+    /// Takes an image with the given size and pixel format from the pool.
+    ///
+    /// The image is packed (its [`pitch`][Image::pitch] is equal to
+    /// [`packed_pitch()`][Image::packed_pitch]) and its pixels are set to zero. You can use it as
+    /// the output for decompression.
+    pub fn take_image(&self, width: usize, height: usize, format: PixelFormat) -> Image<Vec<u8>> {
+        let pitch = width * format.size();
+        let pixels = self.take_vec(pitch * height);
+        Image { pixels, width, pitch, height, format }
+    }
+
+    /// Returns the pixels of an image to the pool.
+    pub fn put_image(&self, image: Image<Vec<u8>>) {
+        self.put_vec(image.pixels);
+    }
+
+    /// Takes an [`OwnedBuf`] with at least `len` bytes from the pool.
+    ///
+    /// Returns the smallest pooled buffer that is large enough, or allocates a new buffer if there
+    /// is no such buffer in the pool. The contents of the buffer are unspecified.
+    ///
+    /// Note that TurboJPEG sets the length of an owned output buffer to the size of the
+    /// compressed data, so a buffer that was used for compression is returned to the pool with
+    /// this smaller length. To reuse memory for compressed outputs, it is usually better to use
+    /// [`take_vec()`][Self::take_vec] with [`Compressor::buf_len()`][crate::Compressor::buf_len]
+    /// and [`Compressor::compress_to_slice()`][crate::Compressor::compress_to_slice].
+    pub fn take_owned_buf(&self, len: usize) -> OwnedBuf {
+        let mut bufs = lock(&self.owned_bufs);
+        let best = bufs.iter().enumerate()
+            .filter(|(_, buf)| buf.len() >= len)
+            .min_by_key(|(_, buf)| buf.len())
+            .map(|(i, _)| i);
+        match best {
+            Some(i) => bufs.swap_remove(i),
+            None => {
+                drop(bufs);
+                OwnedBuf::allocate(len)
+            },
+        }
+    }
+
+    /// Returns an [`OwnedBuf`] to the pool.
+    pub fn put_owned_buf(&self, buf: OwnedBuf) {
+        if buf.is_empty() {
+            return
+        }
+        let mut bufs = lock(&self.owned_bufs);
+        put(&mut bufs, buf, self.max_buffers, OwnedBuf::len);
+    }
+
+    /// Returns the number of buffers (of all kinds) that are currently kept in the pool.
+    pub fn len(&self) -> usize {
+        lock(&self.vecs).len() + lock(&self.owned_bufs).len()
+    }
+
+    /// Returns true if there are no buffers in the pool.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all buffers that are kept in the pool.
+    pub fn clear(&self) {
+        lock(&self.vecs).clear();
+        lock(&self.owned_bufs).clear();
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Locks the mutex, ignoring poisoning (the pooled buffers cannot be left in an invalid state).
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Adds `buf` to the pool; if the pool is full, the smallest buffer is dropped.
+fn put<B>(bufs: &mut Vec<B>, buf: B, max_buffers: usize, size: fn(&B) -> usize) {
+    if bufs.len() < max_buffers {
+        bufs.push(buf);
+        return
+    }
+
+    let smallest = bufs.iter().enumerate()
+        .min_by_key(|(_, buf)| size(buf))
+        .map(|(i, _)| i);
+    if let Some(i) = smallest {
+        if size(&bufs[i]) < size(&buf) {
+            bufs[i] = buf;
+        }
+    }
+}
@@ -0,0 +1,22 @@
+/// Progress of a long-running operation, passed to progress callbacks.
+///
+/// The callbacks are called after every item (such as an image in a batch) is finished, so GUI
+/// tools can render progress bars (for example, using
+/// [`Compressor::compress_batch_with_progress()`][crate::Compressor::compress_batch_with_progress]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// Number of items that are finished.
+    pub done: usize,
+    /// Total number of items, if it is known in advance.
+    pub total: Option<usize>,
+    /// Number of bytes of compressed data produced so far.
+    pub bytes: usize,
+}
+
+impl Progress {
+    /// Returns the finished fraction of the operation, from 0 to 1, if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| if total == 0 { 1.0 } else { self.done as f64 / total as f64 })
+    }
+}
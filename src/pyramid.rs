@@ -0,0 +1,300 @@
+//! Generating tile pyramids for deep-zoom viewers.
+//!
+//! Viewers of very large images (such as OpenSeadragon or IIIF viewers) do not load the whole
+//! image, but only the tiles that are visible at the current zoom level. The tiles are taken from
+//! a pyramid of levels: the largest level is the full image, and every smaller level is half the
+//! size of the previous one, down to a single pixel. This module produces such a pyramid from a
+//! JPEG image in the Deep Zoom (DZI) layout, which is described by [`Layout`].
+//!
+//! The [`generate()`] function combines several features of this crate to do this efficiently:
+//!
+//! - The four largest levels are decompressed directly with DCT scaling (see
+//!   [`ScalingFactor`][crate::ScalingFactor]), so the full image never needs to be downscaled in
+//!   the pixel domain.
+//! - Every row of tiles is decompressed as a [region][crate::Decompressor::decompress_region], so
+//!   only a band of rows is held in memory for the large levels.
+//! - The tiles in a row are compressed as a [batch][crate::Compressor::compress_batch], sharing a
+//!   single compressor and scratch buffer.
+//!
+//! The smaller levels are produced by averaging 2×2 pixels of the previous level.
+//!
+//! # Example
+//!
+//! ```
+//! use turbojpeg::pyramid;
+//!
+//! let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+//! let options = pyramid::Options { tile_size: 128, ..Default::default() };
+//!
+//! let mut tiles = Vec::new();
+//! let layout = pyramid::generate(&jpeg_data, &options, |tile| {
+//!     // write the tile into `parrots_files/{level}/{column}_{row}.jpg`
+//!     tiles.push((tile.dzi_path("jpg"), tile.jpeg_data));
+//!     Ok(())
+//! })?;
+//! // write the descriptor into `parrots.dzi`
+//! let descriptor = layout.dzi("jpg");
+//! assert!(descriptor.contains("TileSize=\"128\""));
+//!
+//! assert_eq!(layout.max_level(), 9);
+//! assert_eq!(layout.level_size(9), (384, 256));
+//! assert_eq!(layout.tile_count(9), (3, 2));
+//! assert!(tiles.iter().any(|(path, _)| path == "9/2_1.jpg"));
+//! assert!(tiles.iter().any(|(path, _)| path == "0/0_0.jpg"));
+//!
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::Image;
+use crate::buf::OwnedBuf;
+use crate::common::{PixelFormat, Result, Subsamp};
+use crate::compress::Compressor;
+use crate::decompress::{DecompressCrop, Decompressor, ScalingFactor};
+use crate::settings::Settings;
+
+/// Number of the largest levels that are decompressed directly with DCT scaling (with factors 1,
+/// 1/2, 1/4 and 1/8).
+const SCALED_LEVELS: usize = 4;
+
+/// Options for [`generate()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Options {
+    /// Size of the tiles in pixels (without the overlap). The default is 254, which is the default
+    /// of Deep Zoom.
+    pub tile_size: usize,
+    /// Number of pixels that every tile shares with its neighbors on each side. The default is 1.
+    pub overlap: usize,
+    /// Settings of the compressor for the tiles. The limits are also applied to the decompression
+    /// of the source image.
+    pub settings: Settings,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options { tile_size: 254, overlap: 1, settings: Settings::default() }
+    }
+}
+
+/// Geometry of a tile pyramid in the Deep Zoom layout.
+///
+/// Level `max_level()` is the full image, and every lower level is half the size of the level
+/// above (rounding up), so level 0 is a single pixel. Every level is divided into tiles of
+/// `tile_size` × `tile_size` pixels, which are extended by `overlap` pixels on each side that has
+/// a neighbor (the tiles on the right and bottom edge may be smaller).
+///
+/// In IIIF terms, level `l` corresponds to the scale factor `2^(max_level() - l)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Layout {
+    /// Width of the full image.
+    pub width: usize,
+    /// Height of the full image.
+    pub height: usize,
+    /// Size of the tiles (without the overlap).
+    pub tile_size: usize,
+    /// Overlap of the tiles.
+    pub overlap: usize,
+}
+
+impl Layout {
+    /// Returns the number of the level with the full image.
+    pub fn max_level(&self) -> usize {
+        let mut size = self.width.max(self.height);
+        let mut level = 0;
+        while size > 1 {
+            size = size.div_ceil(2);
+            level += 1;
+        }
+        level
+    }
+
+    /// Returns the width and height of the image at the given level.
+    pub fn level_size(&self, level: usize) -> (usize, usize) {
+        let shift = self.max_level().saturating_sub(level) as u32;
+        let scale = |size: usize| 1usize.checked_shl(shift).map_or(1, |factor| size.div_ceil(factor));
+        (scale(self.width), scale(self.height))
+    }
+
+    /// Returns the number of columns and rows of tiles at the given level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tile_size` is zero.
+    pub fn tile_count(&self, level: usize) -> (usize, usize) {
+        let (width, height) = self.level_size(level);
+        (width.div_ceil(self.tile_size), height.div_ceil(self.tile_size))
+    }
+
+    /// Returns the region `(x, y, width, height)` of the image at the given level that is covered
+    /// by the tile, including the overlap.
+    pub fn tile_rect(&self, level: usize, column: usize, row: usize) -> (usize, usize, usize, usize) {
+        let (level_width, level_height) = self.level_size(level);
+        let span = |index: usize, size: usize| {
+            let start = (index * self.tile_size).saturating_sub(self.overlap);
+            let end = ((index + 1) * self.tile_size + self.overlap).min(size);
+            (start, end.saturating_sub(start))
+        };
+        let (x, width) = span(column, level_width);
+        let (y, height) = span(row, level_height);
+        (x, y, width, height)
+    }
+
+    /// Returns the Deep Zoom descriptor (the `.dzi` file) for tiles with the given file extension.
+    pub fn dzi(&self, format: &str) -> String {
+        format!(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" ",
+            "Format=\"{}\" Overlap=\"{}\" TileSize=\"{}\">\n",
+            "  <Size Width=\"{}\" Height=\"{}\"/>\n",
+            "</Image>\n"),
+            format, self.overlap, self.tile_size, self.width, self.height)
+    }
+}
+
+/// A compressed tile produced by [`generate()`].
+#[derive(Debug)]
+pub struct Tile {
+    /// Level of the tile (see [`Layout`]).
+    pub level: usize,
+    /// Column of the tile in the level.
+    pub column: usize,
+    /// Row of the tile in the level.
+    pub row: usize,
+    /// The compressed tile.
+    pub jpeg_data: OwnedBuf,
+}
+
+impl Tile {
+    /// Returns the path of the tile in a Deep Zoom directory (`{level}/{column}_{row}.{format}`).
+    pub fn dzi_path(&self, format: &str) -> String {
+        format!("{}/{}_{}.{}", self.level, self.column, self.row, format)
+    }
+}
+
+/// Generates a tile pyramid from a JPEG image.
+///
+/// Every tile is passed to `callback` as soon as it is compressed, from the largest level to the
+/// smallest, row by row. If the callback returns an error, the generation stops and the error is
+/// returned. Returns the layout of the pyramid.
+///
+/// The tiles are compressed into grayscale if the [subsampling][Settings::subsamp] is
+/// [`Subsamp::Gray`] and into RGB otherwise. The memory used for the pixels is proportional to a
+/// row of tiles at the largest level, plus the whole image at 1/8 of its size. Note that every row
+/// of tiles is decompressed as a separate region, which needs to decode the compressed data above
+/// the row again; images with restart markers are decoded faster. Images with
+/// [unknown subsampling][Subsamp::Unknown] cannot be decompressed by regions, so every scaled
+/// level is decompressed at once.
+///
+/// # Panics
+///
+/// Panics if `options.tile_size` is zero.
+pub fn generate<F>(jpeg_data: &[u8], options: &Options, mut callback: F) -> Result<Layout>
+    where F: FnMut(Tile) -> Result<()>
+{
+    assert!(options.tile_size > 0, "tile size must be positive");
+    let mut decompressor = Decompressor::new()?;
+    decompressor.set_limits(options.settings.limits)?;
+    let header = decompressor.read_header(jpeg_data)?;
+    let layout = Layout {
+        width: header.width,
+        height: header.height,
+        tile_size: options.tile_size,
+        overlap: options.overlap,
+    };
+    let format = if options.settings.subsamp == Subsamp::Gray { PixelFormat::GRAY } else { PixelFormat::RGB };
+    let mut compressor = Compressor::new()?;
+    compressor.apply(&options.settings)?;
+
+    let max_level = layout.max_level();
+    let mut band = Vec::new();
+    let mut smallest_scaled = None;
+    for (k, level) in (0..=max_level).rev().take(SCALED_LEVELS).enumerate() {
+        let scaling_factor = ScalingFactor::new(1, 1 << k);
+        let (width, height) = layout.level_size(level);
+        let pitch = width * format.size();
+        let (_, rows) = layout.tile_count(level);
+
+        let whole_level = k + 1 == SCALED_LEVELS || level == 0 || header.subsamp == Subsamp::Unknown;
+        if whole_level {
+            let mut image = Image { pixels: vec![0; pitch * height], width, pitch, height, format };
+            decompressor.decompress_region(jpeg_data, scaling_factor, None, image.as_deref_mut())?;
+            for row in 0..rows {
+                compress_row(&mut compressor, &layout, level, row, image.as_deref(), 0, &mut callback)?;
+            }
+            smallest_scaled = Some((level, image));
+            continue
+        }
+
+        for row in 0..rows {
+            let (_, y, _, band_height) = layout.tile_rect(level, 0, row);
+            band.resize(pitch * band_height, 0);
+            let crop = DecompressCrop { x: 0, y, width: None, height: Some(band_height) };
+            let image = Image { pixels: &mut band[..], width, pitch, height: band_height, format };
+            decompressor.decompress_region(jpeg_data, scaling_factor, Some(crop), image)?;
+            let image = Image { pixels: &band[..], width, pitch, height: band_height, format };
+            compress_row(&mut compressor, &layout, level, row, image, y, &mut callback)?;
+        }
+    }
+
+    if let Some((smallest_level, mut image)) = smallest_scaled {
+        for level in (0..smallest_level).rev() {
+            image = downsample(&image);
+            let (_, rows) = layout.tile_count(level);
+            for row in 0..rows {
+                compress_row(&mut compressor, &layout, level, row, image.as_deref(), 0, &mut callback)?;
+            }
+        }
+    }
+    Ok(layout)
+}
+
+/// Compresses a row of tiles from `image`, which contains the rows of the level starting at
+/// `image_y`.
+fn compress_row<F>(
+    compressor: &mut Compressor,
+    layout: &Layout,
+    level: usize,
+    row: usize,
+    image: Image<&[u8]>,
+    image_y: usize,
+    callback: &mut F,
+) -> Result<()>
+    where F: FnMut(Tile) -> Result<()>
+{
+    let (columns, _) = layout.tile_count(level);
+    let (_, y, _, height) = layout.tile_rect(level, 0, row);
+    let tiles = (0..columns).map(|column| {
+        let (x, _, width, _) = layout.tile_rect(level, column, row);
+        let pitch = image.row_pitch();
+        let offset = (y - image_y) * pitch + x * image.format.size();
+        Image { pixels: &image.pixels[offset..], width, pitch, height, format: image.format }
+    });
+    let tiles = compressor.compress_batch(tiles)?;
+    for (column, jpeg_data) in tiles.into_iter().enumerate() {
+        callback(Tile { level, column, row, jpeg_data })?;
+    }
+    Ok(())
+}
+
+/// Halves the size of the image (rounding up) by averaging blocks of 2×2 pixels.
+fn downsample(image: &Image<Vec<u8>>) -> Image<Vec<u8>> {
+    let channels = image.format.size();
+    let src_pitch = image.row_pitch();
+    let width = image.width.div_ceil(2);
+    let height = image.height.div_ceil(2);
+    let pitch = width * channels;
+    let mut pixels = vec![0; pitch * height];
+    for y in 0..height {
+        let src_rows = 2 * y..(2 * y + 2).min(image.height);
+        for x in 0..width {
+            let src_columns = 2 * x..(2 * x + 2).min(image.width);
+            let count = (src_rows.len() * src_columns.len()) as u32;
+            for c in 0..channels {
+                let sum = src_rows.clone()
+                    .flat_map(|src_y| src_columns.clone().map(move |src_x| (src_y, src_x)))
+                    .map(|(src_y, src_x)| image.pixels[src_y * src_pitch + src_x * channels + c] as u32)
+                    .sum::<u32>();
+                pixels[y * pitch + x * channels + c] = ((sum + count / 2) / count) as u8;
+            }
+        }
+    }
+    Image { pixels, width, pitch, height, format: image.format }
+}
@@ -0,0 +1,179 @@
+use std::f64::consts::PI;
+use crate::Image;
+use crate::common::{PixelFormat, Result};
+use crate::decompress::{Decompressor, ScalingFactor};
+
+/// Radius of the Lanczos filter used by [`Decompressor::decompress_resized()`].
+const LANCZOS_RADIUS: f64 = 3.0;
+
+impl Decompressor {
+    /// Decompress a JPEG image resized to exactly `width` x `height` pixels.
+    ///
+    /// The image is resized in two steps. First, the image is decompressed with the smallest
+    /// supported [`ScalingFactor`] that still produces at least `width` x `height` pixels, so most
+    /// of the downscaling happens cheaply in the inverse DCT. Then, the decompressed image is
+    /// resampled to the exact size with a Lanczos-3 filter, which is sharper and has fewer
+    /// artifacts than the DCT scaling alone, and is much faster than resampling the full image. If
+    /// the target is larger than the image, the full image is upscaled with the same filter.
+    ///
+    /// The image is stretched if `width` x `height` does not have the aspect ratio of the image;
+    /// use [`read_header()`][Self::read_header] to compute a size that preserves it. Returns a
+    /// newly allocated image without padding between rows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` or `height` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    ///
+    /// // make a 150x100 thumbnail: the 384x256 image is decompressed at 1/2 scale (192x128) and
+    /// // then resampled to the exact size
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// let thumbnail = decompressor.decompress_resized(&jpeg_data, 150, 100, turbojpeg::PixelFormat::RGB)?;
+    /// assert_eq!((thumbnail.width, thumbnail.height), (150, 100));
+    /// assert_eq!(thumbnail.pixels.len(), 150 * 100 * 3);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "resize")))]
+    pub fn decompress_resized(
+        &mut self,
+        jpeg_data: &[u8],
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Result<Image<Vec<u8>>> {
+        assert!(width > 0 && height > 0, "the size of the resized image must not be zero");
+        let header = self.read_header(jpeg_data)?;
+        let scaling_factor = covering_factor(header.width, header.height, width, height);
+
+        let (scaled_width, scaled_height) = (scaling_factor.scale(header.width), scaling_factor.scale(header.height));
+        let pitch = scaled_width * format.size();
+        let mut scaled = Image {
+            pixels: vec![0; pitch * scaled_height],
+            width: scaled_width,
+            pitch,
+            height: scaled_height,
+            format,
+        };
+        self.decompress_region(jpeg_data, scaling_factor, None, scaled.as_deref_mut())?;
+
+        if (scaled_width, scaled_height) == (width, height) {
+            return Ok(scaled)
+        }
+        Ok(resample(&scaled, width, height))
+    }
+}
+
+/// Decompress a JPEG image resized to exactly `width` x `height` pixels.
+///
+/// See [`Decompressor::decompress_resized()`] for details.
+///
+/// # Panics
+///
+/// Panics if `width` or `height` is zero.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let image = turbojpeg::decompress_resized(&jpeg_data, 64, 64, turbojpeg::PixelFormat::GRAY)?;
+/// assert_eq!(image.pixels.len(), 64 * 64);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "resize")))]
+pub fn decompress_resized(jpeg_data: &[u8], width: usize, height: usize, format: PixelFormat) -> Result<Image<Vec<u8>>> {
+    let mut decompressor = Decompressor::new()?;
+    decompressor.decompress_resized(jpeg_data, width, height, format)
+}
+
+/// Returns the smallest supported scaling factor (at most 1) that scales the image to at least
+/// `width` x `height` pixels.
+fn covering_factor(image_width: usize, image_height: usize, width: usize, height: usize) -> ScalingFactor {
+    ScalingFactor::supported().into_iter()
+        .filter(|factor| factor.num <= factor.denom)
+        .filter(|factor| factor.scale(image_width) >= width && factor.scale(image_height) >= height)
+        .min_by(|a, b| (a.num * b.denom).cmp(&(b.num * a.denom)))
+        .unwrap_or(ScalingFactor::ONE)
+}
+
+/// Source pixels (starting at `start`) and their weights that contribute to a resampled pixel.
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+fn lanczos(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else if x.abs() < LANCZOS_RADIUS {
+        let x = PI * x;
+        LANCZOS_RADIUS * x.sin() * (x / LANCZOS_RADIUS).sin() / (x * x)
+    } else {
+        0.0
+    }
+}
+
+/// Computes the contributions for resampling `src_len` pixels to `dst_len` pixels.
+///
+/// When downscaling, the filter is stretched to cover all source pixels (so that it averages them
+/// instead of skipping some of them).
+fn contributions(src_len: usize, dst_len: usize) -> Vec<Contribution> {
+    let scale = src_len as f64 / dst_len as f64;
+    let filter_scale = scale.max(1.0);
+    let support = LANCZOS_RADIUS * filter_scale;
+    (0..dst_len).map(|i| {
+        let center = (i as f64 + 0.5) * scale;
+        let start = (center - support).floor().max(0.0) as usize;
+        let end = ((center + support).ceil() as usize).min(src_len);
+        let weights = (start..end)
+            .map(|j| lanczos((j as f64 + 0.5 - center) / filter_scale))
+            .collect::<Vec<_>>();
+        let sum = weights.iter().sum::<f64>();
+        let weights = weights.iter().map(|&weight| (weight / sum) as f32).collect();
+        Contribution { start, weights }
+    }).collect()
+}
+
+/// Resamples a packed image to `width` x `height` pixels, first horizontally and then vertically.
+fn resample(image: &Image<Vec<u8>>, width: usize, height: usize) -> Image<Vec<u8>> {
+    let channels = image.format.size();
+    let src_pitch = image.row_pitch();
+
+    let horizontal = contributions(image.width, width);
+    let mut rows = vec![0f32; image.height * width * channels];
+    for (y, row) in rows.chunks_exact_mut(width * channels).enumerate() {
+        let src_row = &image.pixels[y * src_pitch..][..image.width * channels];
+        for (pixel, contribution) in row.chunks_exact_mut(channels).zip(&horizontal) {
+            let src = &src_row[contribution.start * channels..];
+            for (src_pixel, &weight) in src.chunks_exact(channels).zip(&contribution.weights) {
+                for (value, &src_value) in pixel.iter_mut().zip(src_pixel) {
+                    *value += weight * src_value as f32;
+                }
+            }
+        }
+    }
+
+    let pitch = width * channels;
+    let vertical = contributions(image.height, height);
+    let mut pixels = vec![0; height * pitch];
+    let mut sums = vec![0f32; pitch];
+    for (row, contribution) in pixels.chunks_exact_mut(pitch).zip(&vertical) {
+        sums.fill(0.0);
+        let src = &rows[contribution.start * pitch..];
+        for (src_row, &weight) in src.chunks_exact(pitch).zip(&contribution.weights) {
+            for (sum, &src_value) in sums.iter_mut().zip(src_row) {
+                *sum += weight * src_value;
+            }
+        }
+        for (value, &sum) in row.iter_mut().zip(&sums) {
+            *value = sum.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    Image { pixels, width, pitch, height, format: image.format }
+}
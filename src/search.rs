@@ -0,0 +1,113 @@
+use crate::Image;
+use crate::buf::{OutputBuf, OwnedBuf};
+use crate::common::Result;
+use crate::compress::Compressor;
+use crate::decompress::Decompressor;
+use crate::diff::{luma_plane, ssim_planes};
+
+/// Result of [`Compressor::compress_to_quality_metric()`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct QualitySearch {
+    /// The compressed JPEG image.
+    pub jpeg_data: OwnedBuf,
+    /// The quality that was used to compress the image.
+    pub quality: i32,
+    /// The [SSIM][crate::ssim()] of the decompressed image against the source image.
+    pub ssim: f64,
+}
+
+impl QualitySearch {
+    /// Returns true if the compressed image reached the requested SSIM.
+    ///
+    /// This is false only if even the quality 100 was not enough.
+    pub fn reached(&self, min_ssim: f64) -> bool {
+        self.ssim >= min_ssim
+    }
+}
+
+impl Compressor {
+    /// Compresses an image with the lowest quality that reaches the given SSIM.
+    ///
+    /// The image is compressed with different qualities (using a binary search between 1 and
+    /// 100), every result is decompressed and compared with `image` using [`ssim()`][crate::ssim()],
+    /// and the smallest quality whose SSIM is at least `min_ssim` is selected. This is the usual
+    /// way to find the smallest JPEG image that is "perceptually lossless"; for example, `0.99` is
+    /// a common threshold. The other settings of the compressor (such as the subsampling) are used
+    /// in all iterations, and the quality of the compressor is restored afterwards.
+    ///
+    /// The search needs about 7 iterations, which reuse this compressor, a single decompressor and
+    /// the buffers for the compressed and decompressed images. If even the quality 100 does not
+    /// reach `min_ssim`, the image compressed with quality 100 is returned (see
+    /// [`QualitySearch::reached()`]).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is invalid (see [`Image::check()`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let image = turbojpeg::Image::mandelbrot(300, 200, turbojpeg::PixelFormat::RGB);
+    /// let mut compressor = turbojpeg::Compressor::new()?;
+    /// compressor.set_subsamp(turbojpeg::Subsamp::Sub2x2)?;
+    ///
+    /// let search = compressor.compress_to_quality_metric(image.as_deref(), 0.95)?;
+    /// assert!(search.reached(0.95));
+    /// println!("quality {} gives SSIM {:.4}", search.quality, search.ssim);
+    ///
+    /// // a higher threshold needs a higher quality
+    /// let better = compressor.compress_to_quality_metric(image.as_deref(), 0.99)?;
+    /// assert!(better.quality >= search.quality);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn compress_to_quality_metric(&mut self, image: Image<&[u8]>, min_ssim: f64) -> Result<QualitySearch> {
+        image.assert_valid(image.pixels.len());
+        let original_quality = self.settings().quality;
+        let result = self.search_quality(image, min_ssim);
+        self.set_quality(original_quality)?;
+        result
+    }
+
+    fn search_quality(&mut self, image: Image<&[u8]>, min_ssim: f64) -> Result<QualitySearch> {
+        let (width, height) = (image.width, image.height);
+        let mut source_luma = Vec::new();
+        luma_plane(&image, &mut source_luma);
+
+        let mut decompressor = Decompressor::new()?;
+        let pitch = image.packed_pitch();
+        let mut decompressed = vec![0; pitch * height];
+        let mut decompressed_luma = Vec::new();
+
+        let mut jpeg_data = OwnedBuf::new();
+        let mut best: Option<QualitySearch> = None;
+        let mut last_ssim = 0.0;
+        let (mut low, mut high) = (1, 100);
+        while low <= high {
+            let quality = (low + high) / 2;
+            self.set_quality(quality)?;
+            let mut output = OutputBuf::owned(jpeg_data);
+            self.compress(image, &mut output)?;
+            jpeg_data = output.into_owned();
+
+            let output = Image { pixels: &mut decompressed[..], width, pitch, height, format: image.format };
+            decompressor.decompress(&jpeg_data, output)?;
+            let output = Image { pixels: &decompressed[..], width, pitch, height, format: image.format };
+            luma_plane(&output, &mut decompressed_luma);
+            last_ssim = ssim_planes(&source_luma, &decompressed_luma, width, height);
+
+            if last_ssim >= min_ssim {
+                // keep this result and reuse the buffer of the previous best result
+                let previous = best.replace(QualitySearch { jpeg_data, quality, ssim: last_ssim });
+                jpeg_data = previous.map_or_else(OwnedBuf::new, |previous| previous.jpeg_data);
+                high = quality - 1;
+            } else {
+                low = quality + 1;
+            }
+        }
+
+        // if no quality reached `min_ssim`, the last iteration used quality 100
+        Ok(best.unwrap_or(QualitySearch { jpeg_data, quality: 100, ssim: last_ssim }))
+    }
+}
@@ -0,0 +1,75 @@
+use crate::common::Subsamp;
+use crate::compress::{DEFAULT_QUALITY, DEFAULT_SUBSAMP};
+use crate::limits::Limits;
+
+/// Settings of a [`Compressor`][crate::Compressor] that determine the compressed JPEG data.
+///
+/// The settings capture the parameters of the compressor that are usually tuned by applications,
+/// so you can define encoder profiles (such as "thumbnail" or "archive") in one place, apply them
+/// using [`Compressor::apply()`][crate::Compressor::apply] and read them back using
+/// [`Compressor::settings()`][crate::Compressor::settings]. With the `serde` feature, the
+/// settings implement `Serialize` and `Deserialize`, so the profiles can be loaded from
+/// configuration files; the missing fields are taken from [`Settings::default()`].
+///
+/// The default settings are the settings of a new compressor, except that the limits are
+/// [`Limits::NONE`] instead of [`Limits::global()`].
+///
+/// # Example
+///
+/// ```
+/// let image = turbojpeg::Image::mandelbrot(300, 200, turbojpeg::PixelFormat::RGB);
+///
+/// let thumbnail = turbojpeg::Settings {
+///     quality: 75,
+///     subsamp: turbojpeg::Subsamp::Sub2x2,
+///     progressive: true,
+///     ..Default::default()
+/// };
+///
+/// let mut compressor = turbojpeg::Compressor::new()?;
+/// compressor.apply(&thumbnail)?;
+/// assert_eq!(compressor.settings(), thumbnail);
+/// let jpeg_data = compressor.compress_to_vec(image.as_deref())?;
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Settings {
+    /// Quality of the compressed image, from 1 (worst) to 100 (best). See
+    /// [`Compressor::set_quality()`][crate::Compressor::set_quality].
+    pub quality: i32,
+    /// Chrominance subsampling. See [`Compressor::set_subsamp()`][crate::Compressor::set_subsamp].
+    pub subsamp: Subsamp,
+    /// Use progressive entropy coding (`TJPARAM_PROGRESSIVE`).
+    pub progressive: bool,
+    /// Compute optimal Huffman tables. See
+    /// [`Compressor::set_optimize()`][crate::Compressor::set_optimize].
+    pub optimize: bool,
+    /// Use arithmetic entropy coding instead of Huffman coding (`TJPARAM_ARITHMETIC`). Many
+    /// decoders do not support arithmetic coding.
+    pub arithmetic: bool,
+    /// Restart marker interval in MCU blocks (`TJPARAM_RESTARTBLOCKS`), or 0 to disable.
+    pub restart_blocks: usize,
+    /// Restart marker interval in MCU rows (`TJPARAM_RESTARTROWS`), or 0 to disable. This is
+    /// ignored if `restart_blocks` is not 0.
+    pub restart_rows: usize,
+    /// Limits on the resources used by the compressor.
+    pub limits: Limits,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            quality: DEFAULT_QUALITY,
+            subsamp: DEFAULT_SUBSAMP,
+            progressive: false,
+            optimize: false,
+            arithmetic: false,
+            restart_blocks: 0,
+            restart_rows: 0,
+            limits: Limits::NONE,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+/// Status of the SIMD extensions of TurboJPEG, returned by [`simd_status()`].
+///
+/// TurboJPEG is fast because of its SIMD extensions (hand-written assembly or intrinsics for the
+/// DCT, color conversion, upsampling and Huffman coding). When the library is built without them
+/// (for example, when NASM is not installed on x86), it still works correctly, but it is several
+/// times slower. The SIMD extensions are selected at runtime according to the CPU, and they can be
+/// disabled using the `JSIMD_FORCENONE` environment variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SimdStatus {
+    /// Whether the library was built with the SIMD extensions.
+    ///
+    /// This is `Some(true)` if `turbojpeg-sys` built the library from source with the
+    /// `require-simd` feature (which is enabled by default), `Some(false)` if it built the library
+    /// without NASM on x86 or x86-64, and `None` if we do not know (for example, if the library was
+    /// found using pkg-config).
+    pub built_with_simd: Option<bool>,
+    /// The instruction sets that the SIMD extensions use on this CPU, such as `"sse2"` and
+    /// `"avx2"` on x86-64 or `"neon"` on Arm, after applying the `JSIMD_FORCE*` environment
+    /// variables. This is empty if the library was built without the SIMD extensions, if they are
+    /// disabled, or if TurboJPEG has no SIMD extensions for this architecture.
+    pub instruction_sets: Vec<&'static str>,
+}
+
+impl SimdStatus {
+    /// Returns whether TurboJPEG uses the SIMD extensions, if we know it.
+    ///
+    /// Returns `Some(false)` if the SIMD extensions are certainly not used and `Some(true)` if they
+    /// are certainly used; returns `None` if we do not know how the library was built, but the CPU
+    /// supports the SIMD extensions.
+    pub fn is_active(&self) -> Option<bool> {
+        match self.built_with_simd {
+            _ if self.instruction_sets.is_empty() => Some(false),
+            Some(built) => Some(built),
+            None => None,
+        }
+    }
+}
+
+/// Returns the status of the SIMD extensions of TurboJPEG.
+///
+/// The instruction sets are detected the same way as TurboJPEG selects its SIMD extensions, so
+/// you can check at startup that your application was not accidentally built with the slow scalar
+/// code.
+///
+/// # Example
+///
+/// ```
+/// let status = turbojpeg::simd_status();
+/// if status.is_active() == Some(false) {
+///     eprintln!("warning: TurboJPEG does not use SIMD, performance will suffer");
+/// }
+/// if let Some(true) = status.is_active() {
+///     assert!(!status.instruction_sets.is_empty());
+/// }
+/// ```
+pub fn simd_status() -> SimdStatus {
+    let built_with_simd = raw::SIMD_BUILD;
+    let mut instruction_sets = Vec::new();
+    if built_with_simd != Some(false) && !env_flag("JSIMD_FORCENONE") {
+        detect_instruction_sets(&mut instruction_sets);
+    }
+    SimdStatus { built_with_simd, instruction_sets }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn detect_instruction_sets(sets: &mut Vec<&'static str>) {
+    // on x86-64, SSE2 is always available
+    if cfg!(target_arch = "x86") && std::arch::is_x86_feature_detected!("sse") {
+        sets.push("sse");
+    }
+    if std::arch::is_x86_feature_detected!("sse2") {
+        sets.push("sse2");
+    }
+    if std::arch::is_x86_feature_detected!("avx2") {
+        sets.push("avx2");
+    }
+    // TurboJPEG keeps only the forced instruction set (if it is supported)
+    for (var, set) in [("JSIMD_FORCESSE", "sse"), ("JSIMD_FORCESSE2", "sse2"), ("JSIMD_FORCEAVX2", "avx2")] {
+        if env_flag(var) {
+            sets.retain(|&s| s == set);
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn detect_instruction_sets(sets: &mut Vec<&'static str>) {
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        sets.push("neon");
+    }
+}
+
+#[cfg(target_arch = "arm")]
+fn detect_instruction_sets(sets: &mut Vec<&'static str>) {
+    // runtime detection of Neon is not stable in Rust, so we trust the target
+    if cfg!(target_feature = "neon") {
+        sets.push("neon");
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+fn detect_instruction_sets(_sets: &mut Vec<&'static str>) {}
+
+/// Returns true if the environment variable is set to `1`, as TurboJPEG checks it.
+fn env_flag(var: &str) -> bool {
+    std::env::var_os(var).is_some_and(|value| value == "1")
+}
@@ -0,0 +1,86 @@
+use std::io::{Read, Write};
+use crate::common::{Error, PixelFormat, Result, Subsamp};
+use crate::compress::{Compressor, StripCompressor};
+use crate::decompress::Decompressor;
+use crate::settings::Settings;
+
+/// Number of rows that are decompressed and compressed at once by [`recompress_stream()`].
+const STRIP_HEIGHT: usize = 256;
+
+/// Recompresses a JPEG image from `reader` into `writer` with bounded memory.
+///
+/// The image is decompressed in strips of rows (see
+/// [`Decompressor::decompress_strips()`][crate::Decompressor::decompress_strips]), every strip is
+/// compressed with the given settings (see [`StripCompressor`]) and the compressed data is written
+/// to `writer` as soon as it is available. So unlike decompressing the whole image and
+/// compressing it again, the memory used for pixels does not grow with the height of the image,
+/// which is useful for services that transcode large uploads on the fly. Returns the number of
+/// bytes written to `writer`.
+///
+/// Some parts of the process still need memory that is proportional to the image:
+///
+/// - TurboJPEG needs the whole compressed image in memory, so the data from `reader` is read into
+///   a buffer before the decompression starts.
+/// - TurboJPEG holds the DCT coefficients of the whole image to decompress a progressive image.
+/// - If the input image does not contain restart markers at the start of MCU rows, every strip is
+///   decompressed as a cropped region, which decodes the compressed data again for every strip.
+///
+/// The [limits][Settings::limits] are applied to both the decompression and the compression, so
+/// you can use them to reject images that are too large (`max_output_bytes` is checked after
+/// every strip is written). The output is always compressed with restart markers after every MCU
+/// row and without optimized Huffman tables (see [`StripCompressor`]), so
+/// [`Settings::restart_blocks`], [`Settings::restart_rows`] and [`Settings::optimize`] are
+/// ignored; progressive and arithmetic coding are not supported and return an error. The image is
+/// decompressed into grayscale if [`Settings::subsamp`] is [`Subsamp::Gray`] and into RGB
+/// otherwise.
+///
+/// If an error is returned, some data may have already been written to `writer`.
+///
+/// # Example
+///
+/// ```
+/// let upload = std::fs::File::open("examples/parrots.jpg")?;
+/// let mut output = Vec::new();
+///
+/// let settings = turbojpeg::Settings {
+///     quality: 80,
+///     subsamp: turbojpeg::Subsamp::Sub2x2,
+///     ..Default::default()
+/// };
+/// let len = turbojpeg::recompress_stream(upload, &mut output, &settings)?;
+/// assert_eq!(len, output.len());
+///
+/// let header = turbojpeg::read_header(&output)?;
+/// assert_eq!((header.width, header.height), (384, 256));
+/// assert_eq!(header.subsamp, turbojpeg::Subsamp::Sub2x2);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn recompress_stream<R: Read, W: Write>(mut reader: R, mut writer: W, settings: &Settings) -> Result<usize> {
+    if settings.progressive || settings.arithmetic {
+        return Err(Error::StripCompression("progressive and arithmetic coding are not supported"))
+    }
+
+    let mut jpeg_data = Vec::new();
+    reader.read_to_end(&mut jpeg_data)?;
+
+    let mut decompressor = Decompressor::new()?;
+    decompressor.set_limits(settings.limits)?;
+    let header = decompressor.read_header(&jpeg_data)?;
+    let format = if settings.subsamp == Subsamp::Gray { PixelFormat::GRAY } else { PixelFormat::RGB };
+
+    let mut compressor = Compressor::new()?;
+    compressor.apply(settings)?;
+    let mut strip_compressor = StripCompressor::new(compressor, header.width, format)?;
+
+    let mut len = 0;
+    decompressor.decompress_strips(&jpeg_data, format, STRIP_HEIGHT, |_, strip| {
+        strip_compressor.write_rows(strip)?;
+        len += strip_compressor.write_compressed(header.height, &mut writer)?;
+        settings.limits.check_output_bytes(len)
+    })?;
+    len += strip_compressor.finish_to(header.height, &mut writer)?;
+    settings.limits.check_output_bytes(len)?;
+    writer.flush()?;
+    Ok(len)
+}
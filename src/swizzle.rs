@@ -0,0 +1,202 @@
+use crate::Image;
+use crate::common::{Error, PixelFormat, Result};
+use crate::decompress::Decompressor;
+
+/// A channel of the output of [`Decompressor::decompress_swizzled()`] and
+/// [`Decompressor::decompress_planar()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SwizzleChannel {
+    /// The red component.
+    Red,
+    /// The green component.
+    Green,
+    /// The blue component.
+    Blue,
+    /// A constant value (for example, `Constant(255)` for an opaque alpha channel).
+    Constant(u8),
+}
+
+/// Number of rows that are swizzled at once, so that they are still in the cache after
+/// decompression.
+const STRIP_HEIGHT: usize = 16;
+
+impl Decompressor {
+    /// Decompress a JPEG image into pixels with an arbitrary order of channels.
+    ///
+    /// Every pixel in `output` consists of `channels.len()` bytes, with the components given by
+    /// `channels`. This is useful for GPU and NPU input formats that are not covered by
+    /// [`PixelFormat`], such as RGB pixels with a constant padding byte, or BGRA pixels with a
+    /// semi-transparent alpha. Rows are `pitch` bytes apart (0 means no padding between rows).
+    ///
+    /// If the channels match one of the RGB pixel formats with an opaque alpha channel (such as
+    /// `[Blue, Green, Red, Constant(255)]` for [`PixelFormat::BGRA`]), the image is decompressed
+    /// directly into `output`. Otherwise, the image is decompressed into RGB and the channels are
+    /// swizzled in strips of a few rows, immediately after the rows are decompressed.
+    ///
+    /// If `output` is too short, returns [`Error::PixelsTooSmall`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is empty or if `pitch` is not zero and smaller than a row.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::SwizzleChannel::{Red, Green, Blue, Constant};
+    ///
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// let header = decompressor.read_header(&jpeg_data)?;
+    ///
+    /// // BGRA pixels with alpha 128
+    /// let channels = [Blue, Green, Red, Constant(128)];
+    /// let mut pixels = vec![0; 4 * header.width * header.height];
+    /// decompressor.decompress_swizzled(&jpeg_data, &channels, &mut pixels, 0)?;
+    /// assert!(pixels.chunks(4).all(|pixel| pixel[3] == 128));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn decompress_swizzled(
+        &mut self,
+        jpeg_data: &[u8],
+        channels: &[SwizzleChannel],
+        output: &mut [u8],
+        pitch: usize,
+    ) -> Result<()> {
+        assert!(!channels.is_empty(), "there must be at least one channel");
+        let header = self.read_header(jpeg_data)?;
+        let (width, height) = (header.width, header.height);
+        let row_len = width * channels.len();
+        let pitch = if pitch == 0 { row_len } else { pitch };
+        assert!(pitch >= row_len, "pitch {} is too small for {} channels", pitch, channels.len());
+        let len = min_len(pitch, row_len, height);
+        if output.len() < len {
+            return Err(Error::PixelsTooSmall(output.len(), len))
+        }
+
+        if let Some(format) = matching_format(channels) {
+            let image = Image { pixels: &mut output[..len], width, pitch, height, format };
+            return self.decompress(jpeg_data, image)
+        }
+
+        let sources = channels.iter().map(|&channel| source(channel)).collect::<Vec<_>>();
+        self.for_each_strip(jpeg_data, PixelFormat::RGB, STRIP_HEIGHT, false, |y, strip| {
+            for (row, rgb_row) in strip.pixels.chunks_exact(3 * width).enumerate() {
+                let out_row = &mut output[(y + row) * pitch..][..row_len];
+                for (out_pixel, rgb) in out_row.chunks_exact_mut(channels.len()).zip(rgb_row.chunks_exact(3)) {
+                    for (value, source) in out_pixel.iter_mut().zip(sources.iter()) {
+                        *value = match *source {
+                            Ok(offset) => rgb[offset],
+                            Err(constant) => constant,
+                        };
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Decompress a JPEG image into separate planes, one plane for every channel.
+    ///
+    /// Every plane in `planes` receives one byte per pixel, with the component given by the
+    /// corresponding channel in `channels` (for example, `[Red, Green, Blue]` for planar RGB).
+    /// Rows in every plane are `pitch` bytes apart (0 means no padding between rows). The image is
+    /// decompressed into RGB and the channels are split in strips of a few rows, immediately after
+    /// the rows are decompressed.
+    ///
+    /// If any plane is too short, returns [`Error::PixelsTooSmall`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of planes differs from the number of channels, or if `pitch` is not
+    /// zero and smaller than the width of the image.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::SwizzleChannel::{Red, Green, Blue};
+    ///
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    /// let header = decompressor.read_header(&jpeg_data)?;
+    ///
+    /// let plane_len = header.width * header.height;
+    /// let mut pixels = vec![0; 3 * plane_len];
+    /// let (r, gb) = pixels.split_at_mut(plane_len);
+    /// let (g, b) = gb.split_at_mut(plane_len);
+    /// decompressor.decompress_planar(&jpeg_data, &[Red, Green, Blue], &mut [r, g, b], 0)?;
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn decompress_planar(
+        &mut self,
+        jpeg_data: &[u8],
+        channels: &[SwizzleChannel],
+        planes: &mut [&mut [u8]],
+        pitch: usize,
+    ) -> Result<()> {
+        assert_eq!(planes.len(), channels.len(), "there must be one plane for every channel");
+        let header = self.read_header(jpeg_data)?;
+        let (width, height) = (header.width, header.height);
+        let pitch = if pitch == 0 { width } else { pitch };
+        assert!(pitch >= width, "pitch {} is too small for width {}", pitch, width);
+        let len = min_len(pitch, width, height);
+        for plane in planes.iter() {
+            if plane.len() < len {
+                return Err(Error::PixelsTooSmall(plane.len(), len))
+            }
+        }
+
+        self.for_each_strip(jpeg_data, PixelFormat::RGB, STRIP_HEIGHT, false, |y, strip| {
+            for (plane, &channel) in planes.iter_mut().zip(channels.iter()) {
+                for (row, rgb_row) in strip.pixels.chunks_exact(3 * width).enumerate() {
+                    let out_row = &mut plane[(y + row) * pitch..][..width];
+                    match source(channel) {
+                        Ok(offset) => {
+                            for (value, rgb) in out_row.iter_mut().zip(rgb_row.chunks_exact(3)) {
+                                *value = rgb[offset];
+                            }
+                        },
+                        Err(constant) => out_row.fill(constant),
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Returns the offset of the channel in an RGB pixel, or the constant value.
+fn source(channel: SwizzleChannel) -> std::result::Result<usize, u8> {
+    match channel {
+        SwizzleChannel::Red => Ok(0),
+        SwizzleChannel::Green => Ok(1),
+        SwizzleChannel::Blue => Ok(2),
+        SwizzleChannel::Constant(value) => Err(value),
+    }
+}
+
+/// Returns the RGB pixel format whose pixels TurboJPEG decompresses exactly as `channels`.
+fn matching_format(channels: &[SwizzleChannel]) -> Option<PixelFormat> {
+    PixelFormat::iter().find(|&format| {
+        // TurboJPEG sets the alpha channel to 255 when decompressing
+        let (Some(r), Some(g), Some(b)) = (format.red_offset(), format.green_offset(), format.blue_offset()) else {
+            return false
+        };
+        if channels.len() != format.size() || (format.size() == 4 && !format.has_alpha()) {
+            return false
+        }
+        channels.iter().enumerate().all(|(i, &channel)| match channel {
+            SwizzleChannel::Red => i == r,
+            SwizzleChannel::Green => i == g,
+            SwizzleChannel::Blue => i == b,
+            SwizzleChannel::Constant(value) => value == 255 && format.alpha_offset() == Some(i),
+        })
+    })
+}
+
+/// Returns the minimal length of a buffer with `height` rows of `row_len` bytes, `pitch` bytes
+/// apart.
+fn min_len(pitch: usize, row_len: usize, height: usize) -> usize {
+    if height == 0 { 0 } else { pitch * (height - 1) + row_len }
+}
@@ -0,0 +1,220 @@
+use std::convert::TryInto as _;
+use crate::markers;
+
+/// Estimates the quality that was used to compress a JPEG image.
+///
+/// The quality is estimated by comparing the quantization tables of the image with the standard
+/// tables from the JPEG specification, scaled in the same way as in libjpeg (and TurboJPEG) for
+/// every quality from 1 to 100. Returns the quality with the closest tables, or `None` if the
+/// image does not contain any quantization tables or if the JPEG header cannot be read.
+///
+/// For images that were compressed by libjpeg or TurboJPEG, this returns the exact quality. Other
+/// encoders may use different tables, so the result is only an approximation. This is useful to
+/// avoid recompressing an image with a higher quality than the original (which only increases the
+/// size of the image without improving its quality).
+///
+/// # Example
+///
+/// ```
+/// let image = turbojpeg::Image::mandelbrot(64, 64, turbojpeg::PixelFormat::RGB);
+/// let jpeg_data = turbojpeg::compress(image.as_deref(), 75, turbojpeg::Subsamp::Sub2x2)?;
+/// assert_eq!(turbojpeg::estimate_quality(&jpeg_data), Some(75));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn estimate_quality(jpeg_data: &[u8]) -> Option<u8> {
+    let tables = read_quant_tables(jpeg_data)?;
+    let luma = tables.iter().find(|table| table.id == 0).or(tables.first())?;
+    let chroma = tables.iter().find(|table| table.id == 1);
+
+    let error = |quality: u32| -> u64 {
+        let mut error = table_error(&luma.values, &STD_LUMA_QUANT, quality, luma.precision);
+        if let Some(chroma) = chroma {
+            error += table_error(&chroma.values, &STD_CHROMA_QUANT, quality, chroma.precision);
+        }
+        error
+    };
+    // prefer the higher quality if multiple qualities have the same tables
+    (1..=100).rev().min_by_key(|&quality| error(quality)).map(|quality| quality as u8)
+}
+
+/// Returns the difference between `values` and the `standard` table scaled for `quality`.
+fn table_error(values: &[u16; 64], standard: &[u16; 64], quality: u32, precision: u8) -> u64 {
+    // this is the same as jpeg_quality_scaling() and jpeg_add_quant_table() in libjpeg
+    let scale = if quality < 50 { 5000 / quality } else { 200 - 2 * quality };
+    let max = if precision == 0 { 255 } else { 32767 };
+    values.iter().zip(standard.iter())
+        .map(|(&value, &standard)| {
+            let scaled = ((standard as u32 * scale + 50) / 100).clamp(1, max);
+            (value as i64 - scaled as i64).unsigned_abs()
+        })
+        .sum()
+}
+
+/// A quantization table from a DQT segment.
+///
+/// Returned by [`read_quant_tables()`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct QuantTable {
+    /// Table identifier (0 to 3), which is referenced by the components in the frame header.
+    pub id: u8,
+    /// Precision of the values: 0 for 8-bit values, 1 for 16-bit values.
+    pub precision: u8,
+    /// Values of the table in the natural (row-major) order, so `values[8*v + u]` is the
+    /// quantization step of the DCT coefficient with horizontal frequency `u` and vertical
+    /// frequency `v`.
+    pub values: [u16; 64],
+}
+
+/// Class of a [`HuffmanTable`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum HuffmanClass {
+    /// Table for the DC coefficients.
+    Dc,
+    /// Table for the AC coefficients.
+    Ac,
+}
+
+/// A Huffman table from a DHT segment.
+///
+/// Returned by [`read_huffman_tables()`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct HuffmanTable {
+    /// Class of the table (DC or AC).
+    pub class: HuffmanClass,
+    /// Table identifier (0 to 3), which is referenced by the components in the scan headers.
+    pub id: u8,
+    /// Number of codes of each length: `counts[i]` is the number of codes with length `i + 1`
+    /// bits.
+    pub counts: [u8; 16],
+    /// Symbols in the order of increasing code length.
+    pub symbols: Vec<u8>,
+}
+
+/// Reads the quantization tables (DQT segments) of a JPEG image.
+///
+/// The tables are returned in the order in which they are defined in the image. Progressive
+/// images may redefine the tables between the scans, so the same table identifier may appear
+/// multiple times. Returns `None` if the data is not a JPEG image or if a DQT segment is
+/// malformed.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let tables = turbojpeg::read_quant_tables(&jpeg_data).unwrap();
+/// for table in tables.iter() {
+///     println!("table {}: DC quantization step {}", table.id, table.values[0]);
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[doc(alias = "DQT")]
+pub fn read_quant_tables(jpeg_data: &[u8]) -> Option<Vec<QuantTable>> {
+    let mut tables = Vec::new();
+    for segment in markers::read_all_segments(jpeg_data)? {
+        if segment.marker != markers::DQT {
+            continue
+        }
+        let mut data = segment.data;
+        while let Some(&pq_tq) = data.first() {
+            let (precision, id) = (pq_tq >> 4, pq_tq & 0xf);
+            let len = if precision == 0 { 64 } else { 128 };
+            let values = data.get(1..1 + len)?;
+            let mut table = QuantTable { id, precision, values: [0; 64] };
+            for (i, &natural) in ZIGZAG.iter().enumerate() {
+                table.values[natural] = if precision == 0 {
+                    values[i] as u16
+                } else {
+                    u16::from_be_bytes([values[2 * i], values[2 * i + 1]])
+                };
+            }
+            tables.push(table);
+            data = &data[1 + len..];
+        }
+    }
+    Some(tables)
+}
+
+/// Reads the Huffman tables (DHT segments) of a JPEG image.
+///
+/// The tables are returned in the order in which they are defined in the image. Progressive
+/// images usually define new tables before every scan, so the same class and identifier may
+/// appear multiple times. Images that use arithmetic coding do not contain any Huffman tables.
+/// Returns `None` if the data is not a JPEG image or if a DHT segment is malformed.
+///
+/// Note that some images (for example, frames of Motion JPEG videos) do not contain Huffman tables
+/// and rely on the standard tables from the JPEG specification instead.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let tables = turbojpeg::read_huffman_tables(&jpeg_data).unwrap();
+/// for table in tables.iter() {
+///     println!("{:?} table {} with {} symbols", table.class, table.id, table.symbols.len());
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[doc(alias = "DHT")]
+pub fn read_huffman_tables(jpeg_data: &[u8]) -> Option<Vec<HuffmanTable>> {
+    let mut tables = Vec::new();
+    for segment in markers::read_all_segments(jpeg_data)? {
+        if segment.marker != markers::DHT {
+            continue
+        }
+        let mut data = segment.data;
+        while let Some(&tc_th) = data.first() {
+            let class = match tc_th >> 4 {
+                0 => HuffmanClass::Dc,
+                1 => HuffmanClass::Ac,
+                _ => return None,
+            };
+            let counts: [u8; 16] = data.get(1..17)?.try_into().unwrap();
+            let symbol_count = counts.iter().map(|&count| count as usize).sum::<usize>();
+            let symbols = data.get(17..17 + symbol_count)?.to_vec();
+            tables.push(HuffmanTable { class, id: tc_th & 0xf, counts, symbols });
+            data = &data[17 + symbol_count..];
+        }
+    }
+    Some(tables)
+}
+
+/// Positions of the coefficients in the zigzag order, in the natural order.
+const ZIGZAG: [usize; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// Luminance quantization table from Annex K of the JPEG specification, in the natural order.
+const STD_LUMA_QUANT: [u16; 64] = [
+    16,  11,  10,  16,  24,  40,  51,  61,
+    12,  12,  14,  19,  26,  58,  60,  55,
+    14,  13,  16,  24,  40,  57,  69,  56,
+    14,  17,  22,  29,  51,  87,  80,  62,
+    18,  22,  37,  56,  68, 109, 103,  77,
+    24,  35,  55,  64,  81, 104, 113,  92,
+    49,  64,  78,  87, 103, 121, 120, 101,
+    72,  92,  95,  98, 112, 100, 103,  99,
+];
+
+/// Chrominance quantization table from Annex K of the JPEG specification, in the natural order.
+const STD_CHROMA_QUANT: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
@@ -0,0 +1,230 @@
+use crate::common::{Error, PixelFormat, Result};
+use crate::decompress::Decompressor;
+
+/// Order of the values in a tensor produced by [`decompress_to_tensor()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum TensorLayout {
+    /// Height × width × channels: the channels of every pixel are stored together, as in the
+    /// decompressed image (this is the layout used by TensorFlow).
+    #[default]
+    Hwc,
+    /// Channels × height × width: every channel is stored in a separate plane (this is the layout
+    /// used by PyTorch and ONNX models).
+    Chw,
+}
+
+/// Format of a tensor produced by [`decompress_to_tensor()`].
+///
+/// Every sample of the decompressed image (from 0 to 255) is converted to `(sample / 255 -
+/// mean[c]) / std[c]`, where `c` is the index of the channel in the pixel format. The default
+/// format uses [`PixelFormat::RGB`], mean 0 and standard deviation 1, so the values are in the
+/// range from 0 to 1.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TensorFormat {
+    /// Order of the values in the tensor.
+    pub layout: TensorLayout,
+    /// Pixel format of the decompressed image, which determines the number and order of the
+    /// channels (for example, [`PixelFormat::BGR`] for models trained on BGR images).
+    pub pixel_format: PixelFormat,
+    /// Mean of every channel, subtracted from the values after scaling them to the range from 0 to
+    /// 1. Only the first `pixel_format.size()` values are used.
+    pub mean: [f32; 4],
+    /// Standard deviation of every channel, which divides the values after subtracting the mean.
+    /// Only the first `pixel_format.size()` values are used.
+    pub std: [f32; 4],
+}
+
+impl TensorFormat {
+    /// Returns the RGB format normalized with the mean and standard deviation of the ImageNet
+    /// dataset, which is expected by most pretrained image classification models.
+    pub fn imagenet(layout: TensorLayout) -> TensorFormat {
+        TensorFormat {
+            layout,
+            pixel_format: PixelFormat::RGB,
+            mean: [0.485, 0.456, 0.406, 0.0],
+            std: [0.229, 0.224, 0.225, 1.0],
+        }
+    }
+
+    /// Returns the number of values in the tensor for an image with the given size.
+    pub fn tensor_len(&self, width: usize, height: usize) -> usize {
+        width * height * self.pixel_format.size()
+    }
+}
+
+impl Default for TensorFormat {
+    fn default() -> TensorFormat {
+        TensorFormat {
+            layout: TensorLayout::Hwc,
+            pixel_format: PixelFormat::RGB,
+            mean: [0.0; 4],
+            std: [1.0; 4],
+        }
+    }
+}
+
+/// Type of the values in a tensor produced by [`decompress_to_tensor()`].
+///
+/// This is implemented for `f32` and, with the `half` feature, for [`half::f16`], which can be
+/// uploaded directly into GPU textures with 16-bit float channels.
+pub trait TensorElement: Copy {
+    /// Converts a value from `f32`.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl TensorElement for f32 {
+    fn from_f32(value: f32) -> f32 {
+        value
+    }
+}
+
+#[cfg(feature = "half")]
+#[cfg_attr(docsrs, doc(cfg(feature = "half")))]
+impl TensorElement for half::f16 {
+    fn from_f32(value: f32) -> half::f16 {
+        half::f16::from_f32(value)
+    }
+}
+
+/// A tensor produced by [`decompress_to_tensor()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor<T> {
+    /// The values of the tensor.
+    pub data: Vec<T>,
+    /// Width of the image in pixels.
+    pub width: usize,
+    /// Height of the image in pixels.
+    pub height: usize,
+    /// Number of channels.
+    pub channels: usize,
+    /// Order of the values in `data`.
+    pub layout: TensorLayout,
+}
+
+impl<T> Tensor<T> {
+    /// Returns the shape of the tensor, in the order given by the [layout][TensorLayout].
+    pub fn shape(&self) -> [usize; 3] {
+        match self.layout {
+            TensorLayout::Hwc => [self.height, self.width, self.channels],
+            TensorLayout::Chw => [self.channels, self.height, self.width],
+        }
+    }
+}
+
+/// Number of rows that are converted at once, so that they are still in the cache after
+/// decompression.
+const STRIP_HEIGHT: usize = 16;
+
+impl Decompressor {
+    /// Decompress a JPEG image into a normalized tensor for machine learning models.
+    ///
+    /// The values are stored into `output` in the layout given by `format` (see [`TensorFormat`]
+    /// for the normalization). Use [`read_header()`][Self::read_header] and
+    /// [`TensorFormat::tensor_len()`] to determine the required length of `output`; if `output` is
+    /// too short, returns [`Error::PixelsTooSmall`].
+    ///
+    /// The samples are converted using a lookup table in strips of a few rows, immediately after
+    /// the rows are decompressed, so they are still in the cache. If the image contains restart
+    /// markers at the start of MCU rows, the image is decompressed in strips (see
+    /// [`decompress_strips()`][Self::decompress_strips]), otherwise it is decompressed into a
+    /// temporary buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    ///
+    /// let header = decompressor.read_header(&jpeg_data)?;
+    /// let format = turbojpeg::TensorFormat::imagenet(turbojpeg::TensorLayout::Chw);
+    /// let mut tensor = vec![0f32; format.tensor_len(header.width, header.height)];
+    /// decompressor.decompress_to_tensor(&jpeg_data, &format, &mut tensor)?;
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn decompress_to_tensor<T: TensorElement>(
+        &mut self,
+        jpeg_data: &[u8],
+        format: &TensorFormat,
+        output: &mut [T],
+    ) -> Result<()> {
+        let header = self.read_header(jpeg_data)?;
+        let (width, height) = (header.width, header.height);
+        let len = format.tensor_len(width, height);
+        if output.len() < len {
+            return Err(Error::PixelsTooSmall(output.len(), len))
+        }
+        let output = &mut output[..len];
+
+        let channels = format.pixel_format.size();
+        let tables = (0..channels).map(|c| {
+            let mut table = [T::from_f32(0.0); 256];
+            for (sample, value) in table.iter_mut().enumerate() {
+                *value = T::from_f32((sample as f32 / 255.0 - format.mean[c]) / format.std[c]);
+            }
+            table
+        }).collect::<Vec<_>>();
+
+        self.for_each_strip(jpeg_data, format.pixel_format, STRIP_HEIGHT, false, |y, strip| {
+            let samples = &strip.pixels[..strip.height * width * channels];
+            match format.layout {
+                TensorLayout::Hwc => {
+                    let values = &mut output[y * width * channels..][..samples.len()];
+                    for ((value, &sample), c) in values.iter_mut().zip(samples).zip((0..channels).cycle()) {
+                        *value = tables[c][sample as usize];
+                    }
+                },
+                TensorLayout::Chw => {
+                    for (c, table) in tables.iter().enumerate() {
+                        let plane = &mut output[c * width * height..][y * width..][..strip.height * width];
+                        for (value, pixel) in plane.iter_mut().zip(samples.chunks_exact(channels)) {
+                            *value = table[pixel[c] as usize];
+                        }
+                    }
+                },
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Decompress a JPEG image into a normalized tensor for machine learning models.
+///
+/// Returns a newly allocated tensor. See [`Decompressor::decompress_to_tensor()`] for details.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let format = turbojpeg::TensorFormat { layout: turbojpeg::TensorLayout::Chw, ..Default::default() };
+/// let tensor = turbojpeg::decompress_to_tensor::<f32>(&jpeg_data, &format)?;
+/// assert_eq!(tensor.shape(), [3, 256, 384]);
+/// assert!(tensor.data.iter().all(|&value| (0.0..=1.0).contains(&value)));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// With the `half` feature, the values can be converted to half-precision floats in the same pass:
+///
+/// ```
+/// # #[cfg(feature = "half")] {
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let format = turbojpeg::TensorFormat { pixel_format: turbojpeg::PixelFormat::RGBA, ..Default::default() };
+/// let tensor = turbojpeg::decompress_to_tensor::<half::f16>(&jpeg_data, &format)?;
+/// assert_eq!(tensor.shape(), [256, 384, 4]);
+/// # }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn decompress_to_tensor<T: TensorElement>(jpeg_data: &[u8], format: &TensorFormat) -> Result<Tensor<T>> {
+    let mut decompressor = Decompressor::new()?;
+    let header = decompressor.read_header(jpeg_data)?;
+    let mut data = vec![T::from_f32(0.0); format.tensor_len(header.width, header.height)];
+    decompressor.decompress_to_tensor(jpeg_data, format, &mut data)?;
+    Ok(Tensor {
+        data,
+        width: header.width,
+        height: header.height,
+        channels: format.pixel_format.size(),
+        layout: format.layout,
+    })
+}
@@ -0,0 +1,199 @@
+//! Utilities for testing code that uses JPEG images.
+//!
+//! This module is available with the `test-util` feature. It provides generators of synthetic
+//! images (in addition to [`Image::mandelbrot()`]) and functions to check that images survive a
+//! roundtrip through JPEG compression and decompression with acceptable quality.
+//!
+//! # Example
+//!
+//! ```
+//! use turbojpeg::{PixelFormat, Subsamp, test_util};
+//!
+//! let image = test_util::color_bars(320, 240, PixelFormat::RGB);
+//! let psnr = test_util::assert_roundtrip(image.as_deref(), 90, Subsamp::Sub2x2, 30.);
+//! println!("PSNR after roundtrip: {:.1} dB", psnr);
+//! ```
+use crate::Image;
+use crate::common::{PixelFormat, Subsamp};
+
+/// Generates a smooth gradient.
+///
+/// The red component increases from left to right, the green component increases from top to
+/// bottom and the blue component decreases along the diagonal. Smooth images like this compress
+/// very well, so they test the low-frequency DCT coefficients.
+///
+/// # Example
+///
+/// ```
+/// let image = turbojpeg::test_util::gradient(256, 256, turbojpeg::PixelFormat::RGB);
+/// assert_eq!(&image.pixels[0..3], &[0, 0, 255]);
+/// ```
+pub fn gradient(width: usize, height: usize, format: PixelFormat) -> Image<Vec<u8>> {
+    let scale = |value: usize, max: usize| (value * 255 / max.saturating_sub(1).max(1)) as u8;
+    from_fn(width, height, format, |x, y| {
+        let diagonal = (x * height + y * width) / 2;
+        [scale(x, width), scale(y, height), 255 - scale(diagonal, width * height)]
+    })
+}
+
+/// Generates uniform random noise.
+///
+/// The noise is generated from the given `seed` using a simple pseudo-random generator, so the
+/// same seed always produces the same image. Noise is the worst case for JPEG compression, so it
+/// tests the high-frequency DCT coefficients and the size of the compressed data.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{PixelFormat, test_util};
+/// let image_1 = test_util::noise(64, 64, PixelFormat::RGB, 42);
+/// let image_2 = test_util::noise(64, 64, PixelFormat::RGB, 42);
+/// assert_eq!(image_1.pixels, image_2.pixels);
+/// ```
+pub fn noise(width: usize, height: usize, format: PixelFormat, seed: u64) -> Image<Vec<u8>> {
+    // xorshift64* (the state must not be zero)
+    let mut state = seed ^ 0x9e37_79b9_7f4a_7c15;
+    let mut next = move || {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    };
+    from_fn(width, height, format, |_, _| {
+        let [r, g, b, ..] = next().to_le_bytes();
+        [r, g, b]
+    })
+}
+
+/// Generates vertical color bars.
+///
+/// The image contains eight bars of equal width with the colors white, yellow, cyan, green,
+/// magenta, red, blue and black (similar to the SMPTE color bars). The sharp edges between
+/// saturated colors test the chrominance subsampling.
+///
+/// # Example
+///
+/// ```
+/// let image = turbojpeg::test_util::color_bars(80, 10, turbojpeg::PixelFormat::RGB);
+/// assert_eq!(&image.pixels[0..3], &[255, 255, 255]);
+/// assert_eq!(&image.pixels[3*15..3*16], &[255, 255, 0]);
+/// ```
+pub fn color_bars(width: usize, height: usize, format: PixelFormat) -> Image<Vec<u8>> {
+    const BARS: [[u8; 3]; 8] = [
+        [255, 255, 255], [255, 255, 0], [0, 255, 255], [0, 255, 0],
+        [255, 0, 255], [255, 0, 0], [0, 0, 255], [0, 0, 0],
+    ];
+    from_fn(width, height, format, |x, _| BARS[x * BARS.len() / width])
+}
+
+/// Computes the peak signal-to-noise ratio (PSNR) between two images, in decibels.
+///
+/// Higher values mean that the images are more similar; identical images have infinite PSNR.
+/// Typical values for JPEG images are between 30 and 50 dB. Only the color components are
+/// compared; the alpha and padding bytes are ignored.
+///
+/// # Panics
+///
+/// Panics if the images have different sizes or pixel formats.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{PixelFormat, test_util};
+/// let image = test_util::gradient(64, 64, PixelFormat::RGB);
+/// assert_eq!(test_util::psnr(image.as_deref(), image.as_deref()), f64::INFINITY);
+/// ```
+pub fn psnr(a: Image<&[u8]>, b: Image<&[u8]>) -> f64 {
+    assert_eq!((a.width, a.height, a.format), (b.width, b.height, b.format),
+        "images must have the same size and pixel format");
+
+    let format = a.format;
+    let components: Vec<usize> = match format.alpha_offset() {
+        Some(alpha) => (0..format.size()).filter(|&c| c != alpha).collect(),
+        None => (0..format.size()).collect(),
+    };
+
+    let mut sum_squares = 0f64;
+    for y in 0..a.height {
+        let row_a = &a.pixels[y * a.row_pitch()..];
+        let row_b = &b.pixels[y * b.row_pitch()..];
+        for x in 0..a.width {
+            for &c in components.iter() {
+                let offset = x * format.size() + c;
+                let diff = row_a[offset] as f64 - row_b[offset] as f64;
+                sum_squares += diff * diff;
+            }
+        }
+    }
+
+    let count = (a.width * a.height * components.len()).max(1) as f64;
+    let mse = sum_squares / count;
+    if mse == 0. {
+        return f64::INFINITY
+    }
+    10. * f64::log10(255. * 255. / mse)
+}
+
+/// Compresses the image into JPEG, decompresses it and checks that the result is similar to the
+/// original image.
+///
+/// Returns the [PSNR][psnr] between the original and the decompressed image.
+///
+/// # Panics
+///
+/// Panics if the compression or decompression fails, or if the PSNR is lower than `min_psnr`.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::{PixelFormat, Subsamp, test_util};
+/// let image = test_util::gradient(128, 128, PixelFormat::BGRA);
+/// test_util::assert_roundtrip(image.as_deref(), 95, Subsamp::None, 35.);
+/// ```
+#[track_caller]
+pub fn assert_roundtrip(image: Image<&[u8]>, quality: i32, subsamp: Subsamp, min_psnr: f64) -> f64 {
+    let jpeg_data = crate::compress(image, quality, subsamp)
+        .unwrap_or_else(|err| panic!("could not compress the image: {}", err));
+    let decompressed = crate::decompress(&jpeg_data, image.format)
+        .unwrap_or_else(|err| panic!("could not decompress the image: {}", err));
+
+    let psnr = psnr(image, decompressed.as_deref());
+    assert!(psnr >= min_psnr,
+        "PSNR after roundtrip is {:.2} dB, expected at least {:.2} dB (quality {}, subsampling {:?})",
+        psnr, min_psnr, quality, subsamp);
+    psnr
+}
+
+/// Generates an image from a function that returns the RGB color of each pixel.
+fn from_fn<F>(width: usize, height: usize, format: PixelFormat, mut f: F) -> Image<Vec<u8>>
+    where F: FnMut(usize, usize) -> [u8; 3]
+{
+    let pixel_size = format.size();
+    let pitch = pixel_size * width;
+    let mut pixels = vec![0; pitch * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = f(x, y);
+            let pixel = &mut pixels[y * pitch + x * pixel_size..][..pixel_size];
+            match format {
+                PixelFormat::GRAY => {
+                    pixel[0] = ((77 * r as u32 + 150 * g as u32 + 29 * b as u32) >> 8) as u8;
+                },
+                PixelFormat::CMYK => {
+                    pixel.copy_from_slice(&[255 - r, 255 - g, 255 - b, 0]);
+                },
+                _ => {
+                    pixel[format.red_offset().unwrap()] = r;
+                    pixel[format.green_offset().unwrap()] = g;
+                    pixel[format.blue_offset().unwrap()] = b;
+                    if let Some(a) = format.alpha_offset() {
+                        pixel[a] = 255;
+                    }
+                },
+            }
+        }
+    }
+
+    Image { pixels, width, pitch, height, format }
+}
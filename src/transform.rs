@@ -1,8 +1,11 @@
 use std::ptr;
 use std::convert::TryInto as _;
 use crate::buf::{OwnedBuf, OutputBuf};
-use crate::common::{Error, Result};
+use crate::common::{Error, Result, Subsamp};
 use crate::handle::Handle;
+use crate::join::{self, Filter, FilterRegion};
+use crate::limits::Limits;
+use crate::markers;
 
 /// Transforms JPEG images without recompression.
 ///
@@ -54,6 +57,14 @@ pub struct Transformer {
 /// let mut transform = Transform::default();
 /// transform.crop = Some(TransformCrop { x: 16, y: 32, width: Some(200), height: Some(100) });
 /// ```
+///
+/// Crop a region of size (200, 100) from the center of the image:
+///
+/// ```
+/// # use turbojpeg::{Transform, TransformOp, TransformCrop};
+/// let mut transform = Transform::default();
+/// transform.crop = Some(TransformCrop::centered(200, 100));
+/// ```
 #[derive(Debug, Default, Clone)]
 #[doc(alias = "tjtransform")]
 #[non_exhaustive]
@@ -110,6 +121,23 @@ pub struct Transform {
     pub copy_none: bool,
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> arbitrary::Arbitrary<'a> for Transform {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Transform {
+            op: u.arbitrary()?,
+            crop: u.arbitrary()?,
+            perfect: u.arbitrary()?,
+            trim: u.arbitrary()?,
+            gray: u.arbitrary()?,
+            progressive: u.arbitrary()?,
+            optimize: u.arbitrary()?,
+            copy_none: u.arbitrary()?,
+        })
+    }
+}
+
 impl Transform {
     /// Creates a [`Transform`] with the given `op` and all other parameters set to default.
     ///
@@ -183,17 +211,64 @@ pub enum TransformOp {
     Rot270 = raw::TJXOP_TJXOP_ROT270,
 }
 
+impl TransformOp {
+    /// Returns the operation that rotates the image clockwise by the given angle in degrees.
+    ///
+    /// Negative angles rotate counter-clockwise and any multiple of 90 degrees is accepted (so
+    /// `-90` and `270` both return [`Rot270`][Self::Rot270], and `0` or `360` return
+    /// [`None`][Self::None]). This is convenient when the angle comes from user input or
+    /// metadata. Returns [`Error::BadRotation`] if the angle is not a multiple of 90 degrees.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::TransformOp;
+    /// assert_eq!(TransformOp::from_degrees(90)?, TransformOp::Rot90);
+    /// assert_eq!(TransformOp::from_degrees(-90)?, TransformOp::Rot270);
+    /// assert_eq!(TransformOp::from_degrees(540)?, TransformOp::Rot180);
+    /// assert!(TransformOp::from_degrees(45).is_err());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_degrees(degrees: i32) -> Result<TransformOp> {
+        match degrees.rem_euclid(360) {
+            0 => Ok(TransformOp::None),
+            90 => Ok(TransformOp::Rot90),
+            180 => Ok(TransformOp::Rot180),
+            270 => Ok(TransformOp::Rot270),
+            _ => Err(Error::BadRotation(degrees)),
+        }
+    }
+}
+
 impl Default for TransformOp {
     fn default() -> Self {
         TransformOp::None
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> arbitrary::Arbitrary<'a> for TransformOp {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[
+            TransformOp::None, TransformOp::Hflip, TransformOp::Vflip, TransformOp::Transpose,
+            TransformOp::Transverse, TransformOp::Rot90, TransformOp::Rot180, TransformOp::Rot270,
+        ])?)
+    }
+}
+
 /// Transform cropping region.
 ///
 /// The [`x`][Self::x] and [`y`][Self::y] position of the region must be aligned on MCU boundaries.
 /// The size of the MCU depends on the chrominance subsampling option, which can be obtained using
-/// [`Decompressor::read_header()`][crate::Decompressor::read_header].
+/// [`Decompressor::read_header()`][crate::Decompressor::read_header] (see also
+/// [`DecompressHeader::align_crop()`][crate::DecompressHeader::align_crop]).
+///
+/// The constructors [`centered()`][Self::centered], [`top_left()`][Self::top_left] and
+/// [`full_height()`][Self::full_height] cover the common cases; the position and size that depend
+/// on the image are determined from the JPEG header when the transform is performed, so you do not
+/// need to read the header yourself.
 ///
 /// The default instance performs no cropping.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -212,6 +287,112 @@ pub struct TransformCrop {
     pub height: Option<usize>,
 }
 
+impl TransformCrop {
+    /// Value of [`x`][Self::x] or [`y`][Self::y] that centers the region horizontally or
+    /// vertically.
+    ///
+    /// The position is computed from the size of the image when the transform is performed,
+    /// rounded down to MCU boundaries (so the region may be slightly off-center). If the region is
+    /// larger than the image, it is reduced to the size of the image.
+    pub const CENTER: usize = usize::MAX;
+
+    /// Crops a region of the given size from the center of the image.
+    ///
+    /// Both [`x`][Self::x] and [`y`][Self::y] are set to [`CENTER`][Self::CENTER].
+    pub const fn centered(width: usize, height: usize) -> TransformCrop {
+        TransformCrop { x: Self::CENTER, y: Self::CENTER, width: Some(width), height: Some(height) }
+    }
+
+    /// Crops a region of the given size from the upper left corner of the image.
+    pub const fn top_left(width: usize, height: usize) -> TransformCrop {
+        TransformCrop { x: 0, y: 0, width: Some(width), height: Some(height) }
+    }
+
+    /// Crops a region with the given width that starts at column `x` and spans the whole height of
+    /// the image. The column `x` must be divisible by the MCU width.
+    pub const fn full_height(x: usize, width: usize) -> TransformCrop {
+        TransformCrop { x, y: 0, width: Some(width), height: None }
+    }
+
+    /// Returns true if [`x`][Self::x] or [`y`][Self::y] is [`CENTER`][Self::CENTER].
+    fn is_centered(&self) -> bool {
+        self.x == Self::CENTER || self.y == Self::CENTER
+    }
+
+    /// Computes the position of a centered region in an image of the given size.
+    fn resolve(self, width: usize, height: usize, mcu_width: usize, mcu_height: usize) -> TransformCrop {
+        let (x, width) = center(self.x, self.width, width, mcu_width);
+        let (y, height) = center(self.y, self.height, height, mcu_height);
+        TransformCrop { x, y, width, height }
+    }
+}
+
+/// Computes the position and length of a region in one dimension of the image, if the position is
+/// [`TransformCrop::CENTER`].
+fn center(pos: usize, len: Option<usize>, image_len: usize, mcu_len: usize) -> (usize, Option<usize>) {
+    if pos != TransformCrop::CENTER {
+        return (pos, len)
+    }
+    let len = len.map_or(image_len, |len| len.min(image_len));
+    ((image_len - len) / 2 / mcu_len * mcu_len, Some(len))
+}
+
+/// Report about partial MCU blocks that could not be transformed.
+///
+/// Returned by [`Transformer::transform_with_report()`]. If the transform is not [perfect][Transform::perfect]
+/// and [`Transform::trim`] is not enabled, the partial MCU blocks on the right or bottom edge of
+/// the source image that cannot be transformed are left in place. If `trim` is enabled, these
+/// blocks are discarded instead.
+///
+/// The counts are in pixels of the source image (after cropping with [`Transform::crop`]).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub struct TransformReport {
+    /// Number of columns on the right edge of the source image that were not transformed.
+    pub imperfect_columns: usize,
+    /// Number of rows on the bottom edge of the source image that were not transformed.
+    pub imperfect_rows: usize,
+    /// True if the imperfect columns and rows were discarded ([`Transform::trim`]), false if they
+    /// were left in place without the transform.
+    pub trimmed: bool,
+}
+
+impl TransformReport {
+    /// Returns true if the whole image was transformed.
+    pub fn is_perfect(&self) -> bool {
+        self.imperfect_columns == 0 && self.imperfect_rows == 0
+    }
+}
+
+/// DCT coefficients passed to the filter in [`Transformer::transform_with_filter()`].
+///
+/// The coefficients belong to a rectangle of blocks (8×8 samples) in the plane of one component of
+/// the transformed image. The positions and sizes are measured in blocks, and the planes of
+/// subsampled components are smaller than the plane of the luminance component.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CoefficientBlocks<'a> {
+    /// Index of the component (0 is luminance, 1 and 2 are chrominance in YCbCr images).
+    pub component: usize,
+    /// First block column of the rectangle in the component plane.
+    pub x: usize,
+    /// First block row of the rectangle in the component plane.
+    pub y: usize,
+    /// Number of block columns in the rectangle.
+    pub width: usize,
+    /// Number of block rows in the rectangle. The last rows may be below the component plane, if
+    /// the height of the plane is not a multiple of the number of rows that TurboJPEG processes at
+    /// once.
+    pub height: usize,
+    /// Width of the component plane in blocks.
+    pub plane_width: usize,
+    /// Height of the component plane in blocks.
+    pub plane_height: usize,
+    /// The quantized coefficients, 64 for every block in natural (row-major) order, with the blocks
+    /// in row-major order.
+    pub coeffs: &'a mut [i16],
+}
+
 impl Transformer {
     /// Create a new transformer instance.
     #[doc(alias = "tj3Init")]
@@ -220,11 +401,68 @@ impl Transformer {
         Ok(Self { handle })
     }
 
+    /// Set the identifier of the image that is attached to logged warnings.
+    ///
+    /// With the `log` feature, the warnings that TurboJPEG reports about corrupted data are logged
+    /// using the [`log`] crate (with target `turbojpeg`), together with the operation and this
+    /// identifier (such as a file name or URL). Use `None` to remove the identifier.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "log")] {
+    /// let mut transformer = turbojpeg::Transformer::new()?;
+    /// transformer.set_image_id(Some("photos/IMG_0001.jpg"));
+    /// # }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "log")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "log")))]
+    pub fn set_image_id(&mut self, image_id: Option<&str>) {
+        self.handle.set_image_id(image_id);
+    }
+
+    /// Set the limits on the resources used by this transformer.
+    ///
+    /// The limits are initialized from [`Limits::global()`] when the transformer is created. See
+    /// [`Limits`] for details.
+    #[doc(alias = "TJPARAM_SCANLIMIT")]
+    pub fn set_limits(&mut self, limits: Limits) -> Result<()> {
+        self.handle.set_limits(limits)
+    }
+
+    /// Get the limits on the resources used by this transformer.
+    pub fn limits(&self) -> Limits {
+        *self.handle.limits()
+    }
+
+    /// Set the limit on the estimated memory in bytes that TurboJPEG needs to process an image.
+    ///
+    /// This changes only [`Limits::max_memory`] in the [limits][Self::limits] of this transformer
+    /// (`None` means no limit).
+    #[doc(alias = "TJPARAM_MAXMEMORY")]
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) -> Result<()> {
+        self.set_limits(Limits { max_memory, ..self.limits() })
+    }
+
+    /// Set the limit on the number of pixels (width × height) of an image.
+    ///
+    /// This changes only [`Limits::max_pixels`] in the [limits][Self::limits] of this transformer
+    /// (`None` means no limit).
+    #[doc(alias = "TJPARAM_MAXPIXELS")]
+    pub fn set_max_pixels(&mut self, max_pixels: Option<usize>) -> Result<()> {
+        self.set_limits(Limits { max_pixels, ..self.limits() })
+    }
+
     /// Apply a transformation to the compressed JPEG.
     ///
     /// This is the main transformation method, which gives you full control of the output buffer. If
     /// you don't need this level of control, you can use one of the convenience wrappers below.
     ///
+    /// Returns [`Error::UnsupportedSubsampForTransformCrop`] if the transform specifies
+    /// [`Transform::crop`] without [`Transform::gray`] and the image uses [unknown
+    /// subsampling][Subsamp::Unknown].
+    ///
     /// # Example
     ///
     /// ```
@@ -256,6 +494,110 @@ impl Transformer {
         jpeg_data: &[u8],
         output: &mut OutputBuf,
     ) -> Result<()> {
+        self.transform_impl(transform, jpeg_data, output, None)?;
+        Ok(())
+    }
+
+    /// Apply a transformation to the compressed JPEG and report the edges that were not
+    /// transformed.
+    ///
+    /// This is the same as [`transform()`][Self::transform], but it returns a [`TransformReport`]
+    /// that describes the edges of the image that could not be transformed if the transform is
+    /// imperfect (see [`Transform::perfect`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut transformer = turbojpeg::Transformer::new()?;
+    ///
+    /// let mut transform = turbojpeg::Transform::op(turbojpeg::TransformOp::Vflip);
+    /// transform.trim = true;
+    ///
+    /// let mut flipped_data = turbojpeg::OutputBuf::new_owned();
+    /// let report = transformer.transform_with_report(&transform, &jpeg_data, &mut flipped_data)?;
+    /// if !report.is_perfect() {
+    ///     println!("trimmed {} rows on the bottom edge", report.imperfect_rows);
+    /// }
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn transform_with_report(
+        &mut self,
+        transform: &Transform,
+        jpeg_data: &[u8],
+        output: &mut OutputBuf,
+    ) -> Result<TransformReport> {
+        self.transform_impl(transform, jpeg_data, output, None)
+    }
+
+    /// Apply a transformation to the compressed JPEG and modify the DCT coefficients using `filter`.
+    ///
+    /// This is the same as [`transform_with_report()`][Self::transform_with_report], but TurboJPEG
+    /// calls `filter` with the quantized DCT coefficients of the transformed image, which the
+    /// filter can modify (for example, to blur or watermark a region of the image without
+    /// recompression). The filter is called for a strip of block rows of one component at a time,
+    /// see [`CoefficientBlocks`].
+    ///
+    /// If the filter returns an error, the transformation stops and the error is returned. The
+    /// filter is called from the C code of TurboJPEG, so a panic in the filter must not unwind
+    /// through TurboJPEG; instead, it is caught and returned as [`Error::CallbackPanicked`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::{OutputBuf, Transform};
+    ///
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut transformer = turbojpeg::Transformer::new()?;
+    /// let transform = Transform::default();
+    ///
+    /// // remove the colors by zeroing the chrominance coefficients (the DC coefficients of neutral
+    /// // colors are 0 as well)
+    /// let mut output = OutputBuf::new_owned();
+    /// transformer.transform_with_filter(&transform, &jpeg_data, &mut output, |blocks| {
+    ///     if blocks.component > 0 {
+    ///         blocks.coeffs.fill(0);
+    ///     }
+    ///     Ok(())
+    /// })?;
+    ///
+    /// // panics in the filter are returned as errors
+    /// let mut output = OutputBuf::new_owned();
+    /// let result = transformer.transform_with_filter(&transform, &jpeg_data, &mut output, |_| {
+    ///     panic!("filter failed")
+    /// });
+    /// assert!(matches!(result, Err(turbojpeg::Error::CallbackPanicked(_))));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[doc(alias = "customFilter")]
+    pub fn transform_with_filter<F>(
+        &mut self,
+        transform: &Transform,
+        jpeg_data: &[u8],
+        output: &mut OutputBuf,
+        mut filter: F,
+    ) -> Result<TransformReport>
+        where F: FnMut(CoefficientBlocks<'_>) -> Result<()>
+    {
+        let mut state = FilterState { filter: &mut filter, result: Ok(()) };
+        let data = &mut state as *mut FilterState as *mut libc::c_void;
+        let report = self.transform_impl(transform, jpeg_data, output, Some((user_filter, data)));
+        state.result.and(report)
+    }
+
+    fn transform_impl(
+        &mut self,
+        transform: &Transform,
+        jpeg_data: &[u8],
+        output: &mut OutputBuf,
+        filter: Option<(Filter, *mut libc::c_void)>,
+    ) -> Result<TransformReport> {
+        let limits = *self.handle.limits();
+        limits.check_jpeg(jpeg_data, true)?;
+        let (report, crop) = self.prepare(transform, jpeg_data)?;
+
         let mut options = 0;
         if transform.perfect { options |= raw::TJXOPT_PERFECT }
         if transform.trim { options |= raw::TJXOPT_TRIM }
@@ -268,7 +610,7 @@ impl Transformer {
             x: 0, y: 0,
             w: 0, h: 0,
         };
-        if let Some(crop) = transform.crop {
+        if let Some(crop) = crop {
             region.x = crop.x.try_into().map_err(|_| Error::IntegerOverflow("crop.x"))?;
             region.y = crop.y.try_into().map_err(|_| Error::IntegerOverflow("crop.y"))?;
             if let Some(crop_w) = crop.width {
@@ -280,12 +622,12 @@ impl Transformer {
             options |= raw::TJXOPT_CROP;
         }
 
-        let mut transform = raw::tjtransform {
+        let transform = raw::tjtransform {
             r: region,
             op: transform.op as libc::c_int,
             options: options as libc::c_int,
-            data: ptr::null_mut(),
-            customFilter: None,
+            data: filter.map_or(ptr::null_mut(), |(_, data)| data),
+            customFilter: filter.map(|(filter, _)| filter),
         };
 
         self.handle.set(
@@ -298,18 +640,72 @@ impl Transformer {
                 self.handle.as_ptr(),
                 jpeg_data.as_ptr(), jpeg_data.len() as raw::size_t,
                 1, &mut output.ptr, &mut output_len,
-                &mut transform,
+                &transform,
             )
         };
         output.len = output_len as usize;
         if res != 0 {
-            return Err(self.handle.get_error())
+            return Err(self.handle.get_error_in("transform"))
         } else if output.ptr.is_null() {
             output.len = 0;
             return Err(Error::Null)
         }
 
-        Ok(())
+        limits.check_output_bytes(output.len)?;
+        Ok(report)
+    }
+
+    /// Determines which partial MCU blocks will not be transformed and computes the position of a
+    /// centered crop.
+    fn prepare(
+        &mut self,
+        transform: &Transform,
+        jpeg_data: &[u8],
+    ) -> Result<(TransformReport, Option<TransformCrop>)> {
+        let (fix_columns, fix_rows) = match transform.op {
+            TransformOp::None | TransformOp::Transpose => (false, false),
+            TransformOp::Hflip | TransformOp::Rot270 => (true, false),
+            TransformOp::Vflip | TransformOp::Rot90 => (false, true),
+            TransformOp::Transverse | TransformOp::Rot180 => (true, true),
+        };
+        // TurboJPEG cannot crop images with unknown subsampling unless they are converted to gray
+        let check_subsamp = transform.crop.is_some() && !transform.gray;
+        let centered = transform.crop.is_some_and(|crop| crop.is_centered());
+        if !fix_columns && !fix_rows && !check_subsamp && !centered {
+            return Ok((TransformReport::default(), transform.crop))
+        }
+
+        let res = unsafe {
+            raw::tj3DecompressHeader(
+                self.handle.as_ptr(),
+                jpeg_data.as_ptr(),
+                jpeg_data.len() as raw::size_t,
+            )
+        };
+        if res != 0 {
+            return Err(self.handle.get_error_in("transform"))
+        }
+        let mut width = self.handle.get(raw::TJPARAM_TJPARAM_JPEGWIDTH) as usize;
+        let mut height = self.handle.get(raw::TJPARAM_TJPARAM_JPEGHEIGHT) as usize;
+        let subsamp = Subsamp::from_int(self.handle.get(raw::TJPARAM_TJPARAM_SUBSAMP))?;
+        if check_subsamp && subsamp == Subsamp::Unknown {
+            return Err(Error::UnsupportedSubsampForTransformCrop)
+        }
+
+        // the grayscale output has only the luminance component, so its MCU is a single block
+        let (mcu_width, mcu_height) = if transform.gray { (8, 8) } else { subsamp.mcu_size() };
+        let crop = transform.crop.map(|crop| crop.resolve(width, height, mcu_width, mcu_height));
+        if let Some(crop) = crop {
+            width = crop.width.unwrap_or(width.saturating_sub(crop.x));
+            height = crop.height.unwrap_or(height.saturating_sub(crop.y));
+        }
+
+        let report = TransformReport {
+            imperfect_columns: if fix_columns { width % mcu_width } else { 0 },
+            imperfect_rows: if fix_rows { height % mcu_height } else { 0 },
+            trimmed: transform.trim,
+        };
+        Ok((report, crop))
     }
 
     /// Transforms the `image` into an owned buffer.
@@ -384,3 +780,142 @@ pub fn transform(transform: &Transform, jpeg_data: &[u8]) -> Result<OwnedBuf> {
     let mut transformer = Transformer::new()?;
     transformer.transform_to_owned(transform, jpeg_data)
 }
+
+/// Losslessly transform a JPEG image into the `output` buffer.
+///
+/// This is the same as [`transform()`], but it writes the transformed JPEG data into an
+/// [`OutputBuf`] that you provide (which can reuse memory from a previous transformation) and
+/// returns the [`TransformReport`]. See [`Transformer::transform_with_report()`] for details.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let transform = turbojpeg::Transform::op(turbojpeg::TransformOp::Hflip);
+///
+/// let mut output = turbojpeg::OutputBuf::new_owned();
+/// let report = turbojpeg::transform_into(&transform, &jpeg_data, &mut output)?;
+/// assert!(report.is_perfect());
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn transform_into(transform: &Transform, jpeg_data: &[u8], output: &mut OutputBuf) -> Result<TransformReport> {
+    let mut transformer = Transformer::new()?;
+    transformer.transform_with_report(transform, jpeg_data, output)
+}
+
+/// Losslessly transform a JPEG image into the slice `output`.
+///
+/// Returns the size of the transformed JPEG data, or an error if it does not fit into `output`.
+/// See [`Transformer::transform_to_slice()`] for the size of the buffer.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let header = turbojpeg::read_header(&jpeg_data)?;
+/// let transform = turbojpeg::Transform::op(turbojpeg::TransformOp::Rot180);
+///
+/// let mut output = vec![0; turbojpeg::compressed_buf_len(header.width, header.height, header.subsamp)?];
+/// let len = turbojpeg::transform_to_slice(&transform, &jpeg_data, &mut output)?;
+/// let rotated_data = &output[..len];
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn transform_to_slice(transform: &Transform, jpeg_data: &[u8], output: &mut [u8]) -> Result<usize> {
+    let mut transformer = Transformer::new()?;
+    transformer.transform_to_slice(transform, jpeg_data, output)
+}
+
+/// Losslessly convert a JPEG image to grayscale.
+///
+/// This is a shortcut for [`transform()`] with [`Transform::gray`] set: the chrominance
+/// components are dropped and the luminance component is kept without recompression, so the
+/// quality of the image is preserved. Markers from the original image (such as EXIF metadata) are
+/// copied, except for the ICC color profile, which describes the original color image and would
+/// not be valid for the grayscale image.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let gray_data = turbojpeg::to_grayscale(&jpeg_data)?;
+///
+/// let header = turbojpeg::read_header(&gray_data)?;
+/// assert_eq!(header.colorspace, turbojpeg::Colorspace::Gray);
+/// assert_eq!(header.subsamp, turbojpeg::Subsamp::Gray);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn to_grayscale(jpeg_data: &[u8]) -> Result<OwnedBuf> {
+    let gray = Transform { gray: true, ..Transform::default() };
+    let gray_data = transform(&gray, jpeg_data)?;
+
+    let has_icc_profile = markers::read_header_segments(&gray_data)
+        .is_some_and(|segments| segments.iter().any(markers::is_icc_profile));
+    if !has_icc_profile {
+        return Ok(gray_data)
+    }
+    let stripped = markers::remove_segments(&gray_data, markers::is_icc_profile)
+        .ok_or(Error::BadJpeg("cannot read the header segments of the grayscale image"))?;
+    Ok(OwnedBuf::copy_from_slice(&stripped))
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> arbitrary::Arbitrary<'a> for TransformCrop {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        // mostly generate small values that are plausible for real images, but sometimes also huge
+        // values to exercise the overflow checks
+        fn coord(u: &mut arbitrary::Unstructured) -> arbitrary::Result<usize> {
+            if u.ratio(1, 8)? {
+                u.arbitrary()
+            } else {
+                Ok(u.int_in_range(0..=512u16)? as usize)
+            }
+        }
+
+        Ok(TransformCrop {
+            x: coord(u)?,
+            y: coord(u)?,
+            width: if u.arbitrary()? { Some(coord(u)?) } else { None },
+            height: if u.arbitrary()? { Some(coord(u)?) } else { None },
+        })
+    }
+}
+
+struct FilterState<'f> {
+    filter: &'f mut dyn FnMut(CoefficientBlocks<'_>) -> Result<()>,
+    result: Result<()>,
+}
+
+// called from the C code of TurboJPEG, so it must never unwind (see `join::catch_filter_panic()`)
+unsafe extern "C" fn user_filter(
+    coeffs: *mut libc::c_short,
+    array_region: raw::tjregion,
+    plane_region: raw::tjregion,
+    component: libc::c_int,
+    _transform_id: libc::c_int,
+    transform: *mut raw::tjtransform,
+) -> libc::c_int {
+    let state = &mut *((*transform).data as *mut FilterState);
+    let Some(region) = FilterRegion::new(array_region, plane_region, component) else {
+        state.result = Err(Error::BadCoefficients("invalid region in custom filter"));
+        return -1
+    };
+    let blocks = CoefficientBlocks {
+        component: region.component,
+        x: region.x,
+        y: region.y,
+        width: region.width,
+        height: region.height,
+        plane_width: region.plane_width,
+        plane_height: region.plane_height,
+        coeffs: std::slice::from_raw_parts_mut(coeffs, region.width * region.height * 64),
+    };
+    state.result = match join::catch_filter_panic(|| (state.filter)(blocks)) {
+        Ok(result) => result,
+        Err(message) => Err(Error::CallbackPanicked(message)),
+    };
+    if state.result.is_ok() { 0 } else { -1 }
+}
@@ -0,0 +1,257 @@
+//! Images with the pixel format in the type.
+//!
+//! An [`Image`] stores its [`PixelFormat`] at runtime, so passing an image with the wrong format
+//! or a pitch that does not match the format is only detected when the image is used (compression
+//! and decompression panic on invalid images). If you know the pixel format at compile time, you
+//! can use [`TypedImage`] instead: the format is given by a type parameter (such as [`Rgb8`] or
+//! [`Bgra8`]), and the layout of the image is checked once when the image is created, so a
+//! `TypedImage` is always valid.
+//!
+//! A `TypedImage` can be converted to an [`Image`] at no cost using
+//! [`as_image()`][TypedImage::as_image] and [`as_image_mut()`][TypedImage::as_image_mut], so it
+//! works with all functions in this crate.
+//!
+//! # Example
+//!
+//! ```
+//! use turbojpeg::typed::{Rgb8, TypedImage};
+//!
+//! let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+//! let mut decompressor = turbojpeg::Decompressor::new()?;
+//! let image: TypedImage<Rgb8> = decompressor.decompress_typed(&jpeg_data)?;
+//! assert_eq!((image.width(), image.height()), (384, 256));
+//! assert_eq!(image.row(0).len(), 384 * 3);
+//!
+//! let mut compressor = turbojpeg::Compressor::new()?;
+//! let jpeg_data = compressor.compress_to_vec(image.as_image())?;
+//!
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use crate::Image;
+use crate::common::{Error, PixelFormat, Result};
+use crate::decompress::Decompressor;
+
+/// A pixel format that is known at compile time, used as the type parameter of [`TypedImage`].
+///
+/// This is implemented for a type for every [`PixelFormat`], such as [`Rgb8`] for
+/// [`PixelFormat::RGB`].
+pub trait Format: Copy + Debug + Default + Send + Sync + 'static {
+    /// The pixel format.
+    const FORMAT: PixelFormat;
+    /// The size of a pixel in bytes (equal to `FORMAT.size()`).
+    const SIZE: usize;
+}
+
+macro_rules! formats {
+    ($($name:ident = $format:ident, $size:literal;)*) => {$(
+        #[doc = concat!("Type for [`PixelFormat::", stringify!($format), "`].")]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+        pub struct $name;
+
+        impl Format for $name {
+            const FORMAT: PixelFormat = PixelFormat::$format;
+            const SIZE: usize = $size;
+        }
+    )*};
+}
+
+formats! {
+    Rgb8 = RGB, 3;
+    Bgr8 = BGR, 3;
+    Rgbx8 = RGBX, 4;
+    Bgrx8 = BGRX, 4;
+    Xbgr8 = XBGR, 4;
+    Xrgb8 = XRGB, 4;
+    Gray8 = GRAY, 1;
+    Rgba8 = RGBA, 4;
+    Bgra8 = BGRA, 4;
+    Abgr8 = ABGR, 4;
+    Argb8 = ARGB, 4;
+    Cmyk8 = CMYK, 4;
+}
+
+/// An image with pixels of type `T` in the pixel format `F`.
+///
+/// Unlike [`Image`], the fields are private and the layout is checked when the image is created,
+/// so the image is always valid: the pitch is at least `width * F::SIZE` and the pixels are long
+/// enough for all rows. See the [module documentation][self] for an example.
+#[derive(Debug, Copy, Clone)]
+pub struct TypedImage<F, T = Vec<u8>> {
+    image: Image<T>,
+    format: PhantomData<F>,
+}
+
+impl<F: Format> TypedImage<F, Vec<u8>> {
+    /// Allocates a zeroed image without padding between rows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the image overflows `usize`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::typed::{Gray8, TypedImage};
+    /// let image = TypedImage::<Gray8>::new(100, 50);
+    /// assert_eq!(image.pitch(), 100);
+    /// assert_eq!(image.pixels().len(), 100 * 50);
+    /// ```
+    pub fn new(width: usize, height: usize) -> TypedImage<F, Vec<u8>> {
+        let len = width.checked_mul(F::SIZE)
+            .and_then(|pitch| pitch.checked_mul(height))
+            .expect("image size overflowed");
+        let image = Image { pixels: vec![0; len], width, pitch: width * F::SIZE, height, format: F::FORMAT };
+        TypedImage { image, format: PhantomData }
+    }
+}
+
+impl<F: Format, T: Deref<Target = [u8]>> TypedImage<F, T> {
+    /// Creates an image from pixels without padding between rows.
+    ///
+    /// Returns an error if `pixels` are too short for an image of the given size.
+    pub fn from_pixels(pixels: T, width: usize, height: usize) -> Result<TypedImage<F, T>> {
+        TypedImage::from_pixels_with_pitch(pixels, width, 0, height)
+    }
+
+    /// Creates an image from pixels with rows that are `pitch` bytes apart.
+    ///
+    /// As in [`Image`], pitch 0 means that the rows are not padded. Returns an error if the pitch
+    /// is too small for the width or if `pixels` are too short (see [`Image::check()`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use turbojpeg::typed::{Rgba8, TypedImage};
+    /// let pixels = vec![0; 128 * 10];
+    /// let image = TypedImage::<Rgba8, _>::from_pixels_with_pitch(&pixels[..], 30, 128, 10)?;
+    /// assert_eq!(image.row(9).len(), 30 * 4);
+    ///
+    /// // the pitch is too small for 40 RGBA pixels
+    /// assert!(TypedImage::<Rgba8, _>::from_pixels_with_pitch(&pixels[..], 40, 128, 10).is_err());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_pixels_with_pitch(pixels: T, width: usize, pitch: usize, height: usize) -> Result<TypedImage<F, T>> {
+        Image { pixels, width, pitch, height, format: F::FORMAT }.try_into()
+    }
+
+    /// Returns the width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.image.width
+    }
+
+    /// Returns the height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.image.height
+    }
+
+    /// Returns the distance between rows in bytes (never 0, unlike [`Image::pitch`]).
+    pub fn pitch(&self) -> usize {
+        self.image.row_pitch()
+    }
+
+    /// Returns the pixel data.
+    pub fn pixels(&self) -> &[u8] {
+        &self.image.pixels
+    }
+
+    /// Returns the pixels in row `y` (without the padding).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not less than the height.
+    pub fn row(&self, y: usize) -> &[u8] {
+        assert!(y < self.image.height, "row {} is out of bounds", y);
+        &self.image.pixels[y * self.pitch()..][..self.image.width * F::SIZE]
+    }
+
+    /// Returns the bytes of the pixel in column `x` and row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel is out of bounds.
+    pub fn pixel(&self, x: usize, y: usize) -> &[u8] {
+        assert!(x < self.image.width, "column {} is out of bounds", x);
+        &self.row(y)[x * F::SIZE..][..F::SIZE]
+    }
+
+    /// Returns an [`Image`] that borrows the pixels.
+    pub fn as_image(&self) -> Image<&[u8]> {
+        self.image.as_deref()
+    }
+
+    /// Converts this image into an [`Image`].
+    pub fn into_image(self) -> Image<T> {
+        self.image
+    }
+}
+
+impl<F: Format, T: DerefMut<Target = [u8]>> TypedImage<F, T> {
+    /// Returns the mutable pixel data.
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.image.pixels
+    }
+
+    /// Returns the mutable pixels in row `y` (without the padding).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not less than the height.
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        assert!(y < self.image.height, "row {} is out of bounds", y);
+        let pitch = self.pitch();
+        &mut self.image.pixels[y * pitch..][..self.image.width * F::SIZE]
+    }
+
+    /// Returns the mutable bytes of the pixel in column `x` and row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel is out of bounds.
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut [u8] {
+        assert!(x < self.image.width, "column {} is out of bounds", x);
+        &mut self.row_mut(y)[x * F::SIZE..][..F::SIZE]
+    }
+
+    /// Returns an [`Image`] that mutably borrows the pixels.
+    pub fn as_image_mut(&mut self) -> Image<&mut [u8]> {
+        self.image.as_deref_mut()
+    }
+}
+
+/// Checks the pixel format and the layout of the image.
+///
+/// Returns [`Error::UnexpectedPixelFormat`] if the format of the image is not `F::FORMAT`, or an
+/// error from [`Image::check()`].
+impl<F: Format, T: Deref<Target = [u8]>> TryFrom<Image<T>> for TypedImage<F, T> {
+    type Error = Error;
+    fn try_from(image: Image<T>) -> Result<TypedImage<F, T>> {
+        if image.format != F::FORMAT {
+            return Err(Error::UnexpectedPixelFormat(F::FORMAT, image.format))
+        }
+        image.check(image.pixels.len())?;
+        Ok(TypedImage { image, format: PhantomData })
+    }
+}
+
+impl<F, T> From<TypedImage<F, T>> for Image<T> {
+    fn from(image: TypedImage<F, T>) -> Image<T> {
+        image.image
+    }
+}
+
+impl Decompressor {
+    /// Decompress a JPEG image into a newly allocated [`TypedImage`].
+    ///
+    /// The image has no padding between rows. See the [module documentation][self] for an
+    /// example.
+    pub fn decompress_typed<F: Format>(&mut self, jpeg_data: &[u8]) -> Result<TypedImage<F>> {
+        let header = self.read_header(jpeg_data)?;
+        let mut image = TypedImage::new(header.width, header.height);
+        self.decompress(jpeg_data, image.as_image_mut())?;
+        Ok(image)
+    }
+}
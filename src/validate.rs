@@ -0,0 +1,288 @@
+use std::fmt;
+use std::ops::Range;
+use crate::markers::{self, ReadSegment, SegmentReader};
+
+/// Checks the structure of JPEG data without decompressing it.
+///
+/// This walks through the marker segments and the entropy-coded data of all scans and checks that
+/// the data is complete and well-formed: it starts with SOI and ends with EOI, the lengths of the
+/// segments are consistent, there is a frame header before the first scan, and the restart markers
+/// are in the correct order. The returned [`ValidationReport`] contains the first problem (with
+/// its byte offset in the data), which is much more useful for ingestion pipelines than a generic
+/// decompression error.
+///
+/// The entropy-coded data itself is not decoded, so an image that passes the validation may still
+/// fail to decompress (or decompress with artifacts) if the data is corrupted.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let report = turbojpeg::validate(&jpeg_data);
+/// assert!(report.is_valid());
+///
+/// // a truncated image is detected
+/// let report = turbojpeg::validate(&jpeg_data[..jpeg_data.len() / 2]);
+/// let problem = report.problem.unwrap();
+/// assert_eq!(problem.kind, turbojpeg::ProblemKind::Truncated);
+/// println!("problem at offset {}: {}", problem.offset, problem.kind);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn validate(jpeg_data: &[u8]) -> ValidationReport {
+    let mut report = ValidationReport { problem: None, scans: 0, end: 0 };
+    if let Err(problem) = validate_into(jpeg_data, &mut report) {
+        report.problem = Some(problem);
+    }
+    report
+}
+
+/// Finds the first complete JPEG image embedded in a larger buffer.
+///
+/// Some files contain a JPEG image that is surrounded by other data, for example, the preview
+/// image in a camera RAW file (which is a TIFF container), a frame from a camera with padding
+/// after the image, or a JPEG image after a custom header. TurboJPEG fails if the data does not
+/// start with the SOI marker, so this function searches the buffer for an SOI marker that starts a
+/// [valid][validate()] image and returns the range of the image, from SOI to EOI (inclusive).
+/// Pass `&data[range]` to decompression.
+///
+/// Returns `None` if the buffer does not contain a complete JPEG image. Note that a JPEG image
+/// may itself contain a smaller embedded JPEG image (such as the EXIF thumbnail); this function
+/// returns the outer image, unless the outer image is truncated.
+///
+/// # Example
+///
+/// ```
+/// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+/// let mut data = b"custom header\xff\xd8 with a fake SOI".to_vec();
+/// data.extend_from_slice(&jpeg_data);
+/// data.extend_from_slice(&[0; 100]);
+///
+/// let range = turbojpeg::find_jpeg(&data).unwrap();
+/// assert_eq!(&data[range.clone()], &jpeg_data[..]);
+/// let header = turbojpeg::read_header(&data[range])?;
+/// assert_eq!((header.width, header.height), (384, 256));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn find_jpeg(data: &[u8]) -> Option<Range<usize>> {
+    find_jpeg_from(data, 0)
+}
+
+/// Finds all complete JPEG images embedded in a larger buffer.
+///
+/// This is like [`find_jpeg()`], but it returns the ranges of all images that follow each other in
+/// the buffer. For example, camera RAW files often contain a small thumbnail and a larger preview,
+/// and you probably want the largest one:
+///
+/// ```
+/// # let data = std::fs::read("examples/parrots.jpg")?;
+/// let preview = turbojpeg::find_jpegs(&data).into_iter().max_by_key(|range| range.len());
+/// # assert_eq!(preview, Some(0..data.len()));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// Images that are nested in another image (such as EXIF thumbnails) are not returned.
+pub fn find_jpegs(data: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut pos = 0;
+    while let Some(range) = find_jpeg_from(data, pos) {
+        pos = range.end;
+        ranges.push(range);
+    }
+    ranges
+}
+
+fn find_jpeg_from(data: &[u8], mut pos: usize) -> Option<Range<usize>> {
+    while pos < data.len() {
+        let start = pos + data[pos..].windows(3).position(|w| w == [0xff, markers::SOI, 0xff])?;
+        let report = validate(&data[start..]);
+        if report.is_valid() {
+            return Some(start..start + report.end)
+        }
+        pos = start + 1;
+    }
+    None
+}
+
+/// Result of [`validate()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The first problem in the data, or `None` if the data is valid.
+    pub problem: Option<Problem>,
+    /// Number of complete scans (progressive images have multiple scans).
+    pub scans: usize,
+    /// Offset of the end of the checked data. If the data is valid, this is the offset after the
+    /// EOI marker, so any data after this offset is trailing garbage (which is ignored by
+    /// decompression).
+    pub end: usize,
+}
+
+impl ValidationReport {
+    /// Returns true if no problem was found.
+    pub fn is_valid(&self) -> bool {
+        self.problem.is_none()
+    }
+}
+
+/// A problem found by [`validate()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Problem {
+    /// Byte offset of the problem in the JPEG data.
+    pub offset: usize,
+    /// Kind of the problem.
+    pub kind: ProblemKind,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+/// Kind of a [`Problem`] found by [`validate()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProblemKind {
+    /// The data does not start with the SOI marker, so it is probably not a JPEG image.
+    MissingSoi,
+    /// The data ends in the middle of a marker segment or a scan. The offset is the start of the
+    /// incomplete segment or scan.
+    ///
+    /// Note that if the data ends right after the entropy-coded data of a scan (without EOI), we
+    /// cannot tell whether the scan is complete, so this is also reported as truncation.
+    Truncated,
+    /// The data ends after a complete segment or scan, but without the EOI marker. The offset is
+    /// the end of the data.
+    MissingEoi,
+    /// Expected a marker, but found other data.
+    ExpectedMarker,
+    /// The marker (the byte after 0xFF) is not allowed at this position (for example, a second SOI
+    /// marker or a restart marker outside of a scan).
+    UnexpectedMarker(u8),
+    /// The length of the marker segment is not consistent with its contents.
+    BadSegmentLength(u8),
+    /// The scan (SOS) is not preceded by a frame header (SOF).
+    MissingFrameHeader,
+    /// The image ends (EOI) before the first scan (SOS).
+    MissingScan,
+    /// A restart marker is out of order: the first number is the expected index (0 to 7) and the
+    /// second number is the actual index.
+    BadRestartMarker(u8, u8),
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ProblemKind::MissingSoi => write!(f, "data does not start with SOI marker"),
+            ProblemKind::Truncated => write!(f, "data is truncated"),
+            ProblemKind::MissingEoi => write!(f, "data ends without EOI marker"),
+            ProblemKind::ExpectedMarker => write!(f, "expected a marker"),
+            ProblemKind::UnexpectedMarker(marker) => write!(f, "unexpected marker 0x{:02x}", marker),
+            ProblemKind::BadSegmentLength(marker) =>
+                write!(f, "bad length of segment with marker 0x{:02x}", marker),
+            ProblemKind::MissingFrameHeader => write!(f, "scan is not preceded by a frame header"),
+            ProblemKind::MissingScan => write!(f, "image does not contain any scan"),
+            ProblemKind::BadRestartMarker(expected, actual) =>
+                write!(f, "expected restart marker {}, found {}", expected, actual),
+        }
+    }
+}
+
+fn validate_into(data: &[u8], report: &mut ValidationReport) -> Result<(), Problem> {
+    let problem = |offset, kind| Problem { offset, kind };
+    if data.get(0..2) != Some(&[0xff, markers::SOI]) {
+        return Err(problem(0, ProblemKind::MissingSoi))
+    }
+
+    let mut reader = SegmentReader::with_position(data, 2);
+    let mut has_frame = false;
+    loop {
+        let pos = reader.position();
+        report.end = pos;
+        let segment = match reader.next_segment() {
+            ReadSegment::Segment(segment) => segment,
+            ReadSegment::NeedMore(_) if pos == data.len() =>
+                return Err(problem(pos, ProblemKind::MissingEoi)),
+            ReadSegment::NeedMore(_) =>
+                return Err(problem(pos, ProblemKind::Truncated)),
+            ReadSegment::Invalid => {
+                let kind = match data.get(pos + 1..).and_then(|d| d.iter().find(|&&b| b != 0xff)) {
+                    Some(&marker) if data[pos] == 0xff && marker != 0 =>
+                        ProblemKind::BadSegmentLength(marker),
+                    _ => ProblemKind::ExpectedMarker,
+                };
+                return Err(problem(pos, kind))
+            },
+        };
+
+        let offset = segment.offset;
+        let len = segment.data.len();
+        match segment.marker {
+            markers::SOI | markers::RST0..=markers::RST7 =>
+                return Err(problem(offset, ProblemKind::UnexpectedMarker(segment.marker))),
+            markers::EOI => {
+                if report.scans == 0 {
+                    return Err(problem(offset, ProblemKind::MissingScan))
+                }
+                report.end = segment.end();
+                return Ok(())
+            },
+            marker if markers::is_sof(marker) => {
+                if has_frame {
+                    return Err(problem(offset, ProblemKind::UnexpectedMarker(marker)))
+                }
+                let count = segment.data.get(5).copied().unwrap_or(0) as usize;
+                if len != 6 + 3 * count {
+                    return Err(problem(offset, ProblemKind::BadSegmentLength(marker)))
+                }
+                has_frame = true;
+            },
+            markers::DRI if len != 2 =>
+                return Err(problem(offset, ProblemKind::BadSegmentLength(markers::DRI))),
+            markers::SOS => {
+                if !has_frame {
+                    return Err(problem(offset, ProblemKind::MissingFrameHeader))
+                }
+                let count = segment.data.first().copied().unwrap_or(0) as usize;
+                if len != 1 + 2 * count + 3 {
+                    return Err(problem(offset, ProblemKind::BadSegmentLength(markers::SOS)))
+                }
+                let end = validate_scan(data, offset, segment.end())?;
+                report.scans += 1;
+                reader = SegmentReader::with_position(data, end);
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Checks the entropy-coded data of the scan with SOS marker at `sos_offset`, starting at `start`.
+///
+/// Returns the offset of the marker that ends the scan.
+fn validate_scan(data: &[u8], sos_offset: usize, start: usize) -> Result<usize, Problem> {
+    let truncated = Problem { offset: sos_offset, kind: ProblemKind::Truncated };
+    let mut pos = start;
+    let mut next_restart = 0;
+    loop {
+        pos += data.get(pos..).unwrap_or(&[]).iter().position(|&b| b == 0xff)
+            .ok_or(truncated)?;
+        // skip fill bytes
+        while data.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        match *data.get(pos + 1).ok_or(truncated)? {
+            0x00 => pos += 2,
+            marker @ markers::RST0..=markers::RST7 => {
+                let index = marker - markers::RST0;
+                if index != next_restart {
+                    let kind = ProblemKind::BadRestartMarker(next_restart, index);
+                    return Err(Problem { offset: pos, kind })
+                }
+                next_restart = (next_restart + 1) % 8;
+                pos += 2;
+            },
+            _ => return Ok(pos),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::Image;
+use crate::common::{Error, PixelFormat, Result};
+use crate::decompress::Decompressor;
+
+/// Layout of an image in a staging buffer for `wgpu::CommandEncoder::copy_buffer_to_texture()`.
+///
+/// wgpu requires that the rows of an image in a buffer are aligned to
+/// [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`][wgpu_types::COPY_BYTES_PER_ROW_ALIGNMENT] (256 bytes),
+/// so the pitch of the image is usually larger than `width * format.size()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(docsrs, doc(cfg(feature = "wgpu")))]
+pub struct StagingLayout {
+    /// Width of the image in pixels.
+    pub width: usize,
+    /// Height of the image in pixels (number of rows).
+    pub height: usize,
+    /// Size of one image row in bytes, aligned to 256 bytes. Use this as `bytes_per_row` in the
+    /// buffer layout passed to `copy_buffer_to_texture()`.
+    pub bytes_per_row: usize,
+    /// Size of the whole image in bytes. The staging buffer must be at least this large.
+    pub size: usize,
+}
+
+impl StagingLayout {
+    /// Computes the layout of an image with the given size and pixel format in a staging buffer.
+    ///
+    /// Returns an error on integer overflow.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let layout = turbojpeg::StagingLayout::new(100, 50, turbojpeg::PixelFormat::RGBA)?;
+    /// assert_eq!(layout.bytes_per_row, 512);
+    /// assert_eq!(layout.size, 512 * 50);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn new(width: usize, height: usize, format: PixelFormat) -> Result<StagingLayout> {
+        let alignment = wgpu_types::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+        let bytes_per_row = width.checked_mul(format.size())
+            .and_then(|row_size| row_size.checked_next_multiple_of(alignment))
+            .ok_or(Error::IntegerOverflow("bytes_per_row"))?;
+        let size = bytes_per_row.checked_mul(height)
+            .ok_or(Error::IntegerOverflow("size"))?;
+        Ok(StagingLayout { width, height, bytes_per_row, size })
+    }
+}
+
+impl Decompressor {
+    /// Decompress a JPEG image into a mapped wgpu staging buffer.
+    ///
+    /// The image is decompressed directly into `staging` (typically a `wgpu::BufferViewMut`
+    /// obtained from a buffer mapped for writing) with rows aligned as required by
+    /// `copy_buffer_to_texture()`, so that you don't need to repack the pixels on the CPU. Use
+    /// [`StagingLayout::new()`] with the size from [`read_header()`][Self::read_header] to
+    /// determine how large the staging buffer must be.
+    ///
+    /// Returns the layout of the image in the buffer. If the buffer is too small, returns
+    /// [`Error::PixelsTooSmall`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let jpeg_data = std::fs::read("examples/parrots.jpg")?;
+    /// let mut decompressor = turbojpeg::Decompressor::new()?;
+    ///
+    /// let header = decompressor.read_header(&jpeg_data)?;
+    /// let format = turbojpeg::PixelFormat::RGBA;
+    /// let layout = turbojpeg::StagingLayout::new(header.width, header.height, format)?;
+    ///
+    /// // in a real application, this would be a buffer from `wgpu::BufferSlice::get_mapped_range_mut()`
+    /// let mut staging = vec![0; layout.size];
+    /// let layout = decompressor.decompress_to_staging(&jpeg_data, format, &mut staging)?;
+    /// assert_eq!(layout.bytes_per_row % 256, 0);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "wgpu")))]
+    pub fn decompress_to_staging(
+        &mut self,
+        jpeg_data: &[u8],
+        format: PixelFormat,
+        staging: &mut [u8],
+    ) -> Result<StagingLayout> {
+        let header = self.read_header(jpeg_data)?;
+        let layout = StagingLayout::new(header.width, header.height, format)?;
+        if staging.len() < layout.size {
+            return Err(Error::PixelsTooSmall(staging.len(), layout.size))
+        }
+
+        let image = Image {
+            pixels: &mut staging[..layout.size],
+            width: layout.width,
+            pitch: layout.bytes_per_row,
+            height: layout.height,
+            format,
+        };
+        self.decompress(jpeg_data, image)?;
+        Ok(layout)
+    }
+}
+
+/// Returns the wgpu texture format that matches the pixel format, if there is one.
+///
+/// If `srgb` is true, returns the sRGB variant of the texture format (when it exists), which is
+/// usually what you want for color images decoded from JPEG.
+///
+/// # Example
+///
+/// ```
+/// use turbojpeg::PixelFormat;
+/// assert_eq!(
+///     turbojpeg::texture_format(PixelFormat::RGBA, true),
+///     Some(turbojpeg::wgpu_types::TextureFormat::Rgba8UnormSrgb),
+/// );
+/// assert_eq!(turbojpeg::texture_format(PixelFormat::RGB, true), None);
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "wgpu")))]
+pub fn texture_format(format: PixelFormat, srgb: bool) -> Option<wgpu_types::TextureFormat> {
+    use wgpu_types::TextureFormat;
+    Some(match (format, srgb) {
+        (PixelFormat::RGBA | PixelFormat::RGBX, false) => TextureFormat::Rgba8Unorm,
+        (PixelFormat::RGBA | PixelFormat::RGBX, true) => TextureFormat::Rgba8UnormSrgb,
+        (PixelFormat::BGRA | PixelFormat::BGRX, false) => TextureFormat::Bgra8Unorm,
+        (PixelFormat::BGRA | PixelFormat::BGRX, true) => TextureFormat::Bgra8UnormSrgb,
+        (PixelFormat::GRAY, _) => TextureFormat::R8Unorm,
+        _ => return None,
+    })
+}
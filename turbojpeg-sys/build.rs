@@ -10,7 +10,16 @@ fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=build.rs");
     let link_kind = get_link_kind()?;
     let library = build_or_find_library(link_kind)?;
+    // exposed as `turbojpeg_sys::SIMD_BUILD`
+    println!("cargo:rustc-env=TURBOJPEG_SYS_SIMD={}", match library.simd {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "unknown",
+    });
     generate_or_copy_bindings(&library)?;
+    if cfg!(feature = "jpeglib") {
+        generate_jpeglib_bindings(&library)?;
+    }
     Ok(())
 }
 
@@ -44,6 +53,8 @@ fn get_link_kind() -> Result<LinkKind> {
 struct Library {
     include_paths: Vec<PathBuf>,
     defines: HashMap<String, Option<String>>,
+    /// Whether the library was built with the SIMD extensions, if we know it.
+    simd: Option<bool>,
 }
 
 fn build_or_find_library(link_kind: LinkKind) -> Result<Library> {
@@ -58,9 +69,12 @@ fn build_or_find_library(link_kind: LinkKind) -> Result<Library> {
                 find_pkg_config(link_kind)
             } else if source.eq_ignore_ascii_case("explicit") {
                 find_explicit(link_kind)
+            } else if source.eq_ignore_ascii_case("download") {
+                build_download(link_kind)
             } else {
                 bail!("Unknown value of TURBOJPEG_SOURCE, supported values are:\n\
                     - 'vendor' to build the library from source bundled with the turbojpeg-sys crate,\n\
+                    - 'download' to download the library source and build it,\n\
                     - 'pkg-config' to find the library using pkg-config,\n\
                     - 'explicit' to use TURBOJPEG_LIB_DIR and TURBOJPEG_INCLUDE_DIR")
             }
@@ -82,7 +96,8 @@ fn find_pkg_config(link_kind: LinkKind) -> Result<Library> {
     println!("Using pkg-config to find libturbojpeg");
 
     let mut cfg = pkg_config::Config::new();
-    cfg.atleast_version("3.0");
+    // with the `compat-2x` feature, we implement the TurboJPEG 3 API on top of TurboJPEG 2
+    cfg.atleast_version(if cfg!(feature = "compat-2x") { "2.0" } else { "3.0" });
     match link_kind {
         LinkKind::Static => { cfg.statik(true); },
         LinkKind::Dynamic => { cfg.statik(false); },
@@ -91,10 +106,20 @@ fn find_pkg_config(link_kind: LinkKind) -> Result<Library> {
 
     let lib = cfg.probe("libturbojpeg")
         .context("could not find turbojpeg using pkg-config")?;
+    let mut include_paths = lib.include_paths;
+
+    // the shared libturbojpeg exports only the TurboJPEG API, so the libjpeg API must be linked
+    // from libjpeg (the static libturbojpeg contains both)
+    if cfg!(feature = "jpeglib") && !matches!(link_kind, LinkKind::Static) {
+        let jpeg_lib = cfg.probe("libjpeg")
+            .context("could not find libjpeg using pkg-config (needed by the `jpeglib` feature)")?;
+        include_paths.extend(jpeg_lib.include_paths);
+    }
 
     Ok(Library {
-        include_paths: lib.include_paths,
+        include_paths,
         defines: lib.defines,
+        simd: None,
     })
 }
 
@@ -125,21 +150,49 @@ fn find_explicit(link_kind: LinkKind) -> Result<Library> {
         LinkKind::Static | LinkKind::Default => "static",
         LinkKind::Dynamic => "dylib",
     });
+    link_jpeglib(&link_kind);
 
     Ok(Library {
         include_paths: include_dir.into_iter().collect(),
         defines: HashMap::new(),
+        simd: None,
     })
 }
 
 #[cfg(feature = "cmake")]
 fn build_vendor(link_kind: LinkKind) -> Result<Library> {
-    println!("Building turbojpeg from source");
-    if !cfg!(feature = "require-simd") {
-        check_nasm();
+    let source_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?).join("libjpeg-turbo");
+    if !source_path.join("CMakeLists.txt").exists() {
+        bail!("The bundled TurboJPEG source in {} is missing (maybe the git submodule is not \
+            checked out?). You have two options:\n\
+            - run `git submodule update --init` if you build turbojpeg-sys from git\n\
+            - use TURBOJPEG_SOURCE=download to download the source (requires the `download` feature)",
+            source_path.display());
     }
+    build_source(source_path, link_kind)
+}
+
+#[cfg(feature = "cmake")]
+fn build_source(source_path: PathBuf, link_kind: LinkKind) -> Result<Library> {
+    println!("Building turbojpeg from source in {}", source_path.display());
+    let is_musl = env("CARGO_CFG_TARGET_ENV").unwrap() == "musl";
+    if is_musl && matches!(link_kind, LinkKind::Dynamic) {
+        bail!("Dynamic linking of TurboJPEG is not supported on musl targets, \
+            please unset TURBOJPEG_DYNAMIC/TURBOJPEG_SHARED to link it statically");
+    }
+
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    // NASM is only needed for the x86 SIMD extensions, the Arm SIMD extensions use intrinsics.
+    // With `require-simd`, the build fails if the SIMD extensions cannot be built; otherwise, we
+    // only know that they are missing if NASM is missing
+    let simd = if cfg!(feature = "require-simd") {
+        Some(true)
+    } else if matches!(target_arch.as_str(), "x86" | "x86_64") && !check_nasm() {
+        Some(false)
+    } else {
+        None
+    };
 
-    let source_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?).join("libjpeg-turbo");
     let mut cmake = cmake::Config::new(source_path);
     cmake.configure_arg(format!("-DENABLE_SHARED={}", matches!(link_kind, LinkKind::Dynamic) as u32));
     cmake.configure_arg(format!("-DENABLE_STATIC={}", !matches!(link_kind, LinkKind::Dynamic) as u32));
@@ -152,8 +205,8 @@ fn build_vendor(link_kind: LinkKind) -> Result<Library> {
     }
 
     let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let is_msvc = env("CARGO_CFG_TARGET_ENV").unwrap() == "msvc";
     if target_os == "android" {
-        let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
         let android_abi = match target_arch.as_str() {
             "arm" => "armeabi-v7a",
             "aarch64" => "arm64-v8a",
@@ -164,13 +217,72 @@ fn build_vendor(link_kind: LinkKind) -> Result<Library> {
         cmake.configure_arg(format!("-DANDROID_ABI={android_abi}"));
     }
 
+    if target_os == "linux" && env::var("HOST")? != env::var("TARGET")? {
+        // without this, CMake assumes that we build for the host, so libjpeg-turbo would select
+        // the SIMD extensions for the wrong architecture (e.g. when building for
+        // `aarch64-unknown-linux-musl` on x86-64)
+        cmake.define("CMAKE_SYSTEM_NAME", "Linux");
+        cmake.define("CMAKE_SYSTEM_PROCESSOR", &target_arch);
+    }
+
+    // Rust produces static position-independent executables for musl targets, so the static
+    // library (including the NASM code) must be compiled as position-independent
+    if env_bool("TURBOJPEG_PIC")?.unwrap_or(is_musl) {
+        cmake.define("CMAKE_POSITION_INDEPENDENT_CODE", "ON");
+    }
+
+    if let Some(build_type) = env("TURBOJPEG_BUILD_TYPE") {
+        let build_type = build_type.into_string()
+            .map_err(|_| anyhow!("TURBOJPEG_BUILD_TYPE is not valid UTF-8"))?;
+        match build_type.as_str() {
+            "Debug" | "Release" | "RelWithDebInfo" | "MinSizeRel" => { cmake.profile(&build_type); },
+            _ => bail!("Unknown value of TURBOJPEG_BUILD_TYPE, supported values are \
+                `Debug`, `Release`, `RelWithDebInfo` and `MinSizeRel`"),
+        }
+    }
+
+    if let Some(target_cpu) = env("TURBOJPEG_TARGET_CPU") {
+        let target_cpu = target_cpu.into_string()
+            .map_err(|_| anyhow!("TURBOJPEG_TARGET_CPU is not valid UTF-8"))?;
+        if is_msvc {
+            bail!("TURBOJPEG_TARGET_CPU is not supported on MSVC targets");
+        }
+        // GCC and Clang use -march on x86, but -mcpu on Arm and other architectures
+        let flag = match target_arch.as_str() {
+            "x86" | "x86_64" => format!("-march={target_cpu}"),
+            _ => format!("-mcpu={target_cpu}"),
+        };
+        println!("cargo:warning=TurboJPEG is compiled with {flag}, the resulting binary may crash \
+            with illegal instructions on other CPUs");
+        cmake.cflag(&flag);
+    }
+
+    if env_bool("TURBOJPEG_LTO")?.unwrap_or(false) {
+        cmake.define("CMAKE_POLICY_DEFAULT_CMP0069", "NEW");
+        cmake.define("CMAKE_INTERPROCEDURAL_OPTIMIZATION", "ON");
+    }
+
+    if is_msvc {
+        // libjpeg-turbo links with the static CRT (/MT) unless WITH_CRT_DLL is set, but Rust uses
+        // the CRT DLL (/MD) unless the `crt-static` target feature is enabled
+        let crt_static = env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default()
+            .split(',').any(|feature| feature == "crt-static");
+        cmake.define("WITH_CRT_DLL", if crt_static { "OFF" } else { "ON" });
+
+        // with the Visual Studio generator, CMAKE_SYSTEM_PROCESSOR is the host architecture, so
+        // libjpeg-turbo would try to build the x86 SIMD extensions when cross-compiling to ARM64
+        let host_is_aarch64 = env::var("HOST").unwrap().starts_with("aarch64-");
+        if target_arch == "aarch64" && !host_is_aarch64 {
+            cmake.define("CMAKE_SYSTEM_NAME", "Windows");
+            cmake.define("CMAKE_SYSTEM_PROCESSOR", "ARM64");
+        }
+    }
+
     let dst_path = cmake.build();
 
     let lib_path = dst_path.join("lib");
     let include_path = dst_path.join("include");
 
-    let is_msvc = env("CARGO_CFG_TARGET_ENV").unwrap() == "msvc";
-
     println!("cargo:rustc-link-search=native={}", lib_path.display());
     println!("cargo:rustc-link-lib={}=turbojpeg{}", match link_kind {
         LinkKind::Static | LinkKind::Default => "static",
@@ -180,18 +292,32 @@ fn build_vendor(link_kind: LinkKind) -> Result<Library> {
     } else {
         ""
     });
+    link_jpeglib(&link_kind);
 
     Ok(Library {
         include_paths: vec![include_path],
         defines: HashMap::new(),
+        simd,
     })
 }
 
-fn check_nasm() {
-    if !Command::new("nasm").arg("-v").status().map(|s| s.success()).unwrap_or(false) {
+/// Links libjpeg if we need the libjpeg API (the `jpeglib` feature) and link TurboJPEG dynamically.
+///
+/// The shared libturbojpeg exports only the TurboJPEG API, but the static libturbojpeg contains the
+/// whole libjpeg, so we must not link libjpeg statically as well (the symbols would be duplicated).
+fn link_jpeglib(link_kind: &LinkKind) {
+    if cfg!(feature = "jpeglib") && matches!(link_kind, LinkKind::Dynamic) {
+        println!("cargo:rustc-link-lib=dylib=jpeg");
+    }
+}
+
+fn check_nasm() -> bool {
+    let found = Command::new("nasm").arg("-v").status().map(|s| s.success()).unwrap_or(false);
+    if !found {
         println!("cargo:warning=NASM does not seem to be installed, so turbojpeg will be compiled without \
             SIMD extensions. Performance will suffer.");
     }
+    found
 }
 
 #[cfg(not(feature = "cmake"))]
@@ -202,13 +328,90 @@ fn build_vendor(_link_kind: LinkKind) -> Result<Library> {
         - use TURBOJPEG_SOURCE to select other source for the library")
 }
 
+/// The libjpeg-turbo release that is downloaded with `TURBOJPEG_SOURCE=download` (the same version
+/// as the bundled source).
+const DOWNLOAD_URL: &str =
+    "https://github.com/libjpeg-turbo/libjpeg-turbo/releases/download/3.0.1/libjpeg-turbo-3.0.1.tar.gz";
+const DOWNLOAD_SHA256: &str = "22429507714ae147b3acacd299e82099fce5d9f456882fc28e252e4579ba2a75";
+
+#[cfg(feature = "download")]
+fn build_download(link_kind: LinkKind) -> Result<Library> {
+    use sha2::{Digest as _, Sha256};
+    use std::io::Read as _;
+
+    // the URL can be overriden to use a mirror, but then the checksum must be overriden as well
+    let (url, sha256) = match env("TURBOJPEG_DOWNLOAD_URL") {
+        Some(url) => {
+            let sha256 = env("TURBOJPEG_DOWNLOAD_SHA256")
+                .context("TURBOJPEG_DOWNLOAD_URL is set, but TURBOJPEG_DOWNLOAD_SHA256 is not set")?;
+            (url.into_string().map_err(|_| anyhow!("TURBOJPEG_DOWNLOAD_URL is not valid UTF-8"))?,
+                sha256.into_string().map_err(|_| anyhow!("TURBOJPEG_DOWNLOAD_SHA256 is not valid UTF-8"))?)
+        },
+        None => (DOWNLOAD_URL.into(), DOWNLOAD_SHA256.into()),
+    };
+
+    // we remember the checksum of the extracted archive, so that we don't download it again when
+    // the build script is rerun
+    let extract_path = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("libjpeg-turbo-download");
+    let checksum_path = extract_path.join(".sha256");
+    if fs::read_to_string(&checksum_path).ok().as_deref() != Some(sha256.as_str()) {
+        println!("Downloading turbojpeg source from {url}");
+        let mut archive = Vec::new();
+        ureq::get(&url).call()
+            .with_context(|| format!("could not download {url}"))?
+            .into_reader()
+            .read_to_end(&mut archive)
+            .with_context(|| format!("could not download {url}"))?;
+
+        let actual_sha256 = Sha256::digest(&archive).iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        if !actual_sha256.eq_ignore_ascii_case(&sha256) {
+            bail!("SHA-256 of {url} is {actual_sha256}, but expected {sha256}");
+        }
+
+        let _ = fs::remove_dir_all(&extract_path);
+        tar::Archive::new(flate2::read::GzDecoder::new(&archive[..]))
+            .unpack(&extract_path)
+            .context("could not extract the downloaded archive")?;
+        fs::write(&checksum_path, &sha256)?;
+    }
+
+    // the archive contains a single top-level directory with the source
+    let source_path = fs::read_dir(&extract_path)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.join("CMakeLists.txt").exists())
+        .context("the downloaded archive does not contain a CMakeLists.txt")?;
+
+    build_source(source_path, link_kind)
+}
+
+#[cfg(not(feature = "download"))]
+fn build_download(_link_kind: LinkKind) -> Result<Library> {
+    bail!("Trying to download turbojpeg source, but the `download` feature is disabled. \
+        You have two options:\n\
+        - enable `download` feature of `turbojpeg-sys` crate\n\
+        - use TURBOJPEG_SOURCE to select other source for the library")
+}
+
 
 
 fn generate_or_copy_bindings(library: &Library) -> Result<()> {
+    if cfg!(feature = "compat-2x") {
+        // the TurboJPEG 2.x headers do not declare the TurboJPEG 3 API, so bindgen would not
+        // generate the types and constants that are needed by the compatibility layer
+        if env("TURBOJPEG_BINDING").is_some_and(|b| b.eq_ignore_ascii_case("bindgen")) {
+            bail!("The `compat-2x` feature requires pregenerated bindings, \
+                but TURBOJPEG_BINDING is set to `bindgen`")
+        }
+        return copy_pregenerated_bindings(library)
+    }
+
     match env("TURBOJPEG_BINDING") {
         Some(binding) => {
             if binding.eq_ignore_ascii_case("pregenerated") {
-                copy_pregenerated_bindings()
+                copy_pregenerated_bindings(library)
             } else if binding.eq_ignore_ascii_case("bindgen") {
                 generate_bindings(library)
             } else {
@@ -221,14 +424,15 @@ fn generate_or_copy_bindings(library: &Library) -> Result<()> {
             if cfg!(feature = "bindgen") {
                 generate_bindings(library)
             } else {
-                copy_pregenerated_bindings()
+                copy_pregenerated_bindings(library)
             }
         },
     }
 }
 
-fn copy_pregenerated_bindings() -> Result<()> {
+fn copy_pregenerated_bindings(library: &Library) -> Result<()> {
     println!("Using pregenerated bindings");
+    check_header(library)?;
     let out_path = PathBuf::from(env::var_os("OUT_DIR").unwrap());
     let crate_path = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
     fs::copy(crate_path.join("bindings.rs"), out_path.join("bindings.rs"))?;
@@ -236,13 +440,75 @@ fn copy_pregenerated_bindings() -> Result<()> {
     Ok(())
 }
 
+/// Checks that the `turbojpeg.h` header of the library matches the pregenerated bindings.
+///
+/// The pregenerated bindings declare the TurboJPEG 3 API, so if we link to TurboJPEG 2.x without
+/// the `compat-2x` feature, the `tj3*` functions would fail to link at best. If we cannot find the
+/// header (for example, if TURBOJPEG_INCLUDE_DIR is not set), we skip the check.
+fn check_header(library: &Library) -> Result<()> {
+    let Some(header_path) = library.include_paths.iter()
+        .map(|path| path.join("turbojpeg.h"))
+        .find(|path| path.exists())
+        else { return Ok(()) };
+    println!("cargo:rerun-if-changed={}", header_path.display());
+
+    let header = fs::read_to_string(&header_path)
+        .with_context(|| format!("could not read {}", header_path.display()))?;
+    let is_3x = header.contains("tj3Init") && header.contains("TJPARAM_DENSITYUNITS");
+    if !is_3x && !cfg!(feature = "compat-2x") {
+        bail!("The header {} is from TurboJPEG 2.x, but the pregenerated bindings are for \
+            TurboJPEG 3.x, so they would not work with this library. You have these options:\n\
+            - enable `compat-2x` feature of `turbojpeg-sys` crate to use TurboJPEG 2.x\n\
+            - use TURBOJPEG_SOURCE=vendor to build TurboJPEG 3.x from source\n\
+            - point TURBOJPEG_LIB_DIR and TURBOJPEG_INCLUDE_DIR to TurboJPEG 3.x",
+            header_path.display());
+    }
+    Ok(())
+}
+
 #[cfg(feature = "bindgen")]
 fn generate_bindings(library: &Library) -> Result<()> {
     println!("Generating bindings using bindgen");
 
+    let bindings = bindgen_builder(library, "wrapper.h").generate()
+        .map_err(|_| anyhow!("could not generate bindings"))?;
+
+    let out_file = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("bindings.rs");
+    bindings.write_to_file(&out_file)
+        .context("could not write bindings to OUT_DIR")?;
+    println!("Generated bindings are stored in {}", out_file.display());
+
+    Ok(())
+}
+
+/// Generates the bindings for the libjpeg API (`jpeglib.h` and `jerror.h`).
+///
+/// The layout of the libjpeg structs depends on the platform and on the configuration of the
+/// library (`jconfig.h`), so we cannot ship pregenerated bindings and we always use bindgen.
+#[cfg(feature = "bindgen")]
+fn generate_jpeglib_bindings(library: &Library) -> Result<()> {
+    println!("Generating libjpeg bindings using bindgen");
+
+    let bindings = bindgen_builder(library, "jpeglib-wrapper.h")
+        .whitelist_function("jpeg_.*")
+        .whitelist_type("(jpeg|j)_.*|J[A-Z_]+|boolean|UINT8|UINT16|INT16|INT32")
+        .whitelist_var("JPEG_.*|JMSG_.*|JCS_.*|JDCT_.*|DCTSIZE.*|NUM_.*|[CD]_MAX_.*|MAX_COMPONENTS")
+        .generate()
+        .map_err(|_| anyhow!("could not generate libjpeg bindings (is jpeglib.h in the include path?)"))?;
+
+    let out_file = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("jpeglib.rs");
+    bindings.write_to_file(&out_file)
+        .context("could not write libjpeg bindings to OUT_DIR")?;
+    println!("Generated libjpeg bindings are stored in {}", out_file.display());
+
+    Ok(())
+}
+
+#[cfg(feature = "bindgen")]
+fn bindgen_builder(library: &Library, header: &str) -> bindgen::Builder {
     let target = env::var("TARGET").unwrap();
     let mut builder = bindgen::Builder::default()
-        .header("wrapper.h")
+        .header(header)
         .use_core()
         .ctypes_prefix("libc")
         .clang_args(&["-target", &target]);
@@ -260,16 +526,7 @@ fn generate_bindings(library: &Library) -> Result<()> {
             builder = builder.clang_arg(format!("-D{}", name));
         }
     }
-
-    let bindings = builder.generate()
-        .map_err(|_| anyhow!("could not generate bindings"))?;
-
-    let out_file = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("bindings.rs");
-    bindings.write_to_file(&out_file)
-        .context("could not write bindings to OUT_DIR")?;
-    println!("Generated bindings are stored in {}", out_file.display());
-
-    Ok(())
+    builder
 }
 
 #[cfg(not(feature = "bindgen"))]
@@ -280,6 +537,11 @@ fn generate_bindings(_: &Library) -> Result<()> {
         - use TURBOJPEG_BINDING to select other method to obtain the bindings")
 }
 
+#[cfg(not(feature = "bindgen"))]
+fn generate_jpeglib_bindings(_: &Library) -> Result<()> {
+    bail!("The `jpeglib` feature requires the `bindgen` feature")
+}
+
 
 
 fn env(name: &str) -> Option<OsString> {
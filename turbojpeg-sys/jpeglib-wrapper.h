@@ -0,0 +1,5 @@
+// jpeglib.h uses FILE and size_t, but it does not include the headers that define them
+#include <stddef.h>
+#include <stdio.h>
+#include <jpeglib.h>
+#include <jerror.h>
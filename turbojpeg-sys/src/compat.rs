@@ -0,0 +1,357 @@
+//! Implementation of the TurboJPEG 3 API on top of the TurboJPEG 2 API.
+//!
+//! TurboJPEG 2.x (which is shipped by many Linux distributions) does not provide the `tj3*`
+//! functions. With the `compat-2x` feature, we implement the subset of these functions that is used
+//! by the `turbojpeg` crate using the older `tj*` functions, so that the crate can link to
+//! TurboJPEG 2.0 or 2.1.
+//!
+//! The handle returned by [`tj3Init()`] points to a [`Handle`], which wraps the TurboJPEG 2 handle
+//! and stores the parameters that TurboJPEG 3 keeps in the handle. Features that do not exist in
+//! TurboJPEG 2 (such as partial decompression or lossless JPEG) are reported as errors.
+//!
+//! The safety requirements of these functions are the same as for the corresponding functions in
+//! TurboJPEG 3.
+#![allow(clippy::missing_safety_doc)]
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ptr;
+use crate::bindings::*;
+
+const NUM_PARAMS: usize = TJPARAM_TJPARAM_DENSITYUNITS as usize + 1;
+
+struct Handle {
+    inner: tjhandle,
+    params: [libc::c_int; NUM_PARAMS],
+    scaling_factor: tjscalingfactor,
+    error: Option<&'static [u8]>,
+}
+
+/// Number of scans that `TJFLAG_LIMITSCANS` limits the decompression to (it cannot be changed).
+const LIMITSCANS: libc::c_int = 500;
+
+const ERR_UNSUPPORTED_PARAM: &[u8] = b"This parameter is not supported by TurboJPEG 2.x\0";
+const ERR_INVALID_PARAM: &[u8] = b"Invalid parameter\0";
+const ERR_CROPPING: &[u8] = b"Partial decompression is not supported by TurboJPEG 2.x\0";
+const ERR_SCALING_FACTOR: &[u8] = b"Unsupported scaling factor\0";
+const ERR_QUALITY_SUBSAMP: &[u8] = b"TJPARAM_QUALITY and TJPARAM_SUBSAMP must be specified\0";
+
+impl Handle {
+    fn new(inner: tjhandle) -> Handle {
+        let mut params = [0; NUM_PARAMS];
+        params[TJPARAM_TJPARAM_QUALITY as usize] = -1;
+        params[TJPARAM_TJPARAM_SUBSAMP as usize] = -1;
+        params[TJPARAM_TJPARAM_JPEGWIDTH as usize] = -1;
+        params[TJPARAM_TJPARAM_JPEGHEIGHT as usize] = -1;
+        params[TJPARAM_TJPARAM_PRECISION as usize] = 8;
+        params[TJPARAM_TJPARAM_COLORSPACE as usize] = -1;
+        params[TJPARAM_TJPARAM_XDENSITY as usize] = 1;
+        params[TJPARAM_TJPARAM_YDENSITY as usize] = 1;
+        Handle { inner, params, scaling_factor: tjscalingfactor { num: 1, denom: 1 }, error: None }
+    }
+
+    fn param(&self, param: TJPARAM) -> libc::c_int {
+        self.params[param as usize]
+    }
+
+    fn fail(&mut self, error: &'static [u8]) -> libc::c_int {
+        self.error = Some(error);
+        -1
+    }
+
+    /// Translates the parameters into TurboJPEG 2 flags.
+    fn flags(&self) -> libc::c_int {
+        let mut flags = 0;
+        let mut flag = |param, flag| if self.param(param) != 0 { flags |= flag };
+        flag(TJPARAM_TJPARAM_STOPONWARNING, TJFLAG_STOPONWARNING);
+        flag(TJPARAM_TJPARAM_BOTTOMUP, TJFLAG_BOTTOMUP);
+        flag(TJPARAM_TJPARAM_NOREALLOC, TJFLAG_NOREALLOC);
+        flag(TJPARAM_TJPARAM_FASTUPSAMPLE, TJFLAG_FASTUPSAMPLE);
+        flag(TJPARAM_TJPARAM_FASTDCT, TJFLAG_FASTDCT);
+        flag(TJPARAM_TJPARAM_PROGRESSIVE, TJFLAG_PROGRESSIVE);
+        flag(TJPARAM_TJPARAM_SCANLIMIT, TJFLAG_LIMITSCANS);
+        flags as libc::c_int
+    }
+
+    /// Reads the JPEG header and stores the image parameters.
+    unsafe fn read_header(&mut self, jpeg_buf: *const libc::c_uchar, jpeg_size: size_t) -> libc::c_int {
+        let (mut width, mut height, mut subsamp, mut colorspace) = (0, 0, 0, 0);
+        let res = tjDecompressHeader3(
+            self.inner, jpeg_buf, jpeg_size,
+            &mut width, &mut height, &mut subsamp, &mut colorspace,
+        );
+        if res == 0 {
+            self.params[TJPARAM_TJPARAM_JPEGWIDTH as usize] = width;
+            self.params[TJPARAM_TJPARAM_JPEGHEIGHT as usize] = height;
+            self.params[TJPARAM_TJPARAM_SUBSAMP as usize] = subsamp;
+            self.params[TJPARAM_TJPARAM_COLORSPACE as usize] = colorspace;
+        }
+        res
+    }
+
+    /// Returns the size of the decompressed image (after scaling).
+    fn scaled_size(&self) -> (libc::c_int, libc::c_int) {
+        let scale = |dim: libc::c_int| {
+            (dim * self.scaling_factor.num + self.scaling_factor.denom - 1) / self.scaling_factor.denom
+        };
+        (scale(self.param(TJPARAM_TJPARAM_JPEGWIDTH)), scale(self.param(TJPARAM_TJPARAM_JPEGHEIGHT)))
+    }
+}
+
+unsafe fn get_handle<'a>(handle: tjhandle) -> Option<&'a mut Handle> {
+    let handle = (handle as *mut Handle).as_mut()?;
+    handle.error = None;
+    Some(handle)
+}
+
+pub unsafe extern "C" fn tj3Init(initType: libc::c_int) -> tjhandle {
+    let inner = match initType as TJINIT {
+        TJINIT_TJINIT_COMPRESS => tjInitCompress(),
+        TJINIT_TJINIT_DECOMPRESS => tjInitDecompress(),
+        TJINIT_TJINIT_TRANSFORM => tjInitTransform(),
+        _ => ptr::null_mut(),
+    };
+    if inner.is_null() {
+        return ptr::null_mut()
+    }
+    Box::into_raw(Box::new(Handle::new(inner))) as tjhandle
+}
+
+pub unsafe extern "C" fn tj3Destroy(handle: tjhandle) {
+    if !handle.is_null() {
+        let handle = Box::from_raw(handle as *mut Handle);
+        tjDestroy(handle.inner);
+    }
+}
+
+pub unsafe extern "C" fn tj3GetErrorStr(handle: tjhandle) -> *mut libc::c_char {
+    match (handle as *mut Handle).as_ref() {
+        Some(Handle { error: Some(error), .. }) => error.as_ptr() as *mut libc::c_char,
+        Some(handle) => tjGetErrorStr2(handle.inner),
+        None => tjGetErrorStr2(ptr::null_mut()),
+    }
+}
+
+pub unsafe extern "C" fn tj3GetErrorCode(handle: tjhandle) -> libc::c_int {
+    match (handle as *mut Handle).as_ref() {
+        Some(Handle { error: Some(_), .. }) => TJERR_TJERR_FATAL as libc::c_int,
+        Some(handle) => tjGetErrorCode(handle.inner),
+        None => TJERR_TJERR_FATAL as libc::c_int,
+    }
+}
+
+pub unsafe extern "C" fn tj3Set(handle: tjhandle, param: libc::c_int, value: libc::c_int) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    let index = match usize::try_from(param) {
+        Ok(index) if index < NUM_PARAMS => index,
+        _ => return handle.fail(ERR_INVALID_PARAM),
+    };
+    let supported = matches!(param as TJPARAM,
+        TJPARAM_TJPARAM_STOPONWARNING | TJPARAM_TJPARAM_BOTTOMUP | TJPARAM_TJPARAM_NOREALLOC |
+        TJPARAM_TJPARAM_QUALITY | TJPARAM_TJPARAM_SUBSAMP | TJPARAM_TJPARAM_FASTUPSAMPLE |
+        TJPARAM_TJPARAM_FASTDCT | TJPARAM_TJPARAM_PROGRESSIVE | TJPARAM_TJPARAM_SCANLIMIT
+    );
+    if !supported && value != handle.params[index] {
+        return handle.fail(ERR_UNSUPPORTED_PARAM)
+    }
+    if param as TJPARAM == TJPARAM_TJPARAM_SCANLIMIT && value != 0 && value != LIMITSCANS {
+        return handle.fail(ERR_UNSUPPORTED_PARAM)
+    }
+    handle.params[index] = value;
+    0
+}
+
+pub unsafe extern "C" fn tj3Get(handle: tjhandle, param: libc::c_int) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    match usize::try_from(param) {
+        Ok(index) if index < NUM_PARAMS => handle.params[index],
+        _ => -1,
+    }
+}
+
+pub unsafe extern "C" fn tj3DecompressHeader(
+    handle: tjhandle,
+    jpegBuf: *const libc::c_uchar,
+    jpegSize: size_t,
+) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    handle.read_header(jpegBuf, jpegSize)
+}
+
+pub unsafe extern "C" fn tj3SetScalingFactor(
+    handle: tjhandle,
+    scalingFactor: tjscalingfactor,
+) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    let mut count = 0;
+    let factors = tjGetScalingFactors(&mut count);
+    if factors.is_null() {
+        return handle.fail(ERR_SCALING_FACTOR)
+    }
+    let factors = core::slice::from_raw_parts(factors, count.max(0) as usize);
+    let supported = factors.iter().any(|factor| {
+        factor.num * scalingFactor.denom == scalingFactor.num * factor.denom
+    });
+    if !supported {
+        return handle.fail(ERR_SCALING_FACTOR)
+    }
+    handle.scaling_factor = scalingFactor;
+    0
+}
+
+pub unsafe extern "C" fn tj3SetCroppingRegion(
+    handle: tjhandle,
+    croppingRegion: tjregion,
+) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    let tjregion { x, y, w, h } = croppingRegion;
+    if (x, y, w, h) != (0, 0, 0, 0) {
+        return handle.fail(ERR_CROPPING)
+    }
+    0
+}
+
+pub unsafe extern "C" fn tj3GetScalingFactors(numScalingFactors: *mut libc::c_int) -> *mut tjscalingfactor {
+    tjGetScalingFactors(numScalingFactors)
+}
+
+pub unsafe extern "C" fn tj3Decompress8(
+    handle: tjhandle,
+    jpegBuf: *const libc::c_uchar,
+    jpegSize: size_t,
+    dstBuf: *mut libc::c_uchar,
+    pitch: libc::c_int,
+    pixelFormat: libc::c_int,
+) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    if handle.read_header(jpegBuf, jpegSize) != 0 {
+        return -1
+    }
+    let (width, height) = handle.scaled_size();
+    tjDecompress2(
+        handle.inner, jpegBuf, jpegSize,
+        dstBuf, width, pitch, height, pixelFormat,
+        handle.flags(),
+    )
+}
+
+pub unsafe extern "C" fn tj3DecompressToYUV8(
+    handle: tjhandle,
+    jpegBuf: *const libc::c_uchar,
+    jpegSize: size_t,
+    dstBuf: *mut libc::c_uchar,
+    align: libc::c_int,
+) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    if handle.read_header(jpegBuf, jpegSize) != 0 {
+        return -1
+    }
+    let (width, height) = handle.scaled_size();
+    tjDecompressToYUV2(
+        handle.inner, jpegBuf, jpegSize,
+        dstBuf, width, align, height,
+        handle.flags(),
+    )
+}
+
+pub unsafe extern "C" fn tj3Compress8(
+    handle: tjhandle,
+    srcBuf: *const libc::c_uchar,
+    width: libc::c_int,
+    pitch: libc::c_int,
+    height: libc::c_int,
+    pixelFormat: libc::c_int,
+    jpegBuf: *mut *mut libc::c_uchar,
+    jpegSize: *mut size_t,
+) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    let quality = handle.param(TJPARAM_TJPARAM_QUALITY);
+    let subsamp = handle.param(TJPARAM_TJPARAM_SUBSAMP);
+    if quality < 1 || subsamp < 0 {
+        return handle.fail(ERR_QUALITY_SUBSAMP)
+    }
+    tjCompress2(
+        handle.inner, srcBuf, width, pitch, height, pixelFormat,
+        jpegBuf, jpegSize, subsamp, quality,
+        handle.flags(),
+    )
+}
+
+pub unsafe extern "C" fn tj3CompressFromYUV8(
+    handle: tjhandle,
+    srcBuf: *const libc::c_uchar,
+    width: libc::c_int,
+    align: libc::c_int,
+    height: libc::c_int,
+    jpegBuf: *mut *mut libc::c_uchar,
+    jpegSize: *mut size_t,
+) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    let quality = handle.param(TJPARAM_TJPARAM_QUALITY);
+    let subsamp = handle.param(TJPARAM_TJPARAM_SUBSAMP);
+    if quality < 1 || subsamp < 0 {
+        return handle.fail(ERR_QUALITY_SUBSAMP)
+    }
+    tjCompressFromYUV(
+        handle.inner, srcBuf, width, align, height, subsamp,
+        jpegBuf, jpegSize, quality,
+        handle.flags(),
+    )
+}
+
+pub unsafe extern "C" fn tj3Transform(
+    handle: tjhandle,
+    jpegBuf: *const libc::c_uchar,
+    jpegSize: size_t,
+    n: libc::c_int,
+    dstBufs: *mut *mut libc::c_uchar,
+    dstSizes: *mut size_t,
+    transforms: *const tjtransform,
+) -> libc::c_int {
+    let Some(handle) = get_handle(handle) else { return -1 };
+    // TurboJPEG 2 takes a mutable pointer to the transforms, so we pass a copy
+    let mut transforms: Vec<tjtransform> = if transforms.is_null() || n <= 0 {
+        Vec::new()
+    } else {
+        core::slice::from_raw_parts(transforms, n as usize).to_vec()
+    };
+    tjTransform(
+        handle.inner, jpegBuf, jpegSize, n,
+        dstBufs, dstSizes, transforms.as_mut_ptr(),
+        handle.flags(),
+    )
+}
+
+pub unsafe extern "C" fn tj3Alloc(bytes: size_t) -> *mut libc::c_void {
+    match libc::c_int::try_from(bytes) {
+        Ok(bytes) => tjAlloc(bytes) as *mut libc::c_void,
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+pub unsafe extern "C" fn tj3Free(buffer: *mut libc::c_void) {
+    tjFree(buffer as *mut libc::c_uchar)
+}
+
+pub unsafe extern "C" fn tj3JPEGBufSize(
+    width: libc::c_int,
+    height: libc::c_int,
+    jpegSubsamp: libc::c_int,
+) -> size_t {
+    // TurboJPEG 2 returns -1 on error, TurboJPEG 3 returns 0
+    match tjBufSize(width, height, jpegSubsamp) {
+        size if size == libc::c_ulong::MAX => 0,
+        size => size as size_t,
+    }
+}
+
+pub unsafe extern "C" fn tj3YUVBufSize(
+    width: libc::c_int,
+    align: libc::c_int,
+    height: libc::c_int,
+    subsamp: libc::c_int,
+) -> size_t {
+    match tjBufSizeYUV2(width, align, height, subsamp) {
+        size if size == libc::c_ulong::MAX => 0,
+        size => size as size_t,
+    }
+}
@@ -3,8 +3,63 @@
 #![allow(non_snake_case)]
 #![allow(non_upper_case_globals)]
 #![allow(deref_nullptr)]
+
+#[cfg(not(feature = "compat-2x"))]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+#[cfg(feature = "compat-2x")]
+extern crate alloc;
+
+// the `tj3*` functions that are shadowed by `compat` are never used
+#[cfg(feature = "compat-2x")]
+#[allow(dead_code)]
+mod bindings {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
+
+#[cfg(feature = "compat-2x")]
+mod compat;
+
+#[cfg(feature = "compat-2x")]
+pub use bindings::*;
+
+// these shadow the `tj3*` functions from `bindings`, which do not exist in TurboJPEG 2.x
+#[cfg(feature = "compat-2x")]
+pub use compat::{
+    tj3Init, tj3Destroy, tj3Set, tj3Get, tj3GetErrorStr, tj3GetErrorCode,
+    tj3DecompressHeader, tj3Decompress8, tj3DecompressToYUV8,
+    tj3Compress8, tj3CompressFromYUV8, tj3Transform,
+    tj3SetScalingFactor, tj3SetCroppingRegion, tj3GetScalingFactors,
+    tj3Alloc, tj3Free, tj3JPEGBufSize, tj3YUVBufSize,
+};
+
+/// Raw bindings for the libjpeg API (`jpeglib.h` and `jerror.h`) of the linked library.
+///
+/// These bindings are generated with bindgen (the `jpeglib` feature implies the `bindgen` feature),
+/// because the layout of the libjpeg structs depends on the platform and on the configuration of
+/// the library. The functions come from the same library as the TurboJPEG API, so you can
+/// implement custom source and destination managers or work with the DCT coefficients without
+/// linking a second copy of libjpeg.
+///
+/// The macros from `jpeglib.h` are not available, so you must call the functions behind them
+/// directly (for example, `jpeg_CreateDecompress()` instead of `jpeg_create_decompress()`).
+#[cfg(feature = "jpeglib")]
+pub mod jpeglib {
+    include!(concat!(env!("OUT_DIR"), "/jpeglib.rs"));
+}
+
+/// Whether the linked TurboJPEG was built with the SIMD extensions.
+///
+/// This is `Some(true)` if the library was built from source with the `require-simd` feature,
+/// `Some(false)` if it was built from source without NASM on x86 or x86-64, and `None` if we do
+/// not know (the library was built without `require-simd` on other targets, or it was found using
+/// pkg-config or `TURBOJPEG_LIB_DIR`).
+pub const SIMD_BUILD: Option<bool> = match env!("TURBOJPEG_SYS_SIMD").as_bytes() {
+    b"yes" => Some(true),
+    b"no" => Some(false),
+    _ => None,
+};
+
 #[cfg(test)]
 mod tests {
     #[test]
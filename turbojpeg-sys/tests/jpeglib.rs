@@ -0,0 +1,24 @@
+//! Creates and destroys a libjpeg decompressor through the libjpeg bindings.
+//!
+//! This checks that the libjpeg API is linked from the same library as the TurboJPEG API and that
+//! the size of the generated struct matches the library.
+#![cfg(feature = "jpeglib")]
+use std::mem::{self, MaybeUninit};
+use turbojpeg_sys::jpeglib;
+
+#[test]
+fn test_create_decompress() {
+    unsafe {
+        let mut err = MaybeUninit::<jpeglib::jpeg_error_mgr>::zeroed();
+        let mut cinfo = MaybeUninit::<jpeglib::jpeg_decompress_struct>::zeroed();
+        (*cinfo.as_mut_ptr()).err = jpeglib::jpeg_std_error(err.as_mut_ptr());
+        // `jpeg_create_decompress()` is a macro, which checks that the size of the struct matches
+        jpeglib::jpeg_CreateDecompress(
+            cinfo.as_mut_ptr(),
+            jpeglib::JPEG_LIB_VERSION as libc::c_int,
+            mem::size_of::<jpeglib::jpeg_decompress_struct>() as libc::size_t,
+        );
+        assert!(!(*cinfo.as_ptr()).mem.is_null());
+        jpeglib::jpeg_destroy_decompress(cinfo.as_mut_ptr());
+    }
+}
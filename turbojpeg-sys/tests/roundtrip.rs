@@ -0,0 +1,62 @@
+//! Compresses and decompresses an image through the raw bindings.
+//!
+//! This checks that the library (including its SIMD extensions, which are selected per target) is
+//! built and linked correctly, which is mostly interesting on less common targets such as
+//! `aarch64-pc-windows-msvc`.
+use std::ptr;
+use turbojpeg_sys as raw;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+
+#[test]
+fn test_roundtrip() {
+    let mut pixels = vec![0u8; 3 * WIDTH * HEIGHT];
+    for (i, pixel) in pixels.chunks_exact_mut(3).enumerate() {
+        let (x, y) = (i % WIDTH, i / WIDTH);
+        pixel.copy_from_slice(&[(4 * x) as u8, (5 * y) as u8, 128]);
+    }
+
+    unsafe {
+        let compressor = raw::tj3Init(raw::TJINIT_TJINIT_COMPRESS as libc::c_int);
+        assert!(!compressor.is_null());
+        assert_eq!(raw::tj3Set(compressor, raw::TJPARAM_TJPARAM_QUALITY as libc::c_int, 95), 0);
+        assert_eq!(raw::tj3Set(
+            compressor, raw::TJPARAM_TJPARAM_SUBSAMP as libc::c_int,
+            raw::TJSAMP_TJSAMP_420 as libc::c_int,
+        ), 0);
+
+        let mut jpeg_buf: *mut libc::c_uchar = ptr::null_mut();
+        let mut jpeg_size: raw::size_t = 0;
+        let res = raw::tj3Compress8(
+            compressor, pixels.as_ptr(),
+            WIDTH as libc::c_int, 0, HEIGHT as libc::c_int,
+            raw::TJPF_TJPF_RGB as libc::c_int,
+            &mut jpeg_buf, &mut jpeg_size,
+        );
+        assert_eq!(res, 0);
+        assert!(!jpeg_buf.is_null() && jpeg_size > 0);
+        raw::tj3Destroy(compressor);
+
+        let decompressor = raw::tj3Init(raw::TJINIT_TJINIT_DECOMPRESS as libc::c_int);
+        assert!(!decompressor.is_null());
+        assert_eq!(raw::tj3DecompressHeader(decompressor, jpeg_buf, jpeg_size), 0);
+        assert_eq!(raw::tj3Get(decompressor, raw::TJPARAM_TJPARAM_JPEGWIDTH as libc::c_int), WIDTH as libc::c_int);
+        assert_eq!(raw::tj3Get(decompressor, raw::TJPARAM_TJPARAM_JPEGHEIGHT as libc::c_int), HEIGHT as libc::c_int);
+
+        let mut decompressed = vec![0u8; 3 * WIDTH * HEIGHT];
+        let res = raw::tj3Decompress8(
+            decompressor, jpeg_buf, jpeg_size,
+            decompressed.as_mut_ptr(), 0,
+            raw::TJPF_TJPF_RGB as libc::c_int,
+        );
+        assert_eq!(res, 0);
+        raw::tj3Destroy(decompressor);
+        raw::tj3Free(jpeg_buf as *mut libc::c_void);
+
+        let max_diff = pixels.iter().zip(decompressed.iter())
+            .map(|(&a, &b)| (a as i32 - b as i32).abs())
+            .max().unwrap();
+        assert!(max_diff <= 16, "maximal difference is {}", max_diff);
+    }
+}